@@ -0,0 +1,56 @@
+// Per-element Matrix4d * Vector4d against the batch transform APIs.
+// Run with `cargo bench --bench batch`.
+
+use linearly::matrix4d::Matrix4d;
+use linearly::soa::Vector3dSoa;
+use linearly::vector3d::Vector3d;
+use linearly::vector4d::Vector4d;
+use std::hint::black_box;
+use std::time::Instant;
+
+const N: usize = 200_000;
+const ROUNDS: u32 = 50;
+
+fn time<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let ns = start.elapsed().as_nanos() as f64 / (ROUNDS as f64 * N as f64);
+    println!("{:<28} {:>6.2} ns/point", name, ns);
+}
+
+fn main() {
+    let m = Matrix4d::from_row_major([
+        0.8, -0.6, 0.0, 1.0,
+        0.6, 0.8, 0.0, 2.0,
+        0.0, 0.0, 1.0, 3.0,
+        0.0, 0.0, 0.0, 1.0,
+    ]);
+    let src: Vec<Vector3d> = (0..N)
+        .map(|i| Vector3d::new_from([i as f64, (i % 7) as f64, 1.0]))
+        .collect();
+    let mut dst = vec![Vector3d::new(); N];
+
+    time("Matrix4d * Vector4d", || {
+        for (p, out) in src.iter().zip(dst.iter_mut()) {
+            let t = black_box(m) * Vector4d::new_from([p.x, p.y, p.z, 1.0]);
+            *out = Vector3d::new_from([t.x, t.y, t.z]);
+        }
+        black_box(&dst);
+    });
+    time("transform_points", || {
+        black_box(m).transform_points(&src, &mut dst);
+        black_box(&dst);
+    });
+    time("transform_points_in_place", || {
+        black_box(m).transform_points_in_place(&mut dst);
+        black_box(&dst);
+    });
+
+    let mut soa = Vector3dSoa::from_slice(&src);
+    time("Vector3dSoa::transform_points", || {
+        soa.transform_points(&black_box(m));
+        black_box(&soa);
+    });
+}
@@ -0,0 +1,27 @@
+// Sequential against multi-threaded dense products.
+// Run with `cargo bench --bench gemm --features rayon`.
+
+use linearly::matrixxd::MatrixXd;
+use linearly::parallel::{par_mul, ParConfig};
+use std::hint::black_box;
+use std::time::Instant;
+
+fn main() {
+    let n = 1000;
+    let a = MatrixXd::from_fn(n, n, |i, j| ((i * 7 + j * 3) % 11) as f64 - 5.0);
+    let b = MatrixXd::from_fn(n, n, |i, j| ((i * 5 + j * 13) % 17) as f64 * 0.5);
+
+    let start = Instant::now();
+    let seq = black_box(&a) * black_box(&b);
+    let t_seq = start.elapsed().as_secs_f64();
+    println!("sequential {}x{}      {:>8.3} s", n, n, t_seq);
+
+    for bs in [32, 64, 128] {
+        let cfg = ParConfig::new(bs, true);
+        let start = Instant::now();
+        let par = par_mul(black_box(&a), black_box(&b), &cfg);
+        let t = start.elapsed().as_secs_f64();
+        assert_eq!(par, seq);
+        println!("parallel block {:<4}      {:>8.3} s  ({:.2}x)", bs, t, t_seq / t);
+    }
+}
@@ -0,0 +1,72 @@
+// Compares the SIMD kernels against the scalar reference path.
+// Run with `cargo bench --bench simd`.
+
+use linearly::simd;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERS: u32 = 2_000_000;
+
+fn time<F: FnMut()>(name: &str, mut f: F) -> f64 {
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        f();
+    }
+    let ns = start.elapsed().as_nanos() as f64 / ITERS as f64;
+    println!("{:<24} {:>8.2} ns/iter", name, ns);
+    ns
+}
+
+fn compare<S: FnMut(), V: FnMut()>(name: &str, scalar: S, vector: V) {
+    let s = time(&format!("{} scalar", name), scalar);
+    let v = time(&format!("{} simd", name), vector);
+    println!("{:<24} {:>8.2}x\n", "speedup", s / v);
+}
+
+fn main() {
+    let mut a = [0.0; 16];
+    let mut b = [0.0; 16];
+    for i in 0..16 {
+        a[i] = i as f64 * 0.25 + 1.0;
+        b[i] = 3.0 - i as f64 * 0.125;
+    }
+    a[0] = 10.0;
+    let v = [1.0, 2.0, 3.0, 4.0];
+
+    compare(
+        "mat4 * mat4",
+        || {
+            black_box(simd::scalar::mat4_mul(black_box(&a), black_box(&b)));
+        },
+        || {
+            black_box(simd::mat4_mul(black_box(&a), black_box(&b)));
+        },
+    );
+    compare(
+        "mat4 * vec4",
+        || {
+            black_box(simd::scalar::mat4_mul_vec(black_box(&a), black_box(&v)));
+        },
+        || {
+            black_box(simd::mat4_mul_vec(black_box(&a), black_box(&v)));
+        },
+    );
+    compare(
+        "dot4",
+        || {
+            black_box(simd::scalar::dot4(black_box(&v), black_box(&v)));
+        },
+        || {
+            black_box(simd::dot4(black_box(&v), black_box(&v)));
+        },
+    );
+    compare(
+        "mat4 inverse",
+        || {
+            black_box(simd::scalar::mat4_inverse(black_box(&a)));
+        },
+        || {
+            black_box(simd::mat4_inverse(black_box(&a)));
+        },
+    );
+}
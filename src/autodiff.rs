@@ -0,0 +1,540 @@
+// Forward-mode automatic differentiation with dual numbers.
+//
+// DualN<N> carries a value and its gradient with respect to N variables, so a
+// single evaluation of f gives a full gradient or Jacobian. HyperDual carries
+// two first order parts and the mixed second order one, which gives one entry
+// of the Hessian per evaluation. Both implement Scalar, so functions written
+// against Vector3d<T>, Matrix3d<T>, ... can be evaluated on them.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+use crate::math;
+use crate::scalar::{Real, Scalar};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DualN<const N: usize> {
+    pub re: f64,
+    // partial derivatives with respect to the N variables
+    pub eps: [f64; N],
+}
+
+// a single derivative direction
+pub type Dual = DualN<1>;
+pub type Dual2 = DualN<2>;
+pub type Dual3 = DualN<3>;
+pub type Dual4 = DualN<4>;
+
+impl<const N: usize> DualN<N> {
+    pub fn new(re: f64, eps: [f64; N]) -> Self {
+        Self { re, eps }
+    }
+
+    pub fn constant(re: f64) -> Self {
+        Self { re, eps: [0.0; N] }
+    }
+
+    // the i-th independent variable, with value re
+    pub fn variable(re: f64, i: usize) -> Self {
+        let mut eps = [0.0; N];
+        eps[i] = 1.0;
+        Self { re, eps }
+    }
+
+    // f(self) given f(re) and f'(re)
+    fn chain(self, f: f64, df: f64) -> Self {
+        Self {
+            re: f,
+            eps: self.eps.map(|e| df * e),
+        }
+    }
+
+    pub fn recip(self) -> Self {
+        self.chain(1.0 / self.re, -1.0 / (self.re * self.re))
+    }
+
+    pub fn sqrt(self) -> Self {
+        let s = math::sqrt(self.re);
+        self.chain(s, 0.5 / s)
+    }
+
+    pub fn exp(self) -> Self {
+        let e = math::exp(self.re);
+        self.chain(e, e)
+    }
+
+    pub fn ln(self) -> Self {
+        self.chain(math::ln(self.re), 1.0 / self.re)
+    }
+
+    pub fn sin(self) -> Self {
+        self.chain(math::sin(self.re), math::cos(self.re))
+    }
+
+    pub fn cos(self) -> Self {
+        self.chain(math::cos(self.re), -math::sin(self.re))
+    }
+
+    pub fn tan(self) -> Self {
+        let t = math::tan(self.re);
+        self.chain(t, 1.0 + t * t)
+    }
+
+    // self^p for a constant exponent
+    pub fn powf(self, p: f64) -> Self {
+        self.chain(math::powf(self.re, p), p * math::powf(self.re, p - 1.0))
+    }
+
+    pub fn powi(self, n: i32) -> Self {
+        self.powf(n as f64)
+    }
+
+    // the derivative at 0 is taken as 0
+    pub fn abs(self) -> Self {
+        let s = if self.re > 0.0 { 1.0 } else if self.re < 0.0 { -1.0 } else { 0.0 };
+        self.chain(self.re.abs(), s)
+    }
+}
+
+impl Dual {
+    // derivative part of a single direction dual
+    pub fn derivative(&self) -> f64 {
+        self.eps[0]
+    }
+}
+
+impl<const N: usize> From<f64> for DualN<N> {
+    fn from(re: f64) -> Self {
+        Self::constant(re)
+    }
+}
+
+impl<const N: usize> core::ops::Add<DualN<N>> for DualN<N> {
+    type Output = DualN<N>;
+    fn add(self, o: DualN<N>) -> Self::Output {
+        Self::new(self.re + o.re, core::array::from_fn(|i| self.eps[i] + o.eps[i]))
+    }
+}
+
+impl<const N: usize> core::ops::Sub<DualN<N>> for DualN<N> {
+    type Output = DualN<N>;
+    fn sub(self, o: DualN<N>) -> Self::Output {
+        Self::new(self.re - o.re, core::array::from_fn(|i| self.eps[i] - o.eps[i]))
+    }
+}
+
+impl<const N: usize> core::ops::Mul<DualN<N>> for DualN<N> {
+    type Output = DualN<N>;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn mul(self, o: DualN<N>) -> Self::Output {
+        Self::new(self.re * o.re, core::array::from_fn(|i| self.re * o.eps[i] + self.eps[i] * o.re))
+    }
+}
+
+impl<const N: usize> core::ops::Div<DualN<N>> for DualN<N> {
+    type Output = DualN<N>;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, o: DualN<N>) -> Self::Output {
+        let re = self.re / o.re;
+        Self::new(re, core::array::from_fn(|i| (self.eps[i] - re * o.eps[i]) / o.re))
+    }
+}
+
+impl<const N: usize> core::ops::Neg for DualN<N> {
+    type Output = DualN<N>;
+    fn neg(self) -> Self::Output {
+        Self::new(-self.re, self.eps.map(|e| -e))
+    }
+}
+
+impl<const N: usize> core::ops::Add<f64> for DualN<N> {
+    type Output = DualN<N>;
+    fn add(self, o: f64) -> Self::Output {
+        Self::new(self.re + o, self.eps)
+    }
+}
+
+impl<const N: usize> core::ops::Sub<f64> for DualN<N> {
+    type Output = DualN<N>;
+    fn sub(self, o: f64) -> Self::Output {
+        Self::new(self.re - o, self.eps)
+    }
+}
+
+impl<const N: usize> core::ops::Mul<f64> for DualN<N> {
+    type Output = DualN<N>;
+    fn mul(self, o: f64) -> Self::Output {
+        Self::new(self.re * o, self.eps.map(|e| e * o))
+    }
+}
+
+impl<const N: usize> core::ops::Div<f64> for DualN<N> {
+    type Output = DualN<N>;
+    fn div(self, o: f64) -> Self::Output {
+        Self::new(self.re / o, self.eps.map(|e| e / o))
+    }
+}
+
+impl<const N: usize> Scalar for DualN<N> {
+    fn zero() -> Self {
+        Self::constant(0.0)
+    }
+
+    fn one() -> Self {
+        Self::constant(1.0)
+    }
+}
+
+impl<const N: usize> Real for DualN<N> {
+    fn sqrt(self) -> Self {
+        DualN::sqrt(self)
+    }
+}
+
+// re + e1 eps1 + e2 eps2 + e12 eps1 eps2 with eps1^2 = eps2^2 = 0. Seeding
+// eps1 along x_i and eps2 along x_j, f(x) has d2f / dx_i dx_j in e12.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HyperDual {
+    pub re: f64,
+    pub e1: f64,
+    pub e2: f64,
+    pub e12: f64,
+}
+
+impl HyperDual {
+    pub fn new(re: f64, e1: f64, e2: f64, e12: f64) -> Self {
+        Self { re, e1, e2, e12 }
+    }
+
+    pub fn constant(re: f64) -> Self {
+        Self::new(re, 0.0, 0.0, 0.0)
+    }
+
+    // f(self) given f(re), f'(re) and f''(re)
+    fn chain(self, f: f64, df: f64, d2f: f64) -> Self {
+        Self {
+            re: f,
+            e1: df * self.e1,
+            e2: df * self.e2,
+            e12: df * self.e12 + d2f * self.e1 * self.e2,
+        }
+    }
+
+    pub fn recip(self) -> Self {
+        let r = 1.0 / self.re;
+        self.chain(r, -r * r, 2.0 * r * r * r)
+    }
+
+    pub fn sqrt(self) -> Self {
+        let s = math::sqrt(self.re);
+        self.chain(s, 0.5 / s, -0.25 / (s * self.re))
+    }
+
+    pub fn exp(self) -> Self {
+        let e = math::exp(self.re);
+        self.chain(e, e, e)
+    }
+
+    pub fn ln(self) -> Self {
+        self.chain(math::ln(self.re), 1.0 / self.re, -1.0 / (self.re * self.re))
+    }
+
+    pub fn sin(self) -> Self {
+        let (s, c) = (math::sin(self.re), math::cos(self.re));
+        self.chain(s, c, -s)
+    }
+
+    pub fn cos(self) -> Self {
+        let (s, c) = (math::sin(self.re), math::cos(self.re));
+        self.chain(c, -s, -c)
+    }
+
+    pub fn tan(self) -> Self {
+        let t = math::tan(self.re);
+        let sec2 = 1.0 + t * t;
+        self.chain(t, sec2, 2.0 * t * sec2)
+    }
+
+    pub fn powf(self, p: f64) -> Self {
+        self.chain(
+            math::powf(self.re, p),
+            p * math::powf(self.re, p - 1.0),
+            p * (p - 1.0) * math::powf(self.re, p - 2.0),
+        )
+    }
+
+    pub fn powi(self, n: i32) -> Self {
+        self.powf(n as f64)
+    }
+
+    pub fn abs(self) -> Self {
+        let s = if self.re > 0.0 { 1.0 } else if self.re < 0.0 { -1.0 } else { 0.0 };
+        self.chain(self.re.abs(), s, 0.0)
+    }
+}
+
+impl From<f64> for HyperDual {
+    fn from(re: f64) -> Self {
+        Self::constant(re)
+    }
+}
+
+impl core::ops::Add<HyperDual> for HyperDual {
+    type Output = HyperDual;
+    fn add(self, o: HyperDual) -> Self::Output {
+        Self::new(self.re + o.re, self.e1 + o.e1, self.e2 + o.e2, self.e12 + o.e12)
+    }
+}
+
+impl core::ops::Sub<HyperDual> for HyperDual {
+    type Output = HyperDual;
+    fn sub(self, o: HyperDual) -> Self::Output {
+        Self::new(self.re - o.re, self.e1 - o.e1, self.e2 - o.e2, self.e12 - o.e12)
+    }
+}
+
+impl core::ops::Mul<HyperDual> for HyperDual {
+    type Output = HyperDual;
+    fn mul(self, o: HyperDual) -> Self::Output {
+        Self::new(
+            self.re * o.re,
+            self.re * o.e1 + self.e1 * o.re,
+            self.re * o.e2 + self.e2 * o.re,
+            self.re * o.e12 + self.e1 * o.e2 + self.e2 * o.e1 + self.e12 * o.re,
+        )
+    }
+}
+
+impl core::ops::Div<HyperDual> for HyperDual {
+    type Output = HyperDual;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, o: HyperDual) -> Self::Output {
+        self * o.recip()
+    }
+}
+
+impl core::ops::Neg for HyperDual {
+    type Output = HyperDual;
+    fn neg(self) -> Self::Output {
+        Self::new(-self.re, -self.e1, -self.e2, -self.e12)
+    }
+}
+
+impl core::ops::Add<f64> for HyperDual {
+    type Output = HyperDual;
+    fn add(self, o: f64) -> Self::Output {
+        Self::new(self.re + o, self.e1, self.e2, self.e12)
+    }
+}
+
+impl core::ops::Sub<f64> for HyperDual {
+    type Output = HyperDual;
+    fn sub(self, o: f64) -> Self::Output {
+        Self::new(self.re - o, self.e1, self.e2, self.e12)
+    }
+}
+
+impl core::ops::Mul<f64> for HyperDual {
+    type Output = HyperDual;
+    fn mul(self, o: f64) -> Self::Output {
+        Self::new(self.re * o, self.e1 * o, self.e2 * o, self.e12 * o)
+    }
+}
+
+impl core::ops::Div<f64> for HyperDual {
+    type Output = HyperDual;
+    fn div(self, o: f64) -> Self::Output {
+        Self::new(self.re / o, self.e1 / o, self.e2 / o, self.e12 / o)
+    }
+}
+
+impl Scalar for HyperDual {
+    fn zero() -> Self {
+        Self::constant(0.0)
+    }
+
+    fn one() -> Self {
+        Self::constant(1.0)
+    }
+}
+
+impl Real for HyperDual {
+    fn sqrt(self) -> Self {
+        HyperDual::sqrt(self)
+    }
+}
+
+// Links a real vector type to its dual versions, so the helpers below can
+// seed the inputs and read the derivatives back.
+pub trait DiffVector: Copy {
+    const DIM: usize;
+    // the vector with one derivative direction per component
+    type Dual;
+    type DualScalar;
+    type HyperDual;
+    // DIM x DIM matrix for Jacobians and Hessians
+    type Square;
+
+    fn seed(self) -> Self::Dual;
+    // eps1 along component i and eps2 along component j
+    fn seed_hyper(self, i: usize, j: usize) -> Self::HyperDual;
+    fn gradient_of(s: &Self::DualScalar) -> Self;
+    fn jacobian_of(v: &Self::Dual) -> Self::Square;
+    fn square_from_fn(f: impl FnMut(usize, usize) -> f64) -> Self::Square;
+}
+
+macro_rules! impl_diff_vector {
+    ($v:ident, $m:ident, $n:expr) => {
+        impl DiffVector for $v {
+            const DIM: usize = $n;
+            type Dual = $v<DualN<$n>>;
+            type DualScalar = DualN<$n>;
+            type HyperDual = $v<HyperDual>;
+            type Square = $m;
+
+            fn seed(self) -> Self::Dual {
+                let x = self.to_list();
+                $v::new_from(core::array::from_fn(|i| DualN::variable(x[i], i)))
+            }
+
+            fn seed_hyper(self, i: usize, j: usize) -> Self::HyperDual {
+                let x = self.to_list();
+                $v::new_from(core::array::from_fn(|k| {
+                    HyperDual::new(x[k], (k == i) as u8 as f64, (k == j) as u8 as f64, 0.0)
+                }))
+            }
+
+            fn gradient_of(s: &Self::DualScalar) -> Self {
+                $v::new_from(s.eps)
+            }
+
+            fn jacobian_of(v: &Self::Dual) -> Self::Square {
+                let rows = v.to_list();
+                Self::square_from_fn(|i, j| rows[i].eps[j])
+            }
+
+            fn square_from_fn(mut f: impl FnMut(usize, usize) -> f64) -> Self::Square {
+                $m::from_row_major(core::array::from_fn(|k| f(k / $n, k % $n)))
+            }
+        }
+    };
+}
+
+impl_diff_vector!(Vector2d, Matrix2d, 2);
+impl_diff_vector!(Vector3d, Matrix3d, 3);
+impl_diff_vector!(Vector4d, Matrix4d, 4);
+
+// f'(x)
+pub fn derivative(f: impl Fn(Dual) -> Dual, x: f64) -> f64 {
+    f(Dual::variable(x, 0)).derivative()
+}
+
+// gradient of a scalar function, with a single evaluation of f
+pub fn gradient<V: DiffVector>(f: impl Fn(V::Dual) -> V::DualScalar, x: V) -> V {
+    V::gradient_of(&f(x.seed()))
+}
+
+// J[i][j] = d f_i / d x_j, with a single evaluation of f
+pub fn jacobian<V: DiffVector>(f: impl Fn(V::Dual) -> V::Dual, x: V) -> V::Square {
+    V::jacobian_of(&f(x.seed()))
+}
+
+// H[i][j] = d2 f / dx_i dx_j, one evaluation of f per entry of the upper triangle
+pub fn hessian<V: DiffVector>(f: impl Fn(V::HyperDual) -> HyperDual, x: V) -> V::Square {
+    let n = V::DIM;
+    // the vector types have at most 4 components
+    assert!(n <= 4, "hessian supports up to 4 variables");
+    let mut h = [[0.0; 4]; 4];
+    for (i, row) in h.iter_mut().enumerate().take(n) {
+        for (j, e) in row.iter_mut().enumerate().take(n).skip(i) {
+            *e = f(x.seed_hyper(i, j)).e12;
+        }
+    }
+    V::square_from_fn(|i, j| h[i.min(j)][i.max(j)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-12, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn scalar_derivatives() {
+        // d/dx sin(x^2) e^x / x = ...
+        let f = |x: Dual| (x * x).sin() * x.exp() / x;
+        let x = 1.3;
+        let expected = {
+            let (s, c, e) = (math::sin(x * x), math::cos(x * x), math::exp(x));
+            (2.0 * x * c * e + s * e) / x - s * e / (x * x)
+        };
+        assert!((derivative(f, x) - expected).abs() < 1e-12);
+        assert!((derivative(|x| x.sqrt().ln(), 4.0) - 0.125).abs() < 1e-15);
+        assert!((derivative(|x| x.powf(2.5) - x.tan() + x * 3.0, 0.7)
+            - (2.5 * math::powf(0.7, 1.5) - 1.0 / (math::cos(0.7) * math::cos(0.7)) + 3.0))
+            .abs() < 1e-12);
+    }
+
+    #[test]
+    fn gradient_of_vector_length() {
+        // the gradient of |v| is v / |v|
+        let x = Vector3d::new_from([1.0, -2.0, 2.0]);
+        let g = gradient(|v: Vector3d<Dual3>| v.length(), x);
+        assert_close(&g.to_list(), &(x / 3.0).to_list());
+    }
+
+    #[test]
+    fn jacobian_of_matrix_vector_function() {
+        // f(v) = A v + (v.x v.y, sin v.z, 0), J = A + diag part
+        let a = Matrix3d::new([1.0, 2.0, 0.0, 0.0, 1.0, -1.0, 3.0, 0.0, 1.0]);
+        let x = Vector3d::new_from([0.5, 2.0, -1.0]);
+        let j = jacobian(
+            |v: Vector3d<Dual3>| {
+                let ad = Matrix3d::from_row_major(a.to_row_major_array().map(Dual3::constant));
+                ad * v + Vector3d::new_from([v.x * v.y, v.z.sin(), Dual3::constant(0.0)])
+            },
+            x,
+        );
+        let expected = [
+            1.0 + x.y, 2.0 + x.x, 0.0,
+            0.0, 1.0, -1.0 + math::cos(x.z),
+            3.0, 0.0, 1.0,
+        ];
+        assert_close(&j.to_row_major_array(), &expected);
+
+        let j2 = jacobian(|v: Vector2d<Dual2>| Vector2d::new_from([v.x * v.x, v.x * v.y]), Vector2d::new_from([3.0, 4.0]));
+        assert_eq!(j2.to_row_major_array(), [6.0, 0.0, 4.0, 3.0]);
+    }
+
+    #[test]
+    fn hessians() {
+        // f = x^2 y + y^3 z + e^(x z)
+        let x = Vector3d::new_from([0.3, -1.2, 0.8]);
+        let h = hessian(|v: Vector3d<HyperDual>| v.x * v.x * v.y + v.y * v.y * v.y * v.z + (v.x * v.z).exp(), x);
+        let e = math::exp(x.x * x.z);
+        let expected = [
+            2.0 * x.y + x.z * x.z * e, 2.0 * x.x, e + x.x * x.z * e,
+            2.0 * x.x, 6.0 * x.y * x.z, 3.0 * x.y * x.y,
+            e + x.x * x.z * e, 3.0 * x.y * x.y, x.x * x.x * e,
+        ];
+        assert_close(&h.to_row_major_array(), &expected);
+
+        // Hessian of |v| in 4d is (I - u u^T) / |v|
+        let p = Vector4d::new_from([1.0, 2.0, 2.0, 4.0]);
+        let h = hessian(|v: Vector4d<HyperDual>| v.length(), p);
+        let u = (p / 5.0).to_list();
+        for i in 0..4 {
+            for j in 0..4 {
+                let d = if i == j { 1.0 } else { 0.0 };
+                assert!((h.get(i, j) - (d - u[i] * u[j]) / 5.0).abs() < 1e-14);
+            }
+        }
+    }
+}
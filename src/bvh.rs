@@ -0,0 +1,496 @@
+// Bounding volume hierarchy over a slice of primitives.
+//
+// The tree only stores indices into the slice it was built from, so queries
+// take the same slice again. After the primitives move (deforming meshes,
+// animation) refit() recomputes the boxes bottom-up over the existing
+// topology, which is much cheaper than a rebuild but lets the tree degrade
+// when the motion is large.
+
+use super::geometry::*;
+use super::vector3d::*;
+use alloc::vec::Vec;
+
+// Primitives a Bvh can be built over
+pub trait Bounded {
+    fn aabb(&self) -> Aabb;
+
+    fn closest_point(&self, p: Vector3d) -> Vector3d;
+
+    fn overlaps_aabb(&self, b: &Aabb) -> bool {
+        self.aabb().overlaps(b)
+    }
+}
+
+impl Bounded for Aabb {
+    fn aabb(&self) -> Aabb {
+        *self
+    }
+
+    fn closest_point(&self, p: Vector3d) -> Vector3d {
+        Aabb::closest_point(self, p)
+    }
+}
+
+impl Bounded for Triangle {
+    fn aabb(&self) -> Aabb {
+        Triangle::aabb(self)
+    }
+
+    fn closest_point(&self, p: Vector3d) -> Vector3d {
+        Triangle::closest_point(self, p).0
+    }
+
+    fn overlaps_aabb(&self, b: &Aabb) -> bool {
+        b.overlaps_triangle(self)
+    }
+}
+
+impl Bounded for Sphere {
+    fn aabb(&self) -> Aabb {
+        Sphere::aabb(self)
+    }
+
+    fn closest_point(&self, p: Vector3d) -> Vector3d {
+        Sphere::closest_point(self, p)
+    }
+
+    fn overlaps_aabb(&self, b: &Aabb) -> bool {
+        Sphere::overlaps_aabb(self, b)
+    }
+}
+
+// leaves hold indices[first..first + count], interior nodes have count 0 and
+// their children at first and first + 1, always after the parent
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Node {
+    bounds: Aabb,
+    first: usize,
+    count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Split {
+    Median,
+    Sah,
+}
+
+// largest leaf of the median builder, and the size above which the SAH
+// builder splits even when the cost model says not to
+const MAX_LEAF: usize = 4;
+const MAX_SAH_LEAF: usize = 16;
+const SAH_BINS: usize = 16;
+// cost of visiting a node relative to testing one primitive
+const SAH_TRAVERSAL: f64 = 1.0;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    // binned surface area heuristic, slower to build but faster to query
+    pub fn build_sah<P: Bounded>(prims: &[P]) -> Self {
+        Self::build(prims, Split::Sah)
+    }
+
+    // splits at the median centroid along the longest axis
+    pub fn build_median<P: Bounded>(prims: &[P]) -> Self {
+        Self::build(prims, Split::Median)
+    }
+
+    fn build<P: Bounded>(prims: &[P], split: Split) -> Self {
+        let mut bvh = Self { nodes: Vec::new(), indices: (0..prims.len()).collect() };
+        if prims.is_empty() {
+            return bvh;
+        }
+        let boxes: Vec<Aabb> = prims.iter().map(|p| p.aabb()).collect();
+        let centroids: Vec<Vector3d> = boxes.iter().map(|b| b.center()).collect();
+        bvh.nodes.push(Node { bounds: Aabb::empty(), first: 0, count: prims.len() });
+        let mut work = alloc::vec![0];
+        while let Some(n) = work.pop() {
+            let Node { first, count, .. } = bvh.nodes[n];
+            let idx = &mut bvh.indices[first..first + count];
+            bvh.nodes[n].bounds = idx.iter().fold(Aabb::empty(), |b, &i| b.union(&boxes[i]));
+            let mid = match split {
+                Split::Median => median_split(idx, &centroids),
+                Split::Sah => sah_split(idx, &boxes, &centroids, bvh.nodes[n].bounds.surface_area()),
+            };
+            let Some(mid) = mid else { continue };
+            let left = bvh.nodes.len();
+            bvh.nodes.push(Node { bounds: Aabb::empty(), first, count: mid });
+            bvh.nodes.push(Node { bounds: Aabb::empty(), first: first + mid, count: count - mid });
+            bvh.nodes[n] = Node { first: left, count: 0, ..bvh.nodes[n] };
+            work.push(left);
+            work.push(left + 1);
+        }
+        bvh
+    }
+
+    // box around every primitive, empty for an empty tree
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |n| n.bounds)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // recomputes every box from the moved primitives, keeping the topology;
+    // prims must be the same length as the slice the tree was built from
+    pub fn refit<P: Bounded>(&mut self, prims: &[P]) {
+        assert_eq!(prims.len(), self.indices.len(), "the primitive count must not change");
+        for n in (0..self.nodes.len()).rev() {
+            let Node { first, count, .. } = self.nodes[n];
+            self.nodes[n].bounds = if count == 0 {
+                self.nodes[first].bounds.union(&self.nodes[first + 1].bounds)
+            } else {
+                self.indices[first..first + count].iter().fold(Aabb::empty(), |b, &i| b.union(&prims[i].aabb()))
+            };
+        }
+    }
+
+    // closest hit along the ray and the index of the primitive hit
+    pub fn ray_cast<P: Bounded + RayCast>(&self, prims: &[P], ray: &Ray) -> Option<(usize, RayHit)> {
+        let mut best: Option<(usize, RayHit)> = None;
+        self.traverse_ray(ray, f64::INFINITY, |i, t_max| {
+            match prims[i].ray_cast(ray) {
+                Some(hit) if hit.t < t_max => {
+                    best = Some((i, hit));
+                    hit.t
+                }
+                _ => t_max,
+            }
+        });
+        best
+    }
+
+    // any hit with t <= t_max, stopping at the first one found; for
+    // shadow and visibility rays
+    pub fn ray_any<P: Bounded + RayCast>(&self, prims: &[P], ray: &Ray, t_max: f64) -> Option<(usize, RayHit)> {
+        let mut found = None;
+        self.traverse_ray(ray, t_max, |i, t_max| {
+            match prims[i].ray_cast(ray) {
+                Some(hit) if hit.t <= t_max => {
+                    found = Some((i, hit));
+                    // an empty range ends the traversal
+                    -1.0
+                }
+                _ => t_max,
+            }
+        });
+        found
+    }
+
+    // visits the primitives whose leaves the ray reaches within [0, t_max],
+    // nearer children first; visit returns the new t_max
+    fn traverse_ray(&self, ray: &Ray, mut t_max: f64, mut visit: impl FnMut(usize, f64) -> f64) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inv = ray.inv_dir();
+        let mut stack = alloc::vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if t_max < 0.0 || node.bounds.slab(ray, inv, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &i in &self.indices[node.first..node.first + node.count] {
+                    t_max = visit(i, t_max);
+                    if t_max < 0.0 {
+                        return;
+                    }
+                }
+                continue;
+            }
+            let (l, r) = (node.first, node.first + 1);
+            let tl = self.nodes[l].bounds.slab(ray, inv, t_max).map(|t| t.0);
+            let tr = self.nodes[r].bounds.slab(ray, inv, t_max).map(|t| t.0);
+            match (tl, tr) {
+                (Some(a), Some(b)) if a <= b => stack.extend([r, l]),
+                (Some(_), Some(_)) => stack.extend([l, r]),
+                (Some(_), None) => stack.push(l),
+                (None, Some(_)) => stack.push(r),
+                (None, None) => {}
+            }
+        }
+    }
+
+    // indices of the primitives overlapping the box
+    pub fn overlapping<P: Bounded>(&self, prims: &[P], query: &Aabb) -> Vec<usize> {
+        let mut out = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !node.bounds.overlaps(query) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend([node.first, node.first + 1]);
+            } else {
+                let leaf = &self.indices[node.first..node.first + node.count];
+                out.extend(leaf.iter().copied().filter(|&i| prims[i].overlaps_aabb(query)));
+            }
+        }
+        out
+    }
+
+    // the primitive closest to p, its closest point and the distance
+    pub fn nearest<P: Bounded>(&self, prims: &[P], p: Vector3d) -> Option<(usize, Vector3d, f64)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut best: Option<(usize, Vector3d)> = None;
+        let mut best_d2 = f64::INFINITY;
+        let mut stack = alloc::vec![(0, self.nodes[0].bounds.distance_squared(p))];
+        while let Some((n, d2)) = stack.pop() {
+            if d2 > best_d2 {
+                continue;
+            }
+            let node = &self.nodes[n];
+            if node.count > 0 {
+                for &i in &self.indices[node.first..node.first + node.count] {
+                    let q = prims[i].closest_point(p);
+                    let d = q - p;
+                    if d * d < best_d2 {
+                        best_d2 = d * d;
+                        best = Some((i, q));
+                    }
+                }
+                continue;
+            }
+            let (l, r) = (node.first, node.first + 1);
+            let dl = self.nodes[l].bounds.distance_squared(p);
+            let dr = self.nodes[r].bounds.distance_squared(p);
+            // the nearer child is popped first
+            if dl <= dr {
+                stack.extend([(r, dr), (l, dl)]);
+            } else {
+                stack.extend([(l, dl), (r, dr)]);
+            }
+        }
+        best.map(|(i, q)| (i, q, crate::math::sqrt(best_d2)))
+    }
+}
+
+fn centroid_bounds(idx: &[usize], centroids: &[Vector3d]) -> Aabb {
+    idx.iter().fold(Aabb::empty(), |b, &i| b.grow(centroids[i]))
+}
+
+fn coord(v: Vector3d, axis: usize) -> f64 {
+    v.to_list()[axis]
+}
+
+// size of the left half, None to make a leaf
+fn median_split(idx: &mut [usize], centroids: &[Vector3d]) -> Option<usize> {
+    if idx.len() <= MAX_LEAF {
+        return None;
+    }
+    let axis = centroid_bounds(idx, centroids).longest_axis();
+    let mid = idx.len() / 2;
+    idx.select_nth_unstable_by(mid, |&a, &b| coord(centroids[a], axis).total_cmp(&coord(centroids[b], axis)));
+    Some(mid)
+}
+
+fn sah_split(idx: &mut [usize], boxes: &[Aabb], centroids: &[Vector3d], area: f64) -> Option<usize> {
+    let n = idx.len();
+    if n <= 1 {
+        return None;
+    }
+    let cb = centroid_bounds(idx, centroids);
+    let bin_of = |i: usize, axis: usize| {
+        let (lo, hi) = (coord(cb.min, axis), coord(cb.max, axis));
+        (((coord(centroids[i], axis) - lo) / (hi - lo) * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+    };
+    // (cost, axis, last bin of the left side)
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        if coord(cb.max, axis) <= coord(cb.min, axis) {
+            continue;
+        }
+        let mut bins = [(Aabb::empty(), 0usize); SAH_BINS];
+        for &i in idx.iter() {
+            let b = &mut bins[bin_of(i, axis)];
+            *b = (b.0.union(&boxes[i]), b.1 + 1);
+        }
+        // area times count of everything right of each split, swept backwards
+        let mut right_cost = [0.0; SAH_BINS];
+        let mut acc = (Aabb::empty(), 0);
+        for k in (1..SAH_BINS).rev() {
+            acc = (acc.0.union(&bins[k].0), acc.1 + bins[k].1);
+            right_cost[k - 1] = acc.0.surface_area() * acc.1 as f64;
+        }
+        let mut acc = (Aabb::empty(), 0);
+        for (k, bin) in bins[..SAH_BINS - 1].iter().enumerate() {
+            acc = (acc.0.union(&bin.0), acc.1 + bin.1);
+            if acc.1 == 0 || acc.1 == n {
+                continue;
+            }
+            let cost = acc.0.surface_area() * acc.1 as f64 + right_cost[k];
+            if best.is_none_or(|b| cost < b.0) {
+                best = Some((cost, axis, k));
+            }
+        }
+    }
+    let Some((cost, axis, k)) = best else {
+        // every centroid in the same spot, split arbitrarily if too big
+        return (n > MAX_SAH_LEAF).then_some(n / 2);
+    };
+    if n <= MAX_SAH_LEAF && SAH_TRAVERSAL * area + cost >= area * n as f64 {
+        return None;
+    }
+    // partition in place, left side first
+    let mut mid = 0;
+    for j in 0..n {
+        if bin_of(idx[j], axis) <= k {
+            idx.swap(j, mid);
+            mid += 1;
+        }
+    }
+    Some(mid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    fn v3(x: f64, y: f64, z: f64) -> Vector3d {
+        Vector3d::new_from([x, y, z])
+    }
+
+    // a wavy height field of 2 * 15 * 15 triangles
+    fn mesh(phase: f64) -> Vec<Triangle> {
+        let h = |i: usize, j: usize| v3(i as f64, j as f64, math::sin(i as f64 * 0.7 + phase) * math::cos(j as f64 * 0.4));
+        let mut tris = Vec::new();
+        for i in 0..15 {
+            for j in 0..15 {
+                tris.push(Triangle::new(h(i, j), h(i + 1, j), h(i + 1, j + 1)));
+                tris.push(Triangle::new(h(i, j), h(i + 1, j + 1), h(i, j + 1)));
+            }
+        }
+        tris
+    }
+
+    fn rays() -> Vec<Ray> {
+        (0..200)
+            .map(|k| {
+                let t = k as f64;
+                let o = v3(math::sin(t * 1.3) * 10.0 + 7.5, math::cos(t * 0.9) * 10.0 + 7.5, 4.0 + (k % 3) as f64);
+                let target = v3(7.5 + math::sin(t) * 9.0, 7.5 + math::cos(t * 2.1) * 9.0, -1.0);
+                Ray::new(o, target - o)
+            })
+            .collect()
+    }
+
+    fn brute_cast<P: RayCast>(prims: &[P], ray: &Ray) -> Option<f64> {
+        prims.iter().filter_map(|p| p.ray_cast(ray)).map(|h| h.t).reduce(f64::min)
+    }
+
+    fn check_rays(bvh: &Bvh, tris: &[Triangle]) {
+        let mut hits = 0;
+        for ray in rays() {
+            let expected = brute_cast(tris, &ray);
+            let got = bvh.ray_cast(tris, &ray);
+            assert_eq!(got.map(|(_, h)| h.t), expected);
+            if let Some((i, hit)) = got {
+                hits += 1;
+                assert_eq!(tris[i].ray_cast(&ray), Some(hit));
+                assert!(bvh.ray_any(tris, &ray, hit.t).is_some());
+                assert!(bvh.ray_any(tris, &ray, hit.t * 0.999).is_none());
+            } else {
+                assert!(bvh.ray_any(tris, &ray, f64::INFINITY).is_none());
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn ray_queries_match_brute_force() {
+        let tris = mesh(0.0);
+        for bvh in [Bvh::build_sah(&tris), Bvh::build_median(&tris)] {
+            assert_eq!(bvh.bounds(), tris.iter().fold(Aabb::empty(), |b, t| b.union(&t.aabb())));
+            check_rays(&bvh, &tris);
+        }
+    }
+
+    #[test]
+    fn overlap_and_nearest() {
+        let tris = mesh(0.3);
+        for bvh in [Bvh::build_sah(&tris), Bvh::build_median(&tris)] {
+            for k in 0..40 {
+                let t = k as f64;
+                let c = v3(7.5 + math::sin(t) * 9.0, 7.5 + math::cos(t * 1.7) * 9.0, math::sin(t * 0.3) * 3.0);
+                let q = Aabb::new(c - 1.2, c + 1.2);
+                let mut got = bvh.overlapping(&tris, &q);
+                got.sort();
+                let expected: Vec<usize> = (0..tris.len()).filter(|&i| q.overlaps_triangle(&tris[i])).collect();
+                assert_eq!(got, expected);
+
+                let (i, p, d) = bvh.nearest(&tris, c).unwrap();
+                let best = tris.iter().map(|tri| tri.distance(c)).fold(f64::INFINITY, f64::min);
+                assert_eq!(d, best);
+                assert_eq!(tris[i].distance(c), d);
+                assert!(((p - c).length() - d).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn refit_after_motion() {
+        let mut tris = mesh(0.0);
+        let mut bvh = Bvh::build_sah(&tris);
+        let nodes = bvh.node_count();
+        // the waves travel and the whole mesh is lifted and sheared
+        tris = mesh(1.1)
+            .into_iter()
+            .map(|t| {
+                let f = |p: Vector3d| v3(p.x + 0.3 * p.z, p.y, p.z * 1.5 + 2.0);
+                Triangle::new(f(t.a), f(t.b), f(t.c))
+            })
+            .collect();
+        bvh.refit(&tris);
+        assert_eq!(bvh.node_count(), nodes);
+        assert_eq!(bvh.bounds(), tris.iter().fold(Aabb::empty(), |b, t| b.union(&t.aabb())));
+        check_rays(&bvh, &tris);
+    }
+
+    #[test]
+    fn boxes_spheres_and_degenerate_input() {
+        let empty: [Aabb; 0] = [];
+        let bvh = Bvh::build_sah(&empty);
+        assert!(bvh.bounds().is_empty());
+        assert!(bvh.ray_cast(&empty, &Ray::new(v3(0.0, 0.0, 0.0), v3(1.0, 0.0, 0.0))).is_none());
+        assert!(bvh.nearest(&empty, v3(0.0, 0.0, 0.0)).is_none());
+
+        // many primitives sharing one centroid still get split into small leaves
+        let same = [Sphere::new(v3(1.0, 2.0, 3.0), 0.5); 100];
+        let bvh = Bvh::build_sah(&same);
+        assert!(bvh.node_count() > 1);
+        let (_, hit) = bvh.ray_cast(&same, &Ray::new(v3(1.0, 2.0, -3.0), v3(0.0, 0.0, 1.0))).unwrap();
+        assert_eq!(hit.t, 5.5);
+
+        let boxes: Vec<Aabb> = (0..64)
+            .map(|i| {
+                let c = v3((i % 4) as f64 * 3.0, (i / 4 % 4) as f64 * 3.0, (i / 16) as f64 * 3.0);
+                Aabb::new(c, c + 1.0)
+            })
+            .collect();
+        for bvh in [Bvh::build_sah(&boxes), Bvh::build_median(&boxes)] {
+            let (i, hit) = bvh.ray_cast(&boxes, &Ray::new(v3(-5.0, 3.5, 6.5), v3(1.0, 0.0, 0.0))).unwrap();
+            assert_eq!((i, hit.t), (36, 5.0));
+            // grazing the top faces of a row of boxes
+            let (i, hit) = bvh.ray_cast(&boxes, &Ray::new(v3(-5.0, 4.0, 6.5), v3(1.0, 0.0, 0.0))).unwrap();
+            assert_eq!((i, hit.t), (36, 5.0));
+            let (i, _, d) = bvh.nearest(&boxes, v3(4.5, 4.5, 4.5)).unwrap();
+            assert_eq!((i, d), (21, math::sqrt(0.75)));
+            let mut got = bvh.overlapping(&boxes, &Aabb::new(v3(3.5, 3.5, 3.5), v3(4.5, 7.0, 4.5)));
+            got.sort();
+            assert_eq!(got, [21, 25]);
+        }
+    }
+}
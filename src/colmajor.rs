@@ -0,0 +1,513 @@
+// Column-major counterparts of Matrix2d, Matrix3d and Matrix4d.
+//
+// The Matrix*d types store their entries row-major, while OpenGL, Vulkan and
+// most physics engines expect column-major data. These types keep the data in
+// column-major order so the backing array can be handed off as-is, and the
+// From conversions make the transpose explicit instead of silent.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColMatrix2d {
+    v: [f64; 4],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColMatrix3d {
+    v: [f64; 9],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColMatrix4d {
+    v: [f64; 16],
+}
+
+impl ColMatrix2d {
+    pub fn get_rows(&self) -> i32 {
+        2
+    }
+
+    pub fn get_cols(&self) -> i32 {
+        2
+    }
+
+    // v is read column by column
+    pub fn new(v: [f64; 4]) -> Self {
+        Self { v }
+    }
+
+    pub fn new_from_constant(c: f64) -> Self {
+        Self { v: [c; 4] }
+    }
+
+    pub fn from_row_major(v: [f64; 4]) -> Self {
+        Matrix2d::from_row_major(v).into()
+    }
+
+    pub fn from_col_major(v: [f64; 4]) -> Self {
+        Self { v }
+    }
+
+    pub fn to_row_major_array(&self) -> [f64; 4] {
+        Matrix2d::from(*self).to_row_major_array()
+    }
+
+    pub fn to_col_major_array(&self) -> [f64; 4] {
+        self.v
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.v[col * 2 + row]
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_row_major(self.v)
+    }
+}
+
+impl ColMatrix3d {
+    pub fn get_rows(&self) -> i32 {
+        3
+    }
+
+    pub fn get_cols(&self) -> i32 {
+        3
+    }
+
+    // v is read column by column
+    pub fn new(v: [f64; 9]) -> Self {
+        Self { v }
+    }
+
+    pub fn new_from_constant(c: f64) -> Self {
+        Self { v: [c; 9] }
+    }
+
+    pub fn from_row_major(v: [f64; 9]) -> Self {
+        Matrix3d::from_row_major(v).into()
+    }
+
+    pub fn from_col_major(v: [f64; 9]) -> Self {
+        Self { v }
+    }
+
+    pub fn to_row_major_array(&self) -> [f64; 9] {
+        Matrix3d::from(*self).to_row_major_array()
+    }
+
+    pub fn to_col_major_array(&self) -> [f64; 9] {
+        self.v
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.v[col * 3 + row]
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_row_major(self.v)
+    }
+}
+
+impl ColMatrix4d {
+    pub fn get_rows(&self) -> i32 {
+        4
+    }
+
+    pub fn get_cols(&self) -> i32 {
+        4
+    }
+
+    // v is read column by column
+    pub fn new(v: [f64; 16]) -> Self {
+        Self { v }
+    }
+
+    pub fn new_from_constant(c: f64) -> Self {
+        Self { v: [c; 16] }
+    }
+
+    pub fn from_row_major(v: [f64; 16]) -> Self {
+        Matrix4d::from_row_major(v).into()
+    }
+
+    pub fn from_col_major(v: [f64; 16]) -> Self {
+        Self { v }
+    }
+
+    pub fn to_row_major_array(&self) -> [f64; 16] {
+        Matrix4d::from(*self).to_row_major_array()
+    }
+
+    pub fn to_col_major_array(&self) -> [f64; 16] {
+        self.v
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.v[col * 4 + row]
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_row_major(self.v)
+    }
+}
+
+// conversions between the two layouts, these are the only places where data gets transposed
+impl From<Matrix2d> for ColMatrix2d {
+    fn from(m: Matrix2d) -> Self {
+        Self { v: m.to_col_major_array() }
+    }
+}
+
+impl From<ColMatrix2d> for Matrix2d {
+    fn from(m: ColMatrix2d) -> Self {
+        Matrix2d::from_col_major(m.v)
+    }
+}
+
+impl From<Matrix3d> for ColMatrix3d {
+    fn from(m: Matrix3d) -> Self {
+        Self { v: m.to_col_major_array() }
+    }
+}
+
+impl From<ColMatrix3d> for Matrix3d {
+    fn from(m: ColMatrix3d) -> Self {
+        Matrix3d::from_col_major(m.v)
+    }
+}
+
+impl From<Matrix4d> for ColMatrix4d {
+    fn from(m: Matrix4d) -> Self {
+        Self { v: m.to_col_major_array() }
+    }
+}
+
+impl From<ColMatrix4d> for Matrix4d {
+    fn from(m: ColMatrix4d) -> Self {
+        Matrix4d::from_col_major(m.v)
+    }
+}
+
+// subscripts index straight into the column-major storage
+impl core::ops::Index<usize> for ColMatrix2d {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.v[i]
+    }
+}
+
+impl core::ops::IndexMut<usize> for ColMatrix2d {
+    fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+        &mut self.v[i]
+    }
+}
+
+impl core::ops::Index<usize> for ColMatrix3d {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.v[i]
+    }
+}
+
+impl core::ops::IndexMut<usize> for ColMatrix3d {
+    fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+        &mut self.v[i]
+    }
+}
+
+impl core::ops::Index<usize> for ColMatrix4d {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.v[i]
+    }
+}
+
+impl core::ops::IndexMut<usize> for ColMatrix4d {
+    fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+        &mut self.v[i]
+    }
+}
+
+impl core::ops::Mul<ColMatrix2d> for ColMatrix2d {
+    type Output = ColMatrix2d;
+
+    fn mul(self, o: ColMatrix2d) -> Self::Output {
+        let mut v = [0.0; 4];
+        for c in 0..2 {
+            for r in 0..2 {
+                v[c * 2 + r] = self.v[r] * o.v[c * 2] + self.v[2 + r] * o.v[c * 2 + 1];
+            }
+        }
+        Self { v }
+    }
+}
+
+impl core::ops::Mul<ColMatrix3d> for ColMatrix3d {
+    type Output = ColMatrix3d;
+
+    fn mul(self, o: ColMatrix3d) -> Self::Output {
+        let mut v = [0.0; 9];
+        for c in 0..3 {
+            for r in 0..3 {
+                v[c * 3 + r] = self.v[r] * o.v[c * 3]
+                    + self.v[3 + r] * o.v[c * 3 + 1]
+                    + self.v[6 + r] * o.v[c * 3 + 2];
+            }
+        }
+        Self { v }
+    }
+}
+
+impl core::ops::Mul<ColMatrix4d> for ColMatrix4d {
+    type Output = ColMatrix4d;
+
+    fn mul(self, o: ColMatrix4d) -> Self::Output {
+        let mut v = [0.0; 16];
+        for c in 0..4 {
+            for r in 0..4 {
+                v[c * 4 + r] = self.v[r] * o.v[c * 4]
+                    + self.v[4 + r] * o.v[c * 4 + 1]
+                    + self.v[8 + r] * o.v[c * 4 + 2]
+                    + self.v[12 + r] * o.v[c * 4 + 3];
+            }
+        }
+        Self { v }
+    }
+}
+
+// the product is a linear combination of the columns
+impl core::ops::Mul<Vector2d> for ColMatrix2d {
+    type Output = Vector2d;
+
+    fn mul(self, v: Vector2d) -> Self::Output {
+        Vector2d::new_from([
+            self.v[0] * v.x + self.v[2] * v.y,
+            self.v[1] * v.x + self.v[3] * v.y,
+        ])
+    }
+}
+
+impl core::ops::Mul<Vector3d> for ColMatrix3d {
+    type Output = Vector3d;
+
+    fn mul(self, v: Vector3d) -> Self::Output {
+        Vector3d::new_from([
+            self.v[0] * v.x + self.v[3] * v.y + self.v[6] * v.z,
+            self.v[1] * v.x + self.v[4] * v.y + self.v[7] * v.z,
+            self.v[2] * v.x + self.v[5] * v.y + self.v[8] * v.z,
+        ])
+    }
+}
+
+impl core::ops::Mul<Vector4d> for ColMatrix4d {
+    type Output = Vector4d;
+
+    fn mul(self, v: Vector4d) -> Self::Output {
+        Vector4d::new_from([
+            self.v[0] * v.x + self.v[4] * v.y + self.v[8] * v.z + self.v[12] * v.w,
+            self.v[1] * v.x + self.v[5] * v.y + self.v[9] * v.z + self.v[13] * v.w,
+            self.v[2] * v.x + self.v[6] * v.y + self.v[10] * v.z + self.v[14] * v.w,
+            self.v[3] * v.x + self.v[7] * v.y + self.v[11] * v.z + self.v[15] * v.w,
+        ])
+    }
+}
+
+// element-wise operations don't care about the layout
+impl core::ops::Mul<f64> for ColMatrix2d {
+    type Output = ColMatrix2d;
+
+    fn mul(self, l: f64) -> Self::Output {
+        Self { v: self.v.map(|e| e * l) }
+    }
+}
+
+impl core::ops::Mul<f64> for ColMatrix3d {
+    type Output = ColMatrix3d;
+
+    fn mul(self, l: f64) -> Self::Output {
+        Self { v: self.v.map(|e| e * l) }
+    }
+}
+
+impl core::ops::Mul<f64> for ColMatrix4d {
+    type Output = ColMatrix4d;
+
+    fn mul(self, l: f64) -> Self::Output {
+        Self { v: self.v.map(|e| e * l) }
+    }
+}
+
+impl core::ops::Add<ColMatrix2d> for ColMatrix2d {
+    type Output = ColMatrix2d;
+
+    fn add(self, o: ColMatrix2d) -> Self::Output {
+        let mut v = self.v;
+        for (e, x) in v.iter_mut().zip(o.v) {
+            *e += x;
+        }
+        Self { v }
+    }
+}
+
+impl core::ops::Add<ColMatrix3d> for ColMatrix3d {
+    type Output = ColMatrix3d;
+
+    fn add(self, o: ColMatrix3d) -> Self::Output {
+        let mut v = self.v;
+        for (e, x) in v.iter_mut().zip(o.v) {
+            *e += x;
+        }
+        Self { v }
+    }
+}
+
+impl core::ops::Add<ColMatrix4d> for ColMatrix4d {
+    type Output = ColMatrix4d;
+
+    fn add(self, o: ColMatrix4d) -> Self::Output {
+        let mut v = self.v;
+        for (e, x) in v.iter_mut().zip(o.v) {
+            *e += x;
+        }
+        Self { v }
+    }
+}
+
+impl core::ops::Sub<ColMatrix2d> for ColMatrix2d {
+    type Output = ColMatrix2d;
+
+    fn sub(self, o: ColMatrix2d) -> Self::Output {
+        let mut v = self.v;
+        for (e, x) in v.iter_mut().zip(o.v) {
+            *e -= x;
+        }
+        Self { v }
+    }
+}
+
+impl core::ops::Sub<ColMatrix3d> for ColMatrix3d {
+    type Output = ColMatrix3d;
+
+    fn sub(self, o: ColMatrix3d) -> Self::Output {
+        let mut v = self.v;
+        for (e, x) in v.iter_mut().zip(o.v) {
+            *e -= x;
+        }
+        Self { v }
+    }
+}
+
+impl core::ops::Sub<ColMatrix4d> for ColMatrix4d {
+    type Output = ColMatrix4d;
+
+    fn sub(self, o: ColMatrix4d) -> Self::Output {
+        let mut v = self.v;
+        for (e, x) in v.iter_mut().zip(o.v) {
+            *e -= x;
+        }
+        Self { v }
+    }
+}
+
+impl PartialEq for ColMatrix2d {
+    fn eq(&self, other: &Self) -> bool {
+        self.v == other.v
+    }
+}
+
+impl Eq for ColMatrix2d {}
+
+impl PartialEq for ColMatrix3d {
+    fn eq(&self, other: &Self) -> bool {
+        self.v == other.v
+    }
+}
+
+impl Eq for ColMatrix3d {}
+
+impl PartialEq for ColMatrix4d {
+    fn eq(&self, other: &Self) -> bool {
+        self.v == other.v
+    }
+}
+
+impl Eq for ColMatrix4d {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{vec2d, vec3d, vec4d};
+
+    #[test]
+    fn col_major_storage() {
+        let m = ColMatrix2d::from_row_major([1.0, 2.0,
+                                             3.0, 4.0]);
+        assert_eq!(m.to_col_major_array(), [1.0, 3.0, 2.0, 4.0]);
+        assert_eq!(m[1], 3.0);
+        assert_eq!(m.get(1, 0), 3.0);
+        assert_eq!(m.to_row_major_array(), [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn layout_round_trip() {
+        let m = Matrix3d::new([1.0, 2.0, 3.0,
+                               4.0, 5.0, 6.0,
+                               7.0, 8.0, 9.0]);
+        let cm = ColMatrix3d::from(m);
+        for r in 0..3 {
+            for c in 0..3 {
+                assert_eq!(m.get(r, c), cm.get(r, c));
+            }
+        }
+        assert_eq!(Matrix3d::from(cm), m);
+        assert_eq!(cm.transpose(), ColMatrix3d::from(m.transpose()));
+    }
+
+    #[test]
+    fn mul_2d_same_across_layouts() {
+        let a = Matrix2d::new([1.0, 2.0, 3.0, 4.0]);
+        let b = Matrix2d::new([-1.0, 0.5, 2.0, 3.0]);
+        let v = vec2d![2.0, -3.0];
+
+        assert_eq!(ColMatrix2d::from(a) * ColMatrix2d::from(b), ColMatrix2d::from(a * b));
+        assert_eq!(ColMatrix2d::from(a) * v, a * v);
+    }
+
+    #[test]
+    fn mul_3d_same_across_layouts() {
+        let a = Matrix3d::new([1.0, 2.0, 3.0,
+                               4.0, 5.0, 6.0,
+                               7.0, 8.0, 10.0]);
+        let b = Matrix3d::new([0.5, -1.0, 2.0,
+                               3.0, 0.0, 1.0,
+                               -2.0, 4.0, 1.5]);
+        let v = vec3d![2.0, -3.0, 0.25];
+
+        assert_eq!(ColMatrix3d::from(a) * ColMatrix3d::from(b), ColMatrix3d::from(a * b));
+        assert_eq!(ColMatrix3d::from(a) * v, a * v);
+        assert_eq!(ColMatrix3d::from(a) + ColMatrix3d::from(b), ColMatrix3d::from(a + b));
+    }
+
+    #[test]
+    fn mul_4d_same_across_layouts() {
+        let mut a = Matrix4d::new_from_constant(0.0);
+        let mut b = Matrix4d::new_from_constant(0.0);
+        for i in 0..16 {
+            a[i] = i as f64 * 0.5 - 3.0;
+            b[i] = (i * i) as f64 * 0.25 - 7.0;
+        }
+        let v = vec4d![1.0, -2.0, 3.0, 0.5];
+
+        assert_eq!(ColMatrix4d::from(a) * ColMatrix4d::from(b), ColMatrix4d::from(a * b));
+        assert_eq!(ColMatrix4d::from(a) * v, a * v);
+        assert_eq!(Matrix4d::from(ColMatrix4d::from(a) * 2.0), a * 2.0);
+    }
+}
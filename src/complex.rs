@@ -0,0 +1,384 @@
+// Complex numbers, and the complex versions of the vector and matrix types.
+//
+// The vectors and matrices are the usual ones with Complex entries
+// (Vector3c = Vector3d<Complex>, ...), so all the generic operations work on
+// them. This module adds what only makes sense for complex entries: conjugates,
+// conjugate transposes, the Hermitian inner product and LU solves, plus the
+// embedding of the real types.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+use crate::math;
+use crate::scalar::{lu_solve, Scalar};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+pub type Vector2c = Vector2d<Complex>;
+pub type Vector3c = Vector3d<Complex>;
+pub type Vector4c = Vector4d<Complex>;
+pub type Matrix2c = Matrix2d<Complex>;
+pub type Matrix3c = Matrix3d<Complex>;
+pub type Matrix4c = Matrix4d<Complex>;
+
+impl Complex {
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn from_real(re: f64) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    // r e^(i theta)
+    pub fn from_polar(r: f64, theta: f64) -> Self {
+        Self {
+            re: r * math::cos(theta),
+            im: r * math::sin(theta),
+        }
+    }
+
+    pub fn conj(&self) -> Self {
+        Self {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+
+    // |z|^2
+    pub fn norm_sqr(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn abs(&self) -> f64 {
+        math::hypot(self.re, self.im)
+    }
+
+    // argument in (-pi, pi]
+    pub fn arg(&self) -> f64 {
+        math::atan2(self.im, self.re)
+    }
+
+    pub fn recip(&self) -> Self {
+        Self::from_real(1.0) / *self
+    }
+
+    pub fn exp(&self) -> Self {
+        Self::from_polar(math::exp(self.re), self.im)
+    }
+
+    // principal logarithm
+    pub fn ln(&self) -> Self {
+        Self {
+            re: math::ln(self.abs()),
+            im: self.arg(),
+        }
+    }
+
+    // principal square root, the one with a non negative real part
+    pub fn sqrt(&self) -> Self {
+        if self.re == 0.0 && self.im == 0.0 {
+            return Self::default();
+        }
+        let t = math::sqrt((self.abs() + self.re.abs()) / 2.0);
+        if self.re >= 0.0 {
+            Self::new(t, self.im / (2.0 * t))
+        } else {
+            Self::new(self.im.abs() / (2.0 * t), if self.im < 0.0 { -t } else { t })
+        }
+    }
+
+    pub fn powf(&self, p: f64) -> Self {
+        if self.re == 0.0 && self.im == 0.0 {
+            return Self::default();
+        }
+        (self.ln() * p).exp()
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::from_real(re)
+    }
+}
+
+impl core::ops::Add<Complex> for Complex {
+    type Output = Complex;
+    fn add(self, o: Complex) -> Self::Output {
+        Self::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl core::ops::Sub<Complex> for Complex {
+    type Output = Complex;
+    fn sub(self, o: Complex) -> Self::Output {
+        Self::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl core::ops::Mul<Complex> for Complex {
+    type Output = Complex;
+    fn mul(self, o: Complex) -> Self::Output {
+        Self::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re)
+    }
+}
+
+// Smith's algorithm, avoids the overflow of |o|^2 for large denominators
+impl core::ops::Div<Complex> for Complex {
+    type Output = Complex;
+    fn div(self, o: Complex) -> Self::Output {
+        if o.re.abs() >= o.im.abs() {
+            let r = o.im / o.re;
+            let d = o.re + o.im * r;
+            Self::new((self.re + self.im * r) / d, (self.im - self.re * r) / d)
+        } else {
+            let r = o.re / o.im;
+            let d = o.re * r + o.im;
+            Self::new((self.re * r + self.im) / d, (self.im * r - self.re) / d)
+        }
+    }
+}
+
+impl core::ops::Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Self::Output {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl core::ops::Add<f64> for Complex {
+    type Output = Complex;
+    fn add(self, o: f64) -> Self::Output {
+        Self::new(self.re + o, self.im)
+    }
+}
+
+impl core::ops::Sub<f64> for Complex {
+    type Output = Complex;
+    fn sub(self, o: f64) -> Self::Output {
+        Self::new(self.re - o, self.im)
+    }
+}
+
+impl core::ops::Mul<f64> for Complex {
+    type Output = Complex;
+    fn mul(self, o: f64) -> Self::Output {
+        Self::new(self.re * o, self.im * o)
+    }
+}
+
+impl core::ops::Div<f64> for Complex {
+    type Output = Complex;
+    fn div(self, o: f64) -> Self::Output {
+        Self::new(self.re / o, self.im / o)
+    }
+}
+
+impl core::ops::Mul<Complex> for f64 {
+    type Output = Complex;
+    fn mul(self, o: Complex) -> Self::Output {
+        o * self
+    }
+}
+
+impl Scalar for Complex {
+    fn zero() -> Self {
+        Self::default()
+    }
+
+    fn one() -> Self {
+        Self::from_real(1.0)
+    }
+}
+
+macro_rules! impl_complex_vector {
+    ($v:ident, $n:expr) => {
+        impl $v<Complex> {
+            pub fn from_parts(re: $v, im: $v) -> Self {
+                let (re, im) = (re.to_list(), im.to_list());
+                $v::new_from(core::array::from_fn(|i| Complex::new(re[i], im[i])))
+            }
+
+            pub fn re(&self) -> $v {
+                $v::new_from(self.to_list().map(|z| z.re))
+            }
+
+            pub fn im(&self) -> $v {
+                $v::new_from(self.to_list().map(|z| z.im))
+            }
+
+            pub fn conj(&self) -> Self {
+                $v::new_from(self.to_list().map(|z| z.conj()))
+            }
+
+            // Hermitian inner product <self, o> = sum conj(self_i) o_i. The `*`
+            // operator stays the bilinear sum self_i o_i.
+            pub fn inner(&self, o: &Self) -> Complex {
+                self.conj() * *o
+            }
+
+            // Euclidean norm, sqrt(<self, self>)
+            pub fn norm(&self) -> f64 {
+                math::sqrt(self.to_list().iter().map(|z| z.norm_sqr()).sum::<f64>())
+            }
+        }
+
+        impl From<$v> for $v<Complex> {
+            fn from(v: $v) -> Self {
+                $v::new_from(v.to_list().map(Complex::from_real))
+            }
+        }
+    };
+}
+
+macro_rules! impl_complex_matrix {
+    ($m:ident, $v:ident, $n:expr) => {
+        impl $m<Complex> {
+            pub fn from_parts(re: $m, im: $m) -> Self {
+                let (re, im) = (re.to_row_major_array(), im.to_row_major_array());
+                $m::from_row_major(core::array::from_fn(|i| Complex::new(re[i], im[i])))
+            }
+
+            pub fn re(&self) -> $m {
+                $m::from_row_major(self.to_row_major_array().map(|z| z.re))
+            }
+
+            pub fn im(&self) -> $m {
+                $m::from_row_major(self.to_row_major_array().map(|z| z.im))
+            }
+
+            pub fn conj(&self) -> Self {
+                $m::from_row_major(self.to_row_major_array().map(|z| z.conj()))
+            }
+
+            // A^H, the adjoint
+            pub fn conjugate_transpose(&self) -> Self {
+                self.conj().transpose()
+            }
+
+            // A == A^H, up to tol on every entry
+            pub fn is_hermitian(&self, tol: f64) -> bool {
+                let h = self.conjugate_transpose().to_row_major_array();
+                self.to_row_major_array().iter().zip(h.iter()).all(|(a, b)| (*a - *b).abs() <= tol)
+            }
+
+            // x such that A x = b, by LU with partial pivoting. None when A is singular.
+            pub fn solve(&self, b: $v<Complex>) -> Option<$v<Complex>> {
+                let v = self.to_row_major_array();
+                let a = core::array::from_fn(|i| core::array::from_fn(|j| v[i * $n + j]));
+                lu_solve::<Complex, $n>(a, b.to_list(), |z| z.abs()).map($v::new_from)
+            }
+        }
+
+        impl From<$m> for $m<Complex> {
+            fn from(m: $m) -> Self {
+                $m::from_row_major(m.to_row_major_array().map(Complex::from_real))
+            }
+        }
+    };
+}
+
+impl_complex_vector!(Vector2d, 2);
+impl_complex_vector!(Vector3d, 3);
+impl_complex_vector!(Vector4d, 4);
+impl_complex_matrix!(Matrix2d, Vector2d, 2);
+impl_complex_matrix!(Matrix3d, Vector3d, 3);
+impl_complex_matrix!(Matrix4d, Vector4d, 4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Complex, b: Complex) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn arithmetic_and_functions() {
+        let a = Complex::new(3.0, -4.0);
+        let b = Complex::new(1.0, 2.0);
+        assert_eq!(a + b, Complex::new(4.0, -2.0));
+        assert_eq!(a * b, Complex::new(11.0, 2.0));
+        assert!(close(a / b * b, a));
+        assert!(close(Complex::new(1e300, 1e300) / Complex::new(1e300, 1e300), Complex::from_real(1.0)));
+        assert_eq!(a.abs(), 5.0);
+        assert_eq!(Complex::I * Complex::I, Complex::from_real(-1.0));
+
+        assert!(close(a.sqrt() * a.sqrt(), a));
+        assert!(a.sqrt().re >= 0.0);
+        assert!(close(Complex::from_real(-4.0).sqrt(), Complex::new(0.0, 2.0)));
+        assert!(close(a.ln().exp(), a));
+        assert!(close(Complex::new(0.0, core::f64::consts::PI).exp(), Complex::from_real(-1.0)));
+        assert!(close(b.powf(3.0), b * b * b));
+    }
+
+    #[test]
+    fn inner_product_and_conjugate() {
+        let u = Vector3c::from_parts(Vector3d::new_from([1.0, 0.0, 2.0]), Vector3d::new_from([1.0, -1.0, 0.0]));
+        let v = Vector3c::new_from([Complex::new(0.0, 1.0), Complex::from_real(2.0), Complex::new(1.0, 1.0)]);
+        // conjugate symmetric, and <u, u> is |u|^2
+        assert!(close(u.inner(&v), v.inner(&u).conj()));
+        assert!(close(u.inner(&u), Complex::from_real(u.norm() * u.norm())));
+        assert!((u.norm() - math::sqrt(7.0)).abs() < 1e-12);
+        assert_eq!(u.conj().conj(), u);
+        assert_eq!(u.re(), Vector3d::new_from([1.0, 0.0, 2.0]));
+    }
+
+    #[test]
+    fn hermitian_matrices() {
+        let h = Matrix2c::new([
+            Complex::from_real(2.0), Complex::new(1.0, -1.0),
+            Complex::new(1.0, 1.0), Complex::from_real(3.0),
+        ]);
+        assert!(h.is_hermitian(0.0));
+        assert!(!(h * Complex::I).is_hermitian(1e-12));
+        assert_eq!(h.conjugate_transpose(), h);
+
+        // A^H A is always Hermitian
+        let a = Matrix3c::from_parts(
+            Matrix3d::new([1.0, 2.0, 0.0, 0.0, 1.0, -1.0, 3.0, 0.0, 1.0]),
+            Matrix3d::new([0.0, 1.0, 1.0, -2.0, 0.0, 0.5, 0.0, 1.0, 0.0]),
+        );
+        assert!((a.conjugate_transpose() * a).is_hermitian(1e-12));
+    }
+
+    #[test]
+    fn solve_ac_circuit() {
+        // nodal admittance matrix of a small network, Y = G + jB
+        let y = Matrix3c::from_parts(
+            Matrix3d::new([2.0, -1.0, 0.0, -1.0, 3.0, -1.0, 0.0, -1.0, 2.0]),
+            Matrix3d::new([-5.0, 2.0, 0.0, 2.0, -4.0, 1.0, 0.0, 1.0, -3.0]),
+        );
+        let i = Vector3c::new_from([Complex::from_real(1.0), Complex::default(), Complex::new(0.0, -0.5)]);
+        let v = y.solve(i).unwrap();
+        let r = y * v - i;
+        assert!(r.norm() < 1e-12);
+
+        let singular = Matrix2c::from(Matrix2d::new([1.0, 2.0, 2.0, 4.0])) * Complex::new(1.0, 1.0);
+        assert!(singular.solve(Vector2c::new_from([Complex::one(), Complex::one()])).is_none());
+    }
+
+    #[test]
+    fn embedding_commutes_with_products() {
+        let a = Matrix4d::new([
+            1.0, 2.0, 0.0, 1.0,
+            0.0, 1.0, 3.0, 0.0,
+            2.0, 0.0, 1.0, 1.0,
+            1.0, 1.0, 0.0, 2.0,
+        ]);
+        let v = Vector4d::new_from([1.0, -1.0, 2.0, 0.5]);
+        assert_eq!(Matrix4c::from(a) * Matrix4c::from(a), Matrix4c::from(a * a));
+        assert_eq!(Matrix4c::from(a) * Vector4c::from(v), Vector4c::from(a * v));
+        assert_eq!(Matrix4c::from(a).re(), a);
+        assert_eq!(Matrix4c::from(a).im(), Matrix4d::new_from_constant(0.0));
+    }
+}
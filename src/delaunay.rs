@@ -0,0 +1,541 @@
+// Delaunay triangulation of 2d points, optionally with constrained edges, and
+// the dual Voronoi diagram.
+//
+// Points are inserted one at a time with Bowyer–Watson: every triangle whose
+// circumcircle holds the new point is removed and the hole is fanned from
+// the point. The outside of the convex hull is covered with "ghost" triangles
+// sharing a vertex at infinity, whose circumcircle is the open half-plane
+// beyond their hull edge, so points outside the current hull need no
+// special case and no bounding super-triangle distorts the hull. All
+// decisions use the exact predicates.
+
+use super::predicates::*;
+use super::vector2d::*;
+use alloc::vec::Vec;
+
+// the vertex at infinity of the ghost triangles
+const GHOST: usize = usize::MAX;
+
+// during construction, n[i] is the triangle across the edge opposite v[i]
+#[derive(Debug, Clone, Copy)]
+struct Tri {
+    v: [usize; 3],
+    n: [usize; 3],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Delaunay {
+    points: Vec<Vector2d>,
+    // counter-clockwise
+    triangles: Vec<[usize; 3]>,
+    neighbors: Vec<[Option<usize>; 3]>,
+    // (min, max) vertex pairs
+    constraints: Vec<(usize, usize)>,
+    // the first point at the same position, the point itself if unique
+    representative: Vec<usize>,
+}
+
+fn strictly_between(a: Vector2d, b: Vector2d, p: Vector2d) -> bool {
+    let d = b - a;
+    let t = (p - a) * d;
+    t > 0.0 && t < d * d
+}
+
+struct Builder<'a> {
+    points: &'a [Vector2d],
+    tris: Vec<Tri>,
+    // cavity membership, stamped with the index of the point being inserted
+    mark: Vec<usize>,
+    last: usize,
+}
+
+impl Builder<'_> {
+    fn is_ghost(&self, t: usize) -> bool {
+        self.tris[t].v.contains(&GHOST)
+    }
+
+    fn in_conflict(&self, t: usize, p: Vector2d) -> bool {
+        let v = self.tris[t].v;
+        let pt = |i: usize| self.points[v[i % 3]];
+        match v.iter().position(|&x| x == GHOST) {
+            None => incircle(pt(0), pt(1), pt(2), p) > 0.0,
+            Some(k) => {
+                let (a, b) = (pt(k + 1), pt(k + 2));
+                let o = orient2d(a, b, p);
+                o > 0.0 || (o == 0.0 && strictly_between(a, b, p))
+            }
+        }
+    }
+
+    // a triangle in conflict with p by walking towards it, or the vertex at p
+    fn locate(&self, p: Vector2d) -> Result<usize, usize> {
+        let mut t = self.last;
+        loop {
+            if self.is_ghost(t) {
+                return Ok(t);
+            }
+            let Tri { v, n } = self.tris[t];
+            if let Some(&dup) = v.iter().find(|&&x| self.points[x] == p) {
+                return Err(dup);
+            }
+            let step = (0..3).find(|&i| orient2d(self.points[v[(i + 1) % 3]], self.points[v[(i + 2) % 3]], p) < 0.0);
+            match step {
+                Some(i) => t = n[i],
+                None => return Ok(t),
+            }
+        }
+    }
+
+    fn insert(&mut self, pi: usize) -> Result<(), usize> {
+        let p = self.points[pi];
+        let t0 = self.locate(p)?;
+        self.mark[t0] = pi;
+        let mut cavity = alloc::vec![t0];
+        // (a, b, triangle outside, cavity triangle inside) with a -> b
+        // counter-clockwise around the cavity
+        let mut boundary: Vec<(usize, usize, usize, usize)> = Vec::new();
+        let mut k = 0;
+        while k < cavity.len() {
+            let t = cavity[k];
+            k += 1;
+            let Tri { v, n } = self.tris[t];
+            for i in 0..3 {
+                let u = n[i];
+                if self.mark[u] == pi {
+                    continue;
+                }
+                if self.in_conflict(u, p) {
+                    self.mark[u] = pi;
+                    cavity.push(u);
+                } else {
+                    boundary.push((v[(i + 1) % 3], v[(i + 2) % 3], u, t));
+                }
+            }
+        }
+        // fan the cavity from p, reusing its slots; there are always two
+        // more boundary edges than cavity triangles
+        let mut fan: Vec<usize> = Vec::with_capacity(boundary.len());
+        for (j, &(a, b, u, old)) in boundary.iter().enumerate() {
+            let tri = Tri { v: [a, b, pi], n: [0, 0, u] };
+            let id = if j < cavity.len() {
+                self.tris[cavity[j]] = tri;
+                cavity[j]
+            } else {
+                self.tris.push(tri);
+                self.mark.push(usize::MAX);
+                self.tris.len() - 1
+            };
+            let slot = self.tris[u].n.iter().position(|&x| x == old).unwrap();
+            self.tris[u].n[slot] = id;
+            fan.push(id);
+        }
+        // [a, b, p] meets [b, c, p] along b - p
+        for &id in &fan {
+            let b = self.tris[id].v[1];
+            let next = *fan.iter().find(|&&f| self.tris[f].v[0] == b).unwrap();
+            self.tris[id].n[0] = next;
+            self.tris[next].n[1] = id;
+        }
+        self.last = *fan.iter().find(|&&f| !self.is_ghost(f)).unwrap_or(&fan[0]);
+        Ok(())
+    }
+}
+
+impl Delaunay {
+    pub fn new(points: &[Vector2d]) -> Self {
+        let mut representative: Vec<usize> = (0..points.len()).collect();
+        let mut d = Self { points: points.to_vec(), ..Self::default() };
+        // the first three points not on a line start the triangulation
+        let Some(i1) = (1..points.len()).find(|&i| points[i] != points[0]) else {
+            d.representative = alloc::vec![0; points.len()];
+            return d;
+        };
+        let Some(i2) = (i1 + 1..points.len()).find(|&i| orient2d(points[0], points[i1], points[i]) != 0.0) else {
+            d.representative = collinear_representatives(points);
+            return d;
+        };
+        let v = if orient2d(points[0], points[i1], points[i2]) > 0.0 { [0, i1, i2] } else { [0, i2, i1] };
+        // the triangle and the ghost beyond each of its edges
+        let mut tris = alloc::vec![Tri { v, n: [1, 2, 3] }];
+        for k in 0..3 {
+            let (a, b) = (v[(k + 2) % 3], v[(k + 1) % 3]);
+            // ghost k + 1 is [a, b, GHOST], next to the ghosts sharing a and b
+            tris.push(Tri { v: [a, b, GHOST], n: [1 + (k + 2) % 3, 1 + (k + 1) % 3, 0] });
+        }
+        let mut builder = Builder { points, tris, mark: alloc::vec![usize::MAX; 4], last: 0 };
+        for i in 1..points.len() {
+            if i == i1 || i == i2 {
+                continue;
+            }
+            if let Err(dup) = builder.insert(i) {
+                representative[i] = representative[dup];
+            }
+        }
+        d.triangles = builder.tris.iter().filter(|t| !t.v.contains(&GHOST)).map(|t| t.v).collect();
+        d.representative = representative;
+        d.neighbors = build_neighbors(&d.triangles);
+        d
+    }
+
+    // Triangulation in which every given edge appears, Delaunay wherever the
+    // constraints allow. Constrained edges may share endpoints but must not
+    // cross each other; an edge running through other points is split there.
+    pub fn with_constraints(points: &[Vector2d], edges: &[(usize, usize)]) -> Self {
+        let mut d = Self::new(points);
+        for &(i, j) in edges {
+            d.insert_constraint(d.representative[i], d.representative[j]);
+        }
+        d.neighbors = build_neighbors(&d.triangles);
+        d
+    }
+
+    fn insert_constraint(&mut self, i: usize, j: usize) {
+        let (pi, pj) = (self.points[i], self.points[j]);
+        if i == j {
+            return;
+        }
+        // split at the point on the segment nearest to i
+        let on_segment = (0..self.points.len())
+            .filter(|&k| self.representative[k] == k && orient2d(pi, pj, self.points[k]) == 0.0)
+            .filter(|&k| strictly_between(pi, pj, self.points[k]))
+            .min_by(|&a, &b| ((self.points[a] - pi) * (self.points[a] - pi)).total_cmp(&((self.points[b] - pi) * (self.points[b] - pi))));
+        if let Some(k) = on_segment {
+            self.insert_constraint(i, k);
+            self.insert_constraint(k, j);
+            return;
+        }
+        let key = (i.min(j), i.max(j));
+        if !self.constraints.contains(&key) {
+            self.constraints.push(key);
+        }
+        let crosses = |a: usize, b: usize| {
+            let (pa, pb) = (self.points[a], self.points[b]);
+            orient2d(pi, pj, pa) * orient2d(pi, pj, pb) < 0.0 && orient2d(pa, pb, pi) * orient2d(pa, pb, pj) < 0.0
+        };
+        let cavity: Vec<usize> = (0..self.triangles.len())
+            .filter(|&t| {
+                let v = self.triangles[t];
+                (0..3).any(|e| crosses(v[e], v[(e + 1) % 3]))
+            })
+            .collect();
+        if cavity.is_empty() {
+            // already an edge
+            return;
+        }
+        for &t in &cavity {
+            let v = self.triangles[t];
+            for e in 0..3 {
+                let (a, b) = (v[e], v[(e + 1) % 3]);
+                assert!(!(crosses(a, b) && self.is_constrained(a, b)), "constrained edges must not cross");
+            }
+        }
+        // walk the cavity boundary counter-clockwise from i: first the
+        // points right of i -> j up to j, then the ones left of it back to i
+        let edges: Vec<(usize, usize)> = cavity.iter().flat_map(|&t| {
+            let v = self.triangles[t];
+            [(v[0], v[1]), (v[1], v[2]), (v[2], v[0])]
+        }).collect();
+        let next = |a: usize| edges.iter().find(|&&(x, y)| x == a && !edges.contains(&(y, x))).unwrap().1;
+        let mut chain = alloc::vec![i];
+        let mut cur = next(i);
+        while cur != i {
+            chain.push(cur);
+            cur = next(cur);
+        }
+        let at_j = chain.iter().position(|&v| v == j).unwrap();
+        let mut left: Vec<usize> = chain[at_j + 1..].to_vec();
+        left.reverse();
+        let mut right: Vec<usize> = chain[1..at_j].to_vec();
+        right.reverse();
+        for &t in cavity.iter().rev() {
+            self.triangles.swap_remove(t);
+        }
+        fill_pseudo_polygon(&self.points, i, j, &left, &mut self.triangles);
+        fill_pseudo_polygon(&self.points, j, i, &right, &mut self.triangles);
+    }
+
+    pub fn points(&self) -> &[Vector2d] {
+        &self.points
+    }
+
+    // counter-clockwise vertex indices
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    // neighbors()[t][i] is the triangle across the edge opposite
+    // triangles()[t][i], None on the convex hull
+    pub fn neighbors(&self) -> &[[Option<usize>; 3]] {
+        &self.neighbors
+    }
+
+    pub fn is_constrained(&self, a: usize, b: usize) -> bool {
+        self.constraints.contains(&(a.min(b), a.max(b)))
+    }
+
+    // the triangle holding p by walking across the edges that face it, None
+    // outside the triangulation
+    pub fn locate(&self, p: Vector2d) -> Option<usize> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+        let contains = |t: usize| {
+            let v = self.triangles[t];
+            (0..3).all(|i| orient2d(self.points[v[(i + 1) % 3]], self.points[v[(i + 2) % 3]], p) >= 0.0)
+        };
+        let mut t = 0;
+        // walks can cycle next to constrained edges, fall back to a scan
+        for _ in 0..self.triangles.len() {
+            let v = self.triangles[t];
+            let step = (0..3).find(|&i| orient2d(self.points[v[(i + 1) % 3]], self.points[v[(i + 2) % 3]], p) < 0.0);
+            match step {
+                None => return Some(t),
+                Some(i) => t = self.neighbors[t][i]?,
+            }
+        }
+        (0..self.triangles.len()).find(|&t| contains(t))
+    }
+
+    // Voronoi cell of every point clipped to the box, as counter-clockwise
+    // polygons; duplicate points share a cell. Constrained edges are ignored,
+    // these are the cells of the points alone.
+    pub fn voronoi(&self, min: Vector2d, max: Vector2d) -> Vec<Vec<Vector2d>> {
+        let unconstrained;
+        let d = if self.constraints.is_empty() {
+            self
+        } else {
+            unconstrained = Delaunay::new(&self.points);
+            &unconstrained
+        };
+        let n = d.points.len();
+        // sites sharing a Delaunay edge
+        let mut adjacent: Vec<Vec<usize>> = alloc::vec![Vec::new(); n];
+        for t in &d.triangles {
+            for e in 0..3 {
+                let (a, b) = (t[e], t[(e + 1) % 3]);
+                if !adjacent[a].contains(&b) {
+                    adjacent[a].push(b);
+                    adjacent[b].push(a);
+                }
+            }
+        }
+        if d.triangles.is_empty() {
+            // collinear: the neighbours along the line
+            let mut order: Vec<usize> = (0..n).filter(|&i| d.representative[i] == i).collect();
+            order.sort_by(|&a, &b| d.points[a].x.total_cmp(&d.points[b].x).then(d.points[a].y.total_cmp(&d.points[b].y)));
+            for w in order.windows(2) {
+                adjacent[w[0]].push(w[1]);
+                adjacent[w[1]].push(w[0]);
+            }
+        }
+        let bounds = [min, Vector2d::new_from([max.x, min.y]), max, Vector2d::new_from([min.x, max.y])];
+        let mut cells: Vec<Vec<Vector2d>> = alloc::vec![Vec::new(); n];
+        for i in (0..n).filter(|&i| d.representative[i] == i) {
+            let site = d.points[i];
+            cells[i] = adjacent[i].iter().fold(bounds.to_vec(), |cell, &k| clip_bisector(&cell, site, d.points[k]));
+        }
+        for i in 0..n {
+            if d.representative[i] != i {
+                cells[i] = cells[d.representative[i]].clone();
+            }
+        }
+        cells
+    }
+}
+
+// every point of a collinear set is its own representative except repeats
+fn collinear_representatives(points: &[Vector2d]) -> Vec<usize> {
+    (0..points.len()).map(|i| (0..=i).find(|&j| points[j] == points[i]).unwrap()).collect()
+}
+
+// the part of the convex polygon closer to site than to other
+fn clip_bisector(poly: &[Vector2d], site: Vector2d, other: Vector2d) -> Vec<Vector2d> {
+    let (n, m) = (other - site, (site + other) * 0.5);
+    let f = |x: Vector2d| (x - m) * n;
+    let mut out = Vec::with_capacity(poly.len() + 1);
+    for (k, &p) in poly.iter().enumerate() {
+        let q = poly[(k + 1) % poly.len()];
+        let (fp, fq) = (f(p), f(q));
+        if fp <= 0.0 {
+            out.push(p);
+        }
+        if (fp < 0.0 && fq > 0.0) || (fp > 0.0 && fq < 0.0) {
+            out.push(p + (q - p) * (fp / (fp - fq)));
+        }
+    }
+    out
+}
+
+// triangulates the polygon closed by the edge a -> b, whose other vertices
+// are left of it and given in order from a to b (Anglada)
+fn fill_pseudo_polygon(points: &[Vector2d], a: usize, b: usize, verts: &[usize], out: &mut Vec<[usize; 3]>) {
+    if verts.is_empty() {
+        return;
+    }
+    let mut c = 0;
+    for k in 1..verts.len() {
+        if incircle(points[a], points[b], points[verts[c]], points[verts[k]]) > 0.0 {
+            c = k;
+        }
+    }
+    fill_pseudo_polygon(points, a, verts[c], &verts[..c], out);
+    fill_pseudo_polygon(points, verts[c], b, &verts[c + 1..], out);
+    out.push([a, b, verts[c]]);
+}
+
+fn build_neighbors(triangles: &[[usize; 3]]) -> Vec<[Option<usize>; 3]> {
+    // (low vertex, high vertex, triangle, opposite corner) sorted by edge
+    let mut edges: Vec<(usize, usize, usize, usize)> = Vec::with_capacity(triangles.len() * 3);
+    for (t, v) in triangles.iter().enumerate() {
+        for i in 0..3 {
+            let (a, b) = (v[(i + 1) % 3], v[(i + 2) % 3]);
+            edges.push((a.min(b), a.max(b), t, i));
+        }
+    }
+    edges.sort_unstable();
+    let mut neighbors = alloc::vec![[None; 3]; triangles.len()];
+    for w in edges.windows(2) {
+        if (w[0].0, w[0].1) == (w[1].0, w[1].1) {
+            neighbors[w[0].2][w[0].3] = Some(w[1].2);
+            neighbors[w[1].2][w[1].3] = Some(w[0].2);
+        }
+    }
+    neighbors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+    use crate::polygon::{convex_hull, signed_area};
+
+    fn v2(x: f64, y: f64) -> Vector2d {
+        Vector2d::new_from([x, y])
+    }
+
+    fn scattered(n: usize) -> Vec<Vector2d> {
+        (0..n).map(|i| {
+            let t = i as f64;
+            v2(math::sin(t * 12.9898) * 10.0, math::cos(t * 78.233) * 10.0)
+        }).collect()
+    }
+
+    fn area(d: &Delaunay) -> f64 {
+        d.triangles().iter().map(|t| signed_area(&t.map(|i| d.points()[i]))).sum()
+    }
+
+    fn check_structure(d: &Delaunay) {
+        let p = d.points();
+        for (t, v) in d.triangles().iter().enumerate() {
+            assert!(orient2d(p[v[0]], p[v[1]], p[v[2]]) > 0.0);
+            for i in 0..3 {
+                if let Some(u) = d.neighbors()[t][i] {
+                    // the neighbour shares the edge and points back
+                    let (a, b) = (v[(i + 1) % 3], v[(i + 2) % 3]);
+                    assert!(d.triangles()[u].contains(&a) && d.triangles()[u].contains(&b));
+                    assert!(d.neighbors()[u].contains(&Some(t)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_circumcircles() {
+        let points = scattered(300);
+        let d = Delaunay::new(&points);
+        let hull = convex_hull(&points);
+        // 2n - h - 2 triangles covering the hull
+        assert_eq!(d.triangles().len(), 2 * points.len() - hull.len() - 2);
+        assert!((area(&d) - signed_area(&hull)).abs() < 1e-9);
+        check_structure(&d);
+        for t in d.triangles() {
+            let [a, b, c] = t.map(|i| points[i]);
+            assert!(points.iter().all(|&p| incircle(a, b, c, p) <= 0.0));
+        }
+        // walking to a point lands in the triangle that holds it
+        for q in [v2(0.1, 0.2), v2(-3.0, 4.0), v2(5.5, -2.5)] {
+            let t = d.locate(q).unwrap();
+            let [a, b, c] = d.triangles()[t].map(|i| points[i]);
+            assert!(orient2d(a, b, q) >= 0.0 && orient2d(b, c, q) >= 0.0 && orient2d(c, a, q) >= 0.0);
+        }
+        assert_eq!(d.locate(v2(50.0, 0.0)), None);
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        // a lattice is full of cocircular quadruples, collinear hull edges and
+        // repeats
+        let mut points: Vec<Vector2d> = (0..64).map(|i| v2((i % 8) as f64, (i / 8) as f64)).collect();
+        points.extend([v2(3.0, 3.0), v2(0.0, 0.0)]);
+        let d = Delaunay::new(&points);
+        assert_eq!(d.triangles().len(), 2 * 7 * 7);
+        assert_eq!(area(&d), 49.0);
+        check_structure(&d);
+
+        let line: Vec<Vector2d> = (0..5).map(|i| v2(i as f64, 2.0 * i as f64)).collect();
+        assert!(Delaunay::new(&line).triangles().is_empty());
+        assert!(Delaunay::new(&[v2(1.0, 1.0); 4]).triangles().is_empty());
+        // points outside the first triangle and on the extension of its edges
+        let d = Delaunay::new(&[v2(0.0, 0.0), v2(1.0, 0.0), v2(0.0, 1.0), v2(2.0, 0.0), v2(-1.0, 0.0), v2(0.5, 0.5), v2(3.0, 3.0)]);
+        check_structure(&d);
+        assert!((area(&d) - signed_area(&convex_hull(d.points()))).abs() < 1e-12);
+    }
+
+    #[test]
+    fn constrained_edges() {
+        // a slanted row of points: the long edge 0 - 1 is not Delaunay
+        let mut points = alloc::vec![v2(0.0, 0.0), v2(10.0, 0.5)];
+        for k in 1..10 {
+            points.push(v2(k as f64, if k % 2 == 0 { 1.0 } else { -1.0 }));
+        }
+        points.extend([v2(5.0, 5.0), v2(5.0, -5.0)]);
+        assert!(!Delaunay::new(&points).triangles().iter().any(|t| t.contains(&0) && t.contains(&1)));
+        let d = Delaunay::with_constraints(&points, &[(0, 1)]);
+        check_structure(&d);
+        assert!(d.is_constrained(1, 0));
+        assert!(d.triangles().iter().any(|t| t.contains(&0) && t.contains(&1)));
+        assert_eq!(d.triangles().len(), Delaunay::new(&points).triangles().len());
+        assert!((area(&d) - signed_area(&convex_hull(&points))).abs() < 1e-9);
+
+        // through lattice points on the way: split at each of them
+        let grid: Vec<Vector2d> = (0..25).map(|i| v2((i % 5) as f64, (i / 5) as f64)).collect();
+        let d = Delaunay::with_constraints(&grid, &[(0, 24), (1, 9)]);
+        check_structure(&d);
+        assert!(!d.is_constrained(0, 24));
+        for (a, b) in [(0, 6), (6, 12), (12, 18), (18, 24), (1, 9)] {
+            assert!(d.is_constrained(a, b));
+            assert!(d.triangles().iter().any(|t| t.contains(&a) && t.contains(&b)));
+        }
+        assert_eq!(d.triangles().len(), 32);
+        assert_eq!(area(&d), 16.0);
+    }
+
+    #[test]
+    fn voronoi_cells() {
+        let grid: Vec<Vector2d> = (0..9).map(|i| v2((i % 3) as f64, (i / 3) as f64)).collect();
+        let d = Delaunay::new(&grid);
+        let cells = d.voronoi(v2(-0.5, -0.5), v2(2.5, 2.5));
+        // unit squares around each site
+        for (i, cell) in cells.iter().enumerate() {
+            assert!((signed_area(cell) - 1.0).abs() < 1e-12);
+            let c = cell.iter().fold(Vector2d::new(), |s, &p| s + p) / cell.len() as f64;
+            assert!((c - grid[i]).length() < 1e-12);
+        }
+
+        let points = scattered(60);
+        let d = Delaunay::new(&points);
+        let cells = d.voronoi(v2(-12.0, -12.0), v2(12.0, 12.0));
+        let total: f64 = cells.iter().map(|c| signed_area(c)).sum();
+        assert!((total - 24.0 * 24.0).abs() < 1e-9);
+        // a sample point belongs to the cell of its nearest site
+        for k in 0..100 {
+            let q = v2(math::sin(k as f64 * 3.3) * 11.0, math::cos(k as f64 * 1.9) * 11.0);
+            let nearest = (0..points.len()).min_by(|&a, &b| ((points[a] - q).length()).total_cmp(&(points[b] - q).length())).unwrap();
+            assert_ne!(crate::polygon::point_in_polygon(q, &cells[nearest]), crate::polygon::PointLocation::Outside);
+        }
+        // collinear sites give strips
+        let line = [v2(0.0, 0.0), v2(1.0, 0.0), v2(3.0, 0.0)];
+        let cells = Delaunay::new(&line).voronoi(v2(-1.0, -1.0), v2(4.0, 1.0));
+        assert_eq!(cells.iter().map(|c| signed_area(c)).collect::<Vec<_>>(), [3.0, 3.0, 4.0]);
+    }
+}
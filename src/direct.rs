@@ -0,0 +1,1029 @@
+// Sparse direct solvers: simplicial Cholesky for symmetric positive definite
+// matrices and left-looking LU with partial pivoting for general ones.
+//
+// Both are split in a symbolic phase (fill-reducing ordering, elimination
+// tree, memory layout) that only looks at the sparsity pattern, and a numeric
+// phase. A symbolic analysis can be reused to factor any matrix with the same
+// pattern, which is the cheap path when only the values change.
+//
+// Permutations follow perm[new] = old. The factorizations take CSR input,
+// CSC matrices go through to_csr first.
+
+use super::sparse::*;
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+const NONE: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ordering {
+    // keep the matrix as it is
+    Natural,
+    // reverse Cuthill-McKee, reduces the bandwidth
+    #[default]
+    ReverseCuthillMcKee,
+    // greedy minimum degree on the elimination graph, usually less fill than
+    // RCM but slower to compute
+    MinimumDegree,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactorError {
+    NotSquare,
+    // the matrix doesn't have the pattern the symbolic analysis was done on
+    PatternMismatch,
+    // no entry can be used as pivot for this column whatever the values
+    StructurallySingular { column: usize },
+    // all the candidate pivots for this column are zero
+    NumericallySingular { column: usize },
+    // a non-positive pivot came up in the Cholesky factorization
+    NotPositiveDefinite { column: usize },
+}
+
+impl core::fmt::Display for FactorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FactorError::NotSquare => write!(f, "matrix is not square"),
+            FactorError::PatternMismatch => write!(f, "matrix pattern differs from the analyzed one"),
+            FactorError::StructurallySingular { column } => write!(f, "matrix is structurally singular at column {}", column),
+            FactorError::NumericallySingular { column } => write!(f, "matrix is numerically singular at column {}", column),
+            FactorError::NotPositiveDefinite { column } => write!(f, "matrix is not positive definite at column {}", column),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FactorError {}
+
+// adjacency lists of the pattern of A + A^T without the diagonal
+fn symmetric_graph(a: &CsrMatrix) -> Vec<Vec<usize>> {
+    let n = a.get_rows();
+    let mut adj = vec![Vec::new(); n];
+    for (r, c, _) in a.triplets() {
+        if r != c {
+            adj[r].push(c);
+            adj[c].push(r);
+        }
+    }
+    for l in adj.iter_mut() {
+        l.sort_unstable();
+        l.dedup();
+    }
+    adj
+}
+
+// breadth first search from start, returns the visit order, the number of
+// levels and where the last level starts in the order
+fn bfs_levels(adj: &[Vec<usize>], start: usize, visited: &mut [bool], sort_by_degree: bool) -> (Vec<usize>, usize, usize) {
+    let mut order = vec![start];
+    visited[start] = true;
+    let (mut head, mut level_start, mut level_end, mut depth) = (0, 0, 1, 1);
+    while head < order.len() {
+        if head == level_end {
+            depth += 1;
+            level_start = level_end;
+            level_end = order.len();
+        }
+        let v = order[head];
+        head += 1;
+        let first = order.len();
+        for &u in &adj[v] {
+            if !visited[u] {
+                visited[u] = true;
+                order.push(u);
+            }
+        }
+        if sort_by_degree {
+            order[first..].sort_by_key(|u| adj[*u].len());
+        }
+    }
+    (order, depth, level_start)
+}
+
+pub fn reverse_cuthill_mckee(a: &CsrMatrix) -> Vec<usize> {
+    let adj = symmetric_graph(a);
+    let n = adj.len();
+    let mut visited = vec![false; n];
+    let mut perm = Vec::with_capacity(n);
+
+    let mut by_degree: Vec<usize> = (0..n).collect();
+    by_degree.sort_by_key(|v| adj[*v].len());
+    for &seed in &by_degree {
+        if visited[seed] {
+            continue;
+        }
+        // pseudo-peripheral start node, George and Liu: keep jumping to a
+        // low degree node of the last level while the depth grows
+        let mut start = seed;
+        let (mut order, mut depth, mut last) = bfs_levels(&adj, start, &mut visited.clone(), false);
+        loop {
+            let candidate = *order[last..].iter().min_by_key(|v| adj[**v].len()).unwrap();
+            let (o, d, l) = bfs_levels(&adj, candidate, &mut visited.clone(), false);
+            if d <= depth {
+                break;
+            }
+            (start, order, depth, last) = (candidate, o, d, l);
+        }
+        let (component, _, _) = bfs_levels(&adj, start, &mut visited, true);
+        perm.extend(component);
+    }
+    perm.reverse();
+    perm
+}
+
+// greedy minimum degree on the explicit elimination graph
+pub fn minimum_degree(a: &CsrMatrix) -> Vec<usize> {
+    let mut adj = symmetric_graph(a);
+    let n = adj.len();
+    let mut eliminated = vec![false; n];
+    let mut heap: BinaryHeap<Reverse<(usize, usize)>> = (0..n).map(|v| Reverse((adj[v].len(), v))).collect();
+    let mut perm = Vec::with_capacity(n);
+
+    while let Some(Reverse((deg, v))) = heap.pop() {
+        if eliminated[v] || deg != adj[v].len() {
+            // stale entry, the node got a new degree since it was pushed
+            continue;
+        }
+        eliminated[v] = true;
+        perm.push(v);
+        let nbrs = core::mem::take(&mut adj[v]);
+        // the neighbours of v become a clique
+        for &u in &nbrs {
+            let mut merged = Vec::with_capacity(adj[u].len() + nbrs.len());
+            let (mut i, mut j) = (0, 0);
+            let (x, y) = (&adj[u], &nbrs);
+            while i < x.len() || j < y.len() {
+                let next = if j == y.len() || (i < x.len() && x[i] <= y[j]) {
+                    i += 1;
+                    x[i - 1]
+                } else {
+                    j += 1;
+                    y[j - 1]
+                };
+                if next != u && next != v && merged.last() != Some(&next) {
+                    merged.push(next);
+                }
+            }
+            adj[u] = merged;
+            heap.push(Reverse((adj[u].len(), u)));
+        }
+    }
+    perm
+}
+
+pub fn ordering_permutation(a: &CsrMatrix, ordering: Ordering) -> Vec<usize> {
+    match ordering {
+        Ordering::Natural => (0..a.get_rows()).collect(),
+        Ordering::ReverseCuthillMcKee => reverse_cuthill_mckee(a),
+        Ordering::MinimumDegree => minimum_degree(a),
+    }
+}
+
+fn inverse_permutation(perm: &[usize]) -> Vec<usize> {
+    let mut inv = vec![0; perm.len()];
+    for (new, old) in perm.iter().enumerate() {
+        inv[*old] = new;
+    }
+    inv
+}
+
+fn same_pattern(a: &CsrMatrix, rows: usize, row_ptr: &[usize], col_idx: &[usize]) -> bool {
+    a.get_rows() == rows && a.get_cols() == rows && a.row_ptr() == row_ptr && a.col_idx() == col_idx
+}
+
+// nonzero pattern of row k of L, from the elimination tree. The pattern ends
+// up in s[top..] and top is returned.
+fn ereach(cp: &[usize], ci: &[usize], k: usize, parent: &[usize], s: &mut [usize], mark: &mut [usize], stack: &mut Vec<usize>) -> usize {
+    let n = parent.len();
+    let mut top = n;
+    mark[k] = k;
+    for &start in &ci[cp[k]..cp[k + 1]] {
+        let mut i = start;
+        if i > k {
+            continue;
+        }
+        stack.clear();
+        while mark[i] != k {
+            stack.push(i);
+            mark[i] = k;
+            i = parent[i];
+        }
+        while let Some(j) = stack.pop() {
+            top -= 1;
+            s[top] = j;
+        }
+    }
+    top
+}
+
+// symbolic analysis for the Cholesky factorization of a symmetric matrix
+// given with both triangles stored
+#[derive(Debug, Clone)]
+pub struct SymbolicCholesky {
+    n: usize,
+    perm: Vec<usize>,
+    // pattern of A, to check the matrices given to factor
+    a_row_ptr: Vec<usize>,
+    a_col_idx: Vec<usize>,
+    // upper triangle of P A P^T in CSC form, and where each entry of A goes in it
+    c_col_ptr: Vec<usize>,
+    c_row_idx: Vec<usize>,
+    a_to_c: Vec<usize>,
+    parent: Vec<usize>,
+    l_col_ptr: Vec<usize>,
+}
+
+impl SymbolicCholesky {
+    pub fn analyze(a: &CsrMatrix, ordering: Ordering) -> Result<Self, FactorError> {
+        let n = a.get_rows();
+        if n != a.get_cols() {
+            return Err(FactorError::NotSquare);
+        }
+        let perm = ordering_permutation(a, ordering);
+        let inv = inverse_permutation(&perm);
+
+        // upper triangle of C = P A P^T
+        let mut entries: Vec<(usize, usize, usize)> = Vec::new();
+        let mut a_to_c = vec![NONE; a.nnz()];
+        for r in 0..n {
+            for p in a.row_ptr()[r]..a.row_ptr()[r + 1] {
+                let (i, j) = (inv[r], inv[a.col_idx()[p]]);
+                if i <= j {
+                    entries.push((j, i, p));
+                }
+            }
+        }
+        entries.sort_unstable();
+        let mut c_col_ptr = vec![0; n + 1];
+        let mut c_row_idx = Vec::with_capacity(entries.len());
+        for (q, (j, i, p)) in entries.iter().enumerate() {
+            c_col_ptr[j + 1] += 1;
+            c_row_idx.push(*i);
+            a_to_c[*p] = q;
+        }
+        for j in 0..n {
+            c_col_ptr[j + 1] += c_col_ptr[j];
+        }
+
+        // elimination tree
+        let mut parent = vec![NONE; n];
+        let mut ancestor = vec![NONE; n];
+        for k in 0..n {
+            for &start in &c_row_idx[c_col_ptr[k]..c_col_ptr[k + 1]] {
+                let mut i = start;
+                while i != NONE && i < k {
+                    let next = ancestor[i];
+                    ancestor[i] = k;
+                    if next == NONE {
+                        parent[i] = k;
+                    }
+                    i = next;
+                }
+            }
+        }
+
+        // column counts of L, every row pattern adds one entry to its columns
+        let mut counts = vec![1; n];
+        let mut s = vec![0; n];
+        let mut mark = vec![NONE; n];
+        let mut stack = Vec::new();
+        for k in 0..n {
+            let top = ereach(&c_col_ptr, &c_row_idx, k, &parent, &mut s, &mut mark, &mut stack);
+            for &i in &s[top..] {
+                counts[i] += 1;
+            }
+        }
+        let mut l_col_ptr = vec![0; n + 1];
+        for j in 0..n {
+            l_col_ptr[j + 1] = l_col_ptr[j] + counts[j];
+        }
+
+        Ok(Self {
+            n,
+            perm,
+            a_row_ptr: a.row_ptr().to_vec(),
+            a_col_idx: a.col_idx().to_vec(),
+            c_col_ptr,
+            c_row_idx,
+            a_to_c,
+            parent,
+            l_col_ptr,
+        })
+    }
+
+    pub fn permutation(&self) -> &[usize] {
+        &self.perm
+    }
+
+    pub fn elimination_tree(&self) -> &[usize] {
+        &self.parent
+    }
+
+    // number of entries of L, diagonal included
+    pub fn factor_nnz(&self) -> usize {
+        self.l_col_ptr[self.n]
+    }
+
+    pub fn factor(&self, a: &CsrMatrix) -> Result<CholeskyFactor, FactorError> {
+        let mut f = CholeskyFactor {
+            symbolic: self.clone(),
+            l_row_idx: vec![0; self.factor_nnz()],
+            l_values: vec![0.0; self.factor_nnz()],
+            spare_values: Vec::new(),
+        };
+        f.refactor(a)?;
+        Ok(f)
+    }
+}
+
+// P A P^T = L L^T
+#[derive(Debug, Clone)]
+pub struct CholeskyFactor {
+    symbolic: SymbolicCholesky,
+    l_row_idx: Vec<usize>,
+    l_values: Vec<f64>,
+    // refactor computes into this one and swaps it in when it succeeds
+    spare_values: Vec<f64>,
+}
+
+impl CholeskyFactor {
+    pub fn new(a: &CsrMatrix, ordering: Ordering) -> Result<Self, FactorError> {
+        SymbolicCholesky::analyze(a, ordering)?.factor(a)
+    }
+
+    // numeric factorization of a matrix with the analyzed pattern, reusing the
+    // ordering, the elimination tree and the memory of the previous factor.
+    // On failure the previous factor is kept.
+    pub fn refactor(&mut self, a: &CsrMatrix) -> Result<(), FactorError> {
+        let sym = &self.symbolic;
+        let n = sym.n;
+        if !same_pattern(a, n, &sym.a_row_ptr, &sym.a_col_idx) {
+            return Err(FactorError::PatternMismatch);
+        }
+        let mut cx = vec![0.0; sym.c_row_idx.len()];
+        for (p, v) in a.values().iter().enumerate() {
+            if sym.a_to_c[p] != NONE {
+                cx[sym.a_to_c[p]] = *v;
+            }
+        }
+
+        // up-looking: row k of L comes from a sparse triangular solve with the
+        // rows above, its pattern is given by the elimination tree
+        let lp = &sym.l_col_ptr;
+        // the row indices come out the same every time, only the values change
+        let li = &mut self.l_row_idx;
+        let mut lx = core::mem::take(&mut self.spare_values);
+        lx.resize(li.len(), 0.0);
+        let mut next = lp[..n].to_vec();
+        let mut x = vec![0.0; n];
+        let mut s = vec![0; n];
+        let mut mark = vec![NONE; n];
+        let mut stack = Vec::new();
+        for k in 0..n {
+            let top = ereach(&sym.c_col_ptr, &sym.c_row_idx, k, &sym.parent, &mut s, &mut mark, &mut stack);
+            for q in sym.c_col_ptr[k]..sym.c_col_ptr[k + 1] {
+                x[sym.c_row_idx[q]] = cx[q];
+            }
+            let mut d = x[k];
+            x[k] = 0.0;
+            for &i in &s[top..] {
+                let lki = x[i] / lx[lp[i]];
+                x[i] = 0.0;
+                for p in lp[i] + 1..next[i] {
+                    x[li[p]] -= lx[p] * lki;
+                }
+                d -= lki * lki;
+                li[next[i]] = k;
+                lx[next[i]] = lki;
+                next[i] += 1;
+            }
+            if d <= 0.0 || !d.is_finite() {
+                self.spare_values = lx;
+                return Err(FactorError::NotPositiveDefinite { column: sym.perm[k] });
+            }
+            li[next[k]] = k;
+            lx[next[k]] = crate::math::sqrt(d);
+            next[k] += 1;
+        }
+        self.spare_values = core::mem::replace(&mut self.l_values, lx);
+        Ok(())
+    }
+
+    pub fn symbolic(&self) -> &SymbolicCholesky {
+        &self.symbolic
+    }
+
+    // the factor L of the permuted matrix
+    pub fn l(&self) -> CscMatrix {
+        let n = self.symbolic.n;
+        CscMatrix::from_raw_parts(n, n, self.symbolic.l_col_ptr.clone(), self.l_row_idx.clone(), self.l_values.clone())
+    }
+
+    pub fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let sym = &self.symbolic;
+        let n = sym.n;
+        assert_eq!(b.len(), n, "right hand side length doesn't match the matrix");
+        let lp = &sym.l_col_ptr;
+        let (li, lx) = (&self.l_row_idx, &self.l_values);
+
+        let mut y: Vec<f64> = sym.perm.iter().map(|old| b[*old]).collect();
+        // L y = P b, the diagonal is the first entry of every column
+        for j in 0..n {
+            y[j] /= lx[lp[j]];
+            for p in lp[j] + 1..lp[j + 1] {
+                y[li[p]] -= lx[p] * y[j];
+            }
+        }
+        // L^T z = y
+        for j in (0..n).rev() {
+            for p in lp[j] + 1..lp[j + 1] {
+                y[j] -= lx[p] * y[li[p]];
+            }
+            y[j] /= lx[lp[j]];
+        }
+        let mut x = vec![0.0; n];
+        for (new, old) in sym.perm.iter().enumerate() {
+            x[*old] = y[new];
+        }
+        x
+    }
+}
+
+// symbolic analysis for the LU factorization: the fill-reducing column
+// ordering, computed on the pattern of A + A^T
+#[derive(Debug, Clone)]
+pub struct SymbolicLu {
+    n: usize,
+    q: Vec<usize>,
+    a_row_ptr: Vec<usize>,
+    a_col_idx: Vec<usize>,
+    // pattern of A in CSC form, and where each entry of A goes in it
+    c_col_ptr: Vec<usize>,
+    c_row_idx: Vec<usize>,
+    a_to_c: Vec<usize>,
+}
+
+impl SymbolicLu {
+    pub fn analyze(a: &CsrMatrix, ordering: Ordering) -> Result<Self, FactorError> {
+        let n = a.get_rows();
+        if n != a.get_cols() {
+            return Err(FactorError::NotSquare);
+        }
+        let mut c_col_ptr = vec![0; n + 1];
+        for c in a.col_idx() {
+            c_col_ptr[c + 1] += 1;
+        }
+        for j in 0..n {
+            c_col_ptr[j + 1] += c_col_ptr[j];
+        }
+        let mut next = c_col_ptr[..n].to_vec();
+        let mut c_row_idx = vec![0; a.nnz()];
+        let mut a_to_c = vec![0; a.nnz()];
+        for r in 0..n {
+            let (s, e) = (a.row_ptr()[r], a.row_ptr()[r + 1]);
+            for (c, to) in a.col_idx()[s..e].iter().zip(&mut a_to_c[s..e]) {
+                c_row_idx[next[*c]] = r;
+                *to = next[*c];
+                next[*c] += 1;
+            }
+        }
+        Ok(Self {
+            n,
+            q: ordering_permutation(a, ordering),
+            a_row_ptr: a.row_ptr().to_vec(),
+            a_col_idx: a.col_idx().to_vec(),
+            c_col_ptr,
+            c_row_idx,
+            a_to_c,
+        })
+    }
+
+    pub fn column_permutation(&self) -> &[usize] {
+        &self.q
+    }
+
+    pub fn factor(&self, a: &CsrMatrix) -> Result<LuFactor, FactorError> {
+        self.factor_with_threshold(a, 1.0)
+    }
+
+    // threshold partial pivoting: the diagonal entry is kept as pivot as long
+    // as it is at least threshold times the largest candidate, 1 gives plain
+    // partial pivoting and smaller values preserve more of the ordering
+    pub fn factor_with_threshold(&self, a: &CsrMatrix, threshold: f64) -> Result<LuFactor, FactorError> {
+        if !same_pattern(a, self.n, &self.a_row_ptr, &self.a_col_idx) {
+            return Err(FactorError::PatternMismatch);
+        }
+        let mut f = LuFactor {
+            symbolic: self.clone(),
+            threshold,
+            spare: (Vec::new(), Vec::new()),
+            pinv: Vec::new(),
+            l: (Vec::new(), Vec::new(), Vec::new()),
+            u: (Vec::new(), Vec::new(), Vec::new()),
+        };
+        self.numeric(&self.csc_values(a), threshold, &mut f.pinv, &mut f.l, &mut f.u)?;
+        Ok(f)
+    }
+
+    // values of A in the order of the CSC pattern
+    fn csc_values(&self, a: &CsrMatrix) -> Vec<f64> {
+        let mut cx = vec![0.0; a.nnz()];
+        for (p, v) in a.values().iter().enumerate() {
+            cx[self.a_to_c[p]] = *v;
+        }
+        cx
+    }
+
+    // left-looking factorization with pivot search, the output vectors are
+    // cleared first so their memory gets reused
+    fn numeric(&self, cx: &[f64], threshold: f64, pinv: &mut Vec<usize>, l: &mut Columns, u: &mut Columns) -> Result<(), FactorError> {
+        let n = self.n;
+        let (ap, ai) = (&self.c_col_ptr, &self.c_row_idx);
+        let (lp, li, lx) = l;
+        let (up, ui, ux) = u;
+        for v in [&mut *lp, &mut *li, &mut *up, &mut *ui] {
+            v.clear();
+        }
+        lx.clear();
+        ux.clear();
+        lp.push(0);
+        up.push(0);
+        pinv.clear();
+        pinv.resize(n, NONE);
+
+        let mut x = vec![0.0; n];
+        let mut xi = vec![0; n];
+        let mut mark = vec![false; n];
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+
+        for k in 0..n {
+            let col = self.q[k];
+
+            // pattern of x = L \ A(:, col) by depth first search through the
+            // columns of L, in topological order in xi[top..]
+            let mut top = n;
+            for &start in &ai[ap[col]..ap[col + 1]] {
+                if mark[start] {
+                    continue;
+                }
+                stack.clear();
+                mark[start] = true;
+                stack.push((start, 0));
+                while let Some(&mut (j, ref mut pos)) = stack.last_mut() {
+                    let jnew = pinv[j];
+                    let (s, e) = if jnew == NONE { (0, 0) } else { (lp[jnew] + 1, lp[jnew + 1]) };
+                    let mut pushed = false;
+                    while s + *pos < e {
+                        let i = li[s + *pos];
+                        *pos += 1;
+                        if !mark[i] {
+                            mark[i] = true;
+                            stack.push((i, 0));
+                            pushed = true;
+                            break;
+                        }
+                    }
+                    if !pushed {
+                        stack.pop();
+                        top -= 1;
+                        xi[top] = j;
+                    }
+                }
+            }
+
+            // sparse triangular solve
+            for &i in &xi[top..] {
+                x[i] = 0.0;
+            }
+            for p in ap[col]..ap[col + 1] {
+                x[ai[p]] = cx[p];
+            }
+            for &j in &xi[top..] {
+                let jnew = pinv[j];
+                if jnew == NONE {
+                    continue;
+                }
+                for p in lp[jnew] + 1..lp[jnew + 1] {
+                    x[li[p]] -= lx[p] * x[j];
+                }
+            }
+
+            // pick the pivot among the rows that aren't pivotal yet
+            let mut ipiv = NONE;
+            let mut best = -1.0;
+            let mut candidates = false;
+            for &i in &xi[top..] {
+                if pinv[i] == NONE {
+                    candidates = true;
+                    let t = x[i].abs();
+                    if t > best {
+                        best = t;
+                        ipiv = i;
+                    }
+                } else {
+                    ui.push(pinv[i]);
+                    ux.push(x[i]);
+                }
+            }
+            if !candidates {
+                return Err(FactorError::StructurallySingular { column: col });
+            }
+            if best <= 0.0 || !best.is_finite() {
+                return Err(FactorError::NumericallySingular { column: col });
+            }
+            if pinv[col] == NONE && mark[col] && x[col].abs() >= best * threshold {
+                ipiv = col;
+            }
+
+            let pivot = x[ipiv];
+            ui.push(k);
+            ux.push(pivot);
+            up.push(ui.len());
+            pinv[ipiv] = k;
+            li.push(ipiv);
+            lx.push(1.0);
+            for &i in &xi[top..] {
+                if pinv[i] == NONE {
+                    li.push(i);
+                    lx.push(x[i] / pivot);
+                }
+                x[i] = 0.0;
+                mark[i] = false;
+            }
+            lp.push(li.len());
+        }
+
+        // row indices of L in pivot order
+        for i in li.iter_mut() {
+            *i = pinv[*i];
+        }
+        Ok(())
+    }
+}
+
+// compressed columns whose row indices aren't sorted
+type Columns = (Vec<usize>, Vec<usize>, Vec<f64>);
+
+fn sorted_csc(n: usize, (ptr, idx, val): &Columns) -> CscMatrix {
+    let mut coo = CooMatrix::with_capacity(n, n, idx.len());
+    for c in 0..n {
+        for p in ptr[c]..ptr[c + 1] {
+            coo.push(idx[p], c, val[p]);
+        }
+    }
+    coo.to_csc()
+}
+
+// a refactorization keeps the previous pivots unless one of them falls below
+// this fraction of the largest entry left in its column
+const PIVOT_GROWTH: f64 = 1e-3;
+
+// P A Q = L U, L unit lower triangular with the diagonal stored first in
+// every column, U upper triangular with the diagonal stored last
+#[derive(Debug, Clone)]
+pub struct LuFactor {
+    symbolic: SymbolicLu,
+    threshold: f64,
+    // values of L and U that refactor computes into, swapped in on success
+    spare: (Vec<f64>, Vec<f64>),
+    pinv: Vec<usize>,
+    l: Columns,
+    u: Columns,
+}
+
+impl LuFactor {
+    pub fn new(a: &CsrMatrix, ordering: Ordering) -> Result<Self, FactorError> {
+        SymbolicLu::analyze(a, ordering)?.factor(a)
+    }
+
+    // numeric factorization of a matrix with the analyzed pattern. The row
+    // pivots and the patterns of L and U of the previous factor are reused,
+    // only if a pivot gets too small the pivots are searched again. On
+    // failure the previous factor is kept.
+    pub fn refactor(&mut self, a: &CsrMatrix) -> Result<(), FactorError> {
+        let sym = &self.symbolic;
+        let n = sym.n;
+        if !same_pattern(a, n, &sym.a_row_ptr, &sym.a_col_idx) {
+            return Err(FactorError::PatternMismatch);
+        }
+        let cx = sym.csc_values(a);
+        let (lp, li, _) = &self.l;
+        let (up, ui, _) = &self.u;
+        let (lx, ux) = &mut self.spare;
+        lx.resize(li.len(), 0.0);
+        ux.resize(ui.len(), 0.0);
+
+        // x is indexed by the pivot order of the rows. The U entries of a
+        // column were stored in topological order, so every one of them is
+        // final when it is reached.
+        let mut x = vec![0.0; n];
+        let mut degraded = false;
+        for k in 0..n {
+            let col = sym.q[k];
+            for p in sym.c_col_ptr[col]..sym.c_col_ptr[col + 1] {
+                x[self.pinv[sym.c_row_idx[p]]] = cx[p];
+            }
+            for p in up[k]..up[k + 1] - 1 {
+                let j = ui[p];
+                ux[p] = x[j];
+                x[j] = 0.0;
+                for q in lp[j] + 1..lp[j + 1] {
+                    x[li[q]] -= lx[q] * ux[p];
+                }
+            }
+            let pivot = x[k];
+            x[k] = 0.0;
+            let mut best = 0.0f64;
+            for &i in &li[lp[k] + 1..lp[k + 1]] {
+                best = best.max(x[i].abs());
+            }
+            if pivot == 0.0 || !pivot.is_finite() || pivot.abs() < best * PIVOT_GROWTH {
+                degraded = true;
+                break;
+            }
+            ux[up[k + 1] - 1] = pivot;
+            lx[lp[k]] = 1.0;
+            for q in lp[k] + 1..lp[k + 1] {
+                lx[q] = x[li[q]] / pivot;
+                x[li[q]] = 0.0;
+            }
+        }
+        if degraded {
+            let mut pinv = Vec::new();
+            let mut l = (Vec::new(), Vec::new(), Vec::new());
+            let mut u = (Vec::new(), Vec::new(), Vec::new());
+            sym.numeric(&cx, self.threshold, &mut pinv, &mut l, &mut u)?;
+            (self.pinv, self.l, self.u) = (pinv, l, u);
+            return Ok(());
+        }
+        core::mem::swap(&mut self.l.2, lx);
+        core::mem::swap(&mut self.u.2, ux);
+        Ok(())
+    }
+
+    pub fn l(&self) -> CscMatrix {
+        sorted_csc(self.symbolic.n, &self.l)
+    }
+
+    pub fn u(&self) -> CscMatrix {
+        sorted_csc(self.symbolic.n, &self.u)
+    }
+
+    // entries of L and U
+    pub fn factor_nnz(&self) -> usize {
+        self.l.1.len() + self.u.1.len()
+    }
+
+    // pinv[old row] = new row
+    pub fn row_permutation_inverse(&self) -> &[usize] {
+        &self.pinv
+    }
+
+    pub fn column_permutation(&self) -> &[usize] {
+        &self.symbolic.q
+    }
+
+    pub fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let n = self.symbolic.n;
+        assert_eq!(b.len(), n, "right hand side length doesn't match the matrix");
+        let mut y = vec![0.0; n];
+        for (i, v) in b.iter().enumerate() {
+            y[self.pinv[i]] = *v;
+        }
+        let (lp, li, lx) = &self.l;
+        for j in 0..n {
+            for p in lp[j] + 1..lp[j + 1] {
+                y[li[p]] -= lx[p] * y[j];
+            }
+        }
+        let (up, ui, ux) = &self.u;
+        for j in (0..n).rev() {
+            y[j] /= ux[up[j + 1] - 1];
+            for p in up[j]..up[j + 1] - 1 {
+                y[ui[p]] -= ux[p] * y[j];
+            }
+        }
+        let mut x = vec![0.0; n];
+        for (k, col) in self.symbolic.q.iter().enumerate() {
+            x[*col] = y[k];
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrixxd::MatrixXd;
+
+    // 2d Poisson problem on a k x k grid
+    fn poisson(k: usize) -> CsrMatrix {
+        let n = k * k;
+        let mut coo = CooMatrix::new(n, n);
+        for i in 0..k {
+            for j in 0..k {
+                let p = i * k + j;
+                coo.push(p, p, 4.0 + 0.01 * p as f64);
+                if i + 1 < k {
+                    coo.push(p, p + k, -1.0);
+                    coo.push(p + k, p, -1.0);
+                }
+                if j + 1 < k {
+                    coo.push(p, p + 1, -1.0);
+                    coo.push(p + 1, p, -1.0);
+                }
+            }
+        }
+        coo.to_csr()
+    }
+
+    fn rhs(n: usize) -> Vec<f64> {
+        (0..n).map(|i| (i % 5) as f64 - 1.5).collect()
+    }
+
+    fn residual_norm(a: &CsrMatrix, x: &[f64], b: &[f64]) -> f64 {
+        a.mul_vec(x).iter().zip(b).map(|(u, v)| (u - v) * (u - v)).sum::<f64>().sqrt()
+    }
+
+    fn is_permutation(p: &[usize]) -> bool {
+        let mut seen = vec![false; p.len()];
+        p.iter().all(|i| *i < p.len() && !core::mem::replace(&mut seen[*i], true))
+    }
+
+    #[test]
+    fn orderings_are_permutations_and_reduce_fill() {
+        let a = poisson(10);
+        let rcm = reverse_cuthill_mckee(&a);
+        let md = minimum_degree(&a);
+        assert!(is_permutation(&rcm));
+        assert!(is_permutation(&md));
+
+        let natural = SymbolicCholesky::analyze(&a, Ordering::Natural).unwrap().factor_nnz();
+        let min_deg = SymbolicCholesky::analyze(&a, Ordering::MinimumDegree).unwrap().factor_nnz();
+        assert!(min_deg < natural, "{} >= {}", min_deg, natural);
+
+        // RCM recovers a small bandwidth after a random-ish shuffle
+        let n = a.get_rows();
+        let shuffle: Vec<usize> = (0..n).map(|i| (i * 37) % n).collect();
+        let inv = inverse_permutation(&shuffle);
+        let mut coo = CooMatrix::new(n, n);
+        for (r, c, v) in a.triplets() {
+            coo.push(inv[r], inv[c], v);
+        }
+        let shuffled = coo.to_csr();
+        let bandwidth = |m: &CsrMatrix, p: &[usize]| {
+            let pi = inverse_permutation(p);
+            m.triplets().map(|(r, c, _)| pi[r].abs_diff(pi[c])).max().unwrap()
+        };
+        let identity: Vec<usize> = (0..n).collect();
+        assert!(bandwidth(&shuffled, &reverse_cuthill_mckee(&shuffled)) < bandwidth(&shuffled, &identity));
+    }
+
+    #[test]
+    fn cholesky_solves() {
+        let a = poisson(9);
+        let b = rhs(81);
+        for ordering in [Ordering::Natural, Ordering::ReverseCuthillMcKee, Ordering::MinimumDegree] {
+            let f = CholeskyFactor::new(&a, ordering).unwrap();
+            assert!(residual_norm(&a, &f.solve(&b), &b) < 1e-10);
+        }
+    }
+
+    #[test]
+    fn cholesky_factor_matches_dense() {
+        let a = poisson(3);
+        let f = CholeskyFactor::new(&a, Ordering::Natural).unwrap();
+        let l = f.l().to_dense();
+        let llt = &l * &l.transpose();
+        let d = a.to_dense();
+        for i in 0..9 {
+            for j in 0..9 {
+                assert!((llt[(i, j)] - d[(i, j)]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn cholesky_refactor_and_errors() {
+        let a = poisson(6);
+        let sym = SymbolicCholesky::analyze(&a, Ordering::MinimumDegree).unwrap();
+        let mut f = sym.factor(&a).unwrap();
+
+        // same pattern, scaled values
+        let mut a2 = a.clone();
+        a2.values_mut().iter_mut().for_each(|v| *v *= 3.0);
+        f.refactor(&a2).unwrap();
+        let b = rhs(36);
+        assert!(residual_norm(&a2, &f.solve(&b), &b) < 1e-10);
+
+        // indefinite
+        let mut a3 = a.clone();
+        a3.values_mut().iter_mut().for_each(|v| *v = -*v);
+        assert!(matches!(f.refactor(&a3), Err(FactorError::NotPositiveDefinite { .. })));
+        // the failed refactorization left the previous factor alone
+        assert!(residual_norm(&a2, &f.solve(&b), &b) < 1e-10);
+
+        assert_eq!(f.refactor(&poisson(5)).unwrap_err(), FactorError::PatternMismatch);
+    }
+
+    #[test]
+    fn lu_solves_non_symmetric() {
+        let n = 60;
+        let mut coo = CooMatrix::new(n, n);
+        for i in 0..n {
+            // tiny diagonal forces row exchanges
+            coo.push(i, i, if i % 4 == 0 { 1e-8 } else { 2.0 + i as f64 * 0.1 });
+            coo.push(i, (i + 1) % n, -1.5);
+            coo.push((i + 3) % n, i, 0.7);
+        }
+        let a = coo.to_csr();
+        let b = rhs(n);
+        for ordering in [Ordering::Natural, Ordering::ReverseCuthillMcKee, Ordering::MinimumDegree] {
+            let f = LuFactor::new(&a, ordering).unwrap();
+            assert!(residual_norm(&a, &f.solve(&b), &b) < 1e-9);
+        }
+    }
+
+    #[test]
+    fn lu_factor_matches_dense() {
+        let d = MatrixXd::new(3, 3, vec![0.0, 2.0, 1.0, 1.0, 1.0, 0.0, 3.0, 0.0, 1.0]);
+        let a = CsrMatrix::from_dense(&d);
+        let f = LuFactor::new(&a, Ordering::Natural).unwrap();
+        let lu = &f.l().to_dense() * &f.u().to_dense();
+        // P A Q = L U
+        for (old_r, new_r) in f.row_permutation_inverse().iter().enumerate() {
+            for (k, c) in f.column_permutation().iter().enumerate() {
+                assert!((lu[(*new_r, k)] - d[(old_r, *c)]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn lu_singular_matrices() {
+        // empty second column
+        let a = CsrMatrix::from_dense(&MatrixXd::new(2, 2, vec![1.0, 0.0, 1.0, 0.0]));
+        assert_eq!(
+            LuFactor::new(&a, Ordering::Natural).unwrap_err(),
+            FactorError::StructurallySingular { column: 1 }
+        );
+
+        // pattern is fine but the rows are dependent
+        let mut coo = CooMatrix::new(2, 2);
+        coo.push(0, 0, 1.0);
+        coo.push(0, 1, 2.0);
+        coo.push(1, 0, 2.0);
+        coo.push(1, 1, 4.0);
+        assert_eq!(
+            LuFactor::new(&coo.to_csr(), Ordering::Natural).unwrap_err(),
+            FactorError::NumericallySingular { column: 1 }
+        );
+    }
+
+    #[test]
+    fn lu_refactor() {
+        let a = poisson(5);
+        let mut f = LuFactor::new(&a, Ordering::ReverseCuthillMcKee).unwrap();
+        let mut a2 = a.clone();
+        a2.values_mut().iter_mut().enumerate().for_each(|(i, v)| *v += (i % 3) as f64 * 0.1);
+        f.refactor(&a2).unwrap();
+        let b = rhs(25);
+        assert!(residual_norm(&a2, &f.solve(&b), &b) < 1e-10);
+        // the pivots are kept and the values match a fresh factorization
+        let fresh = LuFactor::new(&a2, Ordering::ReverseCuthillMcKee).unwrap();
+        assert_eq!(f.row_permutation_inverse(), fresh.row_permutation_inverse());
+        assert_eq!(f.factor_nnz(), fresh.factor_nnz());
+        assert!(f.u().values().iter().zip(fresh.u().values()).all(|(u, v)| (u - v).abs() < 1e-12));
+        assert!(f.l().values().iter().zip(fresh.l().values()).all(|(u, v)| (u - v).abs() < 1e-12));
+
+        // a zero pivot makes the refactorization look for new pivots
+        let mut coo = CooMatrix::new(2, 2);
+        coo.push(0, 0, 2.0);
+        coo.push(0, 1, 1.0);
+        coo.push(1, 0, 1.0);
+        coo.push(1, 1, 2.0);
+        let a = coo.to_csr();
+        let mut f = LuFactor::new(&a, Ordering::Natural).unwrap();
+        assert_eq!(f.row_permutation_inverse(), &[0, 1]);
+        let mut a2 = a.clone();
+        a2.values_mut()[0] = 0.0;
+        f.refactor(&a2).unwrap();
+        assert_eq!(f.row_permutation_inverse(), &[1, 0]);
+        let b = [1.0, 2.0];
+        assert!(residual_norm(&a2, &f.solve(&b), &b) < 1e-12);
+        assert_eq!(f.l().values(), LuFactor::new(&a2, Ordering::Natural).unwrap().l().values());
+
+        // failing refactorizations keep the previous factor
+        let mut singular = a2.clone();
+        singular.values_mut().copy_from_slice(&[0.0, 1.0, 0.0, 2.0]);
+        assert_eq!(f.refactor(&singular).unwrap_err(), FactorError::NumericallySingular { column: 0 });
+        assert!(residual_norm(&a2, &f.solve(&b), &b) < 1e-12);
+        let mut a3 = a.clone();
+        a3.values_mut().copy_from_slice(&[1.0, 2.0, 3.0, 4.0]);
+        f.refactor(&a3).unwrap();
+        singular.values_mut().copy_from_slice(&[1.0, 2.0, 2.0, 4.0]);
+        assert_eq!(f.refactor(&singular).unwrap_err(), FactorError::NumericallySingular { column: 1 });
+        assert!(residual_norm(&a3, &f.solve(&b), &b) < 1e-12);
+    }
+}
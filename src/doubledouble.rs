@@ -0,0 +1,700 @@
+// Double-double arithmetic: an unevaluated sum hi + lo of two f64 with
+// |lo| <= ulp(hi) / 2, about 106 bits of mantissa. Built on the error-free
+// transformations TwoSum and TwoProd (with an FMA), following the QD library
+// of Hida, Li and Bailey.
+//
+// Also mixed-precision iterative refinement: the system is solved in f64 and
+// the residuals are computed in double-double, which gives a double-double
+// accurate solution as long as the f64 solves make some progress, i.e. for
+// condition numbers up to about 1e15.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+use crate::math;
+use crate::scalar::{lu_solve, Real, Scalar};
+#[cfg(feature = "alloc")]
+use crate::matrixxd::MatrixXd;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+// s + e == a + b exactly
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+// same as two_sum when |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+// p + e == a * b exactly
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, math::fma(a, b, -p))
+}
+
+// largest number of digits after the point in Display
+const MAX_DIGITS: usize = 40;
+
+impl DoubleDouble {
+    pub const ZERO: DoubleDouble = DoubleDouble { hi: 0.0, lo: 0.0 };
+    pub const ONE: DoubleDouble = DoubleDouble { hi: 1.0, lo: 0.0 };
+    // 2^-104, the relative precision
+    pub const EPSILON: f64 = 4.930380657631324e-32;
+    pub const PI: DoubleDouble = DoubleDouble { hi: core::f64::consts::PI, lo: 1.2246467991473532e-16 };
+
+    // hi + lo renormalized, the parts don't need to be ordered
+    pub fn new(hi: f64, lo: f64) -> Self {
+        let (s, e) = two_sum(hi, lo);
+        Self::parts(s, e)
+    }
+
+    pub fn from_f64(x: f64) -> Self {
+        Self { hi: x, lo: 0.0 }
+    }
+
+    // quick_two_sum result, with the infinities and NaN kept in hi
+    fn parts(s: f64, e: f64) -> Self {
+        if s.is_finite() { Self { hi: s, lo: e } } else { Self { hi: s, lo: 0.0 } }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    pub fn is_sign_negative(&self) -> bool {
+        self.hi < 0.0 || (self.hi == 0.0 && self.lo < 0.0)
+    }
+
+    pub fn abs(&self) -> Self {
+        if self.is_sign_negative() { -*self } else { *self }
+    }
+
+    pub fn recip(&self) -> Self {
+        Self::ONE / *self
+    }
+
+    // product with a plain f64, cheaper than going through a DoubleDouble
+    pub fn mul_f64(&self, b: f64) -> Self {
+        let (p1, p2) = two_prod(self.hi, b);
+        let (s, e) = quick_two_sum(p1, p2 + self.lo * b);
+        Self::parts(s, e)
+    }
+
+    pub fn square(&self) -> Self {
+        let (p1, p2) = two_prod(self.hi, self.hi);
+        let (s, e) = quick_two_sum(p1, p2 + 2.0 * self.hi * self.lo);
+        Self::parts(s, e)
+    }
+
+    // one Newton step from the f64 square root, NaN for negative numbers
+    pub fn sqrt(&self) -> Self {
+        if self.hi == 0.0 {
+            return Self::ZERO;
+        }
+        if self.hi < 0.0 {
+            return Self::from_f64(f64::NAN);
+        }
+        if !self.hi.is_finite() {
+            return *self;
+        }
+        let x = 1.0 / math::sqrt(self.hi);
+        let ax = self.hi * x;
+        let (s, e) = two_sum(ax, (*self - Self::from_f64(ax).square()).hi * (x * 0.5));
+        Self::parts(s, e)
+    }
+
+    pub fn powi(&self, n: i32) -> Self {
+        let mut r = Self::ONE;
+        let mut b = *self;
+        let mut k = n.unsigned_abs();
+        while k > 0 {
+            if k & 1 == 1 {
+                r = r * b;
+            }
+            b = b.square();
+            k >>= 1;
+        }
+        if n < 0 { r.recip() } else { r }
+    }
+
+    pub fn floor(&self) -> Self {
+        let hi = math::floor(self.hi);
+        if hi == self.hi {
+            // hi is an integer, the fraction is in lo
+            Self::new(hi, math::floor(self.lo))
+        } else {
+            Self::from_f64(hi)
+        }
+    }
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(x: f64) -> Self {
+        Self::from_f64(x)
+    }
+}
+
+impl PartialOrd for DoubleDouble {
+    fn partial_cmp(&self, o: &Self) -> Option<core::cmp::Ordering> {
+        match self.hi.partial_cmp(&o.hi)? {
+            core::cmp::Ordering::Equal => self.lo.partial_cmp(&o.lo),
+            c => Some(c),
+        }
+    }
+}
+
+impl core::ops::Add<DoubleDouble> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn add(self, o: DoubleDouble) -> Self::Output {
+        let (s1, s2) = two_sum(self.hi, o.hi);
+        let (t1, t2) = two_sum(self.lo, o.lo);
+        let (s1, s2) = quick_two_sum(s1, s2 + t1);
+        let (s, e) = quick_two_sum(s1, s2 + t2);
+        Self::parts(s, e)
+    }
+}
+
+impl core::ops::Sub<DoubleDouble> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn sub(self, o: DoubleDouble) -> Self::Output {
+        self + -o
+    }
+}
+
+impl core::ops::Mul<DoubleDouble> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn mul(self, o: DoubleDouble) -> Self::Output {
+        let (p1, p2) = two_prod(self.hi, o.hi);
+        let (s, e) = quick_two_sum(p1, p2 + (self.hi * o.lo + self.lo * o.hi));
+        Self::parts(s, e)
+    }
+}
+
+// long division, three f64 quotient digits
+impl core::ops::Div<DoubleDouble> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn div(self, o: DoubleDouble) -> Self::Output {
+        let q1 = self.hi / o.hi;
+        if !q1.is_finite() || !o.hi.is_finite() {
+            return Self::from_f64(q1);
+        }
+        let r = self - o.mul_f64(q1);
+        let q2 = r.hi / o.hi;
+        let r = r - o.mul_f64(q2);
+        let q3 = r.hi / o.hi;
+        let (s, e) = quick_two_sum(q1, q2);
+        Self::parts(s, e) + Self::from_f64(q3)
+    }
+}
+
+impl core::ops::Neg for DoubleDouble {
+    type Output = DoubleDouble;
+    fn neg(self) -> Self::Output {
+        Self { hi: -self.hi, lo: -self.lo }
+    }
+}
+
+impl core::ops::Add<f64> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn add(self, o: f64) -> Self::Output {
+        let (s1, s2) = two_sum(self.hi, o);
+        let (s, e) = quick_two_sum(s1, s2 + self.lo);
+        Self::parts(s, e)
+    }
+}
+
+impl core::ops::Sub<f64> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn sub(self, o: f64) -> Self::Output {
+        self + -o
+    }
+}
+
+impl core::ops::Mul<f64> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn mul(self, o: f64) -> Self::Output {
+        self.mul_f64(o)
+    }
+}
+
+impl core::ops::Div<f64> for DoubleDouble {
+    type Output = DoubleDouble;
+    fn div(self, o: f64) -> Self::Output {
+        self / Self::from_f64(o)
+    }
+}
+
+impl Scalar for DoubleDouble {
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn one() -> Self {
+        Self::ONE
+    }
+}
+
+impl Real for DoubleDouble {
+    fn sqrt(self) -> Self {
+        DoubleDouble::sqrt(&self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseDoubleDoubleError {
+    Empty,
+    InvalidDigit,
+    InvalidExponent,
+}
+
+impl core::fmt::Display for ParseDoubleDoubleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseDoubleDoubleError::Empty => write!(f, "cannot parse a number from an empty string"),
+            ParseDoubleDoubleError::InvalidDigit => write!(f, "invalid digit in number"),
+            ParseDoubleDoubleError::InvalidExponent => write!(f, "invalid exponent in number"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseDoubleDoubleError {}
+
+// decimal numbers like "-12.5e-3", the digits are accumulated in double-double
+// so the result is correct to about 32 significant digits
+impl core::str::FromStr for DoubleDouble {
+    type Err = ParseDoubleDoubleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (neg, s) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (mantissa, exp) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        if mantissa.is_empty() || mantissa == "." {
+            return Err(ParseDoubleDoubleError::Empty);
+        }
+
+        let mut r = Self::ZERO;
+        let mut scale = 0i32;
+        let mut seen_point = false;
+        for c in mantissa.bytes() {
+            match c {
+                b'0'..=b'9' => {
+                    r = r * 10.0 + (c - b'0') as f64;
+                    if seen_point {
+                        scale -= 1;
+                    }
+                }
+                b'.' if !seen_point => seen_point = true,
+                _ => return Err(ParseDoubleDoubleError::InvalidDigit),
+            }
+        }
+        if let Some(e) = exp {
+            let e: i32 = e.parse().map_err(|_| ParseDoubleDoubleError::InvalidExponent)?;
+            scale = scale.saturating_add(e);
+        }
+        // 10^-k is inexact, dividing by 10^k is more accurate
+        let ten = Self::from_f64(10.0);
+        r = if scale < 0 { r / ten.powi(-scale) } else { r * ten.powi(scale) };
+        Ok(if neg { -r } else { r })
+    }
+}
+
+// scientific notation with 31 digits after the point by default, the
+// precision of the formatter overrides it (up to MAX_DIGITS)
+impl core::fmt::Display for DoubleDouble {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.hi.is_nan() {
+            return write!(f, "NaN");
+        }
+        if self.is_sign_negative() {
+            write!(f, "-")?;
+        }
+        if self.hi.is_infinite() {
+            return write!(f, "inf");
+        }
+        let n = f.precision().unwrap_or(31).min(MAX_DIGITS);
+        let mut digits = [0u8; MAX_DIGITS + 2];
+        let mut e = 0i32;
+
+        let x = self.abs();
+        if x.hi != 0.0 {
+            e = math::floor(math::ln(x.hi) / core::f64::consts::LN_10) as i32;
+            let ten = Self::from_f64(10.0);
+            let mut r = if e < 0 { x * ten.powi(-e) } else { x / ten.powi(e) };
+            // the estimate of e can be one off
+            if r.hi >= 10.0 {
+                r = r / 10.0;
+                e += 1;
+            } else if r.hi < 1.0 {
+                r = r * 10.0;
+                e -= 1;
+            }
+            // one more digit than printed, for the rounding
+            for d in digits.iter_mut().take(n + 2) {
+                let k = math::floor(r.hi).clamp(0.0, 9.0);
+                r = r - k;
+                let k = if r.is_sign_negative() {
+                    r = r + 1.0;
+                    k - 1.0
+                } else {
+                    k
+                };
+                *d = k.max(0.0) as u8;
+                r = r * 10.0;
+            }
+            if digits[n + 1] >= 5 {
+                let mut i = n;
+                loop {
+                    if digits[i] < 9 {
+                        digits[i] += 1;
+                        break;
+                    }
+                    digits[i] = 0;
+                    if i == 0 {
+                        // 9.99.. rounded up to 10
+                        digits[0] = 1;
+                        e += 1;
+                        break;
+                    }
+                    i -= 1;
+                }
+            }
+        }
+
+        write!(f, "{}", digits[0])?;
+        if n > 0 {
+            write!(f, ".")?;
+            for d in &digits[1..=n] {
+                write!(f, "{}", d)?;
+            }
+        }
+        write!(f, "e{}", e)
+    }
+}
+
+const MAX_REFINEMENTS: usize = 10;
+
+// Iterative refinement: x is improved by the f64 solution d of A d = b - A x
+// until the corrections stop shrinking or are below the double-double
+// precision. solve works in place on d and returns false when A is singular.
+fn refine(
+    x: &mut [DoubleDouble],
+    r: &mut [DoubleDouble],
+    d: &mut [f64],
+    residual: impl Fn(&[DoubleDouble], &mut [DoubleDouble]),
+    solve: impl Fn(&mut [f64]) -> bool,
+) -> bool {
+    let max = |v: &[f64]| v.iter().fold(0.0f64, |m, x| m.max(x.abs()));
+    let mut last = f64::INFINITY;
+    for _ in 0..MAX_REFINEMENTS {
+        residual(x, r);
+        for (d, r) in d.iter_mut().zip(r.iter()) {
+            *d = r.hi;
+        }
+        if !solve(d) {
+            return false;
+        }
+        for (x, d) in x.iter_mut().zip(d.iter()) {
+            *x = *x + *d;
+        }
+        let step = max(d);
+        let size = x.iter().fold(0.0f64, |m, x| m.max(x.hi.abs()));
+        if step <= DoubleDouble::EPSILON * size || step > 0.5 * last {
+            break;
+        }
+        last = step;
+    }
+    true
+}
+
+// b - A x in double-double, for a row-major n x n matrix
+fn residual_dd(a: &[f64], b: &[f64], x: &[DoubleDouble], r: &mut [DoubleDouble]) {
+    let n = x.len();
+    for (i, r) in r.iter_mut().enumerate() {
+        *r = a[i * n..(i + 1) * n].iter().zip(x).fold(DoubleDouble::from_f64(b[i]), |s, (a, x)| s - x.mul_f64(*a));
+    }
+}
+
+macro_rules! impl_double_double_types {
+    ($m:ident, $v:ident, $n:expr) => {
+        impl $v<DoubleDouble> {
+            // rounded to the nearest f64 vector
+            pub fn to_f64(self) -> $v {
+                $v::new_from(self.to_list().map(|x| x.to_f64()))
+            }
+        }
+
+        impl From<$v> for $v<DoubleDouble> {
+            fn from(v: $v) -> Self {
+                $v::new_from(v.to_list().map(DoubleDouble::from_f64))
+            }
+        }
+
+        impl From<$m> for $m<DoubleDouble> {
+            fn from(m: $m) -> Self {
+                $m::from_row_major(m.to_row_major_array().map(DoubleDouble::from_f64))
+            }
+        }
+
+        impl $m<DoubleDouble> {
+            pub fn solve(&self, b: $v<DoubleDouble>) -> Option<$v<DoubleDouble>> {
+                let v = self.to_row_major_array();
+                let a = core::array::from_fn(|i| core::array::from_fn(|j| v[i * $n + j]));
+                lu_solve(a, b.to_list(), |x| x.hi.abs()).map($v::new_from)
+            }
+        }
+
+        impl $m {
+            // Solves A x = b in f64 and refines x with double-double residuals,
+            // None when A is singular
+            pub fn solve_refined(&self, b: $v) -> Option<$v<DoubleDouble>> {
+                let v = self.to_row_major_array();
+                let a: [[f64; $n]; $n] = core::array::from_fn(|i| core::array::from_fn(|j| v[i * $n + j]));
+                let b = b.to_list();
+                let mut x = [DoubleDouble::ZERO; $n];
+                let mut r = [DoubleDouble::ZERO; $n];
+                let mut d = [0.0; $n];
+                let solve = |d: &mut [f64]| match lu_solve(a, core::array::from_fn(|i| d[i]), f64::abs) {
+                    Some(s) => {
+                        d.copy_from_slice(&s);
+                        true
+                    }
+                    None => false,
+                };
+                let ok = refine(&mut x, &mut r, &mut d, |x, r| residual_dd(&v, &b, x, r), solve);
+                ok.then(|| $v::new_from(x))
+            }
+        }
+    };
+}
+
+impl_double_double_types!(Matrix2d, Vector2d, 2);
+impl_double_double_types!(Matrix3d, Vector3d, 3);
+impl_double_double_types!(Matrix4d, Vector4d, 4);
+
+// dense LU with partial pivoting, factored once for all the refinement steps
+#[cfg(feature = "alloc")]
+struct DenseLu {
+    n: usize,
+    lu: Vec<f64>,
+    perm: Vec<usize>,
+}
+
+#[cfg(feature = "alloc")]
+impl DenseLu {
+    fn new(n: usize, mut lu: Vec<f64>) -> Option<Self> {
+        let mut perm: Vec<usize> = (0..n).collect();
+        for c in 0..n {
+            let p = (c..n).max_by(|i, j| lu[i * n + c].abs().total_cmp(&lu[j * n + c].abs()))?;
+            if lu[p * n + c] == 0.0 {
+                return None;
+            }
+            if p != c {
+                for j in 0..n {
+                    lu.swap(p * n + j, c * n + j);
+                }
+                perm.swap(p, c);
+            }
+            let (top, bottom) = lu.split_at_mut((c + 1) * n);
+            let pivot = &top[c * n..];
+            for row in bottom.chunks_mut(n) {
+                let f = row[c] / pivot[c];
+                row[c] = f;
+                for (x, p) in row[c + 1..].iter_mut().zip(&pivot[c + 1..]) {
+                    *x -= f * p;
+                }
+            }
+        }
+        Some(Self { n, lu, perm })
+    }
+
+    fn solve(&self, b: &mut [f64]) {
+        let n = self.n;
+        let mut y: Vec<f64> = self.perm.iter().map(|&i| b[i]).collect();
+        for i in 0..n {
+            let s = (0..i).fold(y[i], |s, j| s - self.lu[i * n + j] * y[j]);
+            y[i] = s;
+        }
+        for i in (0..n).rev() {
+            let s = (i + 1..n).fold(y[i], |s, j| s - self.lu[i * n + j] * y[j]);
+            y[i] = s / self.lu[i * n + i];
+        }
+        b.copy_from_slice(&y);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl MatrixXd {
+    // Solves the square system A x = b in f64 and refines x with double-double
+    // residuals, None when A is singular
+    pub fn solve_refined(&self, b: &[f64]) -> Option<Vec<DoubleDouble>> {
+        assert!(self.is_square(), "solve_refined needs a square matrix");
+        let n = self.get_rows();
+        assert_eq!(b.len(), n, "right-hand side length doesn't match the matrix");
+        let lu = DenseLu::new(n, self.as_slice().to_vec())?;
+        let mut x = vec![DoubleDouble::ZERO; n];
+        let (mut r, mut d) = (vec![DoubleDouble::ZERO; n], vec![0.0; n]);
+        let ok = refine(&mut x, &mut r, &mut d, |x, r| residual_dd(self.as_slice(), b, x, r), |d| {
+            lu.solve(d);
+            true
+        });
+        ok.then_some(x)
+    }
+
+    // Least squares solution of min |A x - b| for a full column rank A, refined
+    // through the augmented system [I A; A^T 0] [r; x] = [b; 0] whose residuals
+    // carry both the fit residual and the normal equations. None when A is
+    // rank deficient.
+    pub fn least_squares_refined(&self, b: &[f64]) -> Option<Vec<DoubleDouble>> {
+        let (m, n) = (self.get_rows(), self.get_cols());
+        assert!(m >= n, "least squares needs at least as many rows as columns");
+        assert_eq!(b.len(), m, "right-hand side length doesn't match the matrix");
+        let k = m + n;
+        let mut aug = vec![0.0; k * k];
+        for i in 0..m {
+            aug[i * k + i] = 1.0;
+            for j in 0..n {
+                aug[i * k + m + j] = self.get(i, j);
+                aug[(m + j) * k + i] = self.get(i, j);
+            }
+        }
+        let mut rhs = b.to_vec();
+        rhs.resize(k, 0.0);
+        let x = MatrixXd::new(k, k, aug).solve_refined(&rhs)?;
+        Some(x[m..].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rational::Rational;
+
+    fn dd(x: f64) -> DoubleDouble {
+        DoubleDouble::from_f64(x)
+    }
+
+    fn close(a: DoubleDouble, b: DoubleDouble, tol: f64) -> bool {
+        (a - b).abs().hi <= tol * b.abs().hi.max(1e-300)
+    }
+
+    // num / den rounded to double-double
+    fn exact(q: Rational) -> DoubleDouble {
+        dd(q.numer() as f64) / dd(q.denom() as f64)
+    }
+
+    #[test]
+    fn error_free_arithmetic() {
+        // 1 + 2^-80 is not an f64 but is a double-double
+        let tiny = dd(2f64.powi(-80));
+        let x = dd(1.0) + tiny;
+        assert_eq!((x.hi, x.lo), (1.0, 2f64.powi(-80)));
+        assert_eq!(x - 1.0, tiny);
+
+        let third = dd(1.0) / dd(3.0);
+        assert!((third * 3.0 - 1.0).abs().hi < 1e-31);
+        assert!(third.lo != 0.0);
+        let s = dd(2.0).sqrt();
+        assert!((s * s - 2.0).abs().hi < 1e-31);
+        assert!(close(dd(7.0).sqrt() * dd(7.0).sqrt(), dd(7.0), 1e-31));
+        assert!(close(dd(1.1).powi(-20) * dd(1.1).powi(20), dd(1.0), 1e-30));
+        assert_eq!(dd(2.5).floor(), dd(2.0));
+        assert!(dd(1.0) + tiny > dd(1.0) && -tiny < DoubleDouble::ZERO);
+    }
+
+    #[test]
+    fn parse_and_format() {
+        let pi: DoubleDouble = "3.14159265358979323846264338327950288".parse().unwrap();
+        assert!(close(pi, DoubleDouble::PI, 1e-31));
+        let tenth: DoubleDouble = "0.1".parse().unwrap();
+        assert!((tenth * 10.0 - 1.0).abs().hi < 1e-31);
+        assert_eq!("-2.5e3".parse::<DoubleDouble>(), Ok(dd(-2500.0)));
+        assert_eq!("".parse::<DoubleDouble>(), Err(ParseDoubleDoubleError::Empty));
+        assert_eq!("1.2.3".parse::<DoubleDouble>(), Err(ParseDoubleDoubleError::InvalidDigit));
+        assert_eq!("1e".parse::<DoubleDouble>(), Err(ParseDoubleDoubleError::InvalidExponent));
+
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(format!("{}", DoubleDouble::PI), "3.1415926535897932384626433832795e0");
+            assert_eq!(format!("{:.3}", dd(-0.0009996)), "-9.996e-4");
+            assert_eq!(format!("{:.2}", dd(9.999)), "1.00e1");
+            assert_eq!(format!("{:.1}", DoubleDouble::ZERO), "0.0e0");
+            let x = dd(1.0) / dd(7.0) * dd(1e20);
+            let back: DoubleDouble = format!("{}", x).parse().unwrap();
+            assert!(close(back, x, 1e-30));
+        }
+    }
+
+    #[test]
+    fn usable_in_the_fixed_size_types() {
+        let third = dd(1.0) / dd(3.0);
+        let m = Matrix2d::new([third, dd(1.0), dd(0.0), third]);
+        let v = Vector2d::new_from([dd(3.0), dd(3.0)]);
+        let p = m * v;
+        assert!(close(p.x, dd(4.0), 1e-31) && close(p.y, dd(1.0), 1e-31));
+        assert!(close(Vector2d::new_from([dd(3.0), dd(4.0)]).length(), dd(5.0), 1e-31));
+        let x = m.solve(p).unwrap();
+        assert!(close(x.x, dd(3.0), 1e-30) && close(x.y, dd(3.0), 1e-30));
+    }
+
+    #[test]
+    fn refinement_reaches_double_double_accuracy() {
+        // condition number around 1e7, the exact solution from rationals
+        let e = 1.0 / (1u64 << 24) as f64;
+        let a = Matrix3d::new([1.0, 1.0, 0.0, 1.0, 1.0 + e, 0.0, 0.0, 1.0, 3.0]);
+        let b = Vector3d::new_from([1.0, 2.0, 2.0]);
+        let scale = 1i64 << 24;
+        let aq = Matrix3d::new([1, 1, 0, 1, scale + 1, 0, 0, 1, 3].map(|k| {
+            if k == scale + 1 { Rational::new(k, scale) } else { Rational::integer(k) }
+        }));
+        let xq = aq.solve(Vector3d::new_from([1, 2, 2].map(Rational::integer))).unwrap();
+
+        let x = a.solve_refined(b).unwrap();
+        for (x, q) in x.to_list().iter().zip(xq.to_list()) {
+            assert!(close(*x, exact(q), 1e-29), "{:?} vs {}", x, q);
+        }
+        // plain f64 stops at its own precision
+        let plain = lu_solve([[1.0, 1.0, 0.0], [1.0, 1.0 + e, 0.0], [0.0, 1.0, 3.0]], b.to_list(), f64::abs).unwrap();
+        assert!(!close(dd(plain[2]), exact(xq.z), 1e-20));
+        assert!(Matrix2d::new([1.0, 2.0, 2.0, 4.0]).solve_refined(Vector2d::new_from([1.0, 1.0])).is_none());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn refined_dense_and_least_squares() {
+        let a = MatrixXd::from_fn(5, 5, |i, j| 1.0 / (i + j + 1) as f64);
+        let b: Vec<f64> = (0..5).map(|i| i as f64 - 1.5).collect();
+        let x = a.solve_refined(&b).unwrap();
+        let mut r = vec![DoubleDouble::ZERO; 5];
+        residual_dd(a.as_slice(), &b, &x, &mut r);
+        assert!(r.iter().all(|r| r.abs().hi < 1e-28));
+
+        // x = (1/3, 1/3) minimizes the fit of the three equations
+        let a = MatrixXd::new(3, 2, vec![1.0, 0.0, 0.0, 1.0, 1.0, 1.0]);
+        let x = a.least_squares_refined(&[1.0, 1.0, 0.0]).unwrap();
+        let third = dd(1.0) / dd(3.0);
+        assert!(close(x[0], third, 1e-30) && close(x[1], third, 1e-30));
+        assert!(MatrixXd::new(2, 2, vec![1.0, 1.0, 1.0, 1.0]).solve_refined(&[1.0, 2.0]).is_none());
+    }
+}
@@ -0,0 +1,450 @@
+// Fixed-point scalars for targets without an FPU and for simulations that
+// need bit-identical results on every machine.
+//
+// Fixed<I, FRAC, M> stores a value as the integer I scaled by 2^FRAC, so
+// Fixed<i32, 16> is Q16.16. Everything, sqrt and the trigonometry included, is
+// integer arithmetic: the same inputs give the same bits everywhere. The
+// intermediate results are computed in i128 and brought back to I according
+// to the overflow mode M, Wrapping (the default, like the integer operators
+// in release builds) or Saturating.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+use crate::scalar::{Real, Scalar};
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+// Integer types that can back a Fixed
+pub trait FixedInt: Copy + Eq + Ord + Hash + Default + core::fmt::Debug {
+    const BITS: u32;
+    const MIN: i128;
+    const MAX: i128;
+
+    fn widen(self) -> i128;
+    // the low BITS bits of x
+    fn wrap(x: i128) -> Self;
+}
+
+macro_rules! impl_fixed_int {
+    ($($t:ty),*) => {
+        $(impl FixedInt for $t {
+            const BITS: u32 = <$t>::BITS;
+            const MIN: i128 = <$t>::MIN as i128;
+            const MAX: i128 = <$t>::MAX as i128;
+
+            fn widen(self) -> i128 {
+                self as i128
+            }
+
+            fn wrap(x: i128) -> Self {
+                x as $t
+            }
+        })*
+    };
+}
+
+impl_fixed_int!(i16, i32, i64);
+
+// What happens to results that don't fit in the backing integer
+pub trait OverflowMode: Copy + Eq + Ord + Hash + Default + core::fmt::Debug {
+    fn narrow<I: FixedInt>(x: i128) -> I;
+    // x / 0 for a raw numerator x
+    fn div_by_zero<I: FixedInt>(x: i128) -> I;
+}
+
+// keeps the low bits, division by zero panics like the integer division
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Wrapping;
+
+// clamps to the representable range, x / 0 goes to the bound with the sign of x
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Saturating;
+
+impl OverflowMode for Wrapping {
+    fn narrow<I: FixedInt>(x: i128) -> I {
+        I::wrap(x)
+    }
+
+    fn div_by_zero<I: FixedInt>(_: i128) -> I {
+        panic!("fixed-point division by zero")
+    }
+}
+
+impl OverflowMode for Saturating {
+    fn narrow<I: FixedInt>(x: i128) -> I {
+        I::wrap(x.clamp(I::MIN, I::MAX))
+    }
+
+    fn div_by_zero<I: FixedInt>(x: i128) -> I {
+        I::wrap(match x.signum() {
+            1 => I::MAX,
+            -1 => I::MIN,
+            _ => 0,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Fixed<I, const FRAC: u32, M = Wrapping> {
+    bits: I,
+    mode: PhantomData<M>,
+}
+
+pub type Q16_16 = Fixed<i32, 16>;
+pub type Q32_32 = Fixed<i64, 32>;
+
+// CORDIC tables in Q2.30: atan(2^-i) and the gain 1 / prod sqrt(1 + 2^-2i)
+const CORDIC_FRAC: u32 = 30;
+const CORDIC_ATAN: [i64; 31] = [
+    843314857, 497837829, 263043837, 133525159, 67021687, 33543516, 16775851, 8388437, 4194283, 2097149,
+    1048576, 524288, 262144, 131072, 65536, 32768, 16384, 8192, 4096, 2048, 1024, 512, 256, 128, 64, 32,
+    16, 8, 4, 2, 1,
+];
+const CORDIC_GAIN: i64 = 652032874;
+
+// angles for the argument reduction, in Q4.60
+const ANGLE_FRAC: u32 = 60;
+const TWO_PI: i128 = 7244019458077122842;
+const PI: i128 = 3622009729038561421;
+const FRAC_PI_2: i128 = 1811004864519280711;
+
+// x * 2^-shift rounded to nearest, ties up
+fn round_shift(x: i128, shift: u32) -> i128 {
+    if shift == 0 { x } else { (x + (1 << (shift - 1))) >> shift }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> Fixed<I, FRAC, M> {
+    // rejects FRAC values that leave no integer bit, at compile time
+    const VALID: () = assert!(FRAC < I::BITS - 1, "FRAC has to leave a sign bit and an integer bit");
+
+    pub fn from_bits(bits: I) -> Self {
+        let () = Self::VALID;
+        Self { bits, mode: PhantomData }
+    }
+
+    pub fn to_bits(self) -> I {
+        self.bits
+    }
+
+    fn narrow(x: i128) -> Self {
+        Self::from_bits(M::narrow(x))
+    }
+
+    pub fn from_int(n: i64) -> Self {
+        Self::narrow((n as i128) << FRAC)
+    }
+
+    // nearest fixed-point value. Out of range values clamp to the bounds in
+    // every overflow mode, NaN gives zero.
+    pub fn from_f64(x: f64) -> Self {
+        let scaled = x * (1u64 << FRAC) as f64;
+        let r = if scaled >= 0.0 { scaled + 0.5 } else { scaled - 0.5 };
+        // float to int casts saturate, NaN goes to zero
+        Self::from_bits(I::wrap((r as i128).clamp(I::MIN, I::MAX)))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.bits.widen() as f64 / (1u64 << FRAC) as f64
+    }
+
+    pub fn min_value() -> Self {
+        Self::from_bits(I::wrap(I::MIN))
+    }
+
+    pub fn max_value() -> Self {
+        Self::from_bits(I::wrap(I::MAX))
+    }
+
+    // the smallest positive value, 2^-FRAC
+    pub fn delta() -> Self {
+        Self::from_bits(I::wrap(1))
+    }
+
+    fn raw(self) -> i128 {
+        self.bits.widen()
+    }
+
+    fn checked(x: i128) -> Option<Self> {
+        (I::MIN..=I::MAX).contains(&x).then(|| Self::from_bits(I::wrap(x)))
+    }
+
+    fn mul_raw(self, o: Self) -> i128 {
+        round_shift(self.raw() * o.raw(), FRAC)
+    }
+
+    // truncated toward zero, like the integer division
+    fn div_raw(self, o: Self) -> Option<i128> {
+        (o.raw() != 0).then(|| (self.raw() << FRAC) / o.raw())
+    }
+
+    pub fn checked_add(self, o: Self) -> Option<Self> {
+        Self::checked(self.raw() + o.raw())
+    }
+
+    pub fn checked_sub(self, o: Self) -> Option<Self> {
+        Self::checked(self.raw() - o.raw())
+    }
+
+    pub fn checked_mul(self, o: Self) -> Option<Self> {
+        Self::checked(self.mul_raw(o))
+    }
+
+    pub fn checked_div(self, o: Self) -> Option<Self> {
+        Self::checked(self.div_raw(o)?)
+    }
+
+    pub fn abs(self) -> Self {
+        Self::narrow(self.raw().abs())
+    }
+
+    pub fn floor(self) -> Self {
+        Self::narrow(self.raw() >> FRAC << FRAC)
+    }
+
+    // integer square root of the scaled value, exact to the last bit (rounded
+    // down); negative numbers give zero
+    pub fn sqrt(self) -> Self {
+        if self.raw() <= 0 {
+            return Self::from_bits(I::wrap(0));
+        }
+        Self::narrow(((self.raw() as u128) << FRAC).isqrt() as i128)
+    }
+
+    // both at once, by CORDIC rotation after reducing the angle to [-pi/2, pi/2]
+    // with a 60-bit 2 pi. The error is a few units of 2^-30, whatever FRAC.
+    pub fn sin_cos(self) -> (Self, Self) {
+        let mut a = if FRAC <= ANGLE_FRAC {
+            self.raw() << (ANGLE_FRAC - FRAC)
+        } else {
+            round_shift(self.raw(), FRAC - ANGLE_FRAC)
+        };
+        a = a.rem_euclid(TWO_PI);
+        if a > PI {
+            a -= TWO_PI;
+        }
+        // sin(pi - a) = sin a while cos changes sign
+        let mut cos_sign = 1;
+        if a > FRAC_PI_2 {
+            a = PI - a;
+            cos_sign = -1;
+        } else if a < -FRAC_PI_2 {
+            a = -PI - a;
+            cos_sign = -1;
+        }
+
+        let mut z = round_shift(a, ANGLE_FRAC - CORDIC_FRAC) as i64;
+        let (mut x, mut y) = (CORDIC_GAIN, 0i64);
+        for (i, atan) in CORDIC_ATAN.iter().enumerate() {
+            let (dx, dy) = (y >> i, x >> i);
+            if z >= 0 {
+                (x, y, z) = (x - dx, y + dy, z - atan);
+            } else {
+                (x, y, z) = (x + dx, y - dy, z + atan);
+            }
+        }
+        let to_fixed = |v: i64| {
+            let v = v as i128;
+            if FRAC >= CORDIC_FRAC { v << (FRAC - CORDIC_FRAC) } else { round_shift(v, CORDIC_FRAC - FRAC) }
+        };
+        (Self::narrow(to_fixed(y)), Self::narrow(cos_sign * to_fixed(x)))
+    }
+
+    pub fn sin(self) -> Self {
+        self.sin_cos().0
+    }
+
+    pub fn cos(self) -> Self {
+        self.sin_cos().1
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> core::fmt::Debug for Fixed<I, FRAC, M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Fixed({:?})", self.to_f64())
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> core::fmt::Display for Fixed<I, FRAC, M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.to_f64(), f)
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> core::ops::Add for Fixed<I, FRAC, M> {
+    type Output = Self;
+    fn add(self, o: Self) -> Self::Output {
+        Self::narrow(self.raw() + o.raw())
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> core::ops::Sub for Fixed<I, FRAC, M> {
+    type Output = Self;
+    fn sub(self, o: Self) -> Self::Output {
+        Self::narrow(self.raw() - o.raw())
+    }
+}
+
+// rounded to nearest
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> core::ops::Mul for Fixed<I, FRAC, M> {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self::Output {
+        Self::narrow(self.mul_raw(o))
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> core::ops::Div for Fixed<I, FRAC, M> {
+    type Output = Self;
+    fn div(self, o: Self) -> Self::Output {
+        match self.div_raw(o) {
+            Some(q) => Self::narrow(q),
+            None => Self::from_bits(M::div_by_zero(self.raw())),
+        }
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> core::ops::Neg for Fixed<I, FRAC, M> {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Self::narrow(-self.raw())
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> Scalar for Fixed<I, FRAC, M> {
+    fn zero() -> Self {
+        Self::from_bits(I::wrap(0))
+    }
+
+    fn one() -> Self {
+        Self::from_int(1)
+    }
+}
+
+impl<I: FixedInt, const FRAC: u32, M: OverflowMode> Real for Fixed<I, FRAC, M> {
+    fn sqrt(self) -> Self {
+        Fixed::sqrt(self)
+    }
+}
+
+macro_rules! impl_fixed_types {
+    ($t:ident, $to:ident, $from:ident) => {
+        impl<I: FixedInt, const FRAC: u32, M: OverflowMode> $t<Fixed<I, FRAC, M>> {
+            // nearest fixed-point values of the entries
+            pub fn from_f64(v: $t) -> Self {
+                $t::$from(v.$to().map(Fixed::from_f64))
+            }
+
+            pub fn to_f64(self) -> $t {
+                $t::$from(self.$to().map(Fixed::to_f64))
+            }
+        }
+    };
+}
+
+impl_fixed_types!(Vector2d, to_list, new_from);
+impl_fixed_types!(Vector3d, to_list, new_from);
+impl_fixed_types!(Vector4d, to_list, new_from);
+impl_fixed_types!(Matrix2d, to_row_major_array, from_row_major);
+impl_fixed_types!(Matrix3d, to_row_major_array, from_row_major);
+impl_fixed_types!(Matrix4d, to_row_major_array, from_row_major);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Sat16 = Fixed<i32, 16, Saturating>;
+
+    fn q(x: f64) -> Q16_16 {
+        Q16_16::from_f64(x)
+    }
+
+    #[test]
+    fn arithmetic_and_overflow_modes() {
+        assert_eq!(q(1.5) * q(2.25), q(3.375));
+        assert_eq!(q(3.375) / q(1.5), q(2.25));
+        assert_eq!(q(1.0).to_bits(), 1 << 16);
+        assert_eq!(-q(2.5) + q(1.0), q(-1.5));
+        assert_eq!(Q16_16::from_int(-3).floor(), Q16_16::from_int(-3));
+        assert_eq!(q(-2.5).floor(), Q16_16::from_int(-3));
+        // 1 / 3 truncated, times 3 one delta short of 1
+        let third = Q16_16::one() / Q16_16::from_int(3);
+        assert_eq!(third * Q16_16::from_int(3) + Q16_16::delta(), Q16_16::one());
+
+        let max = Q16_16::max_value();
+        assert_eq!(max + Q16_16::delta(), Q16_16::min_value());
+        assert_eq!(max.checked_add(Q16_16::delta()), None);
+        assert_eq!(q(100.0).checked_mul(q(100.0)), Some(q(10000.0)));
+        assert_eq!(q(200.0).checked_mul(q(200.0)), None);
+
+        let smax = Sat16::max_value();
+        assert_eq!(smax + Sat16::delta(), smax);
+        assert_eq!(Sat16::from_int(300) * Sat16::from_int(-300), Sat16::min_value());
+        assert_eq!(Sat16::from_int(1) / Sat16::zero(), smax);
+        assert_eq!(Sat16::from_int(-1) / Sat16::zero(), Sat16::min_value());
+        assert_eq!(Sat16::from_f64(1e10), smax);
+
+        // conversions clamp even when the arithmetic wraps
+        assert_eq!(q(1e10), Q16_16::max_value());
+        assert_eq!(q(f64::INFINITY), Q16_16::max_value());
+        assert_eq!(q(f64::NEG_INFINITY), Q16_16::min_value());
+        assert_eq!(q(-1e10), Q16_16::min_value());
+        assert_eq!(q(f64::NAN), Q16_16::zero());
+    }
+
+    #[test]
+    fn sqrt_is_exact_to_the_last_bit() {
+        assert_eq!(q(0.25).sqrt(), q(0.5));
+        assert_eq!(Q16_16::from_int(81).sqrt(), Q16_16::from_int(9));
+        let s = q(2.0).sqrt();
+        // largest value whose square is at most 2
+        assert!(s.to_bits() as i64 * s.to_bits() as i64 <= 2 << 32);
+        assert!((s.to_bits() as i64 + 1) * (s.to_bits() as i64 + 1) > 2 << 32);
+        assert!((Q32_32::from_int(2).sqrt().to_f64() - core::f64::consts::SQRT_2).abs() < 1e-9);
+        assert_eq!(q(-4.0).sqrt(), Q16_16::zero());
+    }
+
+    #[test]
+    fn cordic_sin_cos() {
+        for k in -40..=40 {
+            let t = k as f64 * 0.37;
+            let (s, c) = q(t).sin_cos();
+            // the input itself is rounded to 2^-16
+            let t16 = q(t).to_f64();
+            assert!((s.to_f64() - crate::math::sin(t16)).abs() < 4e-5, "sin {}", t);
+            assert!((c.to_f64() - crate::math::cos(t16)).abs() < 4e-5, "cos {}", t);
+
+            let (s, c) = Q32_32::from_f64(t).sin_cos();
+            let t32 = Q32_32::from_f64(t).to_f64();
+            assert!((s.to_f64() - crate::math::sin(t32)).abs() < 1e-8);
+            assert!((c.to_f64() - crate::math::cos(t32)).abs() < 1e-8);
+        }
+        assert_eq!(Q16_16::zero().sin_cos(), (Q16_16::zero(), Q16_16::one()));
+        assert_eq!(Fixed::<i16, 8>::from_f64(1.0).sin(), Fixed::<i16, 8>::from_f64(crate::math::sin(1.0)));
+    }
+
+    #[test]
+    fn matrix_vector_in_fixed_point() {
+        // rotation about z by 0.5 rad, then a translation-free scale
+        let (s, c) = q(0.5).sin_cos();
+        let (z, o) = (Q16_16::zero(), Q16_16::one());
+        let m = Matrix3d::new([c, -s, z, s, c, z, z, z, o]);
+        let v = Vector3d::<Q16_16>::from_f64(Vector3d::new_from([2.0, 1.0, -3.0]));
+        let r = m * v;
+        let expect = Matrix3d::new([c.to_f64(), -s.to_f64(), 0.0, s.to_f64(), c.to_f64(), 0.0, 0.0, 0.0, 1.0])
+            * Vector3d::new_from([2.0, 1.0, -3.0]);
+        for (a, b) in r.to_f64().to_list().iter().zip(expect.to_list()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+        // the same bits on every machine
+        assert_eq!(r.to_list().map(|x| x.to_bits()), [83606, 120353, -196608]);
+        assert!((r.length().to_f64() - crate::math::sqrt(14.0)).abs() < 1e-4);
+
+        let m4 = Matrix4d::<Q32_32>::from_f64(Matrix4d::new(core::array::from_fn(|i| (i % 5 == 0) as u8 as f64 * 2.0)));
+        let v4 = Vector4d::<Q32_32>::from_f64(Vector4d::new_from([0.5, -1.0, 1.5, 2.0]));
+        assert_eq!((m4 * v4).to_f64(), Vector4d::new_from([1.0, -2.0, 3.0, 4.0]));
+        assert_eq!((m4 * m4).to_f64(), Matrix4d::new(core::array::from_fn(|i| (i % 5 == 0) as u8 as f64 * 4.0)));
+    }
+}
@@ -0,0 +1,220 @@
+// View frustum culling. The planes are pulled straight out of the rows of a
+// view-projection matrix (Gribb–Hartmann), so the frustum is in whatever
+// space the matrix maps from: world space for projection * view, view space
+// for a bare projection. Normals point into the frustum.
+
+use super::geometry::*;
+use super::matrix4d::*;
+use super::vector3d::*;
+
+// Clip-space depth range of the projection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthRange {
+    // -w <= z <= w (OpenGL)
+    NegativeOneToOne,
+    // 0 <= z <= w (Direct3D, Vulkan, Metal)
+    ZeroToOne,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    // left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    pub fn from_matrix(m: &Matrix4d, depth: DepthRange) -> Self {
+        let row = |r: usize| [m.get(r, 0), m.get(r, 1), m.get(r, 2), m.get(r, 3)];
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let plane = |a: [f64; 4], s: f64, b: [f64; 4]| {
+            Plane::new(Vector3d::new_from([a[0] + s * b[0], a[1] + s * b[1], a[2] + s * b[2]]), a[3] + s * b[3])
+        };
+        let near = match depth {
+            DepthRange::NegativeOneToOne => plane(r3, 1.0, r2),
+            DepthRange::ZeroToOne => plane(r2, 0.0, r2),
+        };
+        Self {
+            planes: [
+                plane(r3, 1.0, r0),
+                plane(r3, -1.0, r0),
+                plane(r3, 1.0, r1),
+                plane(r3, -1.0, r1),
+                near,
+                plane(r3, -1.0, r2),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, p: Vector3d) -> bool {
+        self.planes.iter().all(|pl| pl.signed_distance(p) >= 0.0)
+    }
+
+    // classifies a shape from its signed distance s to each plane and its
+    // projected radius r on the plane normal
+    fn classify(&self, radius_and_distance: impl Fn(&Plane) -> (f64, f64)) -> Containment {
+        let mut result = Containment::Inside;
+        for pl in &self.planes {
+            let (r, s) = radius_and_distance(pl);
+            if s < -r {
+                return Containment::Outside;
+            }
+            if s < r {
+                result = Containment::Intersecting;
+            }
+        }
+        result
+    }
+
+    // Plane tests only: a shape near a frustum corner can be reported as
+    // Intersecting while lying outside, but never the other way around.
+    pub fn test_sphere(&self, s: &Sphere) -> Containment {
+        self.classify(|pl| (s.radius, pl.signed_distance(s.center)))
+    }
+
+    pub fn test_aabb(&self, b: &Aabb) -> Containment {
+        let (c, h) = (b.center(), b.half_extents());
+        self.classify(|pl| {
+            let n = pl.normal;
+            (n.x.abs() * h.x + n.y.abs() * h.y + n.z.abs() * h.z, pl.signed_distance(c))
+        })
+    }
+
+    pub fn test_obb(&self, b: &Obb) -> Containment {
+        self.classify(|pl| {
+            let r = b.axes.iter().zip(b.half.to_list()).map(|(a, h)| h * (*a * pl.normal).abs()).sum();
+            (r, pl.signed_distance(b.center))
+        })
+    }
+
+    // visible[i] is false only for spheres entirely outside the frustum
+    pub fn cull_spheres(&self, spheres: &[Sphere], visible: &mut [bool]) {
+        assert_eq!(spheres.len(), visible.len(), "source and destination must have the same length");
+        for (s, v) in spheres.iter().zip(visible.iter_mut()) {
+            *v = self.planes.iter().all(|pl| pl.signed_distance(s.center) >= -s.radius);
+        }
+    }
+
+    // visible[i] is false only for boxes entirely outside the frustum
+    pub fn cull_aabbs(&self, boxes: &[Aabb], visible: &mut [bool]) {
+        assert_eq!(boxes.len(), visible.len(), "source and destination must have the same length");
+        for (b, v) in boxes.iter().zip(visible.iter_mut()) {
+            // the corner furthest along each normal
+            *v = self.planes.iter().all(|pl| {
+                let n = pl.normal;
+                let p = Vector3d::new_from([
+                    if n.x >= 0.0 { b.max.x } else { b.min.x },
+                    if n.y >= 0.0 { b.max.y } else { b.min.y },
+                    if n.z >= 0.0 { b.max.z } else { b.min.z },
+                ]);
+                pl.signed_distance(p) >= 0.0
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v3(x: f64, y: f64, z: f64) -> Vector3d {
+        Vector3d::new_from([x, y, z])
+    }
+
+    // right-handed perspective looking down -z with a 90 degree field of view,
+    // near 1 and far 10, so |x| <= -z and |y| <= -z
+    fn perspective(depth: DepthRange) -> Matrix4d {
+        let (n, f) = (1.0, 10.0);
+        let (a, b) = match depth {
+            DepthRange::NegativeOneToOne => ((f + n) / (n - f), 2.0 * f * n / (n - f)),
+            DepthRange::ZeroToOne => (f / (n - f), f * n / (n - f)),
+        };
+        Matrix4d::new([1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, a, b, 0.0, 0.0, -1.0, 0.0])
+    }
+
+    #[test]
+    fn extraction() {
+        for depth in [DepthRange::NegativeOneToOne, DepthRange::ZeroToOne] {
+            let fr = Frustum::from_matrix(&perspective(depth), depth);
+            let near = fr.planes[4];
+            let far = fr.planes[5];
+            assert!((near.signed_distance(v3(0.0, 0.0, -1.0))).abs() < 1e-12);
+            assert!((far.signed_distance(v3(0.0, 0.0, -10.0))).abs() < 1e-12);
+            assert!((near.normal - v3(0.0, 0.0, -1.0)).length() < 1e-12);
+            let s = core::f64::consts::FRAC_1_SQRT_2;
+            assert!((fr.planes[0].normal - v3(s, 0.0, -s)).length() < 1e-12);
+            assert!(fr.contains_point(v3(0.9, -0.9, -1.0)));
+            assert!(fr.contains_point(v3(9.8, 9.8, -9.9)));
+            assert!(!fr.contains_point(v3(0.0, 0.0, -0.9)));
+            assert!(!fr.contains_point(v3(0.0, 0.0, -10.1)));
+            assert!(!fr.contains_point(v3(2.1, 0.0, -2.0)));
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        // camera at z = 5 looking down -z, the frustum spans z in [-5, 4]
+        let view = Matrix4d::new([1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, -5.0, 0.0, 0.0, 0.0, 1.0]);
+        let depth = DepthRange::ZeroToOne;
+        let fr = Frustum::from_matrix(&(perspective(depth) * view), depth);
+        let sphere = |x, y, z, r| fr.test_sphere(&Sphere::new(v3(x, y, z), r));
+        assert_eq!(sphere(0.0, 0.0, 0.0, 1.0), Containment::Inside);
+        assert_eq!(sphere(0.0, 0.0, 4.5, 1.0), Containment::Intersecting);
+        assert_eq!(sphere(0.0, 0.0, 6.5, 1.0), Containment::Outside);
+        assert_eq!(sphere(0.0, 30.0, 0.0, 1.0), Containment::Outside);
+
+        let aabb = |c: Vector3d, h: f64| fr.test_aabb(&Aabb::new(c - h, c + h));
+        assert_eq!(aabb(v3(0.0, 0.0, 0.0), 1.0), Containment::Inside);
+        assert_eq!(aabb(v3(5.0, 0.0, 0.0), 1.0), Containment::Intersecting);
+        assert_eq!(aabb(v3(8.0, 0.0, 0.0), 1.0), Containment::Outside);
+        assert_eq!(aabb(v3(0.0, 0.0, -6.5), 1.0), Containment::Outside);
+
+        // a box turned 45 degrees about z reaches sqrt(2) along x
+        let s = core::f64::consts::FRAC_1_SQRT_2;
+        let axes = [v3(s, s, 0.0), v3(-s, s, 0.0), v3(0.0, 0.0, 1.0)];
+        let obb = |c: Vector3d| fr.test_obb(&Obb::new(c, axes, v3(1.0, 1.0, 1.0)));
+        // at z = 0 the half width is 5 and the planes lean by 45 degrees
+        assert_eq!(obb(v3(2.0, 0.0, 0.0)), Containment::Inside);
+        assert_eq!(obb(v3(3.5, 0.0, 0.0)), Containment::Intersecting);
+        assert_eq!(obb(v3(7.5, 0.0, 0.0)), Containment::Outside);
+    }
+
+    #[test]
+    fn batch_is_conservative() {
+        let depth = DepthRange::NegativeOneToOne;
+        let fr = Frustum::from_matrix(&perspective(depth), depth);
+        let n = 400;
+        let spheres: [Sphere; 400] = core::array::from_fn(|i| {
+            let t = i as f64;
+            let c = v3(crate::math::sin(t) * 12.0, crate::math::cos(t * 0.7) * 12.0, -((i % 13) as f64));
+            Sphere::new(c, 0.2 + (i % 5) as f64 * 0.4)
+        });
+        let boxes = spheres.map(|s| s.aabb());
+        let mut vis_s = [false; 400];
+        let mut vis_b = [false; 400];
+        fr.cull_spheres(&spheres, &mut vis_s);
+        fr.cull_aabbs(&boxes, &mut vis_b);
+        let mut culled = 0;
+        for i in 0..n {
+            assert_eq!(vis_s[i], fr.test_sphere(&spheres[i]) != Containment::Outside);
+            assert_eq!(vis_b[i], fr.test_aabb(&boxes[i]) != Containment::Outside);
+            // any sampled point inside the frustum keeps the object
+            let s = spheres[i];
+            let hit = (0..=8)
+                .flat_map(|a| (0..=8).flat_map(move |b| (0..=8).map(move |c| (a, b, c))))
+                .map(|(a, b, c)| s.center + v3(a as f64 - 4.0, b as f64 - 4.0, c as f64 - 4.0) * (s.radius / 4.0))
+                .any(|p| fr.contains_point(p) && s.contains(p));
+            if hit {
+                assert!(vis_s[i] && vis_b[i]);
+            }
+            culled += !vis_b[i] as usize;
+        }
+        assert!(culled > 100);
+    }
+}
@@ -0,0 +1,967 @@
+// Geometric primitives over Vector3d and the intersection and distance
+// queries between them.
+//
+// Rays are origin + t dir for t >= 0. The direction doesn't need to be unit
+// length, t is measured in units of dir. A ray cast reports where the ray
+// enters the shape, or where it leaves when the origin is inside a solid.
+// Normals are unit length: outward for the solids, along the winding
+// (a, b, c) for triangles and along the plane normal for planes.
+
+use super::matrix4d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+use crate::math;
+
+fn v3(x: f64, y: f64, z: f64) -> Vector3d {
+    Vector3d::new_from([x, y, z])
+}
+
+fn min_v(a: Vector3d, b: Vector3d) -> Vector3d {
+    v3(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
+fn max_v(a: Vector3d, b: Vector3d) -> Vector3d {
+    v3(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+}
+
+fn abs_v(a: Vector3d) -> Vector3d {
+    v3(a.x.abs(), a.y.abs(), a.z.abs())
+}
+
+fn axis(v: Vector3d, i: usize) -> f64 {
+    v.to_list()[i]
+}
+
+fn unit(i: usize) -> Vector3d {
+    Vector3d::new_from(core::array::from_fn(|j| (i == j) as u8 as f64))
+}
+
+pub(crate) fn transform_point(m: &Matrix4d, p: Vector3d) -> Vector3d {
+    let r = *m * Vector4d::new_from([p.x, p.y, p.z, 1.0]);
+    let p = v3(r.x, r.y, r.z);
+    if r.w == 1.0 { p } else { p / r.w }
+}
+
+pub(crate) fn transform_vector(m: &Matrix4d, v: Vector3d) -> Vector3d {
+    let r = *m * Vector4d::new_from([v.x, v.y, v.z, 0.0]);
+    v3(r.x, r.y, r.z)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3d,
+    pub dir: Vector3d,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3d, dir: Vector3d) -> Self {
+        Self { origin, dir }
+    }
+
+    pub fn at(&self, t: f64) -> Vector3d {
+        self.origin + self.dir * t
+    }
+
+    // componentwise 1 / dir, infinite for the zero components; computed once
+    // per ray for the slab tests
+    pub fn inv_dir(&self) -> Vector3d {
+        v3(1.0 / self.dir.x, 1.0 / self.dir.y, 1.0 / self.dir.z)
+    }
+
+    // the same ray in another frame, t is preserved by affine transforms
+    pub fn transform(&self, m: &Matrix4d) -> Ray {
+        Ray { origin: transform_point(m, self.origin), dir: transform_vector(m, self.dir) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub t: f64,
+    pub point: Vector3d,
+    pub normal: Vector3d,
+    // weights of the vertices a, b and c for triangle hits
+    pub barycentric: Option<Vector3d>,
+}
+
+// Shapes that a ray can be cast against
+pub trait RayCast {
+    fn ray_cast(&self, ray: &Ray) -> Option<RayHit>;
+}
+
+// Points p with normal * p + d = 0, normal is unit length. The signed distance
+// is positive on the side the normal points to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3d,
+    pub d: f64,
+}
+
+impl Plane {
+    // from the coefficients of a x + b y + c z + d = 0, rescaled to a unit normal
+    pub fn new(normal: Vector3d, d: f64) -> Self {
+        let l = normal.length();
+        Self { normal: normal / l, d: d / l }
+    }
+
+    pub fn from_point_normal(p: Vector3d, normal: Vector3d) -> Self {
+        let n = normal.normalize();
+        Self { normal: n, d: -(n * p) }
+    }
+
+    // normal along (b - a) x (c - a), None for collinear points
+    pub fn from_points(a: Vector3d, b: Vector3d, c: Vector3d) -> Option<Self> {
+        let n = (b - a).cross(c - a);
+        (n * n > 0.0).then(|| Self::from_point_normal(a, n))
+    }
+
+    pub fn signed_distance(&self, p: Vector3d) -> f64 {
+        self.normal * p + self.d
+    }
+
+    pub fn project(&self, p: Vector3d) -> Vector3d {
+        p - self.normal * self.signed_distance(p)
+    }
+}
+
+impl RayCast for Plane {
+    fn ray_cast(&self, ray: &Ray) -> Option<RayHit> {
+        let denom = self.normal * ray.dir;
+        if denom == 0.0 {
+            return None;
+        }
+        let t = -self.signed_distance(ray.origin) / denom;
+        (t >= 0.0).then(|| RayHit { t, point: ray.at(t), normal: self.normal, barycentric: None })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Vector3d,
+    pub radius: f64,
+}
+
+impl Sphere {
+    pub fn new(center: Vector3d, radius: f64) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn contains(&self, p: Vector3d) -> bool {
+        let d = p - self.center;
+        d * d <= self.radius * self.radius
+    }
+
+    pub fn closest_point(&self, p: Vector3d) -> Vector3d {
+        let d = p - self.center;
+        let l = d.length();
+        if l <= self.radius { p } else { self.center + d * (self.radius / l) }
+    }
+
+    pub fn overlaps_sphere(&self, o: &Sphere) -> bool {
+        let d = o.center - self.center;
+        let r = self.radius + o.radius;
+        d * d <= r * r
+    }
+
+    pub fn overlaps_aabb(&self, b: &Aabb) -> bool {
+        b.distance_squared(self.center) <= self.radius * self.radius
+    }
+
+    pub fn overlaps_triangle(&self, t: &Triangle) -> bool {
+        let d = t.closest_point(self.center).0 - self.center;
+        d * d <= self.radius * self.radius
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let r = Vector3d::new_from_const(self.radius);
+        Aabb { min: self.center - r, max: self.center + r }
+    }
+}
+
+impl RayCast for Sphere {
+    fn ray_cast(&self, ray: &Ray) -> Option<RayHit> {
+        let oc = ray.origin - self.center;
+        let a = ray.dir * ray.dir;
+        let b = oc * ray.dir;
+        let c = oc * oc - self.radius * self.radius;
+        let disc = b * b - a * c;
+        if disc < 0.0 || a == 0.0 {
+            return None;
+        }
+        let sq = math::sqrt(disc);
+        // the far root when the origin is inside
+        let t = [(-b - sq) / a, (-b + sq) / a].into_iter().find(|t| *t >= 0.0)?;
+        let point = ray.at(t);
+        Some(RayHit { t, point, normal: (point - self.center) / self.radius, barycentric: None })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3d,
+    pub max: Vector3d,
+}
+
+impl Aabb {
+    // box spanned by two opposite corners, in any order
+    pub fn new(a: Vector3d, b: Vector3d) -> Self {
+        Self { min: min_v(a, b), max: max_v(a, b) }
+    }
+
+    // contains nothing, the identity for union and grow
+    pub fn empty() -> Self {
+        Self { min: Vector3d::new_from_const(f64::INFINITY), max: Vector3d::new_from_const(f64::NEG_INFINITY) }
+    }
+
+    pub fn from_points(points: &[Vector3d]) -> Self {
+        points.iter().fold(Self::empty(), |b, p| b.grow(*p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&self, p: Vector3d) -> Self {
+        Self { min: min_v(self.min, p), max: max_v(self.max, p) }
+    }
+
+    pub fn union(&self, o: &Aabb) -> Self {
+        Self { min: min_v(self.min, o.min), max: max_v(self.max, o.max) }
+    }
+
+    pub fn center(&self) -> Vector3d {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vector3d {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // 0, 1 or 2 for x, y or z
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn contains(&self, p: Vector3d) -> bool {
+        (0..3).all(|i| axis(self.min, i) <= axis(p, i) && axis(p, i) <= axis(self.max, i))
+    }
+
+    pub fn overlaps(&self, o: &Aabb) -> bool {
+        (0..3).all(|i| axis(self.min, i) <= axis(o.max, i) && axis(o.min, i) <= axis(self.max, i))
+    }
+
+    pub fn closest_point(&self, p: Vector3d) -> Vector3d {
+        min_v(max_v(p, self.min), self.max)
+    }
+
+    pub fn distance_squared(&self, p: Vector3d) -> f64 {
+        let d = self.closest_point(p) - p;
+        d * d
+    }
+
+    // separating axis test with the box normals, the triangle normal and the
+    // nine edge cross products (Akenine-Möller)
+    pub fn overlaps_triangle(&self, t: &Triangle) -> bool {
+        let c = self.center();
+        let h = self.half_extents();
+        let v = [t.a - c, t.b - c, t.c - c];
+        let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+        let mut axes = [Vector3d::new(); 13];
+        axes[0] = unit(0);
+        axes[1] = unit(1);
+        axes[2] = unit(2);
+        axes[3] = edges[0].cross(edges[1]);
+        for i in 0..3 {
+            for (j, e) in edges.iter().enumerate() {
+                axes[4 + i * 3 + j] = unit(i).cross(*e);
+            }
+        }
+        axes.iter().all(|l| {
+            if *l * *l < 1e-24 {
+                return true;
+            }
+            let p = [v[0] * *l, v[1] * *l, v[2] * *l];
+            let r = abs_v(*l) * h;
+            let lo = p[0].min(p[1]).min(p[2]);
+            let hi = p[0].max(p[1]).max(p[2]);
+            !(lo > r || hi < -r)
+        })
+    }
+
+    // entry and exit t of the ray within [0, t_max], None when it misses.
+    // inv_dir is ray.inv_dir(), passed in so it's computed once per ray.
+    pub fn slab(&self, ray: &Ray, inv_dir: Vector3d, t_max: f64) -> Option<(f64, f64)> {
+        let mut t0 = 0.0f64;
+        let mut t1 = t_max;
+        for i in 0..3 {
+            let o = axis(ray.origin, i);
+            let inv = axis(inv_dir, i);
+            // rays parallel to the slab, 0 * inf would give NaN on its faces
+            if inv.is_infinite() {
+                if o < axis(self.min, i) || o > axis(self.max, i) {
+                    return None;
+                }
+                continue;
+            }
+            let a = (axis(self.min, i) - o) * inv;
+            let b = (axis(self.max, i) - o) * inv;
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        (t0 <= t1).then_some((t0, t1))
+    }
+
+    // bounding box of the transformed box (Arvo), for affine m
+    pub fn transform(&self, m: &Matrix4d) -> Aabb {
+        let c = transform_point(m, self.center());
+        let h = self.half_extents();
+        let e = Vector3d::new_from(core::array::from_fn(|i| {
+            (0..3).map(|j| m.get(i, j).abs() * axis(h, j)).sum::<f64>()
+        }));
+        Aabb { min: c - e, max: c + e }
+    }
+}
+
+// slab test on the box [-h, h] that also reports the face normal
+fn box_ray_cast(h: Vector3d, ray: &Ray) -> Option<(f64, Vector3d)> {
+    let (mut t0, mut t1) = (f64::NEG_INFINITY, f64::INFINITY);
+    let (mut n0, mut n1) = (Vector3d::new(), Vector3d::new());
+    for i in 0..3 {
+        let (o, d, h) = (axis(ray.origin, i), axis(ray.dir, i), axis(h, i));
+        if d == 0.0 {
+            if o < -h || o > h {
+                return None;
+            }
+            continue;
+        }
+        let (mut a, mut b) = ((-h - o) / d, (h - o) / d);
+        // entering through the face facing the ray
+        let na = unit(i) * -d.signum();
+        if a > b {
+            core::mem::swap(&mut a, &mut b);
+        }
+        if a > t0 {
+            t0 = a;
+            n0 = na;
+        }
+        if b < t1 {
+            t1 = b;
+            n1 = na * -1.0;
+        }
+    }
+    if t0 > t1 || t1 < 0.0 {
+        return None;
+    }
+    if t0 >= 0.0 { Some((t0, n0)) } else { Some((t1, n1)) }
+}
+
+impl RayCast for Aabb {
+    fn ray_cast(&self, ray: &Ray) -> Option<RayHit> {
+        let c = self.center();
+        let local = Ray::new(ray.origin - c, ray.dir);
+        let (t, normal) = box_ray_cast(self.half_extents(), &local)?;
+        Some(RayHit { t, point: ray.at(t), normal, barycentric: None })
+    }
+}
+
+// Oriented box: the axes are orthonormal, half holds the half extents along them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: Vector3d,
+    pub axes: [Vector3d; 3],
+    pub half: Vector3d,
+}
+
+impl Obb {
+    pub fn new(center: Vector3d, axes: [Vector3d; 3], half: Vector3d) -> Self {
+        Self { center, axes, half }
+    }
+
+    // the box after an affine transform made of rotation, translation and
+    // scaling (no shear, or the axes wouldn't stay orthogonal)
+    pub fn from_aabb(b: &Aabb, m: &Matrix4d) -> Self {
+        let h = b.half_extents();
+        let cols: [Vector3d; 3] = core::array::from_fn(|j| transform_vector(m, unit(j)));
+        Self {
+            center: transform_point(m, b.center()),
+            axes: cols.map(|c| c.normalize()),
+            half: Vector3d::new_from(core::array::from_fn(|j| axis(h, j) * cols[j].length())),
+        }
+    }
+
+    // p in the box frame
+    pub fn local_point(&self, p: Vector3d) -> Vector3d {
+        let d = p - self.center;
+        v3(d * self.axes[0], d * self.axes[1], d * self.axes[2])
+    }
+
+    fn world_point(&self, p: Vector3d) -> Vector3d {
+        self.center + self.axes[0] * p.x + self.axes[1] * p.y + self.axes[2] * p.z
+    }
+
+    pub fn contains(&self, p: Vector3d) -> bool {
+        let l = abs_v(self.local_point(p));
+        l.x <= self.half.x && l.y <= self.half.y && l.z <= self.half.z
+    }
+
+    pub fn closest_point(&self, p: Vector3d) -> Vector3d {
+        let l = self.local_point(p);
+        self.world_point(min_v(max_v(l, self.half * -1.0), self.half))
+    }
+
+    pub fn corners(&self) -> [Vector3d; 8] {
+        core::array::from_fn(|i| {
+            let s = |k: usize| if i >> k & 1 == 0 { -1.0 } else { 1.0 };
+            self.world_point(v3(s(0) * self.half.x, s(1) * self.half.y, s(2) * self.half.z))
+        })
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let e = Vector3d::new_from(core::array::from_fn(|i| {
+            (0..3).map(|j| axis(self.axes[j], i).abs() * axis(self.half, j)).sum::<f64>()
+        }));
+        Aabb { min: self.center - e, max: self.center + e }
+    }
+
+    // projection radius on the direction l
+    fn radius_on(&self, l: Vector3d) -> f64 {
+        (0..3).map(|i| axis(self.half, i) * (self.axes[i] * l).abs()).sum()
+    }
+
+    // separating axis test on the 6 face normals and the 9 edge cross products
+    pub fn overlaps_obb(&self, o: &Obb) -> bool {
+        let t = o.center - self.center;
+        let mut axes = [Vector3d::new(); 15];
+        axes[..3].copy_from_slice(&self.axes);
+        axes[3..6].copy_from_slice(&o.axes);
+        for i in 0..3 {
+            for j in 0..3 {
+                axes[6 + i * 3 + j] = self.axes[i].cross(o.axes[j]);
+            }
+        }
+        axes.iter().all(|l| *l * *l < 1e-24 || (t * *l).abs() <= self.radius_on(*l) + o.radius_on(*l))
+    }
+
+    pub fn overlaps_aabb(&self, b: &Aabb) -> bool {
+        self.overlaps_obb(&Obb::new(b.center(), [unit(0), unit(1), unit(2)], b.half_extents()))
+    }
+}
+
+impl RayCast for Obb {
+    fn ray_cast(&self, ray: &Ray) -> Option<RayHit> {
+        let dir = v3(ray.dir * self.axes[0], ray.dir * self.axes[1], ray.dir * self.axes[2]);
+        let local = Ray::new(self.local_point(ray.origin), dir);
+        let (t, n) = box_ray_cast(self.half, &local)?;
+        let normal = self.axes[0] * n.x + self.axes[1] * n.y + self.axes[2] * n.z;
+        Some(RayHit { t, point: ray.at(t), normal, barycentric: None })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangle {
+    pub a: Vector3d,
+    pub b: Vector3d,
+    pub c: Vector3d,
+}
+
+impl Triangle {
+    pub fn new(a: Vector3d, b: Vector3d, c: Vector3d) -> Self {
+        Self { a, b, c }
+    }
+
+    // unit normal along (b - a) x (c - a)
+    pub fn normal(&self) -> Vector3d {
+        (self.b - self.a).cross(self.c - self.a).normalize()
+    }
+
+    pub fn area(&self) -> f64 {
+        (self.b - self.a).cross(self.c - self.a).length() * 0.5
+    }
+
+    pub fn centroid(&self) -> Vector3d {
+        (self.a + self.b + self.c) / 3.0
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        Aabb::from_points(&[self.a, self.b, self.c])
+    }
+
+    pub fn point_from_barycentric(&self, w: Vector3d) -> Vector3d {
+        self.a * w.x + self.b * w.y + self.c * w.z
+    }
+
+    // weights of a, b and c for the projection of p on the triangle's plane
+    pub fn barycentric(&self, p: Vector3d) -> Vector3d {
+        let (e0, e1, e2) = (self.b - self.a, self.c - self.a, p - self.a);
+        let (d00, d01, d11) = (e0 * e0, e0 * e1, e1 * e1);
+        let (d20, d21) = (e2 * e0, e2 * e1);
+        let denom = d00 * d11 - d01 * d01;
+        let v = (d11 * d20 - d01 * d21) / denom;
+        let w = (d00 * d21 - d01 * d20) / denom;
+        v3(1.0 - v - w, v, w)
+    }
+
+    // closest point of the triangle to p and its barycentric coordinates, by
+    // the Voronoi regions of the vertices and edges (Ericson 5.1.5)
+    pub fn closest_point(&self, p: Vector3d) -> (Vector3d, Vector3d) {
+        let (a, b, c) = (self.a, self.b, self.c);
+        let (ab, ac, ap) = (b - a, c - a, p - a);
+        let (d1, d2) = (ab * ap, ac * ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return (a, v3(1.0, 0.0, 0.0));
+        }
+        let bp = p - b;
+        let (d3, d4) = (ab * bp, ac * bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return (b, v3(0.0, 1.0, 0.0));
+        }
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            let v = d1 / (d1 - d3);
+            return (a + ab * v, v3(1.0 - v, v, 0.0));
+        }
+        let cp = p - c;
+        let (d5, d6) = (ab * cp, ac * cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return (c, v3(0.0, 0.0, 1.0));
+        }
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            let w = d2 / (d2 - d6);
+            return (a + ac * w, v3(1.0 - w, 0.0, w));
+        }
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+            let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+            return (b + (c - b) * w, v3(0.0, 1.0 - w, w));
+        }
+        let denom = 1.0 / (va + vb + vc);
+        let (v, w) = (vb * denom, vc * denom);
+        (a + ab * v + ac * w, v3(1.0 - v - w, v, w))
+    }
+
+    pub fn distance(&self, p: Vector3d) -> f64 {
+        (self.closest_point(p).0 - p).length()
+    }
+
+    // Möller–Trumbore, both faces, hits with t in [0, t_max]
+    fn intersect(&self, ray: &Ray, t_max: f64) -> Option<RayHit> {
+        let (e1, e2) = (self.b - self.a, self.c - self.a);
+        let p = ray.dir.cross(e2);
+        let det = e1 * p;
+        // parallel to the plane, or a degenerate triangle
+        if det.abs() < 1e-300 {
+            return None;
+        }
+        let inv = 1.0 / det;
+        let s = ray.origin - self.a;
+        let u = (s * p) * inv;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = (ray.dir * q) * inv;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = (e2 * q) * inv;
+        if !(0.0..=t_max).contains(&t) {
+            return None;
+        }
+        Some(RayHit { t, point: ray.at(t), normal: self.normal(), barycentric: Some(v3(1.0 - u - v, u, v)) })
+    }
+}
+
+impl RayCast for Triangle {
+    fn ray_cast(&self, ray: &Ray) -> Option<RayHit> {
+        self.intersect(ray, f64::INFINITY)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub a: Vector3d,
+    pub b: Vector3d,
+}
+
+impl Segment {
+    pub fn new(a: Vector3d, b: Vector3d) -> Self {
+        Self { a, b }
+    }
+
+    pub fn at(&self, t: f64) -> Vector3d {
+        self.a + (self.b - self.a) * t
+    }
+
+    // closest point and its parameter t in [0, 1]
+    pub fn closest_point(&self, p: Vector3d) -> (Vector3d, f64) {
+        let d = self.b - self.a;
+        let dd = d * d;
+        let t = if dd == 0.0 { 0.0 } else { ((p - self.a) * d / dd).clamp(0.0, 1.0) };
+        (self.at(t), t)
+    }
+
+    pub fn distance(&self, p: Vector3d) -> f64 {
+        (self.closest_point(p).0 - p).length()
+    }
+
+    // closest points between the two segments (Ericson 5.1.9)
+    pub fn closest_points(&self, o: &Segment) -> (Vector3d, Vector3d) {
+        let (d1, d2, r) = (self.b - self.a, o.b - o.a, self.a - o.a);
+        let (a, e, f) = (d1 * d1, d2 * d2, d2 * r);
+        let eps = 1e-300;
+        let (s, t) = if a <= eps && e <= eps {
+            (0.0, 0.0)
+        } else if a <= eps {
+            (0.0, (f / e).clamp(0.0, 1.0))
+        } else {
+            let c = d1 * r;
+            if e <= eps {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else {
+                let b = d1 * d2;
+                let denom = a * e - b * b;
+                let s = if denom > 0.0 { ((b * f - c * e) / denom).clamp(0.0, 1.0) } else { 0.0 };
+                let t = (b * s + f) / e;
+                if t < 0.0 {
+                    ((-c / a).clamp(0.0, 1.0), 0.0)
+                } else if t > 1.0 {
+                    (((b - c) / a).clamp(0.0, 1.0), 1.0)
+                } else {
+                    (s, t)
+                }
+            }
+        };
+        (self.at(s), o.at(t))
+    }
+
+    pub fn distance_to_segment(&self, o: &Segment) -> f64 {
+        let (p, q) = self.closest_points(o);
+        (p - q).length()
+    }
+
+    // crossing point with the triangle, t is the segment parameter in [0, 1]
+    pub fn intersect_triangle(&self, tri: &Triangle) -> Option<RayHit> {
+        tri.intersect(&Ray::new(self.a, self.b - self.a), 1.0)
+    }
+}
+
+// Points within radius of the segment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub segment: Segment,
+    pub radius: f64,
+}
+
+impl Capsule {
+    pub fn new(a: Vector3d, b: Vector3d, radius: f64) -> Self {
+        Self { segment: Segment::new(a, b), radius }
+    }
+
+    pub fn contains(&self, p: Vector3d) -> bool {
+        self.segment.distance(p) <= self.radius
+    }
+
+    // distance from p to the surface, zero inside
+    pub fn distance(&self, p: Vector3d) -> f64 {
+        (self.segment.distance(p) - self.radius).max(0.0)
+    }
+
+    pub fn overlaps_sphere(&self, s: &Sphere) -> bool {
+        self.segment.distance(s.center) <= self.radius + s.radius
+    }
+
+    pub fn overlaps_capsule(&self, o: &Capsule) -> bool {
+        self.segment.distance_to_segment(&o.segment) <= self.radius + o.radius
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let ends = [self.segment.a, self.segment.b].map(|c| Sphere::new(c, self.radius).aabb());
+        ends[0].union(&ends[1])
+    }
+
+    // first entry from outside: the earliest entry into the side of the
+    // cylinder or one of the end spheres
+    fn entry(&self, ray: &Ray) -> Option<f64> {
+        let (a, b) = (self.segment.a, self.segment.b);
+        let (ba, oa) = (b - a, ray.origin - a);
+        let (baba, bard, baoa) = (ba * ba, ba * ray.dir, ba * oa);
+        let qa = baba * (ray.dir * ray.dir) - bard * bard;
+        let qb = baba * (oa * ray.dir) - baoa * bard;
+        let qc = baba * (oa * oa) - baoa * baoa - self.radius * self.radius * baba;
+        let h = qb * qb - qa * qc;
+        let mut best = f64::INFINITY;
+        if qa > 0.0 && h >= 0.0 {
+            let t = (-qb - math::sqrt(h)) / qa;
+            let y = baoa + t * bard;
+            if t >= 0.0 && y > 0.0 && y < baba {
+                best = t;
+            }
+        }
+        for c in [a, b] {
+            if let Some(hit) = Sphere::new(c, self.radius).ray_cast(ray) {
+                best = best.min(hit.t);
+            }
+        }
+        best.is_finite().then_some(best)
+    }
+}
+
+impl RayCast for Capsule {
+    fn ray_cast(&self, ray: &Ray) -> Option<RayHit> {
+        let t = if self.contains(ray.origin) {
+            // convex, so the exit is the entry of the reversed ray from far away
+            let reach = (ray.origin - self.segment.a).length() + (self.segment.b - self.segment.a).length() + 2.0 * self.radius;
+            let far = reach / ray.dir.length();
+            far - self.entry(&Ray::new(ray.at(far), ray.dir * -1.0))?
+        } else {
+            self.entry(ray)?
+        };
+        let point = ray.at(t);
+        let normal = (point - self.segment.closest_point(point).0).normalize();
+        Some(RayHit { t, point, normal, barycentric: None })
+    }
+}
+
+// smallest singular value of the upper-left 3x3 block, from the eigenvalues
+// of L^T L in closed form
+fn min_singular_value(m: &Matrix4d) -> f64 {
+    let col = |j: usize| Vector3d::new_from([m.get(0, j), m.get(1, j), m.get(2, j)]);
+    let c = [col(0), col(1), col(2)];
+    let a = |i: usize, j: usize| c[i] * c[j];
+    let off = a(0, 1) * a(0, 1) + a(0, 2) * a(0, 2) + a(1, 2) * a(1, 2);
+    let q = (a(0, 0) + a(1, 1) + a(2, 2)) / 3.0;
+    let p2 = (0..3).map(|i| (a(i, i) - q) * (a(i, i) - q)).sum::<f64>() + 2.0 * off;
+    if p2 == 0.0 {
+        return math::sqrt(q);
+    }
+    let p = math::sqrt(p2 / 6.0);
+    let b = |i: usize, j: usize| (a(i, j) - if i == j { q } else { 0.0 }) / p;
+    let det = b(0, 0) * (b(1, 1) * b(2, 2) - b(1, 2) * b(1, 2)) - b(0, 1) * (b(0, 1) * b(2, 2) - b(1, 2) * b(0, 2))
+        + b(0, 2) * (b(0, 1) * b(1, 2) - b(1, 1) * b(0, 2));
+    let phi = math::acos((det / 2.0).clamp(-1.0, 1.0)) / 3.0;
+    let smallest = q + 2.0 * p * math::cos(phi + 2.0 * core::f64::consts::PI / 3.0);
+    math::sqrt(smallest.max(0.0))
+}
+
+// A shape moved by an affine transform, with the inverse kept for the
+// queries that map points back into the shape's frame. Singular transforms
+// are fine for support mappings, e.g. flattening a sphere into a disc.
+#[derive(Debug, Clone, Copy)]
+pub struct Transformed<S> {
+    pub shape: S,
+    transform: Matrix4d,
+    inverse: Option<Matrix4d>,
+    // how much the transform shrinks distances at most
+    min_scale: f64,
+}
+
+impl<S> Transformed<S> {
+    pub fn new(shape: S, transform: Matrix4d) -> Self {
+        assert!(transform.is_affine(), "shapes can only be moved by affine transforms");
+        Self { shape, transform, inverse: transform.inverse(), min_scale: min_singular_value(&transform) }
+    }
+
+    pub fn transform(&self) -> &Matrix4d {
+        &self.transform
+    }
+
+    // None when the transform is singular
+    pub fn inverse(&self) -> Option<&Matrix4d> {
+        self.inverse.as_ref()
+    }
+
+    // smallest singular value of the linear part
+    pub fn min_scale(&self) -> f64 {
+        self.min_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3d, b: Vector3d) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn ray_triangle() {
+        let tri = Triangle::new(v3(0.0, 0.0, 0.0), v3(1.0, 0.0, 0.0), v3(0.0, 1.0, 0.0));
+        let hit = tri.ray_cast(&Ray::new(v3(0.25, 0.5, 2.0), v3(0.0, 0.0, -2.0))).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert!(close(hit.point, v3(0.25, 0.5, 0.0)));
+        assert!(close(hit.normal, v3(0.0, 0.0, 1.0)));
+        let w = hit.barycentric.unwrap();
+        assert!(close(w, v3(0.25, 0.25, 0.5)));
+        assert!(close(tri.point_from_barycentric(w), hit.point));
+        assert!(close(tri.barycentric(v3(0.25, 0.5, 7.0)), w));
+
+        // outside, behind, parallel and from below (both faces)
+        assert!(tri.ray_cast(&Ray::new(v3(0.8, 0.8, 1.0), v3(0.0, 0.0, -1.0))).is_none());
+        assert!(tri.ray_cast(&Ray::new(v3(0.2, 0.2, 1.0), v3(0.0, 0.0, 1.0))).is_none());
+        assert!(tri.ray_cast(&Ray::new(v3(0.2, 0.2, 1.0), v3(1.0, 0.0, 0.0))).is_none());
+        assert!(tri.ray_cast(&Ray::new(v3(0.2, 0.2, -1.0), v3(0.0, 0.0, 1.0))).is_some());
+
+        let seg = Segment::new(v3(0.1, 0.1, -1.0), v3(0.1, 0.1, 3.0));
+        assert_eq!(seg.intersect_triangle(&tri).unwrap().t, 0.25);
+        assert!(Segment::new(v3(0.1, 0.1, -1.0), v3(0.1, 0.1, -0.5)).intersect_triangle(&tri).is_none());
+    }
+
+    #[test]
+    fn ray_boxes() {
+        let b = Aabb::new(v3(1.0, -1.0, -1.0), v3(-1.0, 1.0, 1.0));
+        let hit = b.ray_cast(&Ray::new(v3(-5.0, 0.5, 0.0), v3(2.0, 0.0, 0.0))).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert!(close(hit.normal, v3(-1.0, 0.0, 0.0)));
+        // from inside, the exit
+        let hit = b.ray_cast(&Ray::new(v3(0.0, 0.0, 0.0), v3(0.0, 0.0, -1.0))).unwrap();
+        assert_eq!((hit.t, hit.normal), (1.0, v3(0.0, 0.0, -1.0)));
+        // axis aligned ray in a face plane of the slabs and one beside it
+        assert!(b.ray_cast(&Ray::new(v3(-5.0, 1.0, 0.0), v3(1.0, 0.0, 0.0))).is_some());
+        assert!(b.ray_cast(&Ray::new(v3(-5.0, 1.5, 0.0), v3(1.0, 0.0, 0.0))).is_none());
+        let ray = Ray::new(v3(-5.0, 0.2, 0.3), v3(1.0, 0.1, 0.0));
+        assert_eq!(b.slab(&ray, ray.inv_dir(), 3.0), None);
+        let (t0, t1) = b.slab(&ray, ray.inv_dir(), 10.0).unwrap();
+        assert!((t0 - 4.0).abs() < 1e-12 && (t1 - 6.0).abs() < 1e-12);
+        // the slab test agrees with ray_cast on the face planes
+        let ray = Ray::new(v3(-5.0, 1.0, 0.0), v3(1.0, 0.0, 0.0));
+        assert_eq!(b.slab(&ray, ray.inv_dir(), 10.0), Some((4.0, 6.0)));
+        let ray = Ray::new(v3(-5.0, -1.0, 1.0), v3(1.0, 0.0, 0.0));
+        assert_eq!(b.slab(&ray, ray.inv_dir(), 10.0), Some((4.0, 6.0)));
+        let ray = Ray::new(v3(-5.0, 1.5, 0.0), v3(1.0, 0.0, 0.0));
+        assert_eq!(b.slab(&ray, ray.inv_dir(), 10.0), None);
+
+        // a rotated box, cast in its own frame and in the world
+        let (s, c) = (math::sin(0.7), math::cos(0.7));
+        let m = Matrix4d::new([c, -s, 0.0, 3.0, s, c, 0.0, 1.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let obb = Obb::from_aabb(&b, &m);
+        assert!(close(obb.half, v3(1.0, 1.0, 2.0)));
+        let ray = Ray::new(v3(3.0, 1.0, 10.0), v3(0.0, 0.0, -1.0));
+        let hit = obb.ray_cast(&ray).unwrap();
+        assert!((hit.t - 8.0).abs() < 1e-12 && close(hit.normal, v3(0.0, 0.0, 1.0)));
+        let side = obb.ray_cast(&Ray::new(v3(3.0, 1.0, 0.0) + obb.axes[0] * 5.0, obb.axes[0] * -1.0)).unwrap();
+        assert!((side.t - 4.0).abs() < 1e-12 && close(side.normal, obb.axes[0]));
+        for p in obb.corners() {
+            assert!(obb.aabb().contains(p) && b.transform(&m).contains(p));
+            assert!(close(obb.closest_point(p), p));
+        }
+    }
+
+    #[test]
+    fn ray_sphere_plane_capsule() {
+        let s = Sphere::new(v3(0.0, 0.0, 5.0), 1.0);
+        let hit = s.ray_cast(&Ray::new(v3(0.0, 0.0, 0.0), v3(0.0, 0.0, 1.0))).unwrap();
+        assert_eq!((hit.t, hit.normal), (4.0, v3(0.0, 0.0, -1.0)));
+        let hit = s.ray_cast(&Ray::new(v3(0.0, 0.0, 5.0), v3(0.0, 2.0, 0.0))).unwrap();
+        assert_eq!((hit.t, hit.normal), (0.5, v3(0.0, 1.0, 0.0)));
+        assert!(s.ray_cast(&Ray::new(v3(0.0, 0.0, 0.0), v3(0.0, 1.0, 0.0))).is_none());
+
+        let plane = Plane::from_points(v3(0.0, 0.0, 1.0), v3(1.0, 0.0, 1.0), v3(0.0, 1.0, 1.0)).unwrap();
+        assert_eq!(plane.signed_distance(v3(3.0, 4.0, 3.0)), 2.0);
+        assert_eq!(plane.project(v3(3.0, 4.0, 3.0)), v3(3.0, 4.0, 1.0));
+        assert_eq!(plane.ray_cast(&Ray::new(v3(0.0, 0.0, 3.0), v3(0.0, 0.5, -1.0))).unwrap().t, 2.0);
+        assert!(Plane::from_points(v3(0.0, 0.0, 0.0), v3(1.0, 1.0, 1.0), v3(2.0, 2.0, 2.0)).is_none());
+
+        let cap = Capsule::new(v3(0.0, 0.0, 0.0), v3(0.0, 0.0, 4.0), 1.0);
+        // side, end cap, from inside and a miss
+        let hit = cap.ray_cast(&Ray::new(v3(5.0, 0.0, 2.0), v3(-1.0, 0.0, 0.0))).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-12 && close(hit.normal, v3(1.0, 0.0, 0.0)));
+        let hit = cap.ray_cast(&Ray::new(v3(0.0, 0.0, 10.0), v3(0.0, 0.0, -1.0))).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-12 && close(hit.normal, v3(0.0, 0.0, 1.0)));
+        let hit = cap.ray_cast(&Ray::new(v3(0.0, 0.0, 2.0), v3(0.0, 0.0, -2.0))).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-12 && close(hit.point, v3(0.0, 0.0, -1.0)));
+        assert!(cap.ray_cast(&Ray::new(v3(5.0, 1.5, 2.0), v3(-1.0, 0.0, 0.0))).is_none());
+        // diagonal hits land on the surface
+        for k in 0..20 {
+            let ray = Ray::new(v3(4.0, -3.0, k as f64 * 0.4 - 2.0), v3(-1.0, 0.8, 0.3));
+            if let Some(hit) = cap.ray_cast(&ray) {
+                assert!((cap.segment.distance(hit.point) - 1.0).abs() < 1e-9);
+                assert!(!cap.contains(ray.at(hit.t * 0.999)));
+            }
+        }
+    }
+
+    #[test]
+    fn closest_points() {
+        let tri = Triangle::new(v3(0.0, 0.0, 0.0), v3(2.0, 0.0, 0.0), v3(0.0, 2.0, 0.0));
+        // face, edge and vertex regions
+        assert_eq!(tri.closest_point(v3(0.5, 0.5, 3.0)).0, v3(0.5, 0.5, 0.0));
+        assert_eq!(tri.closest_point(v3(1.0, -1.0, 0.0)), (v3(1.0, 0.0, 0.0), v3(0.5, 0.5, 0.0)));
+        assert_eq!(tri.closest_point(v3(2.0, 2.0, 0.0)).0, v3(1.0, 1.0, 0.0));
+        assert_eq!(tri.closest_point(v3(-1.0, -1.0, 1.0)), (v3(0.0, 0.0, 0.0), v3(1.0, 0.0, 0.0)));
+        // against a sampled brute force
+        for k in 0..50 {
+            let p = v3(math::sin(k as f64) * 3.0, math::cos(k as f64 * 1.3) * 3.0, (k % 7) as f64 - 3.0);
+            let best = (0..=40)
+                .flat_map(|i| (0..=40 - i).map(move |j| (i, j)))
+                .map(|(i, j)| (tri.point_from_barycentric(v3(1.0 - (i + j) as f64 / 40.0, i as f64 / 40.0, j as f64 / 40.0)) - p).length())
+                .fold(f64::INFINITY, f64::min);
+            let d = tri.distance(p);
+            assert!(d <= best + 1e-12 && d > best - 0.05);
+        }
+
+        let s1 = Segment::new(v3(0.0, 0.0, 0.0), v3(4.0, 0.0, 0.0));
+        let s2 = Segment::new(v3(1.0, 1.0, -1.0), v3(1.0, 1.0, 1.0));
+        assert_eq!(s1.closest_points(&s2), (v3(1.0, 0.0, 0.0), v3(1.0, 1.0, 0.0)));
+        let s3 = Segment::new(v3(6.0, 0.0, 2.0), v3(9.0, 0.0, 2.0));
+        assert_eq!(s1.distance_to_segment(&s3), math::sqrt(8.0));
+        assert_eq!(s1.closest_point(v3(-3.0, 2.0, 0.0)), (v3(0.0, 0.0, 0.0), 0.0));
+
+        let b = Aabb::new(v3(0.0, 0.0, 0.0), v3(1.0, 2.0, 3.0));
+        assert_eq!(b.closest_point(v3(2.0, 1.0, -1.0)), v3(1.0, 1.0, 0.0));
+        assert_eq!(b.distance_squared(v3(2.0, 1.0, -1.0)), 2.0);
+        assert_eq!(b.surface_area(), 22.0);
+        assert_eq!(b.longest_axis(), 2);
+        assert!(Aabb::empty().is_empty() && Aabb::empty().union(&b) == b);
+    }
+
+    #[test]
+    fn overlaps() {
+        let b = Aabb::new(v3(0.0, 0.0, 0.0), v3(1.0, 1.0, 1.0));
+        assert!(Sphere::new(v3(1.5, 0.5, 0.5), 0.6).overlaps_aabb(&b));
+        assert!(!Sphere::new(v3(1.5, 1.5, 1.5), 0.8).overlaps_aabb(&b));
+        assert!(Sphere::new(v3(1.5, 1.5, 1.5), 0.9).overlaps_aabb(&b));
+        assert!(b.overlaps(&Aabb::new(v3(1.0, 1.0, 1.0), v3(2.0, 2.0, 2.0))));
+        assert!(!b.overlaps(&Aabb::new(v3(1.1, 0.0, 0.0), v3(2.0, 2.0, 2.0))));
+
+        // a triangle cutting off a corner, and one whose box overlaps but which misses
+        assert!(b.overlaps_triangle(&Triangle::new(v3(0.4, 1.5, 0.5), v3(1.5, 0.4, 0.5), v3(1.5, 1.5, 0.5))));
+        assert!(!b.overlaps_triangle(&Triangle::new(v3(1.2, 1.9, 0.5), v3(1.9, 1.2, 0.5), v3(1.9, 1.9, 0.5))));
+        assert!(!b.overlaps_triangle(&Triangle::new(v3(2.0, -1.0, 0.0), v3(2.0, 2.0, 0.0), v3(-1.0, 2.0, 3.5))));
+        let tri = Triangle::new(v3(-1.0, -1.0, 0.5), v3(3.0, -1.0, 0.5), v3(-1.0, 3.0, 0.5));
+        assert!(b.overlaps_triangle(&tri) && Sphere::new(v3(0.5, 0.5, 1.4), 1.0).overlaps_triangle(&tri));
+
+        let r = |a: f64| {
+            let (s, c) = (math::sin(a), math::cos(a));
+            [v3(c, s, 0.0), v3(-s, c, 0.0), v3(0.0, 0.0, 1.0)]
+        };
+        let o1 = Obb::new(v3(0.0, 0.0, 0.0), r(0.0), v3(1.0, 1.0, 1.0));
+        // a box turned 45 degrees reaches sqrt(2) along x
+        assert!(o1.overlaps_obb(&Obb::new(v3(2.3, 0.0, 0.0), r(core::f64::consts::FRAC_PI_4), v3(1.0, 1.0, 1.0))));
+        assert!(!o1.overlaps_obb(&Obb::new(v3(2.5, 0.0, 0.0), r(core::f64::consts::FRAC_PI_4), v3(1.0, 1.0, 1.0))));
+        assert!(o1.overlaps_aabb(&Aabb::new(v3(1.0, 1.0, 1.0), v3(2.0, 2.0, 2.0))));
+
+        let c1 = Capsule::new(v3(0.0, 0.0, 0.0), v3(0.0, 0.0, 4.0), 0.5);
+        let c2 = Capsule::new(v3(1.2, -3.0, 2.0), v3(1.2, 3.0, 2.0), 0.5);
+        assert!(!c1.overlaps_capsule(&c2));
+        assert!(c1.overlaps_capsule(&Capsule { radius: 0.8, ..c2 }));
+        assert!(c1.overlaps_sphere(&Sphere::new(v3(0.0, 0.0, 5.0), 0.6)));
+        assert_eq!(c1.distance(v3(3.0, 0.0, 2.0)), 2.5);
+        assert!(c1.aabb().contains(v3(0.5, 0.5, -0.5)));
+    }
+}
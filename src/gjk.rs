@@ -0,0 +1,661 @@
+// Convex collision detection from support functions.
+//
+// A convex shape is described by its support mapping: the point of the shape
+// furthest along a direction. GJK walks the Minkowski difference A - B towards
+// the origin, which gives the distance and closest points of separated
+// shapes. When the shapes overlap the origin is inside A - B, and EPA grows a
+// polytope from GJK's final simplex out to the boundary of A - B to find the
+// penetration depth and contact normal.
+//
+// The 2d queries lift the shapes into the z = 0 plane and reuse the 3d GJK;
+// EPA has a separate polygon version since a flat polytope has no volume.
+
+use super::geometry::*;
+use super::vector2d::*;
+use super::vector3d::*;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+// Convex shapes usable with GJK and EPA; dir doesn't need to be unit length
+pub trait Support<V> {
+    fn support(&self, dir: V) -> V;
+}
+
+impl Support<Vector3d> for Sphere {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        let l = dir.length();
+        if l > 0.0 { self.center + dir * (self.radius / l) } else { self.center }
+    }
+}
+
+impl Support<Vector3d> for Aabb {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        let pick = |d: f64, lo: f64, hi: f64| if d >= 0.0 { hi } else { lo };
+        Vector3d::new_from([
+            pick(dir.x, self.min.x, self.max.x),
+            pick(dir.y, self.min.y, self.max.y),
+            pick(dir.z, self.min.z, self.max.z),
+        ])
+    }
+}
+
+impl Support<Vector3d> for Obb {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        let h = self.half.to_list();
+        (0..3).fold(self.center, |p, i| {
+            let s = if self.axes[i] * dir >= 0.0 { h[i] } else { -h[i] };
+            p + self.axes[i] * s
+        })
+    }
+}
+
+impl Support<Vector3d> for Capsule {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        let (a, b) = (self.segment.a, self.segment.b);
+        let end = if a * dir >= b * dir { a } else { b };
+        Sphere::new(end, self.radius).support(dir)
+    }
+}
+
+impl Support<Vector3d> for Triangle {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        ConvexHull::new(&[self.a, self.b, self.c]).support(dir)
+    }
+}
+
+// Convex hull of a point set, the points don't need to be on the hull
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvexHull<'a, V> {
+    pub points: &'a [V],
+}
+
+impl<'a, V> ConvexHull<'a, V> {
+    pub fn new(points: &'a [V]) -> Self {
+        assert!(!points.is_empty(), "a hull needs at least one point");
+        Self { points }
+    }
+}
+
+macro_rules! impl_hull_support {
+    ($v:ident) => {
+        impl Support<$v> for ConvexHull<'_, $v> {
+            fn support(&self, dir: $v) -> $v {
+                let mut best = self.points[0];
+                for p in &self.points[1..] {
+                    if *p * dir > best * dir {
+                        best = *p;
+                    }
+                }
+                best
+            }
+        }
+    };
+}
+
+impl_hull_support!(Vector2d);
+impl_hull_support!(Vector3d);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub center: Vector2d,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn new(center: Vector2d, radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl Support<Vector2d> for Circle {
+    fn support(&self, dir: Vector2d) -> Vector2d {
+        let l = dir.length();
+        if l > 0.0 { self.center + dir * (self.radius / l) } else { self.center }
+    }
+}
+
+// Every a + b with a in the first shape and b in the second; a sphere added
+// to a box gives a rounded box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinkowskiSum<A, B>(pub A, pub B);
+
+impl<V: core::ops::Add<Output = V> + Copy, A: Support<V>, B: Support<V>> Support<V> for MinkowskiSum<A, B> {
+    fn support(&self, dir: V) -> V {
+        self.0.support(dir) + self.1.support(dir)
+    }
+}
+
+impl<S: Support<Vector3d>> Support<Vector3d> for Transformed<S> {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        // the support of M s is M applied to the support of s along L^T dir,
+        // L the linear part of M
+        let m = self.transform();
+        let local = Vector3d::new_from(core::array::from_fn(|j| m.get(0, j) * dir.x + m.get(1, j) * dir.y + m.get(2, j) * dir.z));
+        transform_point(m, self.shape.support(local))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosestPoints<V = Vector3d> {
+    pub distance: f64,
+    pub point_a: V,
+    pub point_b: V,
+}
+
+// Moving B by depth * normal separates the shapes; point_a is the point of A
+// deepest inside B and point_b = point_a - depth * normal that of B inside A.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Penetration<V = Vector3d> {
+    pub depth: f64,
+    // unit, pointing from A towards B
+    pub normal: V,
+    pub point_a: V,
+    pub point_b: V,
+}
+
+const MAX_ITERATIONS: usize = 128;
+// relative gap between the lower and upper distance bounds at which to stop
+const REL_TOLERANCE: f64 = 1e-12;
+// squared distance, relative to the simplex size, counted as touching
+const TOUCH_TOLERANCE: f64 = 1e-24;
+#[cfg(feature = "alloc")]
+const EPA_TOLERANCE: f64 = 1e-10;
+
+// a point of A - B and the points of A and B it came from
+#[derive(Debug, Clone, Copy)]
+struct Vertex {
+    w: Vector3d,
+    a: Vector3d,
+    b: Vector3d,
+}
+
+fn support_diff<A: Support<Vector3d>, B: Support<Vector3d>>(a: &A, b: &B, dir: Vector3d) -> Vertex {
+    let (pa, pb) = (a.support(dir), b.support(dir * -1.0));
+    Vertex { w: pa - pb, a: pa, b: pb }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Simplex {
+    v: [Vertex; 4],
+    // barycentric weights of the point closest to the origin
+    weights: [f64; 4],
+    n: usize,
+}
+
+impl Simplex {
+    fn point(&self, f: impl Fn(&Vertex) -> Vector3d) -> Vector3d {
+        (0..self.n).fold(Vector3d::new(), |p, i| p + f(&self.v[i]) * self.weights[i])
+    }
+
+    // keeps the vertices with nonzero weight
+    fn compact(&mut self) {
+        let mut k = 0;
+        for i in 0..self.n {
+            if self.weights[i] > 0.0 {
+                self.v[k] = self.v[i];
+                self.weights[k] = self.weights[i];
+                k += 1;
+            }
+        }
+        self.n = k;
+    }
+
+    // moves to the point closest to the origin and the smallest face holding it
+    fn reduce(&mut self) -> Vector3d {
+        let w: [Vector3d; 4] = core::array::from_fn(|i| self.v[i].w);
+        let weights = match self.n {
+            1 => [1.0, 0.0, 0.0, 0.0],
+            2 => segment_weights(w[0], w[1]),
+            3 => triangle_weights(w[0], w[1], w[2]),
+            _ => tetrahedron_weights(w),
+        };
+        self.weights = weights;
+        self.compact();
+        self.point(|v| v.w)
+    }
+}
+
+fn segment_weights(a: Vector3d, b: Vector3d) -> [f64; 4] {
+    let (_, t) = Segment::new(a, b).closest_point(Vector3d::new());
+    [1.0 - t, t, 0.0, 0.0]
+}
+
+fn triangle_weights(a: Vector3d, b: Vector3d, c: Vector3d) -> [f64; 4] {
+    let n = (b - a).cross(c - a);
+    let scale = ((b - a) * (b - a)).max((c - a) * (c - a));
+    if n * n <= 1e-24 * scale * scale {
+        // collinear, the closest point is on the longest edge
+        let p = [a, b, c];
+        let (i, j) = [(0, 1), (1, 2), (0, 2)]
+            .into_iter()
+            .max_by(|&(i, j), &(k, l)| (p[j] - p[i]).length().total_cmp(&(p[l] - p[k]).length()))
+            .unwrap();
+        let s = segment_weights(p[i], p[j]);
+        let mut weights = [0.0; 4];
+        weights[i] = s[0];
+        weights[j] = s[1];
+        return weights;
+    }
+    let (_, l) = Triangle::new(a, b, c).closest_point(Vector3d::new());
+    [l.x, l.y, l.z, 0.0]
+}
+
+fn tetrahedron_weights(w: [Vector3d; 4]) -> [f64; 4] {
+    let faces = [[1, 2, 3], [0, 3, 2], [0, 1, 3], [0, 2, 1]];
+    let volume = (w[1] - w[0]).cross(w[2] - w[0]) * (w[3] - w[0]);
+    let scale = (1..4).map(|i| (w[i] - w[0]).length()).fold(0.0, f64::max);
+    let flat = volume.abs() <= 1e-12 * scale * scale * scale;
+    let mut best = (f64::INFINITY, [0.0; 4]);
+    let mut inside = !flat;
+    for (k, f) in faces.iter().enumerate() {
+        // only the faces with the origin on their far side from the fourth vertex
+        let n = (w[f[1]] - w[f[0]]).cross(w[f[2]] - w[f[0]]);
+        let origin_side = (Vector3d::new() - w[f[0]]) * n;
+        let vertex_side = (w[k] - w[f[0]]) * n;
+        if !flat && origin_side * vertex_side >= 0.0 {
+            continue;
+        }
+        inside = false;
+        let t = triangle_weights(w[f[0]], w[f[1]], w[f[2]]);
+        let p = w[f[0]] * t[0] + w[f[1]] * t[1] + w[f[2]] * t[2];
+        if p * p < best.0 {
+            let mut weights = [0.0; 4];
+            for (j, &i) in f.iter().enumerate() {
+                weights[i] = t[j];
+            }
+            best = (p * p, weights);
+        }
+    }
+    if inside {
+        // barycentric coordinates of the origin
+        let sub = |i: usize| {
+            let mut v = w;
+            v[i] = Vector3d::new();
+            (v[1] - v[0]).cross(v[2] - v[0]) * (v[3] - v[0]) / volume
+        };
+        return core::array::from_fn(sub);
+    }
+    best.1
+}
+
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+enum GjkResult {
+    Separated(Simplex, Vector3d),
+    // the final simplex holds the origin
+    Touching(Simplex),
+}
+
+fn gjk<A: Support<Vector3d>, B: Support<Vector3d>>(a: &A, b: &B, early_out: bool) -> GjkResult {
+    let first = support_diff(a, b, Vector3d::new_from([1.0, 0.0, 0.0]));
+    let mut s = Simplex { v: [first; 4], weights: [1.0, 0.0, 0.0, 0.0], n: 1 };
+    let mut v = first.w;
+    for _ in 0..MAX_ITERATIONS {
+        let vv = v * v;
+        let size = (0..s.n).map(|i| s.v[i].w * s.v[i].w).fold(0.0, f64::max);
+        if s.n == 4 || vv <= TOUCH_TOLERANCE * size {
+            return GjkResult::Touching(s);
+        }
+        let w = support_diff(a, b, v * -1.0);
+        // nothing of A - B lies beyond the plane through w facing the origin
+        if early_out && v * w.w > 0.0 {
+            return GjkResult::Separated(s, v);
+        }
+        if vv - v * w.w <= REL_TOLERANCE * vv || (0..s.n).any(|i| s.v[i].w == w.w) {
+            return GjkResult::Separated(s, v);
+        }
+        s.v[s.n] = w;
+        s.n += 1;
+        v = s.reduce();
+    }
+    GjkResult::Separated(s, v)
+}
+
+pub fn gjk_intersect<A: Support<Vector3d>, B: Support<Vector3d>>(a: &A, b: &B) -> bool {
+    matches!(gjk(a, b, true), GjkResult::Touching(_))
+}
+
+// distance and closest points of separated shapes, None when they touch or overlap
+pub fn gjk_distance<A: Support<Vector3d>, B: Support<Vector3d>>(a: &A, b: &B) -> Option<ClosestPoints> {
+    match gjk(a, b, false) {
+        GjkResult::Touching(_) => None,
+        GjkResult::Separated(s, v) => {
+            Some(ClosestPoints { distance: v.length(), point_a: s.point(|v| v.a), point_b: s.point(|v| v.b) })
+        }
+    }
+}
+
+// shapes in the z = 0 plane
+struct Lift<'a, S>(&'a S);
+
+impl<S: Support<Vector2d>> Support<Vector3d> for Lift<'_, S> {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        let p = self.0.support(Vector2d::new_from([dir.x, dir.y]));
+        Vector3d::new_from([p.x, p.y, 0.0])
+    }
+}
+
+fn flatten(v: Vector3d) -> Vector2d {
+    Vector2d::new_from([v.x, v.y])
+}
+
+pub fn gjk_intersect_2d<A: Support<Vector2d>, B: Support<Vector2d>>(a: &A, b: &B) -> bool {
+    gjk_intersect(&Lift(a), &Lift(b))
+}
+
+pub fn gjk_distance_2d<A: Support<Vector2d>, B: Support<Vector2d>>(a: &A, b: &B) -> Option<ClosestPoints<Vector2d>> {
+    gjk_distance(&Lift(a), &Lift(b)).map(|c| ClosestPoints { distance: c.distance, point_a: flatten(c.point_a), point_b: flatten(c.point_b) })
+}
+
+// the first support point along dirs that is off the current simplex, gap
+// measuring how far
+#[cfg(feature = "alloc")]
+fn first_off<A: Support<Vector3d>, B: Support<Vector3d>>(
+    a: &A,
+    b: &B,
+    dirs: &[Vector3d],
+    gap: impl Fn(Vector3d) -> f64,
+) -> Option<Vertex> {
+    dirs.iter().map(|d| support_diff(a, b, *d)).find(|v| gap(v.w) > EPA_TOLERANCE)
+}
+
+// penetration of overlapping shapes, None when they are separated
+#[cfg(feature = "alloc")]
+pub fn epa_penetration<A: Support<Vector3d>, B: Support<Vector3d>>(a: &A, b: &B) -> Option<Penetration> {
+    let GjkResult::Touching(s) = gjk(a, b, true) else { return None };
+    let mut verts: Vec<Vertex> = s.v[..s.n].to_vec();
+    let units: [Vector3d; 6] = core::array::from_fn(|i| {
+        let mut l = [0.0; 3];
+        l[i / 2] = if i % 2 == 0 { 1.0 } else { -1.0 };
+        Vector3d::new_from(l)
+    });
+    let touching = Penetration { depth: 0.0, normal: units[0], point_a: s.point(|v| v.a), point_b: s.point(|v| v.b) };
+    // grow the simplex into a tetrahedron, which still holds the origin
+    if verts.len() == 1 {
+        let w0 = verts[0].w;
+        let Some(v) = first_off(a, b, &units, |w| (w - w0).length()) else { return Some(touching) };
+        verts.push(v);
+    }
+    if verts.len() == 2 {
+        let (w0, e) = (verts[0].w, (verts[1].w - verts[0].w).normalize());
+        let least = (0..3).min_by(|&i, &j| e.to_list()[i].abs().total_cmp(&e.to_list()[j].abs())).unwrap();
+        let d1 = e.cross(units[2 * least]);
+        let d2 = e.cross(d1);
+        let dirs = [d1, d2, d1 * -1.0, d2 * -1.0];
+        let Some(v) = first_off(a, b, &dirs, |w| (w - w0).cross(e).length()) else { return Some(touching) };
+        verts.push(v);
+    }
+    if verts.len() == 3 {
+        let w0 = verts[0].w;
+        let n = (verts[1].w - w0).cross(verts[2].w - w0).normalize();
+        let Some(v) = first_off(a, b, &[n, n * -1.0], |w| ((w - w0) * n).abs()) else {
+            return Some(Penetration { normal: n, ..touching });
+        };
+        verts.push(v);
+    }
+    if (verts[1].w - verts[0].w).cross(verts[2].w - verts[0].w) * (verts[3].w - verts[0].w) > 0.0 {
+        verts.swap(1, 2);
+    }
+
+    #[derive(Clone, Copy)]
+    struct Face {
+        idx: [usize; 3],
+        normal: Vector3d,
+        dist: f64,
+    }
+    let face = |verts: &[Vertex], idx: [usize; 3]| {
+        let (p, q, r) = (verts[idx[0]].w, verts[idx[1]].w, verts[idx[2]].w);
+        let normal = (q - p).cross(r - p).normalize();
+        Face { idx, normal, dist: normal * p }
+    };
+    let mut faces: Vec<Face> = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]].iter().map(|f| face(&verts, *f)).collect();
+    let mut best = faces[0];
+    for _ in 0..MAX_ITERATIONS {
+        best = *faces.iter().filter(|f| f.dist.is_finite()).min_by(|f, g| f.dist.total_cmp(&g.dist))?;
+        let w = support_diff(a, b, best.normal);
+        if w.w * best.normal - best.dist <= EPA_TOLERANCE * best.dist.max(1.0) {
+            break;
+        }
+        let k = verts.len();
+        verts.push(w);
+        // drop the faces w can see and stitch their boundary to w
+        let mut horizon: Vec<(usize, usize)> = Vec::new();
+        faces.retain(|f| {
+            if f.normal * (w.w - verts[f.idx[0]].w) <= 0.0 {
+                return true;
+            }
+            for e in [(f.idx[0], f.idx[1]), (f.idx[1], f.idx[2]), (f.idx[2], f.idx[0])] {
+                // an edge shared by two dropped faces is interior
+                match horizon.iter().position(|h| *h == (e.1, e.0)) {
+                    Some(i) => {
+                        horizon.swap_remove(i);
+                    }
+                    None => horizon.push(e),
+                }
+            }
+            false
+        });
+        if horizon.is_empty() {
+            break;
+        }
+        faces.extend(horizon.iter().map(|&(i, j)| face(&verts, [i, j, k])));
+    }
+    let [i, j, k] = best.idx;
+    let l = Triangle::new(verts[i].w, verts[j].w, verts[k].w).barycentric(best.normal * best.dist);
+    let mix = |f: fn(&Vertex) -> Vector3d| f(&verts[i]) * l.x + f(&verts[j]) * l.y + f(&verts[k]) * l.z;
+    Some(Penetration { depth: best.dist, normal: best.normal, point_a: mix(|v| v.a), point_b: mix(|v| v.b) })
+}
+
+#[cfg(feature = "alloc")]
+pub fn epa_penetration_2d<A: Support<Vector2d>, B: Support<Vector2d>>(a: &A, b: &B) -> Option<Penetration<Vector2d>> {
+    let (la, lb) = (Lift(a), Lift(b));
+    let GjkResult::Touching(s) = gjk(&la, &lb, true) else { return None };
+    let mut verts: Vec<Vertex> = s.v[..s.n].to_vec();
+    let touching = Penetration {
+        depth: 0.0,
+        normal: Vector2d::new_from([1.0, 0.0]),
+        point_a: flatten(s.point(|v| v.a)),
+        point_b: flatten(s.point(|v| v.b)),
+    };
+    let dirs = [[1.0, 0.0], [-1.0, 0.0], [0.0, 1.0], [0.0, -1.0]].map(|d| Vector3d::new_from([d[0], d[1], 0.0]));
+    if verts.len() == 1 {
+        let w0 = verts[0].w;
+        let Some(v) = first_off(&la, &lb, &dirs, |w| (w - w0).length()) else { return Some(touching) };
+        verts.push(v);
+    }
+    if verts.len() == 2 {
+        let (w0, e) = (verts[0].w, (verts[1].w - verts[0].w).normalize());
+        let n = Vector3d::new_from([-e.y, e.x, 0.0]);
+        let Some(v) = first_off(&la, &lb, &[n, n * -1.0], |w| ((w - w0) * n).abs()) else {
+            return Some(Penetration { normal: flatten(n), ..touching });
+        };
+        verts.push(v);
+    }
+    // counter-clockwise, so the outward normal of edge p -> q is (q - p) turned clockwise
+    let (w0, w1, w2) = (verts[0].w, verts[1].w, verts[2].w);
+    if (w1 - w0).cross(w2 - w0).z < 0.0 {
+        verts.swap(1, 2);
+    }
+    let edge = |verts: &[Vertex], i: usize| {
+        let (p, q) = (verts[i].w, verts[(i + 1) % verts.len()].w);
+        let e = q - p;
+        let n = Vector3d::new_from([e.y, -e.x, 0.0]).normalize();
+        (n, n * p)
+    };
+    let mut best = (0, Vector3d::new(), 0.0);
+    for _ in 0..MAX_ITERATIONS {
+        best = (0..verts.len()).map(|i| (i, edge(&verts, i))).map(|(i, (n, d))| (i, n, d)).min_by(|x, y| x.2.total_cmp(&y.2))?;
+        let w = support_diff(&la, &lb, best.1);
+        if w.w * best.1 - best.2 <= EPA_TOLERANCE * best.2.max(1.0) {
+            break;
+        }
+        verts.insert(best.0 + 1, w);
+    }
+    let (i, n, d) = best;
+    let j = (i + 1) % verts.len();
+    let (_, t) = Segment::new(verts[i].w, verts[j].w).closest_point(n * d);
+    let mix = |f: fn(&Vertex) -> Vector3d| flatten(f(&verts[i]) * (1.0 - t) + f(&verts[j]) * t);
+    Some(Penetration { depth: d, normal: flatten(n), point_a: mix(|v| v.a), point_b: mix(|v| v.b) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+    #[cfg(feature = "alloc")]
+    use crate::matrix4d::*;
+
+    fn v3(x: f64, y: f64, z: f64) -> Vector3d {
+        Vector3d::new_from([x, y, z])
+    }
+
+    #[cfg(feature = "alloc")]
+    fn v2(x: f64, y: f64) -> Vector2d {
+        Vector2d::new_from([x, y])
+    }
+
+    #[cfg(feature = "alloc")]
+    fn close(a: Vector3d, b: Vector3d, tol: f64) -> bool {
+        (a - b).length() < tol
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn spheres_and_boxes() {
+        let a = Sphere::new(v3(0.0, 0.0, 0.0), 1.0);
+        let b = Sphere::new(v3(5.0, 0.0, 0.0), 2.0);
+        let c = gjk_distance(&a, &b).unwrap();
+        assert!((c.distance - 2.0).abs() < 1e-9);
+        assert!(close(c.point_a, v3(1.0, 0.0, 0.0), 1e-6) && close(c.point_b, v3(3.0, 0.0, 0.0), 1e-6));
+        assert!(!gjk_intersect(&a, &b));
+
+        let b = Sphere::new(v3(2.5, 0.0, 0.0), 2.0);
+        assert!(gjk_intersect(&a, &b) && gjk_distance(&a, &b).is_none());
+        let p = epa_penetration(&a, &b).unwrap();
+        assert!((p.depth - 0.5).abs() < 1e-4);
+        assert!(close(p.normal, v3(1.0, 0.0, 0.0), 1e-2));
+        assert!(epa_penetration(&Sphere::new(v3(0.0, 0.0, 0.0), 1.0), &Sphere::new(v3(3.5, 0.0, 0.0), 2.0)).is_none());
+
+        // boxes overlapping by 0.25 along y, the shallowest axis
+        let b1 = Aabb::new(v3(0.0, 0.0, 0.0), v3(2.0, 2.0, 2.0));
+        let b2 = Aabb::new(v3(0.5, 1.75, 0.5), v3(1.5, 3.0, 3.5));
+        let p = epa_penetration(&b1, &b2).unwrap();
+        assert!((p.depth - 0.25).abs() < 1e-9);
+        assert!(close(p.normal, v3(0.0, 1.0, 0.0), 1e-9));
+        assert!(close(p.point_a - p.point_b, p.normal * p.depth, 1e-9));
+        let far = Aabb::new(v3(3.0, 4.0, 2.0), v3(4.0, 5.0, 3.0));
+        let c = gjk_distance(&b1, &far).unwrap();
+        assert!((c.distance - math::sqrt(5.0)).abs() < 1e-9);
+        assert!(close(c.point_b - c.point_a, v3(1.0, 2.0, 0.0), 1e-9));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn rotated_box_and_capsule() {
+        // a cube turned 45 degrees about z reaches sqrt(2) along x
+        let (s, c) = (math::sin(core::f64::consts::FRAC_PI_4), math::cos(core::f64::consts::FRAC_PI_4));
+        let m = Matrix4d::new([c, -s, 0.0, 0.0, s, c, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let unit = Aabb::new(v3(-1.0, -1.0, -1.0), v3(1.0, 1.0, 1.0));
+        let obb = Obb::from_aabb(&unit, &m);
+        let transformed = Transformed::new(unit, m);
+        let wall = Aabb::new(v3(1.2, -5.0, -5.0), v3(3.0, 5.0, 5.0));
+        for p in [epa_penetration(&obb, &wall).unwrap(), epa_penetration(&transformed, &wall).unwrap()] {
+            assert!((p.depth - (math::sqrt(2.0) - 1.2)).abs() < 1e-9);
+            assert!(close(p.normal, v3(1.0, 0.0, 0.0), 1e-9));
+        }
+
+        let cap = Capsule::new(v3(0.0, 0.0, 0.0), v3(0.0, 0.0, 4.0), 0.5);
+        let tri = Triangle::new(v3(2.0, -1.0, 2.0), v3(2.0, 1.0, 2.0), v3(3.0, 0.0, 6.0));
+        let c = gjk_distance(&cap, &tri).unwrap();
+        assert!((c.distance - 1.5).abs() < 1e-9);
+        assert!(close(c.point_b, v3(2.0, 0.0, 2.0), 1e-6));
+        let points = [v3(0.2, 0.2, 4.2), v3(2.0, 2.0, 8.0), v3(-1.0, 2.0, 6.0), v3(1.0, -1.0, 7.0)];
+        let hull = ConvexHull::new(&points);
+        let p = epa_penetration(&cap, &hull).unwrap();
+        // the hull's lowest vertex pokes into the round end
+        assert!((p.depth - (0.5 - math::sqrt(0.2 * 0.2 * 3.0))).abs() < 1e-4);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn minkowski_sums_and_transforms() {
+        // a unit box rounded by 0.5 against a point
+        let rounded = MinkowskiSum(Aabb::new(v3(-1.0, -1.0, -1.0), v3(1.0, 1.0, 1.0)), Sphere::new(v3(0.0, 0.0, 0.0), 0.5));
+        let pt = [v3(3.0, 3.0, 0.0)];
+        let c = gjk_distance(&rounded, &ConvexHull::new(&pt)).unwrap();
+        assert!((c.distance - (math::sqrt(8.0) - 0.5)).abs() < 1e-6);
+        let pt = [v3(1.4, 0.0, 0.2)];
+        let p = epa_penetration(&rounded, &ConvexHull::new(&pt)).unwrap();
+        assert!((p.depth - 0.1).abs() < 1e-9 && close(p.normal, v3(1.0, 0.0, 0.0), 1e-9));
+
+        // a stretched and moved sphere is an ellipsoid
+        let m = Matrix4d::new([3.0, 0.0, 0.0, 10.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let ellipsoid = Transformed::new(Sphere::new(v3(0.0, 0.0, 0.0), 1.0), m);
+        let probe = Sphere::new(v3(0.0, 0.0, 0.0), 1.0);
+        assert!((gjk_distance(&probe, &ellipsoid).unwrap().distance - 6.0).abs() < 1e-6);
+        assert!(gjk_intersect(&Sphere::new(v3(13.5, 0.0, 0.0), 1.0), &ellipsoid));
+        assert!(!gjk_intersect(&Sphere::new(v3(10.0, 2.5, 0.0), 1.0), &ellipsoid));
+
+        // a singular transform still gives a support mapping, a flat disc here
+        let flatten = Matrix4d::new([1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let disc = Transformed::new(Sphere::new(v3(0.0, 0.0, 0.0), 1.0), flatten);
+        assert!(disc.inverse().is_none());
+        assert!((gjk_distance(&Sphere::new(v3(0.0, 0.0, 3.0), 1.0), &disc).unwrap().distance - 2.0).abs() < 1e-9);
+        assert!(gjk_intersect(&Sphere::new(v3(1.5, 0.0, 0.0), 0.6), &disc));
+        assert!(!gjk_intersect(&Sphere::new(v3(0.5, 0.0, 0.7), 0.6), &disc));
+
+        // touching faces count as intersecting, with zero depth
+        let b1 = Aabb::new(v3(0.0, 0.0, 0.0), v3(1.0, 1.0, 1.0));
+        let b2 = Aabb::new(v3(1.0, 0.0, 0.0), v3(2.0, 1.0, 1.0));
+        assert!(gjk_intersect(&b1, &b2));
+        assert!(epa_penetration(&b1, &b2).unwrap().depth.abs() < 1e-12);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn planar_shapes() {
+        let square = [v2(0.0, 0.0), v2(2.0, 0.0), v2(2.0, 2.0), v2(0.0, 2.0)];
+        let a = ConvexHull::new(&square);
+        let moved = square.map(|p| p + v2(3.0, 1.0));
+        let c = gjk_distance_2d(&a, &ConvexHull::new(&moved)).unwrap();
+        assert!((c.distance - 1.0).abs() < 1e-12);
+        assert!((c.point_a.x - 2.0).abs() < 1e-12 && (c.point_b.x - 3.0).abs() < 1e-12);
+
+        let moved = square.map(|p| p + v2(1.5, 0.5));
+        assert!(gjk_intersect_2d(&a, &ConvexHull::new(&moved)));
+        let p = epa_penetration_2d(&a, &ConvexHull::new(&moved)).unwrap();
+        assert!((p.depth - 0.5).abs() < 1e-12);
+        assert!((p.normal - v2(1.0, 0.0)).length() < 1e-12);
+
+        let circle = Circle::new(v2(1.0, 2.6), 1.0);
+        let p = epa_penetration_2d(&a, &circle).unwrap();
+        assert!((p.depth - 0.4).abs() < 1e-6);
+        assert!((p.normal - v2(0.0, 1.0)).length() < 1e-3);
+        assert!((gjk_distance_2d(&Circle::new(v2(5.0, 6.0), 2.0), &a).unwrap().distance - 3.0).abs() < 1e-9);
+        let sum = MinkowskiSum(a, Circle::new(v2(0.0, 0.0), 0.5));
+        assert!(gjk_intersect_2d(&sum, &Circle::new(v2(2.5, 2.5), 0.25)));
+        assert!(!gjk_intersect_2d(&sum, &Circle::new(v2(2.5, 2.5), 0.15)));
+    }
+
+    #[test]
+    fn agrees_with_sphere_overlap() {
+        // random-ish box and sphere pairs against the exact test
+        for k in 0..200 {
+            let t = k as f64;
+            let b = Aabb::new(v3(math::sin(t) * 2.0, 0.0, 0.0), v3(2.0, 1.0 + math::cos(t * 0.3), 1.5));
+            let s = Sphere::new(v3(math::sin(t * 1.7) * 4.0, math::cos(t * 2.3) * 3.0, math::sin(t * 0.7) * 3.0), 0.3 + (k % 4) as f64 * 0.4);
+            let exact = s.overlaps_aabb(&b);
+            let d = math::sqrt(b.distance_squared(s.center)) - s.radius;
+            if d.abs() > 1e-9 {
+                assert_eq!(gjk_intersect(&b, &s), exact);
+            }
+            if let Some(c) = gjk_distance(&b, &s) {
+                assert!((c.distance - d).abs() < 1e-6);
+            }
+            #[cfg(feature = "alloc")]
+            if d < -1e-3 && !b.contains(s.center) {
+                let p = epa_penetration(&b, &s).unwrap();
+                assert!((p.depth + d).abs() < 1e-3);
+            }
+        }
+    }
+}
@@ -0,0 +1,423 @@
+// 16-bit floating point storage types: IEEE binary16 (f16) and bfloat16
+// (bf16, the top half of an f32).
+//
+// These are for storage only, there is no arithmetic: values are widened to
+// f32/f64 to compute and rounded back when stored. Widening is exact, the
+// rounding is to nearest with ties to even, straight from the f64 bits so
+// there is no double rounding. NaNs stay NaNs (quiet, with the top payload
+// bits kept), values too small for the subnormals round to a signed zero and
+// values too large to a signed infinity.
+
+use super::vector3d::*;
+use super::vector4d::*;
+
+// f64 rounded to nearest even in a format with `e` exponent and `m` mantissa bits
+fn narrow_bits(x: f64, e: u32, m: u32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 63) as u16) << (e + m);
+    let exp = ((bits >> 52) & 0x7ff) as i32;
+    let man = bits & ((1 << 52) - 1);
+    let max_exp = (1u16 << e) - 1;
+
+    if exp == 0x7ff {
+        if man == 0 {
+            return sign | max_exp << m;
+        }
+        let payload = (man >> (52 - m)) as u16;
+        return sign | max_exp << m | 1 << (m - 1) | payload;
+    }
+    if exp == 0 {
+        // f64 subnormals are far below the smallest half subnormal
+        return sign;
+    }
+
+    let bias = (1i32 << (e - 1)) - 1;
+    let sig = man | 1 << 52;
+    // biased exponent in the target format, below 1 for the subnormals
+    let te = exp - 1023 + bias;
+    let shift = if te >= 1 { 52 - m } else { 52 - m + (1 - te) as u32 };
+    if shift > 53 {
+        return sign;
+    }
+    let keep = sig >> shift;
+    let rem = sig & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    let keep = keep + (rem > half || (rem == half && keep & 1 == 1)) as u64;
+
+    if te < 1 {
+        // a carry into bit m gives the smallest normal, which is the same encoding
+        return sign | keep as u16;
+    }
+    // a carry out of the mantissa moves to the next binade
+    let (keep, te) = if keep == 1 << (m + 1) { (keep >> 1, te + 1) } else { (keep, te) };
+    if te >= max_exp as i32 {
+        return sign | max_exp << m;
+    }
+    sign | (te as u16) << m | (keep as u16 & ((1 << m) - 1))
+}
+
+// exact value of a half in a format with `e` exponent and `m` mantissa bits
+fn widen_bits(h: u16, e: u32, m: u32) -> f64 {
+    let sign = ((h >> (e + m)) as u64) << 63;
+    let exp = ((h >> m) & ((1 << e) - 1)) as i32;
+    let man = (h & ((1 << m) - 1)) as u64;
+    let bias = (1i32 << (e - 1)) - 1;
+
+    if exp == (1 << e) - 1 {
+        return f64::from_bits(sign | 0x7ff << 52 | man << (52 - m));
+    }
+    if exp == 0 {
+        // man * 2^(1 - bias - m), the power of two is a normal f64 for both formats
+        let unit = f64::from_bits(((1023 + 1 - bias - m as i32) as u64) << 52);
+        let v = man as f64 * unit;
+        return if sign != 0 { -v } else { v };
+    }
+    f64::from_bits(sign | ((exp - bias + 1023) as u64) << 52 | man << (52 - m))
+}
+
+// Common interface of f16 and bf16
+pub trait Half: Copy + PartialEq + PartialOrd + Default + core::fmt::Debug {
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    fn from_f32(x: f32) -> Self {
+        // f32 to f64 is exact, so this rounds once
+        Self::from_f64(x as f64)
+    }
+
+    fn to_f32(self) -> f32 {
+        // exact, both formats fit in f32
+        self.to_f64() as f32
+    }
+}
+
+macro_rules! half_type {
+    ($t:ident, $e:expr, $m:expr, $max:expr, $min_positive:expr, $epsilon:expr) => {
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Default)]
+        #[repr(transparent)]
+        pub struct $t(u16);
+
+        impl $t {
+            pub const ZERO: $t = $t(0);
+            pub const ONE: $t = $t(((1 << ($e - 1)) - 1) << $m);
+            pub const INFINITY: $t = $t(((1 << $e) - 1) << $m);
+            pub const NEG_INFINITY: $t = $t(1 << 15 | ((1 << $e) - 1) << $m);
+            pub const NAN: $t = $t(((1 << $e) - 1) << $m | 1 << ($m - 1));
+            // largest finite value
+            pub const MAX: $t = $t((((1 << $e) - 1) << $m) - 1);
+            // smallest positive normal value
+            pub const MIN_POSITIVE: $t = $t(1 << $m);
+            pub const MAX_F64: f64 = $max;
+            pub const MIN_POSITIVE_F64: f64 = $min_positive;
+            // distance from 1 to the next value
+            pub const EPSILON_F64: f64 = $epsilon;
+
+            pub const fn from_bits(bits: u16) -> Self {
+                $t(bits)
+            }
+
+            pub const fn to_bits(self) -> u16 {
+                self.0
+            }
+
+            pub fn from_f64(x: f64) -> Self {
+                $t(narrow_bits(x, $e, $m))
+            }
+
+            pub fn from_f32(x: f32) -> Self {
+                Self::from_f64(x as f64)
+            }
+
+            pub fn to_f64(self) -> f64 {
+                widen_bits(self.0, $e, $m)
+            }
+
+            pub fn to_f32(self) -> f32 {
+                self.to_f64() as f32
+            }
+
+            pub fn is_nan(self) -> bool {
+                self.0 & 0x7fff > Self::INFINITY.0
+            }
+
+            pub fn is_infinite(self) -> bool {
+                self.0 & 0x7fff == Self::INFINITY.0
+            }
+
+            pub fn is_finite(self) -> bool {
+                self.0 & 0x7fff < Self::INFINITY.0
+            }
+
+            pub fn is_subnormal(self) -> bool {
+                self.0 & 0x7fff != 0 && self.0 & Self::INFINITY.0 == 0
+            }
+
+            pub fn is_sign_negative(self) -> bool {
+                self.0 >> 15 == 1
+            }
+        }
+
+        impl Half for $t {
+            fn from_f64(x: f64) -> Self {
+                $t::from_f64(x)
+            }
+
+            fn to_f64(self) -> f64 {
+                $t::to_f64(self)
+            }
+        }
+
+        // IEEE comparisons: NaN is unordered and -0 == +0
+        impl PartialEq for $t {
+            fn eq(&self, o: &Self) -> bool {
+                self.to_f64() == o.to_f64()
+            }
+        }
+
+        impl PartialOrd for $t {
+            fn partial_cmp(&self, o: &Self) -> Option<core::cmp::Ordering> {
+                self.to_f64().partial_cmp(&o.to_f64())
+            }
+        }
+
+        impl core::fmt::Debug for $t {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Debug::fmt(&self.to_f32(), f)
+            }
+        }
+
+        impl core::fmt::Display for $t {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Display::fmt(&self.to_f32(), f)
+            }
+        }
+
+        impl From<$t> for f32 {
+            fn from(h: $t) -> f32 {
+                h.to_f32()
+            }
+        }
+
+        impl From<$t> for f64 {
+            fn from(h: $t) -> f64 {
+                h.to_f64()
+            }
+        }
+    };
+}
+
+half_type!(f16, 5, 10, 65504.0, 6.103515625e-5, 9.765625e-4);
+half_type!(bf16, 8, 7, 3.3895313892515355e38, 1.1754943508222875e-38, 7.8125e-3);
+
+// Packed 3d vector in 6 bytes (f16 by default)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct Vector3h<H = f16> {
+    pub x: H,
+    pub y: H,
+    pub z: H,
+}
+
+// Packed 4d vector in 8 bytes (f16 by default)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct Vector4h<H = f16> {
+    pub x: H,
+    pub y: H,
+    pub z: H,
+    pub w: H,
+}
+
+impl<H: Half> Vector3h<H> {
+    pub fn new(x: H, y: H, z: H) -> Self {
+        Self { x, y, z }
+    }
+
+    // every component rounded to nearest even
+    pub fn from_vector(v: Vector3d) -> Self {
+        Self { x: H::from_f64(v.x), y: H::from_f64(v.y), z: H::from_f64(v.z) }
+    }
+
+    pub fn to_vector(self) -> Vector3d {
+        Vector3d::new_from([self.x.to_f64(), self.y.to_f64(), self.z.to_f64()])
+    }
+}
+
+impl<H: Half> Vector4h<H> {
+    pub fn new(x: H, y: H, z: H, w: H) -> Self {
+        Self { x, y, z, w }
+    }
+
+    // every component rounded to nearest even
+    pub fn from_vector(v: Vector4d) -> Self {
+        Self { x: H::from_f64(v.x), y: H::from_f64(v.y), z: H::from_f64(v.z), w: H::from_f64(v.w) }
+    }
+
+    pub fn to_vector(self) -> Vector4d {
+        Vector4d::new_from([self.x.to_f64(), self.y.to_f64(), self.z.to_f64(), self.w.to_f64()])
+    }
+}
+
+// widening is lossless
+impl<H: Half> From<Vector3h<H>> for Vector3d {
+    fn from(v: Vector3h<H>) -> Self {
+        v.to_vector()
+    }
+}
+
+impl<H: Half> From<Vector4h<H>> for Vector4d {
+    fn from(v: Vector4h<H>) -> Self {
+        v.to_vector()
+    }
+}
+
+fn convert<S: Copy, D>(src: &[S], dst: &mut [D], f: impl Fn(S) -> D) {
+    assert_eq!(src.len(), dst.len(), "source and destination must have the same length");
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = f(*s);
+    }
+}
+
+// bulk conversions between slices of the same length
+
+pub fn narrow_slice<H: Half>(src: &[f32], dst: &mut [H]) {
+    convert(src, dst, H::from_f32)
+}
+
+pub fn widen_slice<H: Half>(src: &[H], dst: &mut [f32]) {
+    convert(src, dst, H::to_f32)
+}
+
+pub fn narrow_slice_f64<H: Half>(src: &[f64], dst: &mut [H]) {
+    convert(src, dst, H::from_f64)
+}
+
+pub fn widen_slice_f64<H: Half>(src: &[H], dst: &mut [f64]) {
+    convert(src, dst, H::to_f64)
+}
+
+pub fn narrow_vector3_slice<H: Half>(src: &[Vector3d], dst: &mut [Vector3h<H>]) {
+    convert(src, dst, Vector3h::from_vector)
+}
+
+pub fn widen_vector3_slice<H: Half>(src: &[Vector3h<H>], dst: &mut [Vector3d]) {
+    convert(src, dst, Vector3h::to_vector)
+}
+
+pub fn narrow_vector4_slice<H: Half>(src: &[Vector4d], dst: &mut [Vector4h<H>]) {
+    convert(src, dst, Vector4h::from_vector)
+}
+
+pub fn widen_vector4_slice<H: Half>(src: &[Vector4h<H>], dst: &mut [Vector4d]) {
+    convert(src, dst, Vector4h::to_vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16_rounding_and_special_values() {
+        assert_eq!(f16::from_f64(1.0).to_bits(), 0x3c00);
+        assert_eq!(f16::ONE.to_bits(), 0x3c00);
+        assert_eq!(f16::from_f64(-2.0).to_bits(), 0xc000);
+        assert_eq!(f16::from_f64(65504.0), f16::MAX);
+        assert_eq!(f16::MAX.to_f64(), f16::MAX_F64);
+        assert_eq!(f16::MIN_POSITIVE.to_f64(), f16::MIN_POSITIVE_F64);
+        // 1 + eps/2 is a tie, even is 1; 1 + 3 eps/2 is a tie, even is 1 + 2 eps
+        let eps = f16::EPSILON_F64;
+        assert_eq!(f16::from_f64(1.0 + eps / 2.0).to_bits(), 0x3c00);
+        assert_eq!(f16::from_f64(1.0 + 1.5 * eps).to_bits(), 0x3c02);
+        assert_eq!(f16::from_f64(1.0 + eps / 2.0 + 1e-10).to_bits(), 0x3c01);
+        // overflow: 65520 is halfway to 65536 and rounds up to infinity
+        assert_eq!(f16::from_f64(65519.0), f16::MAX);
+        assert!(f16::from_f64(65520.0).is_infinite());
+        assert_eq!(f16::from_f64(-1e10), f16::NEG_INFINITY);
+
+        // subnormals: the smallest is 2^-24, half of it is a tie to zero
+        let tiny = f16::from_bits(1);
+        assert!(tiny.is_subnormal());
+        assert_eq!(tiny.to_f64(), 2f64.powi(-24));
+        assert_eq!(f16::from_f64(2f64.powi(-24)), tiny);
+        assert_eq!(f16::from_f64(2f64.powi(-25)).to_bits(), 0);
+        assert_eq!(f16::from_f64(1.5 * 2f64.powi(-25)).to_bits(), 1);
+        assert_eq!(f16::from_f64(-2f64.powi(-26)).to_bits(), 0x8000);
+        // rounding up out of the subnormals gives the smallest normal
+        assert_eq!(f16::from_f64(f16::MIN_POSITIVE_F64 * (1.0 - 2f64.powi(-12))), f16::MIN_POSITIVE);
+
+        let nan = f16::from_f64(f64::NAN);
+        assert!(nan.is_nan() && nan != nan);
+        assert!(f16::from_f32(f32::from_bits(0x7f80_0001)).is_nan());
+        assert!(f16::NAN.to_f64().is_nan());
+        assert_eq!(f16::from_f64(-0.0), f16::ZERO);
+        assert!(f16::from_f64(-0.0).is_sign_negative());
+    }
+
+    #[test]
+    fn every_f16_round_trips() {
+        for bits in 0..=u16::MAX {
+            let h = f16::from_bits(bits);
+            let back = f16::from_f64(h.to_f64());
+            if h.is_nan() {
+                assert!(back.is_nan());
+            } else {
+                assert_eq!(back.to_bits(), bits);
+                assert_eq!(f16::from_f32(h.to_f32()).to_bits(), bits);
+            }
+            let b = bf16::from_bits(bits);
+            if !b.is_nan() {
+                assert_eq!(bf16::from_f64(b.to_f64()).to_bits(), bits);
+                // bf16 is the top half of the f32
+                assert_eq!(b.to_f32().to_bits(), (bits as u32) << 16);
+            }
+        }
+    }
+
+    #[test]
+    fn bf16_rounding() {
+        assert_eq!(bf16::ONE.to_bits(), 0x3f80);
+        assert_eq!(bf16::from_f32(1.0).to_bits(), 0x3f80);
+        // ties to even on the f32 bits that are dropped
+        assert_eq!(bf16::from_f32(f32::from_bits(0x3f80_8000)).to_bits(), 0x3f80);
+        assert_eq!(bf16::from_f32(f32::from_bits(0x3f81_8000)).to_bits(), 0x3f82);
+        assert_eq!(bf16::from_f32(f32::from_bits(0x3f80_8001)).to_bits(), 0x3f81);
+        assert_eq!(bf16::from_f32(f32::MAX), bf16::INFINITY);
+        assert_eq!(bf16::MAX.to_f64(), bf16::MAX_F64);
+        assert_eq!(bf16::from_f64(1e-40).to_f32(), f32::from_bits(0x0001_0000));
+        // no double rounding: this f64 rounds to an f32 tie, but it's above it
+        let x = 1.0 + 2f64.powi(-8) + 2f64.powi(-40);
+        assert_eq!(bf16::from_f64(x).to_bits(), 0x3f81);
+    }
+
+    #[test]
+    fn packed_vectors_and_slices() {
+        assert_eq!(core::mem::size_of::<Vector3h>(), 6);
+        assert_eq!(core::mem::size_of::<Vector4h<bf16>>(), 8);
+        let n = Vector3d::new_from([0.0, 0.6, 0.8]);
+        let h = Vector3h::<f16>::from_vector(n);
+        let w: Vector3d = h.into();
+        assert!((w - n).to_list().iter().all(|d| d.abs() < 5e-4));
+        // widening is lossless, narrowing the widened value gives the same bits
+        assert_eq!(Vector3h::<f16>::from_vector(w), h);
+
+        let src: [f32; 5] = [0.1, -2.5, 1e-6, 7e4, 3.0];
+        let mut packed = [f16::ZERO; 5];
+        narrow_slice(&src, &mut packed);
+        let mut back = [0.0f32; 5];
+        widen_slice(&packed, &mut back);
+        assert_eq!(back[1], -2.5);
+        assert_eq!(back[3], f32::INFINITY);
+        assert!((back[0] - 0.1).abs() < 1e-4);
+
+        let vs = [Vector4d::new_from([1.0, 2.0, 3.0, 4.0]), Vector4d::new_from([-0.5, 0.25, 1e3, 0.0])];
+        let mut hs = [Vector4h::<bf16>::default(); 2];
+        narrow_vector4_slice(&vs, &mut hs);
+        let mut wide = [Vector4d::new(); 2];
+        widen_vector4_slice(&hs, &mut wide);
+        assert_eq!(wide, vs);
+        let mut d = [0.0; 2];
+        widen_slice_f64(&[hs[1].z, hs[1].x], &mut d);
+        assert_eq!(d, [1000.0, -0.5]);
+    }
+}
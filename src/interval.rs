@@ -0,0 +1,440 @@
+// Interval arithmetic with outward rounding, for guaranteed enclosures.
+//
+// Every operation rounds its lower bound down and its upper bound up by one
+// ulp after the round-to-nearest result, so the exact result of the operation
+// on any points of the operands is always inside. Interval implements Scalar,
+// so Matrix3d<Interval> * Vector3d<Interval> is a box containing the exact
+// product of every matrix and vector in the input boxes.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+use crate::math;
+use crate::scalar::{lu_solve, Real, Scalar};
+use core::f64::consts::{FRAC_PI_2, PI};
+
+// largest error of the libm/std sin, with some margin
+const SIN_ERR: f64 = 1e-15;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+fn down(x: f64) -> f64 {
+    if x.is_finite() { x.next_down() } else { x }
+}
+
+fn up(x: f64) -> f64 {
+    if x.is_finite() { x.next_up() } else { x }
+}
+
+impl Interval {
+    pub const ENTIRE: Interval = Interval { lo: f64::NEG_INFINITY, hi: f64::INFINITY };
+
+    pub fn new(lo: f64, hi: f64) -> Self {
+        assert!(lo <= hi, "interval lower bound {} above upper bound {}", lo, hi);
+        Self { lo, hi }
+    }
+
+    pub fn point(x: f64) -> Self {
+        Self { lo: x, hi: x }
+    }
+
+    // x +- r
+    pub fn around(x: f64, r: f64) -> Self {
+        Self::new(down(x - r), up(x + r))
+    }
+
+    // rounded result [lo, hi] of an operation, widened outwards
+    fn rounded(lo: f64, hi: f64) -> Self {
+        Self { lo: down(lo), hi: up(hi) }
+    }
+
+    pub fn width(&self) -> f64 {
+        up(self.hi - self.lo)
+    }
+
+    pub fn mid(&self) -> f64 {
+        if self.lo.is_finite() && self.hi.is_finite() {
+            self.lo / 2.0 + self.hi / 2.0
+        } else {
+            0.0
+        }
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn contains_zero(&self) -> bool {
+        self.contains(0.0)
+    }
+
+    pub fn is_subset(&self, o: &Interval) -> bool {
+        o.lo <= self.lo && self.hi <= o.hi
+    }
+
+    pub fn intersect(&self, o: &Interval) -> Option<Interval> {
+        let (lo, hi) = (self.lo.max(o.lo), self.hi.min(o.hi));
+        if lo <= hi { Some(Self { lo, hi }) } else { None }
+    }
+
+    pub fn hull(&self, o: &Interval) -> Interval {
+        Self { lo: self.lo.min(o.lo), hi: self.hi.max(o.hi) }
+    }
+
+    // largest |x| in the interval
+    pub fn mag(&self) -> f64 {
+        self.lo.abs().max(self.hi.abs())
+    }
+
+    // smallest |x| in the interval
+    pub fn mig(&self) -> f64 {
+        if self.contains_zero() { 0.0 } else { self.lo.abs().min(self.hi.abs()) }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { lo: self.mig(), hi: self.mag() }
+    }
+
+    pub fn recip(&self) -> Self {
+        Self::point(1.0) / *self
+    }
+
+    // the negative part of the interval is outside the domain and dropped, an
+    // interval entirely below zero gives NaN bounds
+    pub fn sqrt(&self) -> Self {
+        if self.hi < 0.0 {
+            return Self { lo: f64::NAN, hi: f64::NAN };
+        }
+        Self {
+            lo: down(math::sqrt(self.lo.max(0.0))).max(0.0),
+            hi: up(math::sqrt(self.hi)),
+        }
+    }
+
+    pub fn sin(&self) -> Self {
+        // far from 0 the position of the extrema isn't known precisely enough
+        let wide = self.hi - self.lo >= 2.0 * PI || self.lo.is_nan() || self.hi.is_nan();
+        if wide || self.mag() > 1e6 {
+            return Self::new(-1.0, 1.0);
+        }
+        let (a, b) = (math::sin(self.lo), math::sin(self.hi));
+        let mut lo = a.min(b) - SIN_ERR;
+        let mut hi = a.max(b) + SIN_ERR;
+
+        // an extremum at c + 2k pi inside the interval, deciding in doubt that it is
+        let tol = 1e-9 * (1.0 + self.mag());
+        let has = |c: f64| {
+            let k = math::floor((self.lo - c) / (2.0 * PI));
+            (0..3).any(|i| {
+                let p = c + (k + i as f64) * 2.0 * PI;
+                p >= self.lo - tol && p <= self.hi + tol
+            })
+        };
+        if has(FRAC_PI_2) {
+            hi = 1.0;
+        }
+        if has(-FRAC_PI_2) {
+            lo = -1.0;
+        }
+        Self { lo: lo.max(-1.0), hi: hi.min(1.0) }
+    }
+
+    pub fn cos(&self) -> Self {
+        // cos x = sin(x + pi / 2), with an enclosure of pi / 2
+        (*self + Self::rounded(FRAC_PI_2, FRAC_PI_2)).sin()
+    }
+
+    pub fn tan(&self) -> Self {
+        self.sin() / self.cos()
+    }
+}
+
+impl From<f64> for Interval {
+    fn from(x: f64) -> Self {
+        Self::point(x)
+    }
+}
+
+impl core::ops::Add<Interval> for Interval {
+    type Output = Interval;
+    fn add(self, o: Interval) -> Self::Output {
+        Self::rounded(self.lo + o.lo, self.hi + o.hi)
+    }
+}
+
+impl core::ops::Sub<Interval> for Interval {
+    type Output = Interval;
+    fn sub(self, o: Interval) -> Self::Output {
+        Self::rounded(self.lo - o.hi, self.hi - o.lo)
+    }
+}
+
+impl core::ops::Mul<Interval> for Interval {
+    type Output = Interval;
+    fn mul(self, o: Interval) -> Self::Output {
+        // 0 * inf is taken as 0, the bounds are limits of finite values
+        let p = |a: f64, b: f64| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        let c = [p(self.lo, o.lo), p(self.lo, o.hi), p(self.hi, o.lo), p(self.hi, o.hi)];
+        let lo = c.iter().copied().fold(f64::INFINITY, f64::min);
+        let hi = c.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Self::rounded(lo, hi)
+    }
+}
+
+// dividing by an interval containing zero gives the entire real line
+impl core::ops::Div<Interval> for Interval {
+    type Output = Interval;
+    fn div(self, o: Interval) -> Self::Output {
+        if o.contains_zero() {
+            return Self::ENTIRE;
+        }
+        let c = [self.lo / o.lo, self.lo / o.hi, self.hi / o.lo, self.hi / o.hi];
+        let lo = c.iter().copied().fold(f64::INFINITY, f64::min);
+        let hi = c.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Self::rounded(lo, hi)
+    }
+}
+
+impl core::ops::Neg for Interval {
+    type Output = Interval;
+    fn neg(self) -> Self::Output {
+        Self { lo: -self.hi, hi: -self.lo }
+    }
+}
+
+impl core::ops::Add<f64> for Interval {
+    type Output = Interval;
+    fn add(self, o: f64) -> Self::Output {
+        self + Self::point(o)
+    }
+}
+
+impl core::ops::Sub<f64> for Interval {
+    type Output = Interval;
+    fn sub(self, o: f64) -> Self::Output {
+        self - Self::point(o)
+    }
+}
+
+impl core::ops::Mul<f64> for Interval {
+    type Output = Interval;
+    fn mul(self, o: f64) -> Self::Output {
+        self * Self::point(o)
+    }
+}
+
+impl core::ops::Div<f64> for Interval {
+    type Output = Interval;
+    fn div(self, o: f64) -> Self::Output {
+        self / Self::point(o)
+    }
+}
+
+impl Scalar for Interval {
+    fn zero() -> Self {
+        Self::point(0.0)
+    }
+
+    fn one() -> Self {
+        Self::point(1.0)
+    }
+}
+
+impl Real for Interval {
+    fn sqrt(self) -> Self {
+        Interval::sqrt(&self)
+    }
+}
+
+// Encloses the solutions of A x = b for every A and b in the interval inputs.
+// The system is first multiplied by the inverse of the midpoint of A, which
+// makes the preconditioned matrix close to the identity, then solved by
+// Gaussian elimination in interval arithmetic. None when the midpoint is
+// singular or a pivot interval contains zero.
+fn solve_enclosure<const N: usize>(a: [[Interval; N]; N], b: [Interval; N]) -> Option<[Interval; N]> {
+    // C = mid(A)^-1, solved column by column
+    let mid = a.map(|row| row.map(|x| x.mid()));
+    let mut cols = [[0.0; N]; N];
+    for (j, col) in cols.iter_mut().enumerate() {
+        let e = core::array::from_fn(|i| if i == j { 1.0 } else { 0.0 });
+        *col = lu_solve(mid, e, f64::abs)?;
+    }
+    let c: [[f64; N]; N] = core::array::from_fn(|i| core::array::from_fn(|j| cols[j][i]));
+    let dot = |ci: &[f64; N], col: &dyn Fn(usize) -> Interval| {
+        (0..N).fold(Interval::zero(), |s, k| s + col(k) * ci[k])
+    };
+    let mut m: [[Interval; N]; N] = core::array::from_fn(|i| core::array::from_fn(|j| dot(&c[i], &|k| a[k][j])));
+    let mut y: [Interval; N] = core::array::from_fn(|i| dot(&c[i], &|k| b[k]));
+
+    for col in 0..N {
+        let p = (col..N).max_by(|i, j| m[*i][col].mig().total_cmp(&m[*j][col].mig()))?;
+        if m[p][col].contains_zero() {
+            return None;
+        }
+        m.swap(p, col);
+        y.swap(p, col);
+        for r in col + 1..N {
+            let f = m[r][col] / m[col][col];
+            let (top, bottom) = m.split_at_mut(r);
+            for (x, p) in bottom[0][col + 1..].iter_mut().zip(&top[col][col + 1..]) {
+                *x = *x - f * *p;
+            }
+            y[r] = y[r] - f * y[col];
+        }
+    }
+    let mut x = [Interval::zero(); N];
+    for i in (0..N).rev() {
+        let mut s = y[i];
+        for j in i + 1..N {
+            s = s - m[i][j] * x[j];
+        }
+        x[i] = s / m[i][i];
+    }
+    Some(x)
+}
+
+macro_rules! impl_interval_types {
+    ($m:ident, $v:ident, $n:expr) => {
+        impl $v<Interval> {
+            pub fn mid(&self) -> $v {
+                $v::new_from(self.to_list().map(|x| x.mid()))
+            }
+
+            pub fn contains(&self, p: &$v) -> bool {
+                self.to_list().iter().zip(p.to_list()).all(|(i, x)| i.contains(x))
+            }
+        }
+
+        impl From<$v> for $v<Interval> {
+            fn from(v: $v) -> Self {
+                $v::new_from(v.to_list().map(Interval::point))
+            }
+        }
+
+        impl $m<Interval> {
+            // entrywise intervals [lo, hi]
+            pub fn from_bounds(lo: $m, hi: $m) -> Self {
+                let (lo, hi) = (lo.to_row_major_array(), hi.to_row_major_array());
+                $m::from_row_major(core::array::from_fn(|i| Interval::new(lo[i], hi[i])))
+            }
+
+            pub fn mid(&self) -> $m {
+                $m::from_row_major(self.to_row_major_array().map(|x| x.mid()))
+            }
+
+            // enclosure of the solution set of A x = b, see solve_enclosure
+            pub fn solve(&self, b: $v<Interval>) -> Option<$v<Interval>> {
+                let v = self.to_row_major_array();
+                let a = core::array::from_fn(|i| core::array::from_fn(|j| v[i * $n + j]));
+                solve_enclosure::<$n>(a, b.to_list()).map($v::new_from)
+            }
+        }
+
+        impl From<$m> for $m<Interval> {
+            fn from(m: $m) -> Self {
+                $m::from_row_major(m.to_row_major_array().map(Interval::point))
+            }
+        }
+    };
+}
+
+impl_interval_types!(Matrix2d, Vector2d, 2);
+impl_interval_types!(Matrix3d, Vector3d, 3);
+impl_interval_types!(Matrix4d, Vector4d, 4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outward_rounding() {
+        let third = Interval::point(1.0) / Interval::point(3.0);
+        assert!(third.lo < third.hi);
+        assert!((third * 3.0).contains(1.0));
+        let s = Interval::point(0.1) + Interval::point(0.2);
+        assert!(s.contains(0.1 + 0.2));
+        assert!(s.width() < 1e-15);
+
+        let a = Interval::new(-2.0, 3.0);
+        let b = Interval::new(-1.0, 4.0);
+        assert!(Interval::new(-8.0, 12.0).is_subset(&(a * b)));
+        assert!((a * b).is_subset(&Interval::new(-8.000001, 12.000001)));
+        assert_eq!(a / b, Interval::ENTIRE);
+        assert_eq!(-a, Interval::new(-3.0, 2.0));
+        assert_eq!(Interval::new(-3.0, 2.0).abs(), Interval::new(0.0, 3.0));
+    }
+
+    #[test]
+    fn sqrt_and_trig_enclose_samples() {
+        let x = Interval::new(2.0, 5.0);
+        assert!(x.sqrt().contains(math::sqrt(2.0)) && x.sqrt().contains(math::sqrt(5.0)));
+        assert!(Interval::point(2.0).sqrt().contains(core::f64::consts::SQRT_2));
+        assert_eq!(Interval::new(-1.0, 4.0).sqrt().lo, 0.0);
+
+        for (lo, hi) in [(0.0, PI), (-0.3, 0.2), (1.0, 1.5), (3.0, 9.0), (-20.0, -17.5), (100.0, 101.0)] {
+            let x = Interval::new(lo, hi);
+            let (s, c) = (x.sin(), x.cos());
+            for k in 0..=100 {
+                let t = lo + (hi - lo) * k as f64 / 100.0;
+                assert!(s.contains(math::sin(t)), "sin {:?} misses {}", s, t);
+                assert!(c.contains(math::cos(t)), "cos {:?} misses {}", c, t);
+            }
+        }
+        assert_eq!(Interval::new(0.0, PI).sin().hi, 1.0);
+        assert!(Interval::new(0.1, 0.2).sin().width() < 0.11);
+    }
+
+    #[test]
+    fn matrix_vector_product_encloses_exact_result() {
+        let third = Interval::point(1.0) / 3.0;
+        let m = Matrix3d::new([third, third * 2.0, Interval::zero(), third, third, third, Interval::one(), Interval::zero(), third]);
+        let v = Vector3d::<Interval>::from(Vector3d::new_from([3.0, 6.0, 9.0]));
+        // exactly (5, 6, 6)
+        assert!((m * v).contains(&Vector3d::new_from([5.0, 6.0, 6.0])));
+
+        // the box covers the products of all the corner matrices
+        let a = Matrix2d::from_bounds(Matrix2d::new([1.0, -1.0, 0.5, 2.0]), Matrix2d::new([1.5, -0.5, 0.5, 3.0]));
+        let x = Vector2d::new_from([Interval::new(1.0, 2.0), Interval::new(-1.0, 1.0)]);
+        let y = a * x;
+        for c in 0..16 {
+            let pick = |i: usize, lo: f64, hi: f64| if c >> i & 1 == 0 { lo } else { hi };
+            let m = Matrix2d::new([pick(0, 1.0, 1.5), pick(1, -1.0, -0.5), 0.5, pick(2, 2.0, 3.0)]);
+            let p = Vector2d::new_from([pick(3, 1.0, 2.0), 0.3]);
+            assert!(y.contains(&(m * p)));
+        }
+    }
+
+    #[test]
+    fn gaussian_elimination_encloses_solution_set() {
+        let a = Matrix3d::new([4.0, 1.0, 0.0, 1.0, 5.0, 2.0, 0.0, 2.0, 6.0]);
+        let x = Vector3d::new_from([1.0, -2.0, 3.0]);
+        let b = a * x;
+        let sol = Matrix3d::<Interval>::from(a).solve(b.into()).unwrap();
+        assert!(sol.contains(&x));
+        assert!(sol.to_list().iter().all(|i| i.width() < 1e-13));
+
+        // uncertain matrix, every sampled system has its solution in the enclosure
+        let r = 0.05;
+        let lo = a - r;
+        let hi = a + r;
+        let ai = Matrix3d::from_bounds(lo, hi);
+        let sol = ai.solve(b.into()).unwrap();
+        for c in 0..64 {
+            let v = a.to_row_major_array();
+            let sample: [f64; 9] = core::array::from_fn(|i| v[i] + if (c * 7 + i * 3) % 5 < 2 { r } else { -r });
+            let rows = core::array::from_fn(|i| core::array::from_fn(|j| sample[i * 3 + j]));
+            let xs = lu_solve(rows, b.to_list(), f64::abs).unwrap();
+            assert!(sol.contains(&Vector3d::new_from(xs)));
+        }
+
+        let singular = Matrix2d::<Interval>::from(Matrix2d::new([1.0, 2.0, 2.0, 4.0]));
+        assert!(singular.solve(Vector2d::new_from([Interval::one(), Interval::one()])).is_none());
+    }
+}
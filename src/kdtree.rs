@@ -0,0 +1,282 @@
+// k-d tree over 2d and 3d points for nearest-neighbour and radius queries.
+//
+// The tree is implicit: the points are reordered so that every range
+// [lo, hi) has its splitting point at (lo + hi) / 2, the smaller
+// coordinates to the left and the larger ones to the right. Each level is
+// split with a linear-time selection, so the build is O(n log n) and the
+// tree is balanced whatever the input order.
+
+use super::vector2d::*;
+use super::vector3d::*;
+use alloc::vec::Vec;
+
+// Points a KdTree can index
+pub trait KdPoint: Copy {
+    const DIM: usize;
+
+    fn coord(&self, axis: usize) -> f64;
+}
+
+impl KdPoint for Vector2d {
+    const DIM: usize = 2;
+
+    fn coord(&self, axis: usize) -> f64 {
+        self.to_list()[axis]
+    }
+}
+
+impl KdPoint for Vector3d {
+    const DIM: usize = 3;
+
+    fn coord(&self, axis: usize) -> f64 {
+        self.to_list()[axis]
+    }
+}
+
+fn distance_squared<P: KdPoint>(a: &P, b: &P) -> f64 {
+    (0..P::DIM).map(|i| (a.coord(i) - b.coord(i)) * (a.coord(i) - b.coord(i))).sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor<P> {
+    // the payload index of the point, its position in the input by default
+    pub index: usize,
+    pub point: P,
+    pub distance: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KdTree<P> {
+    points: Vec<P>,
+    indices: Vec<usize>,
+    // splitting axis of the node at the same position
+    axes: Vec<u8>,
+}
+
+impl<P: KdPoint> KdTree<P> {
+    pub fn build(points: &[P]) -> Self {
+        Self::build_with_indices(points, &(0..points.len()).collect::<Vec<_>>())
+    }
+
+    // indices[i] is reported as the index of points[i]
+    pub fn build_with_indices(points: &[P], indices: &[usize]) -> Self {
+        assert_eq!(points.len(), indices.len(), "every point needs an index");
+        let mut tree = Self { points: points.to_vec(), indices: indices.to_vec(), axes: alloc::vec![0; points.len()] };
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        let mut work = alloc::vec![(0, points.len())];
+        while let Some((lo, hi)) = work.pop() {
+            if hi - lo <= 1 {
+                continue;
+            }
+            // split along the axis of largest spread
+            let range = &mut order[lo..hi];
+            let spread = |axis: usize| {
+                let (mn, mx) = range.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(mn, mx), &i| {
+                    let c = points[i].coord(axis);
+                    (mn.min(c), mx.max(c))
+                });
+                mx - mn
+            };
+            let axis = (0..P::DIM).map(|a| (spread(a), a)).max_by(|a, b| a.0.total_cmp(&b.0)).map_or(0, |s| s.1);
+            let mid = (lo + hi) / 2;
+            range.select_nth_unstable_by(mid - lo, |&a, &b| points[a].coord(axis).total_cmp(&points[b].coord(axis)));
+            tree.axes[mid] = axis as u8;
+            work.push((lo, mid));
+            work.push((mid + 1, hi));
+        }
+        for (slot, &i) in order.iter().enumerate() {
+            tree.points[slot] = points[i];
+            tree.indices[slot] = indices[i];
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    fn neighbor(&self, slot: usize, d2: f64) -> Neighbor<P> {
+        Neighbor { index: self.indices[slot], point: self.points[slot], distance: crate::math::sqrt(d2) }
+    }
+
+    pub fn nearest(&self, q: P) -> Option<Neighbor<P>> {
+        self.k_nearest(q, 1).pop()
+    }
+
+    // the k closest points, nearest first
+    pub fn k_nearest(&self, q: P, k: usize) -> Vec<Neighbor<P>> {
+        self.k_nearest_approx(q, k, 0.0)
+    }
+
+    // a point at most (1 + eps) times further than the true nearest one;
+    // larger eps visits fewer nodes
+    pub fn nearest_approx(&self, q: P, eps: f64) -> Option<Neighbor<P>> {
+        self.k_nearest_approx(q, 1, eps).pop()
+    }
+
+    // the i-th result is at most (1 + eps) times further than the true
+    // i-th nearest point
+    pub fn k_nearest_approx(&self, q: P, k: usize, eps: f64) -> Vec<Neighbor<P>> {
+        assert!(eps >= 0.0, "eps must not be negative");
+        // (distance squared, slot), sorted ascending
+        let mut best: Vec<(f64, usize)> = Vec::with_capacity(k + 1);
+        if k > 0 {
+            let scale = (1.0 + eps) * (1.0 + eps);
+            self.search(&q, 0, self.len(), k, scale, &mut best);
+        }
+        best.into_iter().map(|(d2, slot)| self.neighbor(slot, d2)).collect()
+    }
+
+    fn search(&self, q: &P, lo: usize, hi: usize, k: usize, scale: f64, best: &mut Vec<(f64, usize)>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        let d2 = distance_squared(q, &self.points[mid]);
+        if best.len() < k || d2 < best[best.len() - 1].0 {
+            let at = best.partition_point(|b| b.0 <= d2);
+            best.insert(at, (d2, mid));
+            best.truncate(k);
+        }
+        let axis = self.axes[mid] as usize;
+        let diff = q.coord(axis) - self.points[mid].coord(axis);
+        let (near, far) = if diff < 0.0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+        self.search(q, near.0, near.1, k, scale, best);
+        // the far side can only help if the splitting plane is closer than
+        // the current k-th distance, shrunk by (1 + eps)
+        if best.len() < k || diff * diff * scale < best[best.len() - 1].0 {
+            self.search(q, far.0, far.1, k, scale, best);
+        }
+    }
+
+    // every point within radius of q, nearest first
+    pub fn within_radius(&self, q: P, radius: f64) -> Vec<Neighbor<P>> {
+        let r2 = radius * radius;
+        let mut found: Vec<(f64, usize)> = Vec::new();
+        let mut work = alloc::vec![(0, self.len())];
+        while let Some((lo, hi)) = work.pop() {
+            if lo >= hi {
+                continue;
+            }
+            let mid = (lo + hi) / 2;
+            let d2 = distance_squared(&q, &self.points[mid]);
+            if d2 <= r2 {
+                found.push((d2, mid));
+            }
+            let axis = self.axes[mid] as usize;
+            let diff = q.coord(axis) - self.points[mid].coord(axis);
+            if diff <= radius {
+                work.push((lo, mid));
+            }
+            if diff >= -radius {
+                work.push((mid + 1, hi));
+            }
+        }
+        found.sort_by(|a, b| a.0.total_cmp(&b.0));
+        found.into_iter().map(|(d2, slot)| self.neighbor(slot, d2)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    // deterministic scattered points with a few exact duplicates
+    fn cloud3(n: usize) -> Vec<Vector3d> {
+        (0..n)
+            .map(|i| {
+                let t = (i % (n - 5)) as f64;
+                Vector3d::new_from([math::sin(t * 12.9898) * 50.0, math::cos(t * 78.233) * 50.0, math::sin(t * 3.7) * 10.0])
+            })
+            .collect()
+    }
+
+    fn brute<P: KdPoint>(points: &[P], q: &P) -> Vec<(f64, usize)> {
+        let mut d: Vec<(f64, usize)> = points.iter().enumerate().map(|(i, p)| (distance_squared(q, p), i)).collect();
+        d.sort_by(|a, b| a.0.total_cmp(&b.0));
+        d
+    }
+
+    #[test]
+    fn nearest_and_k_nearest_match_brute_force() {
+        let points = cloud3(2000);
+        let tree = KdTree::build(&points);
+        assert_eq!(tree.len(), 2000);
+        for j in 0..50 {
+            let t = j as f64;
+            let q = Vector3d::new_from([math::cos(t) * 55.0, math::sin(t * 1.9) * 55.0, math::cos(t * 0.3) * 12.0]);
+            let expected = brute(&points, &q);
+            let nn = tree.nearest(q).unwrap();
+            assert_eq!(nn.distance, math::sqrt(expected[0].0));
+            assert_eq!(points[nn.index], nn.point);
+            let knn = tree.k_nearest(q, 10);
+            assert_eq!(knn.len(), 10);
+            for (n, e) in knn.iter().zip(&expected) {
+                assert_eq!(n.distance, math::sqrt(e.0));
+                assert_eq!(distance_squared(&q, &points[n.index]), e.0);
+            }
+        }
+        // asking for more than there is returns everything
+        let small = KdTree::build(&points[..7]);
+        assert_eq!(small.k_nearest(Vector3d::new(), 20).len(), 7);
+        assert!(KdTree::<Vector3d>::build(&[]).nearest(Vector3d::new()).is_none());
+    }
+
+    #[test]
+    fn radius_search_2d_with_payload() {
+        let points: Vec<Vector2d> = (0..30 * 30).map(|i| Vector2d::new_from([(i % 30) as f64, (i / 30) as f64])).collect();
+        let payload: Vec<usize> = (0..points.len()).map(|i| 1000 + i).collect();
+        let tree = KdTree::build_with_indices(&points, &payload);
+        let q = Vector2d::new_from([10.0, 10.0]);
+        let found = tree.within_radius(q, 2.0);
+        // the lattice points with x^2 + y^2 <= 4 around the centre
+        assert_eq!(found.len(), 13);
+        assert_eq!(found[0].index, 1000 + 10 * 30 + 10);
+        assert_eq!(found[0].distance, 0.0);
+        assert!(found.windows(2).all(|w| w[0].distance <= w[1].distance));
+        for n in &found {
+            assert_eq!(points[n.index - 1000], n.point);
+            assert!(n.distance <= 2.0);
+        }
+        assert!(tree.within_radius(Vector2d::new_from([-5.0, -5.0]), 1.0).is_empty());
+        assert_eq!(tree.nearest(Vector2d::new_from([-5.0, 29.4])).unwrap().index, 1000 + 29 * 30);
+    }
+
+    #[test]
+    fn approximate_search_is_within_bound() {
+        let points = cloud3(3000);
+        let tree = KdTree::build(&points);
+        for eps in [0.1, 0.5, 2.0] {
+            for j in 0..40 {
+                let t = j as f64 * 0.77;
+                let q = Vector3d::new_from([math::sin(t) * 40.0, math::cos(t * 1.3) * 40.0, 0.0]);
+                let exact = tree.k_nearest(q, 5);
+                let approx = tree.k_nearest_approx(q, 5, eps);
+                assert_eq!(approx.len(), 5);
+                for (a, e) in approx.iter().zip(&exact) {
+                    assert!(a.distance <= e.distance * (1.0 + eps) + 1e-12);
+                }
+                let a = tree.nearest_approx(q, eps).unwrap();
+                assert!(a.distance <= exact[0].distance * (1.0 + eps) + 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn build_is_balanced_for_sorted_input() {
+        // already sorted and all-equal inputs are the classic bad cases
+        let line: Vec<Vector3d> = (0..1024).map(|i| Vector3d::new_from([i as f64, 0.0, 0.0])).collect();
+        let tree = KdTree::build(&line);
+        let n = tree.nearest(Vector3d::new_from([511.6, 3.0, 0.0])).unwrap();
+        assert_eq!(n.index, 512);
+        let same = [Vector3d::new_from_const(1.0); 100];
+        let tree = KdTree::build(&same);
+        assert_eq!(tree.within_radius(Vector3d::new_from_const(1.0), 0.0).len(), 100);
+        assert_eq!(tree.k_nearest(Vector3d::new(), 3).len(), 3);
+    }
+}
@@ -0,0 +1,555 @@
+// Iterative Krylov solvers for A x = b on top of LinearOperator.
+//
+// All solvers stop when the residual norm drops below tol * ||b||, or after
+// max_iter iterations. They never fail outright: the result says whether
+// they converged and keeps the residual norm of every iteration. Breakdowns
+// (a zero inner product in the recurrences) stop the iteration early with
+// converged set to false.
+
+use super::linop::*;
+use super::matrixxd::dot;
+use super::precond::*;
+use crate::math::{hypot, sqrt};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolverConfig {
+    // relative tolerance on the residual norm
+    pub tol: f64,
+    pub max_iter: usize,
+    // Krylov subspace size before GMRES restarts, unused by the other solvers
+    pub restart: usize,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            tol: 1e-10,
+            max_iter: 1000,
+            restart: 30,
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn new(tol: f64, max_iter: usize) -> Self {
+        Self {
+            tol,
+            max_iter,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_restart(mut self, restart: usize) -> Self {
+        self.restart = restart.max(1);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveResult {
+    pub x: Vec<f64>,
+    pub converged: bool,
+    pub iterations: usize,
+    // residual norm at the end
+    pub residual_norm: f64,
+    // residual norm before the first iteration and after every iteration
+    pub history: Vec<f64>,
+}
+
+fn norm(x: &[f64]) -> f64 {
+    sqrt(dot(x, x))
+}
+
+// y += a * x
+fn axpy(a: f64, x: &[f64], y: &mut [f64]) {
+    for (y, x) in y.iter_mut().zip(x) {
+        *y += a * x;
+    }
+}
+
+fn check_dims<A: LinearOperator>(a: &A, b: &[f64], x0: Option<&[f64]>) -> Vec<f64> {
+    assert_eq!(a.nrows(), a.ncols(), "the operator must be square");
+    assert_eq!(b.len(), a.nrows(), "right hand side length doesn't match the operator");
+    match x0 {
+        Some(x0) => {
+            assert_eq!(x0.len(), b.len(), "initial guess length doesn't match the operator");
+            x0.to_vec()
+        }
+        None => vec![0.0; b.len()],
+    }
+}
+
+// r = b - A x
+fn residual<A: LinearOperator>(a: &A, b: &[f64], x: &[f64], r: &mut [f64]) {
+    a.apply(x, r);
+    for (r, b) in r.iter_mut().zip(b) {
+        *r = b - *r;
+    }
+}
+
+fn finish(x: Vec<f64>, converged: bool, history: Vec<f64>) -> SolveResult {
+    SolveResult {
+        x,
+        converged,
+        iterations: history.len() - 1,
+        residual_norm: *history.last().unwrap(),
+        history,
+    }
+}
+
+// preconditioned conjugate gradient, A and M must be symmetric positive definite
+pub fn cg<A: LinearOperator, P: Preconditioner>(a: &A, b: &[f64], x0: Option<&[f64]>, m: &P, cfg: &SolverConfig) -> SolveResult {
+    let mut x = check_dims(a, b, x0);
+    let n = b.len();
+    let target = cfg.tol * norm(b);
+
+    let mut r = vec![0.0; n];
+    residual(a, b, &x, &mut r);
+    let mut history = vec![norm(&r)];
+    if history[0] <= target {
+        return finish(x, true, history);
+    }
+
+    let mut z = vec![0.0; n];
+    m.apply(&r, &mut z);
+    let mut p = z.clone();
+    let mut rz = dot(&r, &z);
+    let mut ap = vec![0.0; n];
+
+    for _ in 0..cfg.max_iter {
+        a.apply(&p, &mut ap);
+        let pap = dot(&p, &ap);
+        if pap == 0.0 || !pap.is_finite() {
+            return finish(x, false, history);
+        }
+        let alpha = rz / pap;
+        axpy(alpha, &p, &mut x);
+        axpy(-alpha, &ap, &mut r);
+
+        let rnorm = norm(&r);
+        history.push(rnorm);
+        if rnorm <= target {
+            return finish(x, true, history);
+        }
+
+        m.apply(&r, &mut z);
+        let rz_new = dot(&r, &z);
+        if rz == 0.0 {
+            return finish(x, false, history);
+        }
+        let beta = rz_new / rz;
+        rz = rz_new;
+        for (p, z) in p.iter_mut().zip(&z) {
+            *p = z + beta * *p;
+        }
+    }
+    finish(x, false, history)
+}
+
+// MINRES for symmetric, possibly indefinite A, M must be symmetric positive
+// definite. With a preconditioner the history holds the M^-1 norm of the
+// residual, which is what the method minimizes.
+pub fn minres<A: LinearOperator, P: Preconditioner>(a: &A, b: &[f64], x0: Option<&[f64]>, m: &P, cfg: &SolverConfig) -> SolveResult {
+    let mut x = check_dims(a, b, x0);
+    let n = b.len();
+
+    let mut r1 = vec![0.0; n];
+    residual(a, b, &x, &mut r1);
+    let mut y = vec![0.0; n];
+    m.apply(&r1, &mut y);
+    let beta1 = dot(&r1, &y);
+    if beta1 < 0.0 {
+        // M isn't positive definite
+        return finish(x, false, vec![norm(&r1)]);
+    }
+    let beta1 = sqrt(beta1);
+
+    // same norm as the rest of the history
+    let mut mb = vec![0.0; n];
+    m.apply(b, &mut mb);
+    let target = cfg.tol * sqrt(dot(b, &mb).max(0.0));
+
+    let mut history = vec![beta1];
+    if beta1 <= target {
+        return finish(x, true, history);
+    }
+
+    let mut r2 = r1.clone();
+    let mut v = vec![0.0; n];
+    let mut w = vec![0.0; n];
+    let mut w1 = vec![0.0; n];
+    let mut w2 = vec![0.0; n];
+
+    let (mut oldb, mut beta) = (0.0, beta1);
+    let (mut dbar, mut epsln, mut phibar) = (0.0, 0.0, beta1);
+    let (mut cs, mut sn) = (-1.0, 0.0);
+
+    for itn in 0..cfg.max_iter {
+        // Lanczos step
+        let s = 1.0 / beta;
+        for (v, y) in v.iter_mut().zip(&y) {
+            *v = s * y;
+        }
+        a.apply(&v, &mut y);
+        if itn > 0 {
+            axpy(-beta / oldb, &r1, &mut y);
+        }
+        let alfa = dot(&v, &y);
+        axpy(-alfa / beta, &r2, &mut y);
+        core::mem::swap(&mut r1, &mut r2);
+        r2.copy_from_slice(&y);
+        m.apply(&r2, &mut y);
+        oldb = beta;
+        let bb = dot(&r2, &y);
+        if bb < 0.0 {
+            return finish(x, false, history);
+        }
+        beta = sqrt(bb);
+
+        // plane rotation to keep the tridiagonal system triangular
+        let oldeps = epsln;
+        let delta = cs * dbar + sn * alfa;
+        let gbar = sn * dbar - cs * alfa;
+        epsln = sn * beta;
+        dbar = -cs * beta;
+        let gamma = hypot(gbar, beta).max(f64::EPSILON);
+        cs = gbar / gamma;
+        sn = beta / gamma;
+        let phi = cs * phibar;
+        phibar *= sn;
+
+        // update the search direction and the solution
+        core::mem::swap(&mut w1, &mut w2);
+        core::mem::swap(&mut w2, &mut w);
+        for i in 0..n {
+            w[i] = (v[i] - oldeps * w1[i] - delta * w2[i]) / gamma;
+        }
+        axpy(phi, &w, &mut x);
+
+        history.push(phibar);
+        if phibar <= target {
+            return finish(x, true, history);
+        }
+        if beta == 0.0 {
+            // the Krylov space is invariant, x is as good as it gets
+            return finish(x, phibar <= target, history);
+        }
+    }
+    finish(x, false, history)
+}
+
+// restarted GMRES with right preconditioning, works for any non-singular A
+pub fn gmres<A: LinearOperator, P: Preconditioner>(a: &A, b: &[f64], x0: Option<&[f64]>, m: &P, cfg: &SolverConfig) -> SolveResult {
+    let mut x = check_dims(a, b, x0);
+    let n = b.len();
+    let restart = cfg.restart.max(1);
+    let target = cfg.tol * norm(b);
+
+    let mut r = vec![0.0; n];
+    residual(a, b, &x, &mut r);
+    let mut history = vec![norm(&r)];
+    if history[0] <= target {
+        return finish(x, true, history);
+    }
+
+    let mut z = vec![0.0; n];
+    let mut w = vec![0.0; n];
+    let mut iterations = 0;
+    while iterations < cfg.max_iter {
+        let beta = norm(&r);
+        let mut basis: Vec<Vec<f64>> = vec![r.iter().map(|e| e / beta).collect()];
+        // Hessenberg matrix column by column, already rotated to upper triangular
+        let mut h: Vec<Vec<f64>> = Vec::new();
+        let mut rotations: Vec<(f64, f64)> = Vec::new();
+        let mut g = vec![beta];
+
+        let mut breakdown = false;
+        for j in 0..restart {
+            if iterations == cfg.max_iter {
+                break;
+            }
+            iterations += 1;
+
+            m.apply(&basis[j], &mut z);
+            a.apply(&z, &mut w);
+            // modified Gram-Schmidt
+            let mut col = vec![0.0; j + 2];
+            for (i, q) in basis.iter().enumerate() {
+                col[i] = dot(&w, q);
+                axpy(-col[i], q, &mut w);
+            }
+            col[j + 1] = norm(&w);
+
+            for (i, (c, s)) in rotations.iter().enumerate() {
+                let t = c * col[i] + s * col[i + 1];
+                col[i + 1] = -s * col[i] + c * col[i + 1];
+                col[i] = t;
+            }
+            let d = hypot(col[j], col[j + 1]);
+            let (c, s) = if d == 0.0 { (1.0, 0.0) } else { (col[j] / d, col[j + 1] / d) };
+            let next_norm = col[j + 1];
+            col[j] = d;
+            col[j + 1] = 0.0;
+            rotations.push((c, s));
+            g.push(-s * g[j]);
+            g[j] *= c;
+
+            history.push(g[j + 1].abs());
+            h.push(col);
+            if g[j + 1].abs() <= target || next_norm == 0.0 {
+                breakdown = next_norm == 0.0;
+                break;
+            }
+            basis.push(w.iter().map(|e| e / next_norm).collect());
+        }
+
+        // back substitution for the coefficients, then x += M^-1 (V y)
+        let k = h.len();
+        let mut coeffs = vec![0.0; k];
+        for i in (0..k).rev() {
+            let mut s = g[i];
+            for l in i + 1..k {
+                s -= h[l][i] * coeffs[l];
+            }
+            coeffs[i] = if h[i][i] == 0.0 { 0.0 } else { s / h[i][i] };
+        }
+        let mut update = vec![0.0; n];
+        for (c, q) in coeffs.iter().zip(&basis) {
+            axpy(*c, q, &mut update);
+        }
+        m.apply(&update, &mut z);
+        axpy(1.0, &z, &mut x);
+
+        // the true residual also guards against drift in the recurrence
+        residual(a, b, &x, &mut r);
+        let rnorm = norm(&r);
+        *history.last_mut().unwrap() = rnorm;
+        if rnorm <= target {
+            return finish(x, true, history);
+        }
+        if breakdown {
+            return finish(x, false, history);
+        }
+    }
+    finish(x, false, history)
+}
+
+// BiCGSTAB with right preconditioning, for general non-symmetric A
+pub fn bicgstab<A: LinearOperator, P: Preconditioner>(a: &A, b: &[f64], x0: Option<&[f64]>, m: &P, cfg: &SolverConfig) -> SolveResult {
+    let mut x = check_dims(a, b, x0);
+    let n = b.len();
+    let target = cfg.tol * norm(b);
+
+    let mut r = vec![0.0; n];
+    residual(a, b, &x, &mut r);
+    let mut history = vec![norm(&r)];
+    if history[0] <= target {
+        return finish(x, true, history);
+    }
+
+    let r_hat = r.clone();
+    let (mut rho, mut alpha, mut omega) = (1.0, 1.0, 1.0);
+    let mut v = vec![0.0; n];
+    let mut p = vec![0.0; n];
+    let mut p_hat = vec![0.0; n];
+    let mut s_hat = vec![0.0; n];
+    let mut t = vec![0.0; n];
+
+    for _ in 0..cfg.max_iter {
+        let rho_new = dot(&r_hat, &r);
+        if rho_new == 0.0 || omega == 0.0 {
+            return finish(x, false, history);
+        }
+        let beta = (rho_new / rho) * (alpha / omega);
+        rho = rho_new;
+        for i in 0..n {
+            p[i] = r[i] + beta * (p[i] - omega * v[i]);
+        }
+        m.apply(&p, &mut p_hat);
+        a.apply(&p_hat, &mut v);
+        let rv = dot(&r_hat, &v);
+        if rv == 0.0 {
+            return finish(x, false, history);
+        }
+        alpha = rho / rv;
+
+        // r becomes s = r - alpha v
+        axpy(-alpha, &v, &mut r);
+        axpy(alpha, &p_hat, &mut x);
+        let snorm = norm(&r);
+        if snorm <= target {
+            history.push(snorm);
+            return finish(x, true, history);
+        }
+
+        m.apply(&r, &mut s_hat);
+        a.apply(&s_hat, &mut t);
+        let tt = dot(&t, &t);
+        omega = if tt == 0.0 { 0.0 } else { dot(&t, &r) / tt };
+        axpy(omega, &s_hat, &mut x);
+        axpy(-omega, &t, &mut r);
+
+        let rnorm = norm(&r);
+        history.push(rnorm);
+        if rnorm <= target {
+            return finish(x, true, history);
+        }
+    }
+    finish(x, false, history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrixxd::MatrixXd;
+    use crate::sparse::{CooMatrix, CsrMatrix};
+
+    // 2d Poisson problem on a k x k grid, symmetric positive definite
+    fn poisson(k: usize) -> CsrMatrix {
+        let n = k * k;
+        let mut coo = CooMatrix::new(n, n);
+        for i in 0..k {
+            for j in 0..k {
+                let p = i * k + j;
+                coo.push(p, p, 4.0);
+                if i > 0 {
+                    coo.push(p, p - k, -1.0);
+                }
+                if i + 1 < k {
+                    coo.push(p, p + k, -1.0);
+                }
+                if j > 0 {
+                    coo.push(p, p - 1, -1.0);
+                }
+                if j + 1 < k {
+                    coo.push(p, p + 1, -1.0);
+                }
+            }
+        }
+        coo.to_csr()
+    }
+
+    // convection-diffusion, non-symmetric
+    fn convection(n: usize) -> CsrMatrix {
+        let mut coo = CooMatrix::new(n, n);
+        for i in 0..n {
+            coo.push(i, i, 3.0);
+            if i > 0 {
+                coo.push(i, i - 1, -1.6);
+            }
+            if i + 1 < n {
+                coo.push(i, i + 1, -0.4);
+            }
+        }
+        coo.to_csr()
+    }
+
+    fn rhs(n: usize) -> Vec<f64> {
+        (0..n).map(|i| 1.0 + (i % 7) as f64 * 0.25).collect()
+    }
+
+    fn check<A: LinearOperator>(a: &A, b: &[f64], res: &SolveResult, tol: f64) {
+        assert!(res.converged, "didn't converge: {:?}", res.history.last());
+        assert_eq!(res.history.len(), res.iterations + 1);
+        let mut r = vec![0.0; b.len()];
+        residual(a, b, &res.x, &mut r);
+        assert!(norm(&r) <= tol * norm(b), "residual {}", norm(&r));
+    }
+
+    #[test]
+    fn cg_with_preconditioners() {
+        let a = poisson(12);
+        let b = rhs(a.get_rows());
+        let cfg = SolverConfig::new(1e-10, 500);
+
+        let plain = cg(&a, &b, None, &Identity, &cfg);
+        check(&a, &b, &plain, 1e-9);
+        let jacobi = cg(&a, &b, None, &Jacobi::from_csr(&a).unwrap(), &cfg);
+        check(&a, &b, &jacobi, 1e-9);
+        let ssor = cg(&a, &b, None, &Ssor::new(&a, 1.2).unwrap(), &cfg);
+        check(&a, &b, &ssor, 1e-9);
+        let ic = cg(&a, &b, None, &IncompleteCholesky::new(&a).unwrap(), &cfg);
+        check(&a, &b, &ic, 1e-9);
+        assert!(ic.iterations < plain.iterations);
+        assert!(ssor.iterations < plain.iterations);
+    }
+
+    #[test]
+    fn minres_on_indefinite_system() {
+        // symmetric but with negative eigenvalues, CG isn't guaranteed to work here
+        let d = MatrixXd::from_fn(30, 30, |i, j| {
+            if i == j {
+                if i % 3 == 0 { -2.0 - i as f64 * 0.1 } else { 3.0 + i as f64 * 0.1 }
+            } else if i.abs_diff(j) == 1 {
+                0.5
+            } else {
+                0.0
+            }
+        });
+        let b = rhs(30);
+        let res = minres(&d, &b, None, &Identity, &SolverConfig::new(1e-10, 200));
+        check(&d, &b, &res, 1e-8);
+
+        let a = poisson(8);
+        let b = rhs(64);
+        let res = minres(&a, &b, None, &Jacobi::from_csr(&a).unwrap(), &SolverConfig::new(1e-10, 500));
+        check(&a, &b, &res, 1e-8);
+    }
+
+    #[test]
+    fn gmres_restarted_and_preconditioned() {
+        let a = convection(100);
+        let b = rhs(100);
+        let full = gmres(&a, &b, None, &Identity, &SolverConfig::new(1e-10, 500).with_restart(100));
+        check(&a, &b, &full, 1e-9);
+        let restarted = gmres(&a, &b, None, &Identity, &SolverConfig::new(1e-10, 500).with_restart(5));
+        check(&a, &b, &restarted, 1e-9);
+        let ilu = gmres(&a, &b, None, &Ilu0::new(&a).unwrap(), &SolverConfig::new(1e-10, 500).with_restart(5));
+        check(&a, &b, &ilu, 1e-9);
+        // ILU(0) of a tridiagonal matrix is exact
+        assert!(ilu.iterations <= 2);
+    }
+
+    #[test]
+    fn bicgstab_non_symmetric() {
+        let a = convection(200);
+        let b = rhs(200);
+        let res = bicgstab(&a, &b, None, &Identity, &SolverConfig::new(1e-10, 500));
+        check(&a, &b, &res, 1e-9);
+        let res = bicgstab(&a, &b, None, &Jacobi::from_csr(&a).unwrap(), &SolverConfig::new(1e-10, 500));
+        check(&a, &b, &res, 1e-9);
+    }
+
+    #[test]
+    fn closure_operator_and_initial_guess() {
+        let n = 50;
+        let lap = FnOperator::new(n, |x: &[f64], y: &mut [f64]| {
+            for i in 0..x.len() {
+                let left = if i > 0 { x[i - 1] } else { 0.0 };
+                let right = if i + 1 < x.len() { x[i + 1] } else { 0.0 };
+                y[i] = 2.0 * x[i] - left - right;
+            }
+        });
+        let b = rhs(n);
+        let cfg = SolverConfig::default();
+        let res = cg(&lap, &b, None, &Identity, &cfg);
+        check(&lap, &b, &res, 1e-9);
+
+        // starting from the solution needs no iterations
+        let again = cg(&lap, &b, Some(&res.x), &Identity, &SolverConfig::new(1e-8, 10));
+        assert!(again.converged);
+        assert_eq!(again.iterations, 0);
+    }
+
+    #[test]
+    fn reports_non_convergence() {
+        let a = poisson(10);
+        let b = rhs(100);
+        let res = cg(&a, &b, None, &Identity, &SolverConfig::new(1e-12, 3));
+        assert!(!res.converged);
+        assert_eq!(res.iterations, 3);
+        assert_eq!(res.history.len(), 4);
+    }
+}
@@ -1,8 +1,34 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("linearly needs either the `std` or the `libm` feature");
+
+pub mod math;
+pub mod simd;
+pub mod scalar;
+
 #[macro_use]
 pub mod matrix2d;
 
 pub mod matrix3d;
 pub mod matrix4d;
+pub mod colmajor;
+pub mod matfun;
+pub mod complex;
+pub mod autodiff;
+pub mod interval;
+pub mod rational;
+pub mod doubledouble;
+pub mod fixed;
+pub mod half;
+pub mod geometry;
+pub mod frustum;
+pub mod gjk;
+pub mod predicates;
+pub mod sdf;
 
 #[macro_use]
 pub mod vector2d;
@@ -11,3 +37,42 @@ pub mod vector3d;
 
 
 pub mod vector4d;
+
+#[cfg(feature = "alloc")]
+pub mod soa;
+
+#[cfg(feature = "alloc")]
+pub mod matrixxd;
+
+#[cfg(feature = "alloc")]
+pub mod sparse;
+
+#[cfg(feature = "alloc")]
+pub mod linop;
+
+#[cfg(feature = "alloc")]
+pub mod precond;
+
+#[cfg(feature = "alloc")]
+pub mod krylov;
+
+#[cfg(feature = "alloc")]
+pub mod direct;
+
+#[cfg(feature = "alloc")]
+pub mod bvh;
+
+#[cfg(feature = "alloc")]
+pub mod kdtree;
+
+#[cfg(feature = "alloc")]
+pub mod polygon;
+
+#[cfg(feature = "alloc")]
+pub mod delaunay;
+
+#[cfg(feature = "alloc")]
+pub mod quickhull;
+
+#[cfg(feature = "rayon")]
+pub mod parallel;
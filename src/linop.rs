@@ -0,0 +1,127 @@
+// Anything that can compute y = A x, which is all the iterative solvers need.
+
+use super::matrixxd::*;
+use super::sparse::*;
+
+pub trait LinearOperator {
+    fn nrows(&self) -> usize;
+    fn ncols(&self) -> usize;
+
+    // y = A x, y has nrows entries and x has ncols
+    fn apply(&self, x: &[f64], y: &mut [f64]);
+}
+
+impl<T: LinearOperator + ?Sized> LinearOperator for &T {
+    fn nrows(&self) -> usize {
+        (**self).nrows()
+    }
+
+    fn ncols(&self) -> usize {
+        (**self).ncols()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        (**self).apply(x, y)
+    }
+}
+
+impl LinearOperator for MatrixXd {
+    fn nrows(&self) -> usize {
+        self.get_rows()
+    }
+
+    fn ncols(&self) -> usize {
+        self.get_cols()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        self.mul_vec_into(x, y)
+    }
+}
+
+impl LinearOperator for CsrMatrix {
+    fn nrows(&self) -> usize {
+        self.get_rows()
+    }
+
+    fn ncols(&self) -> usize {
+        self.get_cols()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        self.mul_vec_into(x, y)
+    }
+}
+
+impl LinearOperator for CscMatrix {
+    fn nrows(&self) -> usize {
+        self.get_rows()
+    }
+
+    fn ncols(&self) -> usize {
+        self.get_cols()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        self.mul_vec_into(x, y)
+    }
+}
+
+// wraps a closure computing y = A x for a square n x n operator
+pub struct FnOperator<F> {
+    n: usize,
+    f: F,
+}
+
+impl<F: Fn(&[f64], &mut [f64])> FnOperator<F> {
+    pub fn new(n: usize, f: F) -> Self {
+        Self { n, f }
+    }
+}
+
+impl<F: Fn(&[f64], &mut [f64])> LinearOperator for FnOperator<F> {
+    fn nrows(&self) -> usize {
+        self.n
+    }
+
+    fn ncols(&self) -> usize {
+        self.n
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        (self.f)(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn apply_all<A: LinearOperator>(a: &A, x: &[f64]) -> alloc::vec::Vec<f64> {
+        let mut y = vec![0.0; a.nrows()];
+        a.apply(x, &mut y);
+        y
+    }
+
+    #[test]
+    fn dense_sparse_and_closures_agree() {
+        let d = MatrixXd::new(2, 3, vec![1.0, 0.0, 2.0, 0.0, 3.0, -1.0]);
+        let x = [1.0, 2.0, 3.0];
+        let expected = vec![7.0, 3.0];
+        assert_eq!(apply_all(&d, &x), expected);
+        assert_eq!(apply_all(&CsrMatrix::from_dense(&d), &x), expected);
+        assert_eq!(apply_all(&&CscMatrix::from_dense(&d), &x), expected);
+
+        // 1d laplacian without storing it
+        let lap = FnOperator::new(3, |x: &[f64], y: &mut [f64]| {
+            for i in 0..x.len() {
+                let left = if i > 0 { x[i - 1] } else { 0.0 };
+                let right = if i + 1 < x.len() { x[i + 1] } else { 0.0 };
+                y[i] = 2.0 * x[i] - left - right;
+            }
+        });
+        assert_eq!(lap.nrows(), 3);
+        assert_eq!(apply_all(&lap, &x), vec![0.0, 0.0, 4.0]);
+    }
+}
@@ -0,0 +1,449 @@
+// Matrix functions for the fixed size matrices: exponential, principal
+// logarithm, principal square root and powers.
+//
+// The algorithms run on a small square matrix type generic over the size, the
+// public methods on Matrix2d, Matrix3d and Matrix4d convert to it and back.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatrixFunctionError {
+    // the matrix has eigenvalues on the closed negative real axis, so it has
+    // no principal square root with real entries
+    NoRealSquareRoot,
+    // same for the logarithm
+    NoRealLogarithm,
+    // the matrix has NaN or infinite entries, or its exponential overflows
+    NotFinite,
+}
+
+impl core::fmt::Display for MatrixFunctionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MatrixFunctionError::NoRealSquareRoot => write!(f, "matrix has no real principal square root"),
+            MatrixFunctionError::NoRealLogarithm => write!(f, "matrix has no real principal logarithm"),
+            MatrixFunctionError::NotFinite => write!(f, "matrix function of non-finite entries or overflowing result"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MatrixFunctionError {}
+
+#[derive(Debug, Clone, Copy)]
+struct Square<const N: usize>([[f64; N]; N]);
+
+impl<const N: usize> Square<N> {
+    fn zeros() -> Self {
+        Self([[0.0; N]; N])
+    }
+
+    fn identity() -> Self {
+        let mut m = Self::zeros();
+        for i in 0..N {
+            m.0[i][i] = 1.0;
+        }
+        m
+    }
+
+    fn from_row_major(v: &[f64]) -> Self {
+        let mut m = Self::zeros();
+        for i in 0..N {
+            m.0[i].copy_from_slice(&v[i * N..(i + 1) * N]);
+        }
+        m
+    }
+
+    fn write_row_major(&self, v: &mut [f64]) {
+        for i in 0..N {
+            v[i * N..(i + 1) * N].copy_from_slice(&self.0[i]);
+        }
+    }
+
+    fn mul(&self, o: &Self) -> Self {
+        let mut m = Self::zeros();
+        for i in 0..N {
+            for k in 0..N {
+                let a = self.0[i][k];
+                for j in 0..N {
+                    m.0[i][j] += a * o.0[k][j];
+                }
+            }
+        }
+        m
+    }
+
+    // self * a + o * b
+    fn lin(&self, a: f64, o: &Self, b: f64) -> Self {
+        let mut m = Self::zeros();
+        for i in 0..N {
+            for j in 0..N {
+                m.0[i][j] = self.0[i][j] * a + o.0[i][j] * b;
+            }
+        }
+        m
+    }
+
+    fn add(&self, o: &Self) -> Self {
+        self.lin(1.0, o, 1.0)
+    }
+
+    fn sub(&self, o: &Self) -> Self {
+        self.lin(1.0, o, -1.0)
+    }
+
+    fn scale(&self, s: f64) -> Self {
+        self.lin(s, self, 0.0)
+    }
+
+    // max column sum
+    fn norm1(&self) -> f64 {
+        (0..N).map(|j| (0..N).map(|i| self.0[i][j].abs()).sum::<f64>()).fold(0.0, f64::max)
+    }
+
+    fn is_finite(&self) -> bool {
+        self.0.iter().flatten().all(|x| x.is_finite())
+    }
+
+    // A^-1 B and det(A) by Gauss-Jordan elimination with partial pivoting,
+    // None when A is singular
+    fn solve(&self, b: &Self) -> Option<(Self, f64)> {
+        let mut a = *self;
+        let mut x = *b;
+        let mut det = 1.0;
+        for c in 0..N {
+            let p = (c..N).max_by(|i, j| a.0[*i][c].abs().total_cmp(&a.0[*j][c].abs()))?;
+            if a.0[p][c] == 0.0 || !a.0[p][c].is_finite() {
+                return None;
+            }
+            if p != c {
+                a.0.swap(p, c);
+                x.0.swap(p, c);
+                det = -det;
+            }
+            let pivot = a.0[c][c];
+            det *= pivot;
+            for j in 0..N {
+                a.0[c][j] /= pivot;
+                x.0[c][j] /= pivot;
+            }
+            for r in 0..N {
+                let f = a.0[r][c];
+                if r != c && f != 0.0 {
+                    for j in 0..N {
+                        a.0[r][j] -= f * a.0[c][j];
+                        x.0[r][j] -= f * x.0[c][j];
+                    }
+                }
+            }
+        }
+        Some((x, det))
+    }
+
+    fn inverse(&self) -> Option<(Self, f64)> {
+        self.solve(&Self::identity())
+    }
+}
+
+// scaling and squaring with the degree 13 Pade approximant, Higham 2005
+fn expm<const N: usize>(a: &Square<N>) -> Option<Square<N>> {
+    const B: [f64; 14] = [
+        64764752532480000.0,
+        32382376266240000.0,
+        7771770303897600.0,
+        1187353796428800.0,
+        129060195264000.0,
+        10559470521600.0,
+        670442572800.0,
+        33522128640.0,
+        1323241920.0,
+        40840800.0,
+        960960.0,
+        16380.0,
+        182.0,
+        1.0,
+    ];
+    const THETA_13: f64 = 5.371920351148152;
+
+    // finite entries can still have an infinite norm, which no scaling brings
+    // under the bound
+    let norm = a.norm1();
+    if !a.is_finite() || !norm.is_finite() {
+        return None;
+    }
+    let mut s = 0;
+    let mut factor = 1.0;
+    while norm * factor > THETA_13 {
+        factor /= 2.0;
+        s += 1;
+    }
+    let a = a.scale(factor);
+
+    let id = Square::<N>::identity();
+    let a2 = a.mul(&a);
+    let a4 = a2.mul(&a2);
+    let a6 = a4.mul(&a2);
+    let u = a6.lin(B[13], &a4, B[11]).add(&a2.scale(B[9]));
+    let u = a6.mul(&u).add(&a6.lin(B[7], &a4, B[5])).add(&a2.lin(B[3], &id, B[1]));
+    let u = a.mul(&u);
+    let v = a6.lin(B[12], &a4, B[10]).add(&a2.scale(B[8]));
+    let v = a6.mul(&v).add(&a6.lin(B[6], &a4, B[4])).add(&a2.lin(B[2], &id, B[0]));
+
+    // the denominator of the approximant is always invertible for these
+    // norms, the squarings can still overflow
+    let (mut r, _) = v.sub(&u).solve(&v.add(&u))?;
+    for _ in 0..s {
+        r = r.mul(&r);
+    }
+    r.is_finite().then_some(r)
+}
+
+// product form of the Denman-Beavers iteration with determinant scaling. It
+// converges to the principal square root when there is one, and fails to
+// converge otherwise.
+fn sqrtm<const N: usize>(a: &Square<N>) -> Option<Square<N>> {
+    let id = Square::<N>::identity();
+    let anorm = a.norm1();
+    if anorm == 0.0 {
+        return Some(*a);
+    }
+    let mut x = *a;
+    let mut m = *a;
+    for _ in 0..100 {
+        let (m_inv, det) = m.inverse()?;
+        let mu = crate::math::powf(det.abs(), -0.5 / N as f64);
+        let mu2 = mu * mu;
+        x = x.mul(&id.add(&m_inv.scale(1.0 / mu2))).scale(0.5 * mu);
+        m = id.add(&m.lin(mu2, &m_inv, 1.0 / mu2).scale(0.5)).scale(0.5);
+        if !x.is_finite() {
+            return None;
+        }
+        if m.sub(&id).norm1() <= 1e-15 * N as f64 {
+            break;
+        }
+    }
+    // negative eigenvalues make the iteration wander instead of converging
+    let err = x.mul(&x).sub(a).norm1();
+    if err <= 1e-10 * anorm {
+        Some(x)
+    } else {
+        None
+    }
+}
+
+// inverse scaling and squaring: take square roots until A is close to I, then
+// log(A) = 2 atanh((A - I)(A + I)^-1) summed as a series
+fn logm<const N: usize>(a: &Square<N>) -> Option<Square<N>> {
+    let id = Square::<N>::identity();
+    let mut a = *a;
+    let mut k = 0;
+    while a.sub(&id).norm1() > 0.25 {
+        a = sqrtm(&a)?;
+        k += 1;
+        if k > 60 {
+            return None;
+        }
+    }
+    // A + I is invertible since A is close to I
+    let (inv, _) = a.add(&id).inverse()?;
+    let z = a.sub(&id).mul(&inv);
+    let z2 = z.mul(&z);
+    let mut term = z;
+    let mut sum = z;
+    for j in 1..100 {
+        term = term.mul(&z2);
+        let t = term.scale(1.0 / (2 * j + 1) as f64);
+        sum = sum.add(&t);
+        if t.norm1() <= f64::EPSILON * sum.norm1() {
+            break;
+        }
+    }
+    Some(sum.scale(2.0 * (1u64 << k) as f64))
+}
+
+fn powi<const N: usize>(a: &Square<N>, n: i32) -> Option<Square<N>> {
+    let mut base = if n < 0 { a.inverse()?.0 } else { *a };
+    let mut e = n.unsigned_abs();
+    let mut r = Square::<N>::identity();
+    while e > 0 {
+        if e & 1 == 1 {
+            r = r.mul(&base);
+        }
+        base = base.mul(&base);
+        e >>= 1;
+    }
+    Some(r)
+}
+
+macro_rules! impl_matrix_functions {
+    ($t:ident, $n:expr) => {
+        impl $t {
+            fn as_square(&self) -> Square<$n> {
+                Square::from_row_major(&self.to_row_major_array())
+            }
+
+            fn from_square(m: &Square<$n>) -> Self {
+                let mut v = [0.0; $n * $n];
+                m.write_row_major(&mut v);
+                $t::from_row_major(v)
+            }
+
+            // matrix exponential e^A, an error when A has non-finite entries
+            // or e^A doesn't fit in f64
+            pub fn exp(&self) -> Result<Self, MatrixFunctionError> {
+                expm(&self.as_square())
+                    .map(|m| Self::from_square(&m))
+                    .ok_or(MatrixFunctionError::NotFinite)
+            }
+
+            // principal logarithm, the one whose eigenvalues have imaginary
+            // parts in (-pi, pi). It doesn't exist when A has eigenvalues on
+            // the closed negative real axis, e.g. rotations by exactly pi.
+            pub fn log(&self) -> Result<Self, MatrixFunctionError> {
+                let a = self.as_square();
+                if !a.is_finite() {
+                    return Err(MatrixFunctionError::NotFinite);
+                }
+                logm(&a)
+                    .map(|m| Self::from_square(&m))
+                    .ok_or(MatrixFunctionError::NoRealLogarithm)
+            }
+
+            // principal square root, the one whose eigenvalues have positive
+            // real parts. Singular matrices other than zero are rejected.
+            pub fn sqrt(&self) -> Result<Self, MatrixFunctionError> {
+                let a = self.as_square();
+                if !a.is_finite() {
+                    return Err(MatrixFunctionError::NotFinite);
+                }
+                sqrtm(&a)
+                    .map(|m| Self::from_square(&m))
+                    .ok_or(MatrixFunctionError::NoRealSquareRoot)
+            }
+
+            // A^n by repeated squaring, None when n < 0 and A is singular
+            pub fn pow(&self, n: i32) -> Option<Self> {
+                powi(&self.as_square(), n).map(|m| Self::from_square(&m))
+            }
+
+            // A^p = exp(p log A)
+            pub fn powf(&self, p: f64) -> Result<Self, MatrixFunctionError> {
+                (self.log()? * p).exp()
+            }
+        }
+    };
+}
+
+impl_matrix_functions!(Matrix2d, 2);
+impl_matrix_functions!(Matrix3d, 3);
+impl_matrix_functions!(Matrix4d, 4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    fn assert_close(a: &[f64], b: &[f64], tol: f64) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() <= tol, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn exp_of_known_matrices() {
+        assert_eq!(Matrix3d::new_from_constant(0.0).exp().unwrap().to_row_major_array(), Matrix3d::new([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]).to_row_major_array());
+
+        // generator of the rotations, with a large angle to exercise the squaring
+        let t = 7.5;
+        let r = Matrix2d::new([0.0, -t, t, 0.0]).exp().unwrap();
+        assert_close(&r.to_row_major_array(), &[math::cos(t), -math::sin(t), math::sin(t), math::cos(t)], 1e-13);
+
+        // nilpotent, the series stops after two terms
+        let n = Matrix3d::new([0.0, 1.0, 2.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0]).exp().unwrap();
+        assert_close(&n.to_row_major_array(), &[1.0, 1.0, 3.5, 0.0, 1.0, 3.0, 0.0, 0.0, 1.0], 1e-14);
+
+        let d = Matrix4d::new([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, -2.0, 0.0, 0.0,
+            0.0, 0.0, 10.0, 0.0,
+            0.0, 0.0, 0.0, 0.5,
+        ])
+        .exp()
+        .unwrap();
+        for (i, x) in [1.0, -2.0, 10.0, 0.5].iter().enumerate() {
+            assert!((d.get(i, i) - math::exp(*x)).abs() <= 1e-13 * math::exp(*x));
+        }
+    }
+
+    #[test]
+    fn log_inverts_exp() {
+        // rotation about a skewed axis by 2 radians
+        let (x, y, z) = (0.3 * 2.0, -0.4 * 2.0, math::sqrt(0.75) * 2.0);
+        let w = Matrix3d::new([0.0, -z, y, z, 0.0, -x, -y, x, 0.0]);
+        let l = w.exp().unwrap().log().unwrap();
+        assert_close(&l.to_row_major_array(), &w.to_row_major_array(), 1e-12);
+
+        let a = Matrix4d::new([
+            0.1, 0.5, -0.3, 0.0,
+            0.2, -0.4, 0.1, 0.7,
+            0.0, 0.3, 0.2, -0.1,
+            0.6, 0.0, 0.1, 0.3,
+        ]);
+        assert_close(&a.exp().unwrap().log().unwrap().to_row_major_array(), &a.to_row_major_array(), 1e-12);
+    }
+
+    #[test]
+    fn sqrt_and_powers() {
+        let a = Matrix3d::new([4.0, 1.0, 0.0, 1.0, 3.0, 1.0, 0.0, 1.0, 2.0]);
+        let s = a.sqrt().unwrap();
+        assert_close(&(s * s).to_row_major_array(), &a.to_row_major_array(), 1e-12);
+        assert_close(&a.powf(0.5).unwrap().to_row_major_array(), &s.to_row_major_array(), 1e-12);
+
+        let p = a * a * a * a * a;
+        assert_close(&a.pow(5).unwrap().to_row_major_array(), &p.to_row_major_array(), 1e-9);
+        assert_close(&(a.pow(-2).unwrap() * a * a).to_row_major_array(), &Matrix3d::new([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]).to_row_major_array(), 1e-13);
+        assert_eq!(a.pow(0).unwrap().to_row_major_array(), [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert!(Matrix2d::new([1.0, 2.0, 2.0, 4.0]).pow(-1).is_none());
+
+        // not symmetric, complex eigenvalues with positive real part
+        let b = Matrix2d::new([1.0, -2.0, 3.0, 1.0]);
+        let r = b.sqrt().unwrap();
+        assert_close(&(r * r).to_row_major_array(), &b.to_row_major_array(), 1e-12);
+    }
+
+    #[test]
+    fn no_real_sqrt_or_log() {
+        let neg = Matrix2d::new([-1.0, 0.0, 0.0, 2.0]);
+        assert_eq!(neg.sqrt().unwrap_err(), MatrixFunctionError::NoRealSquareRoot);
+        assert_eq!(neg.log().unwrap_err(), MatrixFunctionError::NoRealLogarithm);
+        assert_eq!(neg.powf(0.3).unwrap_err(), MatrixFunctionError::NoRealLogarithm);
+
+        // rotation by pi, eigenvalues -1 -1
+        let half_turn = Matrix3d::new([-1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert!(half_turn.log().is_err());
+
+        let singular = Matrix2d::new([1.0, 2.0, 2.0, 4.0]);
+        assert!(singular.log().is_err());
+        assert_eq!(Matrix2d::new_from_constant(0.0).sqrt().unwrap(), Matrix2d::new_from_constant(0.0));
+    }
+
+    #[test]
+    fn non_finite_input_or_overflow() {
+        for x in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let m = Matrix2d::new([x, 0.0, 0.0, 1.0]);
+            assert_eq!(m.exp().unwrap_err(), MatrixFunctionError::NotFinite);
+            assert_eq!(m.log().unwrap_err(), MatrixFunctionError::NotFinite);
+            assert_eq!(m.sqrt().unwrap_err(), MatrixFunctionError::NotFinite);
+            assert_eq!(m.powf(0.5).unwrap_err(), MatrixFunctionError::NotFinite);
+        }
+        assert_eq!(Matrix3d::new([1e308, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]).exp().unwrap_err(), MatrixFunctionError::NotFinite);
+        assert_eq!(Matrix2d::new([800.0, 0.0, 0.0, 0.0]).exp().unwrap_err(), MatrixFunctionError::NotFinite);
+        // finite entries whose norm overflows
+        assert_eq!(Matrix2d::new([1e308; 4]).exp().unwrap_err(), MatrixFunctionError::NotFinite);
+        // large but representable
+        let e = Matrix2d::new([700.0, 0.0, 0.0, 0.0]).exp().unwrap();
+        assert!((e.get(0, 0) - math::exp(700.0)).abs() <= 1e-12 * math::exp(700.0));
+    }
+}
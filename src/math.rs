@@ -0,0 +1,142 @@
+// Transcendental functions used throughout the crate.
+//
+// With the `std` feature these forward to the inherent f64 methods, without it
+// they go through libm so everything keeps working on bare metal.
+
+#[cfg(feature = "std")]
+mod imp {
+    pub fn sqrt(x: f64) -> f64 {
+        x.sqrt()
+    }
+
+    pub fn cbrt(x: f64) -> f64 {
+        x.cbrt()
+    }
+
+    pub fn exp(x: f64) -> f64 {
+        x.exp()
+    }
+
+    pub fn ln(x: f64) -> f64 {
+        x.ln()
+    }
+
+    pub fn powf(x: f64, y: f64) -> f64 {
+        x.powf(y)
+    }
+
+    pub fn sin(x: f64) -> f64 {
+        x.sin()
+    }
+
+    pub fn cos(x: f64) -> f64 {
+        x.cos()
+    }
+
+    pub fn tan(x: f64) -> f64 {
+        x.tan()
+    }
+
+    pub fn asin(x: f64) -> f64 {
+        x.asin()
+    }
+
+    pub fn acos(x: f64) -> f64 {
+        x.acos()
+    }
+
+    pub fn atan2(y: f64, x: f64) -> f64 {
+        y.atan2(x)
+    }
+
+    pub fn hypot(x: f64, y: f64) -> f64 {
+        x.hypot(y)
+    }
+
+    pub fn fma(x: f64, y: f64, z: f64) -> f64 {
+        x.mul_add(y, z)
+    }
+
+    pub fn floor(x: f64) -> f64 {
+        x.floor()
+    }
+}
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+mod imp {
+    pub fn sqrt(x: f64) -> f64 {
+        libm::sqrt(x)
+    }
+
+    pub fn cbrt(x: f64) -> f64 {
+        libm::cbrt(x)
+    }
+
+    pub fn exp(x: f64) -> f64 {
+        libm::exp(x)
+    }
+
+    pub fn ln(x: f64) -> f64 {
+        libm::log(x)
+    }
+
+    pub fn powf(x: f64, y: f64) -> f64 {
+        libm::pow(x, y)
+    }
+
+    pub fn sin(x: f64) -> f64 {
+        libm::sin(x)
+    }
+
+    pub fn cos(x: f64) -> f64 {
+        libm::cos(x)
+    }
+
+    pub fn tan(x: f64) -> f64 {
+        libm::tan(x)
+    }
+
+    pub fn asin(x: f64) -> f64 {
+        libm::asin(x)
+    }
+
+    pub fn acos(x: f64) -> f64 {
+        libm::acos(x)
+    }
+
+    pub fn atan2(y: f64, x: f64) -> f64 {
+        libm::atan2(y, x)
+    }
+
+    pub fn hypot(x: f64, y: f64) -> f64 {
+        libm::hypot(x, y)
+    }
+
+    pub fn fma(x: f64, y: f64, z: f64) -> f64 {
+        libm::fma(x, y, z)
+    }
+
+    pub fn floor(x: f64) -> f64 {
+        libm::floor(x)
+    }
+}
+
+#[cfg(any(feature = "std", feature = "libm"))]
+pub use imp::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_values() {
+        assert_eq!(sqrt(16.0), 4.0);
+        assert_eq!(exp(0.0), 1.0);
+        assert_eq!(ln(1.0), 0.0);
+        assert_eq!(sin(0.0), 0.0);
+        assert_eq!(cos(0.0), 1.0);
+        assert_eq!(hypot(3.0, 4.0), 5.0);
+        assert_eq!(fma(2.0, 3.0, 1.0), 7.0);
+        assert_eq!(floor(-1.5), -2.0);
+    }
+}
@@ -1,12 +1,12 @@
 use super::vector2d::*;
-use crate::vec2d;
+use crate::scalar::Scalar;
 
 #[derive(Debug)]
-pub struct Matrix2d {
-    v: [f64; 4],
+pub struct Matrix2d<T = f64> {
+    v: [T; 4],
 }
 
-impl Matrix2d {
+impl<T: Scalar> Matrix2d<T> {
    pub fn get_rows(&self) -> i32 {
         2
    }
@@ -15,30 +15,65 @@ impl Matrix2d {
         2
    }
 
-   pub fn new(v: [f64; 4]) -> Self {
+   pub fn new(v: [T; 4]) -> Self {
         Matrix2d {
             v
         }
    }
 
-   pub fn new_from_constant(c: f64) -> Self {
+   pub fn new_from_constant(c: T) -> Self {
         Matrix2d {
             v: [c, c, c, c]
         }
    }
+
+   // same as new, the storage of Matrix2d is row-major
+   pub fn from_row_major(v: [T; 4]) -> Self {
+        Matrix2d {
+            v
+        }
+   }
+
+   // build from column-major data (OpenGL, Vulkan, ...)
+   pub fn from_col_major(v: [T; 4]) -> Self {
+        Matrix2d {
+            v: [v[0], v[2],
+                v[1], v[3]]
+        }
+   }
+
+   pub fn to_row_major_array(&self) -> [T; 4] {
+        self.v
+   }
+
+   pub fn to_col_major_array(&self) -> [T; 4] {
+        self.transpose().v
+   }
+
+   // entry at (row, col), independent of the storage layout
+   pub fn get(&self, row: usize, col: usize) -> T {
+        self.v[row * 2 + col]
+   }
+
+   pub fn transpose(&self) -> Self {
+        Matrix2d {
+            v: [self.v[0], self.v[2],
+                self.v[1], self.v[3]]
+        }
+   }
 }
 
 // now we can also clone a matrix
-impl Copy for Matrix2d { }
-impl Clone for Matrix2d {
+impl<T: Scalar> Copy for Matrix2d<T> {}
+impl<T: Scalar> Clone for Matrix2d<T> {
     fn clone(&self) -> Self {
         *self
     }
 }
 
 // we can use the subscripts with the matrix
-impl std::ops::Index<usize> for Matrix2d {
-    type Output = f64;
+impl<T: Scalar> core::ops::Index<usize> for Matrix2d<T> {
+    type Output = T;
         
     fn index(&self, i: usize) -> &Self::Output {
         &self.v[i]
@@ -46,17 +81,17 @@ impl std::ops::Index<usize> for Matrix2d {
 }
 
 // we can modify a single entry inside of the matrix using indexmut
-impl std::ops::IndexMut<usize> for Matrix2d {
+impl<T: Scalar> core::ops::IndexMut<usize> for Matrix2d<T> {
     fn index_mut(&mut self, i: usize) -> &mut Self::Output {
         &mut self.v[i]
     }
 }
 
 // we can multiply a matrix by a scalar
-impl std::ops::Mul<f64> for Matrix2d {
-    type Output = Matrix2d;
+impl<T: Scalar> core::ops::Mul<T> for Matrix2d<T> {
+    type Output = Matrix2d<T>;
 
-    fn mul(self, l: f64) -> Self::Output {
+    fn mul(self, l: T) -> Self::Output {
         let new_v = [
             self.v[0] * l,
             self.v[1] * l,
@@ -71,10 +106,10 @@ impl std::ops::Mul<f64> for Matrix2d {
 }
 
 // dot product
-impl std::ops::Mul<Matrix2d> for Matrix2d {
-    type Output = Matrix2d;
+impl<T: Scalar> core::ops::Mul<Matrix2d<T>> for Matrix2d<T> {
+    type Output = Matrix2d<T>;
 
-    fn mul(self, o: Matrix2d) -> Self::Output {
+    fn mul(self, o: Matrix2d<T>) -> Self::Output {
         Self {
             v: [
                 self[0] * o[0] + self[1] * o[2],
@@ -87,9 +122,9 @@ impl std::ops::Mul<Matrix2d> for Matrix2d {
     }
 }
 
-impl std::ops::Mul<Vector2d> for Matrix2d {
-    type Output = Vector2d;
-    fn mul(self, v: Vector2d) -> Self::Output {
+impl<T: Scalar> core::ops::Mul<Vector2d<T>> for Matrix2d<T> {
+    type Output = Vector2d<T>;
+    fn mul(self, v: Vector2d<T>) -> Self::Output {
         Self::Output {
             x: self[0] * v.x + self[1] * v.y,
             y: self[2] * v.x + self[3] * v.y,
@@ -98,10 +133,10 @@ impl std::ops::Mul<Vector2d> for Matrix2d {
 }
 
 // addition between 2 matrices
-impl std::ops::Add<Matrix2d> for Matrix2d {
-    type Output = Matrix2d;
+impl<T: Scalar> core::ops::Add<Matrix2d<T>> for Matrix2d<T> {
+    type Output = Matrix2d<T>;
 
-    fn add(self, o: Matrix2d) -> Self::Output {
+    fn add(self, o: Matrix2d<T>) -> Self::Output {
         Matrix2d {
             v: [self.v[0] + o.v[0], self.v[1] + o.v[1],self.v[2] + o.v[2],self.v[3] + o.v[3]]
         }
@@ -109,10 +144,10 @@ impl std::ops::Add<Matrix2d> for Matrix2d {
 }
 
 // addition between a matrix and a scalar
-impl std::ops::Add<f64> for Matrix2d {
-    type Output = Matrix2d;
+impl<T: Scalar> core::ops::Add<T> for Matrix2d<T> {
+    type Output = Matrix2d<T>;
 
-    fn add(self, l: f64) -> Self::Output {
+    fn add(self, l: T) -> Self::Output {
         Matrix2d {
             v: [self.v[0] + l, self.v[1] + l, self.v[2] + l, self.v[3] + l]
         }
@@ -120,10 +155,10 @@ impl std::ops::Add<f64> for Matrix2d {
 }
 
 // just like add
-impl std::ops::Sub<Matrix2d> for Matrix2d {
-    type Output = Matrix2d;
+impl<T: Scalar> core::ops::Sub<Matrix2d<T>> for Matrix2d<T> {
+    type Output = Matrix2d<T>;
 
-    fn sub(self, o: Matrix2d) -> Self::Output {
+    fn sub(self, o: Matrix2d<T>) -> Self::Output {
         Matrix2d {
             v: [self.v[0] - o.v[0], self.v[1] - o.v[1],self.v[2] - o.v[2],self.v[3] - o.v[3]]
         }
@@ -131,10 +166,10 @@ impl std::ops::Sub<Matrix2d> for Matrix2d {
 }
 
 // just like add
-impl std::ops::Sub<f64> for Matrix2d {
-    type Output = Matrix2d;
+impl<T: Scalar> core::ops::Sub<T> for Matrix2d<T> {
+    type Output = Matrix2d<T>;
 
-    fn sub(self, l: f64) -> Self::Output {
+    fn sub(self, l: T) -> Self::Output {
         Matrix2d {
             v: [self.v[0] - l, self.v[1] - l, self.v[2] - l, self.v[3] - l]
         }
@@ -142,7 +177,7 @@ impl std::ops::Sub<f64> for Matrix2d {
 }
 
 // Equality for Matrix2d
-impl PartialEq for Matrix2d {
+impl<T: Scalar> PartialEq for Matrix2d<T> {
     fn eq(&self, other: &Self) -> bool {
         for i in 0..4 {
             if self[i] != other[i] {
@@ -153,7 +188,7 @@ impl PartialEq for Matrix2d {
     }
 }
 
-impl Eq for Matrix2d {}
+impl<T: Scalar> Eq for Matrix2d<T> {}
 
 #[macro_export]
 macro_rules! mat2d {
@@ -174,6 +209,7 @@ macro_rules! mat2d {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vec2d;
 
     #[test]
     fn matrix_init_macros() {
@@ -232,6 +268,19 @@ mod tests {
         let result = vec2d![2.0 * 2.0 + 4.0 * 5.0, 5.0 * 2.0 + 6.0 * 5.0];
         assert_eq!(result, m2 * v);
     }
+
+    #[test]
+    fn matrix_layout_conversions() {
+        let m = Matrix2d::from_row_major([1.0, 2.0,
+                                          3.0, 4.0]);
+        let m2 = Matrix2d::from_col_major([1.0, 3.0,
+                                           2.0, 4.0]);
+        assert_eq!(m, m2);
+        assert_eq!(m.get(0, 1), 2.0);
+        assert_eq!(m.to_row_major_array(), [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(m.to_col_major_array(), [1.0, 3.0, 2.0, 4.0]);
+        assert_eq!(m.transpose().transpose(), m);
+    }
     
 
 
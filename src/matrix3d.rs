@@ -1,12 +1,12 @@
 use super::vector3d::*;
-use crate::vec3d;
+use crate::scalar::Scalar;
 
 #[derive(Debug)]
-pub struct Matrix3d {
-    v: [f64; 9],
+pub struct Matrix3d<T = f64> {
+    v: [T; 9],
 }
 
-impl Matrix3d {
+impl<T: Scalar> Matrix3d<T> {
    pub fn get_rows(&self) -> i32 {
         3
    }
@@ -15,13 +15,13 @@ impl Matrix3d {
         3
    }
 
-   pub fn new(v: [f64; 9]) -> Self {
+   pub fn new(v: [T; 9]) -> Self {
         Matrix3d {
             v
         }
    }
 
-   pub fn new_from_constant(c: f64) -> Self {
+   pub fn new_from_constant(c: T) -> Self {
         Matrix3d {
             v: [c, c, c, 
                 c, c, c, 
@@ -29,19 +29,56 @@ impl Matrix3d {
             ]
         }
    }
+
+   // same as new, the storage of Matrix3d is row-major
+   pub fn from_row_major(v: [T; 9]) -> Self {
+        Matrix3d {
+            v
+        }
+   }
+
+   // build from column-major data (OpenGL, Vulkan, ...)
+   pub fn from_col_major(v: [T; 9]) -> Self {
+        Matrix3d {
+            v
+        }.transpose()
+   }
+
+   pub fn to_row_major_array(&self) -> [T; 9] {
+        self.v
+   }
+
+   pub fn to_col_major_array(&self) -> [T; 9] {
+        self.transpose().v
+   }
+
+   // entry at (row, col), independent of the storage layout
+   pub fn get(&self, row: usize, col: usize) -> T {
+        self.v[row * 3 + col]
+   }
+
+   pub fn transpose(&self) -> Self {
+        Matrix3d {
+            v: [
+                self.v[0], self.v[3], self.v[6],
+                self.v[1], self.v[4], self.v[7],
+                self.v[2], self.v[5], self.v[8],
+            ]
+        }
+   }
 }
 
 // now we can also clone a matrix
-impl Copy for Matrix3d { }
-impl Clone for Matrix3d {
+impl<T: Scalar> Copy for Matrix3d<T> {}
+impl<T: Scalar> Clone for Matrix3d<T> {
     fn clone(&self) -> Self {
         *self
     }
 }
 
 // we can use the subscripts with the matrix
-impl std::ops::Index<usize> for Matrix3d {
-    type Output = f64;
+impl<T: Scalar> core::ops::Index<usize> for Matrix3d<T> {
+    type Output = T;
         
     fn index(&self, i: usize) -> &Self::Output {
         &self.v[i]
@@ -49,17 +86,17 @@ impl std::ops::Index<usize> for Matrix3d {
 }
 
 // we can modify a single entry inside of the matrix using indexmut
-impl std::ops::IndexMut<usize> for Matrix3d {
+impl<T: Scalar> core::ops::IndexMut<usize> for Matrix3d<T> {
     fn index_mut(&mut self, i: usize) -> &mut Self::Output {
         &mut self.v[i]
     }
 }
 
 // we can multiply a matrix by a scalar
-impl std::ops::Mul<f64> for Matrix3d {
-    type Output = Matrix3d;
+impl<T: Scalar> core::ops::Mul<T> for Matrix3d<T> {
+    type Output = Matrix3d<T>;
 
-    fn mul(self, l: f64) -> Self::Output {
+    fn mul(self, l: T) -> Self::Output {
         let new_v = [
             self.v[0] * l,
             self.v[1] * l,
@@ -79,10 +116,10 @@ impl std::ops::Mul<f64> for Matrix3d {
 }
 
 // dot product
-impl std::ops::Mul<Matrix3d> for Matrix3d {
-    type Output = Matrix3d;
+impl<T: Scalar> core::ops::Mul<Matrix3d<T>> for Matrix3d<T> {
+    type Output = Matrix3d<T>;
 
-    fn mul(self, o: Matrix3d) -> Self::Output {
+    fn mul(self, o: Matrix3d<T>) -> Self::Output {
         Self {
             v: [
                 self[0] * o[0] + self[1] * o[3] + self[2] * o[6],
@@ -102,10 +139,10 @@ impl std::ops::Mul<Matrix3d> for Matrix3d {
     }
 }
 
-impl std::ops::Mul<Vector3d> for Matrix3d {
-    type Output = Vector3d;
+impl<T: Scalar> core::ops::Mul<Vector3d<T>> for Matrix3d<T> {
+    type Output = Vector3d<T>;
 
-    fn mul(self, v: Vector3d) -> Self::Output {
+    fn mul(self, v: Vector3d<T>) -> Self::Output {
         let x = self[0] * v.x + self[1] * v.y + self[2] * v.z;
         let y = self[3] * v.x + self[4] * v.y + self[5] * v.z;
         let z = self[6] * v.x + self[7] * v.y + self[8] * v.z;
@@ -115,10 +152,10 @@ impl std::ops::Mul<Vector3d> for Matrix3d {
 }
 
 // addition between 2 matrices
-impl std::ops::Add<Matrix3d> for Matrix3d {
-    type Output = Matrix3d;
+impl<T: Scalar> core::ops::Add<Matrix3d<T>> for Matrix3d<T> {
+    type Output = Matrix3d<T>;
 
-    fn add(self, o: Matrix3d) -> Self::Output {
+    fn add(self, o: Matrix3d<T>) -> Self::Output {
         Matrix3d {
             v: [
                 self.v[0] + o.v[0], self.v[1] + o.v[1], self.v[2] + o.v[2],
@@ -130,10 +167,10 @@ impl std::ops::Add<Matrix3d> for Matrix3d {
 }
 
 // addition between a matrix and a scalar
-impl std::ops::Add<f64> for Matrix3d {
-    type Output = Matrix3d;
+impl<T: Scalar> core::ops::Add<T> for Matrix3d<T> {
+    type Output = Matrix3d<T>;
 
-    fn add(self, l: f64) -> Self::Output {
+    fn add(self, l: T) -> Self::Output {
         Matrix3d {
             v: [
                 self.v[0] +l, self.v[1] +l, self.v[2] +l,
@@ -145,10 +182,10 @@ impl std::ops::Add<f64> for Matrix3d {
 }
 
 // just like add
-impl std::ops::Sub<Matrix3d> for Matrix3d {
-    type Output = Matrix3d;
+impl<T: Scalar> core::ops::Sub<Matrix3d<T>> for Matrix3d<T> {
+    type Output = Matrix3d<T>;
 
-    fn sub(self, o: Matrix3d) -> Self::Output {
+    fn sub(self, o: Matrix3d<T>) -> Self::Output {
         Matrix3d {
             v: [
                 self.v[0] - o.v[0], self.v[1] - o.v[1], self.v[2] - o.v[2],
@@ -160,10 +197,10 @@ impl std::ops::Sub<Matrix3d> for Matrix3d {
 }
 
 // just like add
-impl std::ops::Sub<f64> for Matrix3d {
-    type Output = Matrix3d;
+impl<T: Scalar> core::ops::Sub<T> for Matrix3d<T> {
+    type Output = Matrix3d<T>;
 
-    fn sub(self, l: f64) -> Self::Output {
+    fn sub(self, l: T) -> Self::Output {
         Matrix3d {
             v: [
                 self.v[0] - l, self.v[1] - l, self.v[2] - l,
@@ -175,7 +212,7 @@ impl std::ops::Sub<f64> for Matrix3d {
 }
 
 // Equality for Matrix3d
-impl PartialEq for Matrix3d {
+impl<T: Scalar> PartialEq for Matrix3d<T> {
     fn eq(&self, other: &Self) -> bool {
         for i in 0..9 {
             if self[i] != other[i] {
@@ -186,7 +223,7 @@ impl PartialEq for Matrix3d {
     }
 }
 
-impl Eq for Matrix3d { }
+impl<T: Scalar> Eq for Matrix3d<T> {}
 
 #[macro_export]
 macro_rules! mat3d {
@@ -202,6 +239,7 @@ macro_rules! mat3d {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vec3d;
 
     #[test]
     fn matrix_init_macros() {
@@ -224,5 +262,19 @@ mod tests {
         assert_eq!(result, expected_result);
     }
 
+    #[test]
+    fn matrix_layout_conversions() {
+        let m = Matrix3d::from_row_major([1.0, 2.0, 3.0,
+                                          4.0, 5.0, 6.0,
+                                          7.0, 8.0, 9.0]);
+        let m2 = Matrix3d::from_col_major([1.0, 4.0, 7.0,
+                                           2.0, 5.0, 8.0,
+                                           3.0, 6.0, 9.0]);
+        assert_eq!(m, m2);
+        assert_eq!(m.get(1, 2), 6.0);
+        assert_eq!(m.to_col_major_array(), [1.0, 4.0, 7.0, 2.0, 5.0, 8.0, 3.0, 6.0, 9.0]);
+        assert_eq!(Matrix3d::from_col_major(m.to_col_major_array()), m);
+    }
+
     // TODO: implement the remaining tests for matrix multiplication, addition, subtraction
 }
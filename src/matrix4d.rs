@@ -1,10 +1,14 @@
+use super::vector3d::*;
 use super::vector4d::*;
+use crate::scalar::Scalar;
+use crate::simd;
 
-pub struct Matrix4d {
-    v: [f64; 16],
+#[derive(Debug)]
+pub struct Matrix4d<T = f64> {
+    v: [T; 16],
 }
 
-impl Matrix4d {
+impl<T: Scalar> Matrix4d<T> {
     pub fn get_rows(&self) -> i32 {
         4
     }
@@ -13,42 +17,163 @@ impl Matrix4d {
         4
     }
 
-    pub fn new(v: [f64; 16]) -> Self {
+    pub fn new(v: [T; 16]) -> Self {
         Self { v }
     }
 
-    pub fn new_from_constant(c: f64) -> Self {
+    pub fn new_from_constant(c: T) -> Self {
         Self {
             v: [c; 16],
         }
     }
+
+    // same as new, the storage of Matrix4d is row-major
+    pub fn from_row_major(v: [T; 16]) -> Self {
+        Self { v }
+    }
+
+    // build from column-major data (OpenGL, Vulkan, ...)
+    pub fn from_col_major(v: [T; 16]) -> Self {
+        Self { v }.transpose()
+    }
+
+    pub fn to_row_major_array(&self) -> [T; 16] {
+        self.v
+    }
+
+    pub fn to_col_major_array(&self) -> [T; 16] {
+        self.transpose().v
+    }
+
+    // entry at (row, col), independent of the storage layout
+    pub fn get(&self, row: usize, col: usize) -> T {
+        self.v[row * 4 + col]
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self {
+            v: [
+                self.v[0], self.v[4], self.v[8], self.v[12],
+                self.v[1], self.v[5], self.v[9], self.v[13],
+                self.v[2], self.v[6], self.v[10], self.v[14],
+                self.v[3], self.v[7], self.v[11], self.v[15],
+            ],
+        }
+    }
 }
 
-impl Copy for Matrix4d {}
-impl Clone for Matrix4d {
+impl Matrix4d {
+    // None when the matrix is singular
+    pub fn inverse(&self) -> Option<Self> {
+        simd::mat4_inverse(&self.v).map(|v| Self { v })
+    }
+}
+
+// batch transforms, the matrix entries are loaded once for the whole slice
+// and no Vector4d gets built for the single points
+impl Matrix4d {
+    // true when the last row is [0, 0, 0, 1], so points don't need the w divide
+    pub fn is_affine(&self) -> bool {
+        self.v[12] == 0.0 && self.v[13] == 0.0 && self.v[14] == 0.0 && self.v[15] == 1.0
+    }
+
+    // transforms points (w = 1), dividing by w for projective matrices
+    pub fn transform_points(&self, src: &[Vector3d], dst: &mut [Vector3d]) {
+        assert_eq!(src.len(), dst.len(), "source and destination must have the same length");
+        let m = self.v;
+        if self.is_affine() {
+            for (p, out) in src.iter().zip(dst.iter_mut()) {
+                *out = Vector3d::new_from([
+                    m[0] * p.x + m[1] * p.y + m[2] * p.z + m[3],
+                    m[4] * p.x + m[5] * p.y + m[6] * p.z + m[7],
+                    m[8] * p.x + m[9] * p.y + m[10] * p.z + m[11],
+                ]);
+            }
+        } else {
+            for (p, out) in src.iter().zip(dst.iter_mut()) {
+                let w = m[12] * p.x + m[13] * p.y + m[14] * p.z + m[15];
+                *out = Vector3d::new_from([
+                    (m[0] * p.x + m[1] * p.y + m[2] * p.z + m[3]) / w,
+                    (m[4] * p.x + m[5] * p.y + m[6] * p.z + m[7]) / w,
+                    (m[8] * p.x + m[9] * p.y + m[10] * p.z + m[11]) / w,
+                ]);
+            }
+        }
+    }
+
+    // transforms directions (w = 0), so the translation is ignored
+    pub fn transform_vectors(&self, src: &[Vector3d], dst: &mut [Vector3d]) {
+        assert_eq!(src.len(), dst.len(), "source and destination must have the same length");
+        let m = self.v;
+        for (p, out) in src.iter().zip(dst.iter_mut()) {
+            *out = Vector3d::new_from([
+                m[0] * p.x + m[1] * p.y + m[2] * p.z,
+                m[4] * p.x + m[5] * p.y + m[6] * p.z,
+                m[8] * p.x + m[9] * p.y + m[10] * p.z,
+            ]);
+        }
+    }
+
+    pub fn transform_points_in_place(&self, points: &mut [Vector3d]) {
+        let m = self.v;
+        let affine = self.is_affine();
+        for p in points.iter_mut() {
+            let x = m[0] * p.x + m[1] * p.y + m[2] * p.z + m[3];
+            let y = m[4] * p.x + m[5] * p.y + m[6] * p.z + m[7];
+            let z = m[8] * p.x + m[9] * p.y + m[10] * p.z + m[11];
+            if affine {
+                *p = Vector3d::new_from([x, y, z]);
+            } else {
+                let w = m[12] * p.x + m[13] * p.y + m[14] * p.z + m[15];
+                *p = Vector3d::new_from([x / w, y / w, z / w]);
+            }
+        }
+    }
+
+    pub fn transform_vectors_in_place(&self, vectors: &mut [Vector3d]) {
+        let m = self.v;
+        for p in vectors.iter_mut() {
+            *p = Vector3d::new_from([
+                m[0] * p.x + m[1] * p.y + m[2] * p.z,
+                m[4] * p.x + m[5] * p.y + m[6] * p.z,
+                m[8] * p.x + m[9] * p.y + m[10] * p.z,
+            ]);
+        }
+    }
+
+    pub fn transform_vec4s(&self, src: &[Vector4d], dst: &mut [Vector4d]) {
+        assert_eq!(src.len(), dst.len(), "source and destination must have the same length");
+        for (p, out) in src.iter().zip(dst.iter_mut()) {
+            *out = Vector4d::new_from(simd::mat4_mul_vec(&self.v, &p.to_list()));
+        }
+    }
+}
+
+impl<T: Scalar> Copy for Matrix4d<T> {}
+impl<T: Scalar> Clone for Matrix4d<T> {
     fn clone(&self) -> Self {
         *self
     }
 }
 
-impl std::ops::Index<usize> for Matrix4d {
-    type Output = f64;
+impl<T: Scalar> core::ops::Index<usize> for Matrix4d<T> {
+    type Output = T;
 
     fn index(&self, i: usize) -> &Self::Output {
         &self.v[i]
     }
 }
 
-impl std::ops::IndexMut<usize> for Matrix4d {
+impl<T: Scalar> core::ops::IndexMut<usize> for Matrix4d<T> {
     fn index_mut(&mut self, i: usize) -> &mut Self::Output {
         &mut self.v[i]
     }
 }
 
-impl std::ops::Mul<f64> for Matrix4d {
-    type Output = Matrix4d;
+impl<T: Scalar> core::ops::Mul<T> for Matrix4d<T> {
+    type Output = Matrix4d<T>;
 
-    fn mul(self, l: f64) -> Self::Output {
+    fn mul(self, l: T) -> Self::Output {
         Self {
             v: [
                 self.v[0] * l, self.v[1] * l, self.v[2] * l, self.v[3] * l,
@@ -60,54 +185,30 @@ impl std::ops::Mul<f64> for Matrix4d {
     }
 }
 
-impl std::ops::Mul<Matrix4d> for Matrix4d {
-    type Output = Matrix4d;
+// for f64 the products go through the SIMD kernels
+impl<T: Scalar> core::ops::Mul<Matrix4d<T>> for Matrix4d<T> {
+    type Output = Matrix4d<T>;
 
-    fn mul(self, o: Matrix4d) -> Self::Output {
+    fn mul(self, o: Matrix4d<T>) -> Self::Output {
         Self {
-            v: [
-                self[0] * o[0] + self[1] * o[4] + self[2] * o[8] + self[3] * o[12],
-                self[0] * o[1] + self[1] * o[5] + self[2] * o[9] + self[3] * o[13],
-                self[0] * o[2] + self[1] * o[6] + self[2] * o[10] + self[3] * o[14],
-                self[0] * o[3] + self[1] * o[7] + self[2] * o[11] + self[3] * o[15],
-
-                self[4] * o[0] + self[5] * o[4] + self[6] * o[8] + self[7] * o[12],
-                self[4] * o[1] + self[5] * o[5] + self[6] * o[9] + self[7] * o[13],
-                self[4] * o[2] + self[5] * o[6] + self[6] * o[10] + self[7] * o[14],
-                self[4] * o[3] + self[5] * o[7] + self[6] * o[11] + self[7] * o[15],
-
-                self[8] * o[0] + self[9] * o[4] + self[10] * o[8] + self[11] * o[12],
-                self[8] * o[1] + self[9] * o[5] + self[10] * o[9] + self[11] * o[13],
-                self[8] * o[2] + self[9] * o[6] + self[10] * o[10] + self[11] * o[14],
-                self[8] * o[3] + self[9] * o[7] + self[10] * o[11] + self[11] * o[15],
-
-                self[12] * o[0] + self[13] * o[4] + self[14] * o[8] + self[15] * o[12],
-                self[12] * o[1] + self[13] * o[5] + self[14] * o[9] + self[15] * o[13],
-                self[12] * o[2] + self[13] * o[6] + self[14] * o[10] + self[15] * o[14],
-                self[12] * o[3] + self[13] * o[7] + self[14] * o[11] + self[15] * o[15],
-            ],
+            v: T::mat4_mul(&self.v, &o.v),
         }
     }
 }
 
-impl std::ops::Mul<Vector4d> for Matrix4d {
-    type Output = Vector4d;
-
-    fn mul(self, v: Vector4d) -> Self::Output {
-        let x = self[0] * v.x + self[1] * v.y + self[2] * v.z + self[3] * v.w;
-        let y = self[4] * v.x + self[5] * v.y + self[6] * v.z + self[7] * v.w;
-        let z = self[8] * v.x + self[9] * v.y + self[10] * v.z + self[11] * v.w;
-        let w = self[12] * v.x + self[13] * v.y + self[14] * v.z + self[15] * v.w;
+impl<T: Scalar> core::ops::Mul<Vector4d<T>> for Matrix4d<T> {
+    type Output = Vector4d<T>;
 
-        Vector4d::new_from([x, y, z, w])
+    fn mul(self, v: Vector4d<T>) -> Self::Output {
+        Vector4d::new_from(T::mat4_mul_vec(&self.v, &v.to_list()))
     }
 }
 
 
-impl std::ops::Add<Matrix4d> for Matrix4d {
-    type Output = Matrix4d;
+impl<T: Scalar> core::ops::Add<Matrix4d<T>> for Matrix4d<T> {
+    type Output = Matrix4d<T>;
 
-    fn add(self, o: Matrix4d) -> Self::Output {
+    fn add(self, o: Matrix4d<T>) -> Self::Output {
         Self {
             v: [
                 self.v[0] + o.v[0], self.v[1] + o.v[1], self.v[2] + o.v[2], self.v[3] + o.v[3],
@@ -119,10 +220,10 @@ impl std::ops::Add<Matrix4d> for Matrix4d {
     }
 }
 
-impl std::ops::Add<f64> for Matrix4d {
-    type Output = Matrix4d;
+impl<T: Scalar> core::ops::Add<T> for Matrix4d<T> {
+    type Output = Matrix4d<T>;
 
-    fn add(self, l: f64) -> Self::Output {
+    fn add(self, l: T) -> Self::Output {
         Self {
             v: [
                 self[0] + l, self[1] + l, self[2] + l, self[3] + l,
@@ -134,7 +235,7 @@ impl std::ops::Add<f64> for Matrix4d {
     }
 }
 // Equality for Matrix4d
-impl PartialEq for Matrix4d {
+impl<T: Scalar> PartialEq for Matrix4d<T> {
     fn eq(&self, other: &Self) -> bool {
         for i in 0..16 {
             if self[i] != other[i] {
@@ -145,7 +246,7 @@ impl PartialEq for Matrix4d {
     }
 }
 
-impl Eq for Matrix4d {}
+impl<T: Scalar> Eq for Matrix4d<T> {}
 
 #[macro_export]
 macro_rules! mat4d {
@@ -156,3 +257,117 @@ macro_rules! mat4d {
         Matrix4d::new_from_constant($c)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix_layout_conversions() {
+        let rows = [
+            1.0, 2.0, 3.0, 4.0,
+            5.0, 6.0, 7.0, 8.0,
+            9.0, 10.0, 11.0, 12.0,
+            13.0, 14.0, 15.0, 16.0,
+        ];
+        let m = Matrix4d::from_row_major(rows);
+        assert_eq!(m.get(0, 3), 4.0);
+        assert_eq!(m.get(3, 0), 13.0);
+
+        let cols = m.to_col_major_array();
+        assert_eq!(&cols[0..4], &[1.0, 5.0, 9.0, 13.0]);
+        assert_eq!(Matrix4d::from_col_major(cols), m);
+        assert_eq!(m.to_row_major_array(), rows);
+    }
+
+    #[test]
+    fn matrix_mul() {
+        let mut m = Matrix4d::new_from_constant(0.0);
+        for i in 0..16 {
+            m[i] = i as f64;
+        }
+        let id = Matrix4d::from_row_major([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ]);
+        assert_eq!(m * id, m);
+        assert_eq!(id * m, m);
+        assert_eq!((m * m).get(1, 2), 4.0 * 2.0 + 5.0 * 6.0 + 6.0 * 10.0 + 7.0 * 14.0);
+        assert_eq!(m * Vector4d::new_from([1.0, 0.0, 0.0, 1.0]), Vector4d::new_from([3.0, 11.0, 19.0, 27.0]));
+    }
+
+    #[test]
+    fn matrix_inverse() {
+        let m = Matrix4d::from_row_major([
+            2.0, 0.0, 0.0, 1.0,
+            0.0, 4.0, 0.0, 2.0,
+            0.0, 0.0, 8.0, 3.0,
+            0.0, 0.0, 0.0, 1.0,
+        ]);
+        let inv = m.inverse().unwrap();
+        assert_eq!(inv.get(0, 0), 0.5);
+        assert_eq!(inv.get(0, 3), -0.5);
+        assert_eq!(inv.get(2, 3), -3.0 / 8.0);
+        assert_eq!(m * inv, Matrix4d::from_row_major([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ]));
+        assert!(Matrix4d::new_from_constant(1.0).inverse().is_none());
+    }
+
+    fn translation(x: f64, y: f64, z: f64) -> Matrix4d {
+        Matrix4d::from_row_major([
+            1.0, 0.0, 0.0, x,
+            0.0, 1.0, 0.0, y,
+            0.0, 0.0, 1.0, z,
+            0.0, 0.0, 0.0, 1.0,
+        ])
+    }
+
+    #[test]
+    fn batch_transforms() {
+        let mut m = translation(1.0, 2.0, 3.0);
+        m[0] = 2.0;
+        let src = [Vector3d::new_from([1.0, 1.0, 1.0]), Vector3d::new_from([0.0, -1.0, 2.0])];
+        let mut dst = [Vector3d::new(); 2];
+
+        m.transform_points(&src, &mut dst);
+        for (p, out) in src.iter().zip(dst) {
+            let full = m * Vector4d::new_from([p.x, p.y, p.z, 1.0]);
+            assert_eq!(out, Vector3d::new_from([full.x, full.y, full.z]));
+        }
+
+        m.transform_vectors(&src, &mut dst);
+        assert_eq!(dst[0], Vector3d::new_from([2.0, 1.0, 1.0]));
+
+        let mut pts = src;
+        m.transform_points_in_place(&mut pts);
+        m.transform_points(&src, &mut dst);
+        assert_eq!(pts, dst);
+
+        let mut dirs = src;
+        m.transform_vectors_in_place(&mut dirs);
+        assert_eq!(dirs[1], Vector3d::new_from([0.0, -1.0, 2.0]));
+    }
+
+    #[test]
+    fn batch_projective_divide() {
+        let mut m = translation(0.0, 0.0, 0.0);
+        m[15] = 2.0;
+        assert!(!m.is_affine());
+        let mut pts = [Vector3d::new_from([2.0, 4.0, 6.0])];
+        m.transform_points_in_place(&mut pts);
+        assert_eq!(pts[0], Vector3d::new_from([1.0, 2.0, 3.0]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn batch_length_mismatch() {
+        let m = translation(1.0, 0.0, 0.0);
+        m.transform_points(&[Vector3d::new(); 3], &mut [Vector3d::new(); 2]);
+    }
+}
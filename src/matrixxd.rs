@@ -0,0 +1,354 @@
+// Heap allocated matrix whose size is only known at runtime.
+//
+// Storage is row-major like the fixed-size matrices. Dimension mismatches
+// panic, the same way slice indexing does.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+// default edge of the square tiles used by the blocked kernels
+pub const DEFAULT_BLOCK_SIZE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixXd {
+    rows: usize,
+    cols: usize,
+    v: Vec<f64>,
+}
+
+impl MatrixXd {
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get_cols(&self) -> usize {
+        self.cols
+    }
+
+    // v is read row by row
+    pub fn new(rows: usize, cols: usize, v: Vec<f64>) -> Self {
+        assert_eq!(v.len(), rows * cols, "expected {} entries for a {}x{} matrix", rows * cols, rows, cols);
+        Self { rows, cols, v }
+    }
+
+    pub fn new_from_constant(rows: usize, cols: usize, c: f64) -> Self {
+        Self {
+            rows,
+            cols,
+            v: vec![c; rows * cols],
+        }
+    }
+
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self::new_from_constant(rows, cols, 0.0)
+    }
+
+    pub fn identity(n: usize) -> Self {
+        let mut m = Self::zeros(n, n);
+        for i in 0..n {
+            m.v[i * n + i] = 1.0;
+        }
+        m
+    }
+
+    pub fn from_fn<F: FnMut(usize, usize) -> f64>(rows: usize, cols: usize, mut f: F) -> Self {
+        let mut v = Vec::with_capacity(rows * cols);
+        for r in 0..rows {
+            for c in 0..cols {
+                v.push(f(r, c));
+            }
+        }
+        Self { rows, cols, v }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self[(row, col)]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, x: f64) {
+        self[(row, col)] = x;
+    }
+
+    pub fn row(&self, r: usize) -> &[f64] {
+        &self.v[r * self.cols..(r + 1) * self.cols]
+    }
+
+    // the row-major storage
+    pub fn as_slice(&self) -> &[f64] {
+        &self.v
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [f64] {
+        &mut self.v
+    }
+
+    pub fn into_vec(self) -> Vec<f64> {
+        self.v
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Self::zeros(self.cols, self.rows);
+        transpose_rows(self, &mut t.v, 0, DEFAULT_BLOCK_SIZE);
+        t
+    }
+
+    pub fn mul_vec(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.rows];
+        self.mul_vec_into(x, &mut y);
+        y
+    }
+
+    // y = self * x without allocating
+    pub fn mul_vec_into(&self, x: &[f64], y: &mut [f64]) {
+        assert_eq!(x.len(), self.cols, "vector length doesn't match the number of columns");
+        assert_eq!(y.len(), self.rows, "output length doesn't match the number of rows");
+        for (r, out) in y.iter_mut().enumerate() {
+            *out = dot(self.row(r), x);
+        }
+    }
+
+    // cache-blocked product with the given tile size
+    pub fn mul_blocked(&self, o: &MatrixXd, block_size: usize) -> MatrixXd {
+        assert_eq!(self.cols, o.rows, "can't multiply a {}x{} matrix by a {}x{} one", self.rows, self.cols, o.rows, o.cols);
+        let mut c = Self::zeros(self.rows, o.cols);
+        gemm_rows(self, o, &mut c.v, 0, block_size.max(1));
+        c
+    }
+
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Self {
+        Self {
+            rows: self.rows,
+            cols: self.cols,
+            v: self.v.iter().map(|e| f(*e)).collect(),
+        }
+    }
+
+    // element-wise product
+    pub fn component_mul(&self, o: &MatrixXd) -> Self {
+        self.zip_with(o, |a, b| a * b)
+    }
+
+    pub fn frobenius_norm(&self) -> f64 {
+        crate::math::sqrt(dot(&self.v, &self.v))
+    }
+
+    pub(crate) fn zip_with<F: Fn(f64, f64) -> f64>(&self, o: &MatrixXd, f: F) -> Self {
+        assert!(self.rows == o.rows && self.cols == o.cols, "matrix dimensions don't match");
+        Self {
+            rows: self.rows,
+            cols: self.cols,
+            v: self.v.iter().zip(&o.v).map(|(a, b)| f(*a, *b)).collect(),
+        }
+    }
+}
+
+pub(crate) fn dot(a: &[f64], b: &[f64]) -> f64 {
+    let mut s = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        s += x * y;
+    }
+    s
+}
+
+// computes the rows of c = a * b starting at first_row, c_rows holds as many
+// full rows as fit. Every entry is accumulated in increasing k order whatever
+// the tiling, so the result doesn't depend on the block size or on how the
+// rows are split between threads.
+pub(crate) fn gemm_rows(a: &MatrixXd, b: &MatrixXd, c_rows: &mut [f64], first_row: usize, bs: usize) {
+    let n = b.cols;
+    let kk = a.cols;
+    if n == 0 {
+        return;
+    }
+    let rows = c_rows.len() / n;
+    for k0 in (0..kk).step_by(bs) {
+        let k1 = (k0 + bs).min(kk);
+        for j0 in (0..n).step_by(bs) {
+            let j1 = (j0 + bs).min(n);
+            for i in 0..rows {
+                let a_row = a.row(first_row + i);
+                let c_row = &mut c_rows[i * n + j0..i * n + j1];
+                for (k, aik) in a_row.iter().enumerate().take(k1).skip(k0) {
+                    let b_row = &b.v[k * n + j0..k * n + j1];
+                    for (c, bkj) in c_row.iter_mut().zip(b_row) {
+                        *c += aik * bkj;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// writes the rows of a^T starting at first_row into t_rows, tile by tile
+pub(crate) fn transpose_rows(a: &MatrixXd, t_rows: &mut [f64], first_row: usize, bs: usize) {
+    let m = a.rows;
+    if m == 0 {
+        return;
+    }
+    let rows = t_rows.len() / m;
+    for i0 in (0..rows).step_by(bs) {
+        let i1 = (i0 + bs).min(rows);
+        for j0 in (0..m).step_by(bs) {
+            let j1 = (j0 + bs).min(m);
+            for i in i0..i1 {
+                for j in j0..j1 {
+                    t_rows[i * m + j] = a.v[j * a.cols + first_row + i];
+                }
+            }
+        }
+    }
+}
+
+impl core::ops::Index<(usize, usize)> for MatrixXd {
+    type Output = f64;
+
+    fn index(&self, (r, c): (usize, usize)) -> &Self::Output {
+        assert!(r < self.rows && c < self.cols, "index ({}, {}) out of bounds", r, c);
+        &self.v[r * self.cols + c]
+    }
+}
+
+impl core::ops::IndexMut<(usize, usize)> for MatrixXd {
+    fn index_mut(&mut self, (r, c): (usize, usize)) -> &mut Self::Output {
+        assert!(r < self.rows && c < self.cols, "index ({}, {}) out of bounds", r, c);
+        &mut self.v[r * self.cols + c]
+    }
+}
+
+impl core::ops::Mul<&MatrixXd> for &MatrixXd {
+    type Output = MatrixXd;
+
+    fn mul(self, o: &MatrixXd) -> Self::Output {
+        self.mul_blocked(o, DEFAULT_BLOCK_SIZE)
+    }
+}
+
+impl core::ops::Mul<MatrixXd> for MatrixXd {
+    type Output = MatrixXd;
+
+    fn mul(self, o: MatrixXd) -> Self::Output {
+        &self * &o
+    }
+}
+
+impl core::ops::Mul<f64> for &MatrixXd {
+    type Output = MatrixXd;
+
+    fn mul(self, l: f64) -> Self::Output {
+        self.map(|e| e * l)
+    }
+}
+
+impl core::ops::Mul<f64> for MatrixXd {
+    type Output = MatrixXd;
+
+    fn mul(self, l: f64) -> Self::Output {
+        &self * l
+    }
+}
+
+impl core::ops::Add<&MatrixXd> for &MatrixXd {
+    type Output = MatrixXd;
+
+    fn add(self, o: &MatrixXd) -> Self::Output {
+        self.zip_with(o, |a, b| a + b)
+    }
+}
+
+impl core::ops::Add<MatrixXd> for MatrixXd {
+    type Output = MatrixXd;
+
+    fn add(self, o: MatrixXd) -> Self::Output {
+        &self + &o
+    }
+}
+
+impl core::ops::Sub<&MatrixXd> for &MatrixXd {
+    type Output = MatrixXd;
+
+    fn sub(self, o: &MatrixXd) -> Self::Output {
+        self.zip_with(o, |a, b| a - b)
+    }
+}
+
+impl core::ops::Sub<MatrixXd> for MatrixXd {
+    type Output = MatrixXd;
+
+    fn sub(self, o: MatrixXd) -> Self::Output {
+        &self - &o
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_mul(a: &MatrixXd, b: &MatrixXd) -> MatrixXd {
+        MatrixXd::from_fn(a.get_rows(), b.get_cols(), |i, j| {
+            let mut s = 0.0;
+            for k in 0..a.get_cols() {
+                s += a[(i, k)] * b[(k, j)];
+            }
+            s
+        })
+    }
+
+    fn sample(rows: usize, cols: usize, seed: usize) -> MatrixXd {
+        MatrixXd::from_fn(rows, cols, |i, j| (((i * 31 + j * 17 + seed) % 23) as f64 - 11.0) * 0.37)
+    }
+
+    #[test]
+    fn construction_and_indexing() {
+        let mut m = MatrixXd::new(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(m.get(1, 0), 4.0);
+        m[(0, 2)] = 9.0;
+        assert_eq!(m.row(0), &[1.0, 2.0, 9.0]);
+        assert_eq!(MatrixXd::identity(2).as_slice(), &[1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn wrong_length() {
+        MatrixXd::new(2, 2, vec![1.0; 3]);
+    }
+
+    #[test]
+    fn blocked_mul_is_exact() {
+        let a = sample(37, 53, 1);
+        let b = sample(53, 29, 2);
+        let expected = naive_mul(&a, &b);
+        for bs in [1, 4, 16, 64, 100] {
+            assert_eq!(a.mul_blocked(&b, bs), expected);
+        }
+        assert_eq!(&a * &b, expected);
+    }
+
+    #[test]
+    fn transpose_and_mul_vec() {
+        let a = sample(70, 90, 3);
+        let t = a.transpose();
+        assert_eq!(t.get_rows(), 90);
+        assert_eq!(t.get(5, 66), a.get(66, 5));
+        assert_eq!(t.transpose(), a);
+
+        let x: Vec<f64> = (0..90).map(|i| i as f64 * 0.5).collect();
+        let y = a.mul_vec(&x);
+        let xm = MatrixXd::new(90, 1, x);
+        assert_eq!(y, naive_mul(&a, &xm).into_vec());
+    }
+
+    #[test]
+    fn element_wise() {
+        let a = sample(4, 5, 4);
+        let b = sample(4, 5, 5);
+        let back = &(&a + &b) - &b;
+        assert!(back.as_slice().iter().zip(a.as_slice()).all(|(x, y)| (x - y).abs() < 1e-12));
+        assert_eq!((&a * 2.0).get(3, 4), a.get(3, 4) * 2.0);
+        assert_eq!(a.component_mul(&b).get(1, 2), a.get(1, 2) * b.get(1, 2));
+        assert_eq!(MatrixXd::new_from_constant(2, 2, 2.0).frobenius_norm(), 4.0);
+    }
+}
@@ -0,0 +1,225 @@
+// Multi-threaded kernels for MatrixXd, enabled by the `rayon` feature.
+//
+// Products, transposes and element-wise ops split the output rows between
+// threads, every entry is still computed by a single thread in a fixed order,
+// so they give the same bits as the sequential code. Reductions (dot, sum,
+// norms) are the only place where the thread count can change the rounding:
+// with `deterministic` set they are summed over fixed-size chunks that are
+// then combined in order, independently of how many threads are running.
+
+use super::matrixxd::*;
+use alloc::vec;
+use alloc::vec::Vec;
+use rayon::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParConfig {
+    // edge of the tiles used by the blocked kernels, also the number of rows
+    // handed to a thread at a time
+    pub block_size: usize,
+    // bit-identical reductions whatever the number of threads
+    pub deterministic: bool,
+}
+
+impl Default for ParConfig {
+    fn default() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            deterministic: true,
+        }
+    }
+}
+
+impl ParConfig {
+    pub fn new(block_size: usize, deterministic: bool) -> Self {
+        Self {
+            block_size: block_size.max(1),
+            deterministic,
+        }
+    }
+}
+
+// length of the chunks summed sequentially in deterministic mode
+const REDUCTION_CHUNK: usize = 4096;
+
+pub fn par_mul(a: &MatrixXd, b: &MatrixXd, cfg: &ParConfig) -> MatrixXd {
+    assert_eq!(a.get_cols(), b.get_rows(), "can't multiply a {}x{} matrix by a {}x{} one",
+        a.get_rows(), a.get_cols(), b.get_rows(), b.get_cols());
+    let bs = cfg.block_size.max(1);
+    let n = b.get_cols();
+    let mut c = MatrixXd::zeros(a.get_rows(), n);
+    if n == 0 {
+        return c;
+    }
+    c.as_mut_slice()
+        .par_chunks_mut(bs * n)
+        .enumerate()
+        .for_each(|(i, rows)| gemm_rows(a, b, rows, i * bs, bs));
+    c
+}
+
+pub fn par_mul_vec(a: &MatrixXd, x: &[f64], cfg: &ParConfig) -> Vec<f64> {
+    assert_eq!(x.len(), a.get_cols(), "vector length doesn't match the number of columns");
+    let mut y = vec![0.0; a.get_rows()];
+    y.par_chunks_mut(cfg.block_size.max(1))
+        .enumerate()
+        .for_each(|(i, out)| {
+            let first = i * cfg.block_size.max(1);
+            for (r, e) in out.iter_mut().enumerate() {
+                *e = dot(a.row(first + r), x);
+            }
+        });
+    y
+}
+
+pub fn par_transpose(a: &MatrixXd, cfg: &ParConfig) -> MatrixXd {
+    let bs = cfg.block_size.max(1);
+    let m = a.get_rows();
+    let mut t = MatrixXd::zeros(a.get_cols(), m);
+    if m == 0 {
+        return t;
+    }
+    t.as_mut_slice()
+        .par_chunks_mut(bs * m)
+        .enumerate()
+        .for_each(|(i, rows)| transpose_rows(a, rows, i * bs, bs));
+    t
+}
+
+pub fn par_zip_with<F>(a: &MatrixXd, b: &MatrixXd, f: F) -> MatrixXd
+where
+    F: Fn(f64, f64) -> f64 + Sync,
+{
+    assert!(a.get_rows() == b.get_rows() && a.get_cols() == b.get_cols(), "matrix dimensions don't match");
+    let v = a.as_slice()
+        .par_iter()
+        .zip(b.as_slice())
+        .map(|(x, y)| f(*x, *y))
+        .collect();
+    MatrixXd::new(a.get_rows(), a.get_cols(), v)
+}
+
+pub fn par_map<F>(a: &MatrixXd, f: F) -> MatrixXd
+where
+    F: Fn(f64) -> f64 + Sync,
+{
+    let v = a.as_slice().par_iter().map(|x| f(*x)).collect();
+    MatrixXd::new(a.get_rows(), a.get_cols(), v)
+}
+
+pub fn par_add(a: &MatrixXd, b: &MatrixXd) -> MatrixXd {
+    par_zip_with(a, b, |x, y| x + y)
+}
+
+pub fn par_sub(a: &MatrixXd, b: &MatrixXd) -> MatrixXd {
+    par_zip_with(a, b, |x, y| x - y)
+}
+
+pub fn par_component_mul(a: &MatrixXd, b: &MatrixXd) -> MatrixXd {
+    par_zip_with(a, b, |x, y| x * y)
+}
+
+pub fn par_scale(a: &MatrixXd, l: f64) -> MatrixXd {
+    par_map(a, |x| x * l)
+}
+
+pub fn par_dot(x: &[f64], y: &[f64], cfg: &ParConfig) -> f64 {
+    assert_eq!(x.len(), y.len(), "vectors must have the same length");
+    if cfg.deterministic {
+        let partials: Vec<f64> = x
+            .par_chunks(REDUCTION_CHUNK)
+            .zip(y.par_chunks(REDUCTION_CHUNK))
+            .map(|(a, b)| dot(a, b))
+            .collect();
+        partials.iter().sum()
+    } else {
+        x.par_iter().zip(y).map(|(a, b)| a * b).sum()
+    }
+}
+
+pub fn par_sum(x: &[f64], cfg: &ParConfig) -> f64 {
+    if cfg.deterministic {
+        let partials: Vec<f64> = x
+            .par_chunks(REDUCTION_CHUNK)
+            .map(|c| c.iter().sum::<f64>())
+            .collect();
+        partials.iter().sum()
+    } else {
+        x.par_iter().sum()
+    }
+}
+
+pub fn par_frobenius_norm(a: &MatrixXd, cfg: &ParConfig) -> f64 {
+    crate::math::sqrt(par_dot(a.as_slice(), a.as_slice(), cfg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(rows: usize, cols: usize, seed: usize) -> MatrixXd {
+        MatrixXd::from_fn(rows, cols, |i, j| {
+            (((i * 131 + j * 71 + seed) % 997) as f64 - 498.0) * 1.0e-3 + 1.0 / (1.0 + (i + j) as f64)
+        })
+    }
+
+    fn pool(threads: usize) -> rayon::ThreadPool {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap()
+    }
+
+    #[test]
+    fn mul_matches_sequential() {
+        let a = sample(130, 97, 1);
+        let b = sample(97, 111, 2);
+        let expected = &a * &b;
+        for bs in [8, 32, 64] {
+            let cfg = ParConfig::new(bs, true);
+            assert_eq!(par_mul(&a, &b, &cfg), expected);
+        }
+    }
+
+    #[test]
+    fn mul_vec_and_transpose_match_sequential() {
+        let a = sample(200, 150, 3);
+        let x: Vec<f64> = (0..150).map(|i| (i as f64).sin()).collect();
+        let cfg = ParConfig::new(16, true);
+        assert_eq!(par_mul_vec(&a, &x, &cfg), a.mul_vec(&x));
+        assert_eq!(par_transpose(&a, &cfg), a.transpose());
+    }
+
+    #[test]
+    fn element_wise_ops() {
+        let a = sample(40, 30, 4);
+        let b = sample(40, 30, 5);
+        assert_eq!(par_add(&a, &b), &a + &b);
+        assert_eq!(par_sub(&a, &b), &a - &b);
+        assert_eq!(par_scale(&a, 3.0), &a * 3.0);
+        assert_eq!(par_component_mul(&a, &b), a.component_mul(&b));
+    }
+
+    #[test]
+    fn deterministic_across_thread_counts() {
+        let x: Vec<f64> = (0..100_000).map(|i| 1.0 / (1.0 + i as f64) * if i % 3 == 0 { -1.0 } else { 1.0 }).collect();
+        let y: Vec<f64> = (0..100_000).map(|i| ((i % 17) as f64).sqrt()).collect();
+        let cfg = ParConfig::default();
+        let a = sample(90, 90, 6);
+        let b = sample(90, 90, 7);
+
+        let reference = pool(1).install(|| (par_dot(&x, &y, &cfg), par_sum(&x, &cfg), par_mul(&a, &b, &cfg)));
+        for threads in [2, 3, 8] {
+            let r = pool(threads).install(|| (par_dot(&x, &y, &cfg), par_sum(&x, &cfg), par_mul(&a, &b, &cfg)));
+            assert_eq!(r.0.to_bits(), reference.0.to_bits());
+            assert_eq!(r.1.to_bits(), reference.1.to_bits());
+            assert_eq!(r.2, reference.2);
+        }
+    }
+
+    #[test]
+    fn non_deterministic_mode_is_close() {
+        let x: Vec<f64> = (0..10_000).map(|i| i as f64 * 0.1).collect();
+        let cfg = ParConfig::new(64, false);
+        let exact: f64 = x.iter().sum();
+        assert!((par_sum(&x, &cfg) - exact).abs() < 1e-6 * exact);
+        assert!((par_frobenius_norm(&MatrixXd::new(100, 100, x.clone()), &cfg) - crate::math::sqrt(par_dot(&x, &x, &ParConfig::default()))).abs() < 1e-6);
+    }
+}
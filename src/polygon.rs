@@ -0,0 +1,416 @@
+// Polygon operations on Vector2d built on the robust predicates.
+//
+// A polygon is a slice of vertices with an implicit closing edge from the
+// last vertex back to the first. Decisions (turns, sides, containment) go
+// through orient2d and are exact; constructed points such as intersections
+// are computed in floating point.
+
+use super::predicates::*;
+use super::vector2d::*;
+use alloc::vec::Vec;
+
+fn v2(x: f64, y: f64) -> Vector2d {
+    Vector2d::new_from([x, y])
+}
+
+fn cross(a: Vector2d, b: Vector2d) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+// Convex hull in counter-clockwise order starting from the lowest-leftmost
+// point, without collinear points (Andrew's monotone chain)
+pub fn convex_hull(points: &[Vector2d]) -> Vec<Vector2d> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+    let mut hull: Vec<Vector2d> = Vec::with_capacity(sorted.len() + 1);
+    // lower chain left to right, then upper chain right to left
+    for pass in 0..2 {
+        let start = hull.len();
+        for &p in sorted.iter() {
+            while hull.len() >= start + 2 && orient2d(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // the last point starts the other chain
+        hull.pop();
+        if pass == 0 {
+            sorted.reverse();
+        }
+    }
+    hull
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentIntersection {
+    None,
+    Point(Vector2d),
+    // collinear segments sharing the part between the two points
+    Overlap(Vector2d, Vector2d),
+}
+
+// p on the closed segment a-b
+fn on_segment(p: Vector2d, a: Vector2d, b: Vector2d) -> bool {
+    orient2d(a, b, p) == 0.0 && p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x) && p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y)
+}
+
+// intersection of the closed segments a0-a1 and b0-b1
+pub fn segment_intersection(a0: Vector2d, a1: Vector2d, b0: Vector2d, b1: Vector2d) -> SegmentIntersection {
+    // a point has no direction to project on
+    if a0 == a1 {
+        return if on_segment(a0, b0, b1) { SegmentIntersection::Point(a0) } else { SegmentIntersection::None };
+    }
+    if b0 == b1 {
+        return if on_segment(b0, a0, a1) { SegmentIntersection::Point(b0) } else { SegmentIntersection::None };
+    }
+    let (d0, d1) = (orient2d(a0, a1, b0), orient2d(a0, a1, b1));
+    let (d2, d3) = (orient2d(b0, b1, a0), orient2d(b0, b1, a1));
+    if d0 == 0.0 && d1 == 0.0 {
+        // collinear, project on the longer axis of a
+        let d = a1 - a0;
+        let key = |p: Vector2d| if d.x.abs() >= d.y.abs() { p.x } else { p.y };
+        let (a_lo, a_hi) = if key(a0) <= key(a1) { (a0, a1) } else { (a1, a0) };
+        let (b_lo, b_hi) = if key(b0) <= key(b1) { (b0, b1) } else { (b1, b0) };
+        let lo = if key(a_lo) >= key(b_lo) { a_lo } else { b_lo };
+        let hi = if key(a_hi) <= key(b_hi) { a_hi } else { b_hi };
+        return match key(lo).partial_cmp(&key(hi)) {
+            Some(core::cmp::Ordering::Less) => SegmentIntersection::Overlap(lo, hi),
+            Some(core::cmp::Ordering::Equal) => SegmentIntersection::Point(lo),
+            _ => SegmentIntersection::None,
+        };
+    }
+    if d0 * d1 > 0.0 || d2 * d3 > 0.0 {
+        return SegmentIntersection::None;
+    }
+    // exact endpoints for touching configurations
+    for (d, p) in [(d0, b0), (d1, b1), (d2, a0), (d3, a1)] {
+        if d == 0.0 {
+            return SegmentIntersection::Point(p);
+        }
+    }
+    let t = d2 / (d2 - d3);
+    SegmentIntersection::Point(a0 + (a1 - a0) * t)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointLocation {
+    Inside,
+    Outside,
+    OnBoundary,
+}
+
+// by winding number, so it also works for self-intersecting polygons where
+// regions wound around non-zero times count as inside
+pub fn point_in_polygon(p: Vector2d, poly: &[Vector2d]) -> PointLocation {
+    let mut winding = 0i32;
+    for i in 0..poly.len() {
+        let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+        let o = orient2d(a, b, p);
+        if on_segment(p, a, b) {
+            return PointLocation::OnBoundary;
+        }
+        if a.y <= p.y {
+            if b.y > p.y && o > 0.0 {
+                winding += 1;
+            }
+        } else if b.y <= p.y && o < 0.0 {
+            winding -= 1;
+        }
+    }
+    if winding != 0 { PointLocation::Inside } else { PointLocation::Outside }
+}
+
+// positive for counter-clockwise polygons
+pub fn signed_area(poly: &[Vector2d]) -> f64 {
+    (0..poly.len()).map(|i| cross(poly[i], poly[(i + 1) % poly.len()])).sum::<f64>() * 0.5
+}
+
+pub fn winding(poly: &[Vector2d]) -> Orientation {
+    Orientation::from_sign(signed_area(poly))
+}
+
+// centroid of the enclosed area, None for polygons with no area
+pub fn centroid(poly: &[Vector2d]) -> Option<Vector2d> {
+    let area = signed_area(poly);
+    if area == 0.0 {
+        return None;
+    }
+    let sum = (0..poly.len()).fold(Vector2d::new(), |s, i| {
+        let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+        s + (a + b) * cross(a, b)
+    });
+    Some(sum / (6.0 * area))
+}
+
+// Sutherland–Hodgman: the part of subject inside the convex polygon clip,
+// which may wind either way. A concave subject can come out with
+// zero-width bridges along the clip edges.
+pub fn clip_polygon(subject: &[Vector2d], clip: &[Vector2d]) -> Vec<Vector2d> {
+    let side = if signed_area(clip) < 0.0 { -1.0 } else { 1.0 };
+    let mut out = subject.to_vec();
+    for i in 0..clip.len() {
+        if out.is_empty() {
+            break;
+        }
+        let (c0, c1) = (clip[i], clip[(i + 1) % clip.len()]);
+        let inside = |p: Vector2d| orient2d(c0, c1, p) * side >= 0.0;
+        let input = core::mem::take(&mut out);
+        let mut prev = input[input.len() - 1];
+        for &p in &input {
+            if inside(p) {
+                if !inside(prev) {
+                    out.push(line_intersection(prev, p, c0, c1));
+                }
+                out.push(p);
+            } else if inside(prev) {
+                out.push(line_intersection(prev, p, c0, c1));
+            }
+            prev = p;
+        }
+    }
+    out
+}
+
+// where segment p-q crosses the line through c0 and c1
+fn line_intersection(p: Vector2d, q: Vector2d, c0: Vector2d, c1: Vector2d) -> Vector2d {
+    let (dp, dq) = (orient2d(c0, c1, p), orient2d(c0, c1, q));
+    p + (q - p) * (dp / (dp - dq))
+}
+
+// Ear-clipping triangulation of a simple polygon with holes. The vertices are
+// numbered outer first and then each hole in turn; the triangles come out
+// counter-clockwise. Holes must lie inside the outer polygon and not touch
+// each other.
+pub fn triangulate(outer: &[Vector2d], holes: &[&[Vector2d]]) -> Vec<[usize; 3]> {
+    let mut points: Vec<Vector2d> = outer.to_vec();
+    let mut ring: Vec<usize> = (0..outer.len()).collect();
+    if signed_area(outer) < 0.0 {
+        ring.reverse();
+    }
+    // holes clockwise, merged rightmost first so the bridges don't cross
+    let mut hole_rings: Vec<Vec<usize>> = Vec::new();
+    for h in holes {
+        let mut r: Vec<usize> = (points.len()..points.len() + h.len()).collect();
+        points.extend_from_slice(h);
+        if signed_area(h) > 0.0 {
+            r.reverse();
+        }
+        hole_rings.push(r);
+    }
+    let max_x = |r: &Vec<usize>| r.iter().map(|&i| points[i].x).fold(f64::NEG_INFINITY, f64::max);
+    hole_rings.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+    for h in &hole_rings {
+        bridge_hole(&points, &mut ring, h);
+    }
+    ear_clip(&points, ring)
+}
+
+// splices a hole into the ring through a bridge from its rightmost vertex m
+// to a vertex of the ring visible from it (Eberly, "Triangulation by ear
+// clipping")
+fn bridge_hole(points: &[Vector2d], ring: &mut Vec<usize>, hole: &[usize]) {
+    let mi = (0..hole.len()).max_by(|&a, &b| points[hole[a]].x.total_cmp(&points[hole[b]].x)).unwrap();
+    let m = points[hole[mi]];
+    // nearest edge hit by the ray from m towards +x
+    let mut best: Option<(f64, usize)> = None;
+    for k in 0..ring.len() {
+        let (a, b) = (points[ring[k]], points[ring[(k + 1) % ring.len()]]);
+        // leaving the inside of a counter-clockwise ring towards +x is
+        // through an upward edge, the others are behind a closer one
+        if !(a.y <= m.y && m.y <= b.y) || a.y == b.y {
+            continue;
+        }
+        let x = a.x + (m.y - a.y) * (b.x - a.x) / (b.y - a.y);
+        if x >= m.x && best.is_none_or(|(bx, _)| x < bx) {
+            best = Some((x, k));
+        }
+    }
+    let (x, k) = best.expect("hole is not inside the polygon");
+    let (ka, kb) = (k, (k + 1) % ring.len());
+    let i = v2(x, m.y);
+    // the edge end with the larger x is the candidate, unless a reflex vertex
+    // of the ring inside the triangle m, i, p hides it
+    let mut pick = if points[ring[ka]].x > points[ring[kb]].x { ka } else { kb };
+    let p = points[ring[pick]];
+    if p != i {
+        let tri = if orient2d(m, i, p) > 0.0 { [m, i, p] } else { [m, p, i] };
+        let mut best_angle = f64::INFINITY;
+        for j in 0..ring.len() {
+            let q = points[ring[j]];
+            let (prev, next) = (points[ring[(j + ring.len() - 1) % ring.len()]], points[ring[(j + 1) % ring.len()]]);
+            if j == pick || orient2d(prev, q, next) >= 0.0 || !in_triangle(tri, q) || q.x < m.x {
+                continue;
+            }
+            let d = q - m;
+            let angle = d.y.abs() / d.length();
+            if angle < best_angle || (angle == best_angle && d * d < (points[ring[pick]] - m) * (points[ring[pick]] - m)) {
+                best_angle = angle;
+                pick = j;
+            }
+        }
+    }
+    // ring[..=pick], m, rest of hole, m, ring[pick], ring[pick + 1..]
+    let mut spliced: Vec<usize> = Vec::with_capacity(ring.len() + hole.len() + 2);
+    spliced.extend_from_slice(&ring[..=pick]);
+    spliced.extend((0..=hole.len()).map(|s| hole[(mi + s) % hole.len()]));
+    spliced.push(ring[pick]);
+    spliced.extend_from_slice(&ring[pick + 1..]);
+    *ring = spliced;
+}
+
+// closed triangle test for a counter-clockwise triangle
+fn in_triangle(t: [Vector2d; 3], p: Vector2d) -> bool {
+    orient2d(t[0], t[1], p) >= 0.0 && orient2d(t[1], t[2], p) >= 0.0 && orient2d(t[2], t[0], p) >= 0.0
+}
+
+fn ear_clip(points: &[Vector2d], mut ring: Vec<usize>) -> Vec<[usize; 3]> {
+    let mut tris = Vec::with_capacity(ring.len().saturating_sub(2));
+    let (mut i, mut stalled) = (0, 0);
+    while ring.len() > 3 {
+        let n = ring.len();
+        let (ia, ib, ic) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+        let (a, b, c) = (points[ia], points[ib], points[ic]);
+        let turn = orient2d(a, b, c);
+        let is_ear = turn > 0.0
+            && ring.iter().all(|&j| {
+                let q = points[j];
+                // the bridge duplicates count as the triangle's own corners
+                q == a || q == b || q == c || !in_triangle([a, b, c], q)
+            });
+        // a full lap without an ear only happens for degenerate input, clip
+        // the corner anyway to make progress
+        if is_ear || stalled >= n {
+            if turn > 0.0 {
+                tris.push([ia, ib, ic]);
+            }
+            ring.remove(i);
+            i = (i + n - 2) % (n - 1);
+            stalled = 0;
+        } else {
+            i = (i + 1) % n;
+            stalled += 1;
+        }
+    }
+    if ring.len() == 3 && orient2d(points[ring[0]], points[ring[1]], points[ring[2]]) > 0.0 {
+        tris.push([ring[0], ring[1], ring[2]]);
+    }
+    tris
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area_of(points: &[Vector2d], tris: &[[usize; 3]]) -> f64 {
+        tris.iter().map(|t| signed_area(&t.map(|i| points[i]))).sum()
+    }
+
+    #[test]
+    fn hull() {
+        let mut points = Vec::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                points.push(v2(i as f64, j as f64));
+            }
+        }
+        points.push(v2(4.5, -1.0));
+        let hull = convex_hull(&points);
+        assert_eq!(hull, [v2(0.0, 0.0), v2(4.5, -1.0), v2(9.0, 0.0), v2(9.0, 9.0), v2(0.0, 9.0)]);
+        assert_eq!(winding(&hull), Orientation::CounterClockwise);
+        let line = [v2(0.0, 0.0), v2(2.0, 2.0), v2(1.0, 1.0), v2(2.0, 2.0)];
+        assert_eq!(convex_hull(&line), [v2(0.0, 0.0), v2(2.0, 2.0)]);
+        assert_eq!(convex_hull(&[v2(1.0, 1.0); 3]), [v2(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn segments_and_containment() {
+        let p = |x, y| v2(x, y);
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(2.0, 2.0), p(0.0, 2.0), p(2.0, 0.0)), SegmentIntersection::Point(p(1.0, 1.0)));
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(1.0, 0.0), p(1.0, 0.0), p(1.0, 5.0)), SegmentIntersection::Point(p(1.0, 0.0)));
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(1.0, 0.0), p(2.0, -1.0), p(2.0, 1.0)), SegmentIntersection::None);
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(4.0, 0.0), p(5.0, 0.0), p(3.0, 0.0)), SegmentIntersection::Overlap(p(3.0, 0.0), p(4.0, 0.0)));
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(1.0, 1.0), p(2.0, 2.0), p(3.0, 3.0)), SegmentIntersection::None);
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(0.0, 1.0), p(0.0, 1.0), p(0.0, 3.0)), SegmentIntersection::Point(p(0.0, 1.0)));
+        // zero length segments
+        assert_eq!(segment_intersection(p(0.5, 5.0), p(0.5, 5.0), p(0.0, 0.0), p(1.0, 0.0)), SegmentIntersection::None);
+        assert_eq!(segment_intersection(p(0.5, 0.0), p(0.5, 0.0), p(0.0, 0.0), p(1.0, 0.0)), SegmentIntersection::Point(p(0.5, 0.0)));
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(1.0, 0.0), p(2.0, 0.0), p(2.0, 0.0)), SegmentIntersection::None);
+        assert_eq!(segment_intersection(p(0.0, 0.0), p(2.0, 2.0), p(1.0, 1.0), p(1.0, 1.0)), SegmentIntersection::Point(p(1.0, 1.0)));
+        assert_eq!(segment_intersection(p(1.0, 1.0), p(1.0, 1.0), p(1.0, 1.0), p(1.0, 1.0)), SegmentIntersection::Point(p(1.0, 1.0)));
+
+        // an L shape, clockwise
+        let l = [p(0.0, 0.0), p(0.0, 3.0), p(1.0, 3.0), p(1.0, 1.0), p(3.0, 1.0), p(3.0, 0.0)];
+        assert_eq!(winding(&l), Orientation::Clockwise);
+        assert_eq!(signed_area(&l), -5.0);
+        let c = centroid(&l).unwrap();
+        assert!((c - p(1.1, 1.1)).length() < 1e-12);
+        assert_eq!(point_in_polygon(p(0.5, 2.0), &l), PointLocation::Inside);
+        assert_eq!(point_in_polygon(p(2.0, 2.0), &l), PointLocation::Outside);
+        assert_eq!(point_in_polygon(p(2.0, 1.0), &l), PointLocation::OnBoundary);
+        assert_eq!(point_in_polygon(p(3.0, 0.0), &l), PointLocation::OnBoundary);
+        assert_eq!(point_in_polygon(p(-1.0, 1.0), &l), PointLocation::Outside);
+        assert_eq!(centroid(&[p(0.0, 0.0), p(1.0, 1.0), p(2.0, 2.0)]), None);
+    }
+
+    #[test]
+    fn clipping() {
+        let square = [v2(0.0, 0.0), v2(2.0, 0.0), v2(2.0, 2.0), v2(0.0, 2.0)];
+        let diamond = [v2(1.0, -0.5), v2(2.5, 1.0), v2(1.0, 2.5), v2(-0.5, 1.0)];
+        let clipped = clip_polygon(&square, &diamond);
+        assert_eq!(clipped.len(), 8);
+        assert!((signed_area(&clipped) - 3.5).abs() < 1e-12);
+        // clip winding doesn't matter, and a subject inside is unchanged
+        let mut reversed = diamond;
+        reversed.reverse();
+        assert!((signed_area(&clip_polygon(&square, &reversed)) - 3.5).abs() < 1e-12);
+        let small = [v2(0.5, 0.5), v2(1.5, 0.5), v2(1.0, 1.5)];
+        assert_eq!(clip_polygon(&small, &square), small);
+        let far = [v2(5.0, 5.0), v2(6.0, 5.0), v2(6.0, 6.0)];
+        assert!(clip_polygon(&far, &square).is_empty());
+    }
+
+    #[test]
+    fn ear_clipping() {
+        // a comb: concave, clockwise
+        let mut comb = alloc::vec![v2(0.0, 0.0)];
+        for k in 0..5 {
+            let x = k as f64 * 2.0;
+            comb.extend([v2(x, 4.0), v2(x + 1.0, 4.0), v2(x + 1.0, 1.0), v2(x + 2.0, 1.0)]);
+        }
+        comb.push(v2(10.0, 0.0));
+        let tris = triangulate(&comb, &[]);
+        assert_eq!(tris.len(), comb.len() - 2);
+        assert!((area_of(&comb, &tris) + signed_area(&comb)).abs() < 1e-12);
+        for t in &tris {
+            assert!(signed_area(&t.map(|i| comb[i])) > 0.0);
+        }
+    }
+
+    #[test]
+    fn ear_clipping_with_holes() {
+        let outer = [v2(0.0, 0.0), v2(10.0, 0.0), v2(10.0, 10.0), v2(0.0, 10.0)];
+        let h1 = [v2(2.0, 2.0), v2(4.0, 2.0), v2(4.0, 4.0), v2(2.0, 4.0)];
+        let h2 = [v2(6.0, 6.0), v2(8.0, 7.0), v2(7.0, 8.0)];
+        let h3 = [v2(6.0, 2.0), v2(8.0, 2.0), v2(7.0, 4.0)];
+        let holes: [&[Vector2d]; 3] = [&h1, &h2, &h3];
+        let tris = triangulate(&outer, &holes);
+        let points: Vec<Vector2d> = outer.iter().chain(&h1).chain(&h2).chain(&h3).copied().collect();
+        // every bridge adds two vertices
+        assert_eq!(tris.len(), points.len() + 2 * holes.len() - 2);
+        let expected = 100.0 - 4.0 - signed_area(&h2).abs() - signed_area(&h3).abs();
+        assert!((area_of(&points, &tris) - expected).abs() < 1e-9);
+        for t in &tris {
+            let tri = t.map(|i| points[i]);
+            assert!(signed_area(&tri) > 0.0);
+            // no triangle covers a hole
+            let c = (tri[0] + tri[1] + tri[2]) / 3.0;
+            for h in holes {
+                assert_eq!(point_in_polygon(c, h), PointLocation::Outside);
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+// Preconditioners for the Krylov solvers. Each one applies z = M^-1 r for
+// some M that approximates A and is cheap to invert.
+//
+// The constructors return None when the factorization can't be built (zero
+// pivots, or a non positive definite matrix for the incomplete Cholesky).
+
+use super::sparse::*;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub trait Preconditioner {
+    // z = M^-1 r
+    fn apply(&self, r: &[f64], z: &mut [f64]);
+}
+
+// any closure can be used as a preconditioner
+impl<F: Fn(&[f64], &mut [f64])> Preconditioner for F {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self(r, z)
+    }
+}
+
+// M = I, i.e. no preconditioning
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl Preconditioner for Identity {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        z.copy_from_slice(r);
+    }
+}
+
+// M = diag(A)
+#[derive(Debug, Clone)]
+pub struct Jacobi {
+    inv_diag: Vec<f64>,
+}
+
+impl Jacobi {
+    pub fn new(diagonal: &[f64]) -> Option<Self> {
+        if diagonal.contains(&0.0) {
+            return None;
+        }
+        Some(Self {
+            inv_diag: diagonal.iter().map(|d| 1.0 / d).collect(),
+        })
+    }
+
+    pub fn from_csr(a: &CsrMatrix) -> Option<Self> {
+        Self::new(&a.diagonal())
+    }
+}
+
+impl Preconditioner for Jacobi {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        for ((z, r), d) in z.iter_mut().zip(r).zip(&self.inv_diag) {
+            *z = r * d;
+        }
+    }
+}
+
+// symmetric successive over-relaxation,
+// M = w / (2 - w) (D / w + L) (D / w)^-1 (D / w + U)
+#[derive(Debug, Clone)]
+pub struct Ssor {
+    a: CsrMatrix,
+    diag: Vec<f64>,
+    omega: f64,
+}
+
+impl Ssor {
+    // omega must be in (0, 2), 1 gives symmetric Gauss-Seidel
+    pub fn new(a: &CsrMatrix, omega: f64) -> Option<Self> {
+        let diag = a.diagonal();
+        if a.get_rows() != a.get_cols() || !(omega > 0.0 && omega < 2.0) || diag.contains(&0.0) {
+            return None;
+        }
+        Some(Self {
+            a: a.clone(),
+            diag,
+            omega,
+        })
+    }
+}
+
+impl Preconditioner for Ssor {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let n = self.diag.len();
+        let w = self.omega;
+
+        // (D / w + L) y = r
+        let mut y = vec![0.0; n];
+        for i in 0..n {
+            let (idx, val) = self.a.row(i);
+            let mut s = r[i];
+            for (j, v) in idx.iter().zip(val) {
+                if *j < i {
+                    s -= v * y[*j];
+                }
+            }
+            y[i] = s * w / self.diag[i];
+        }
+        // y <- (D / w) y
+        for (y, d) in y.iter_mut().zip(&self.diag) {
+            *y *= d / w;
+        }
+        // (D / w + U) z = y
+        for i in (0..n).rev() {
+            let (idx, val) = self.a.row(i);
+            let mut s = y[i];
+            for (j, v) in idx.iter().zip(val) {
+                if *j > i {
+                    s -= v * z[*j];
+                }
+            }
+            z[i] = s * w / self.diag[i];
+        }
+        let scale = (2.0 - w) / w;
+        z.iter_mut().for_each(|e| *e *= scale);
+    }
+}
+
+// incomplete Cholesky with no fill-in, A ~ L L^T where L has the sparsity of
+// the lower triangle of A. A must be symmetric, only its lower triangle is read.
+#[derive(Debug, Clone)]
+pub struct IncompleteCholesky {
+    l: CsrMatrix,
+}
+
+impl IncompleteCholesky {
+    pub fn new(a: &CsrMatrix) -> Option<Self> {
+        let n = a.get_rows();
+        if n != a.get_cols() {
+            return None;
+        }
+
+        // lower triangle, diagonal last in every row
+        let mut ptr = vec![0];
+        let mut idx = Vec::new();
+        let mut val = Vec::new();
+        for i in 0..n {
+            let (ci, vi) = a.row(i);
+            let mut has_diag = false;
+            for (c, v) in ci.iter().zip(vi) {
+                if *c <= i {
+                    has_diag |= *c == i;
+                    idx.push(*c);
+                    val.push(*v);
+                }
+            }
+            if !has_diag {
+                return None;
+            }
+            ptr.push(idx.len());
+        }
+
+        // row i of L only needs the rows k < i, which are already final
+        let mut work = vec![0.0; n];
+        let mut in_row = vec![false; n];
+        for i in 0..n {
+            let (s, e) = (ptr[i], ptr[i + 1]);
+            for k in s..e {
+                work[idx[k]] = val[k];
+                in_row[idx[k]] = true;
+            }
+            for p in s..e - 1 {
+                let k = idx[p];
+                let mut lik = work[k];
+                for q in ptr[k]..ptr[k + 1] - 1 {
+                    if in_row[idx[q]] && idx[q] < k {
+                        lik -= work[idx[q]] * val[q];
+                    }
+                }
+                lik /= val[ptr[k + 1] - 1];
+                work[k] = lik;
+            }
+            let mut d = work[i];
+            for p in s..e - 1 {
+                d -= work[idx[p]] * work[idx[p]];
+            }
+            if d <= 0.0 || !d.is_finite() {
+                return None;
+            }
+            work[i] = crate::math::sqrt(d);
+            for k in s..e {
+                val[k] = work[idx[k]];
+                work[idx[k]] = 0.0;
+                in_row[idx[k]] = false;
+            }
+        }
+
+        Some(Self {
+            l: CsrMatrix::from_raw_parts(n, n, ptr, idx, val),
+        })
+    }
+
+    pub fn factor(&self) -> &CsrMatrix {
+        &self.l
+    }
+}
+
+impl Preconditioner for IncompleteCholesky {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let n = r.len();
+        // L y = r
+        let mut y = vec![0.0; n];
+        for i in 0..n {
+            let (idx, val) = self.l.row(i);
+            let last = idx.len() - 1;
+            let mut s = r[i];
+            for k in 0..last {
+                s -= val[k] * y[idx[k]];
+            }
+            y[i] = s / val[last];
+        }
+        // L^T z = y, going through the rows of L backwards scatters the columns of L^T
+        for i in (0..n).rev() {
+            let (idx, val) = self.l.row(i);
+            let last = idx.len() - 1;
+            z[i] = y[i] / val[last];
+            for k in 0..last {
+                y[idx[k]] -= val[k] * z[i];
+            }
+        }
+    }
+}
+
+// incomplete LU with no fill-in, A ~ L U with L unit lower triangular, both
+// stored in place of the entries of A
+#[derive(Debug, Clone)]
+pub struct Ilu0 {
+    lu: CsrMatrix,
+    diag_pos: Vec<usize>,
+}
+
+impl Ilu0 {
+    pub fn new(a: &CsrMatrix) -> Option<Self> {
+        let n = a.get_rows();
+        if n != a.get_cols() {
+            return None;
+        }
+        let mut lu = a.clone();
+        let ptr = lu.row_ptr().to_vec();
+        let idx = lu.col_idx().to_vec();
+
+        let mut diag_pos = Vec::with_capacity(n);
+        for i in 0..n {
+            let p = idx[ptr[i]..ptr[i + 1]].binary_search(&i).ok()?;
+            diag_pos.push(ptr[i] + p);
+        }
+
+        let val = lu.values_mut();
+        // position of each column of the current row, usize::MAX when absent
+        let mut pos = vec![usize::MAX; n];
+        for i in 0..n {
+            for p in ptr[i]..ptr[i + 1] {
+                pos[idx[p]] = p;
+            }
+            for p in ptr[i]..diag_pos[i] {
+                let k = idx[p];
+                let pivot = val[diag_pos[k]];
+                if pivot == 0.0 {
+                    return None;
+                }
+                val[p] /= pivot;
+                let lik = val[p];
+                for q in diag_pos[k] + 1..ptr[k + 1] {
+                    let j = idx[q];
+                    if pos[j] != usize::MAX {
+                        val[pos[j]] -= lik * val[q];
+                    }
+                }
+            }
+            for p in ptr[i]..ptr[i + 1] {
+                pos[idx[p]] = usize::MAX;
+            }
+            if val[diag_pos[i]] == 0.0 {
+                return None;
+            }
+        }
+
+        Some(Self { lu, diag_pos })
+    }
+}
+
+impl Preconditioner for Ilu0 {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let n = r.len();
+        let ptr = self.lu.row_ptr();
+        let idx = self.lu.col_idx();
+        let val = self.lu.values();
+        // L y = r, L has a unit diagonal
+        for i in 0..n {
+            let mut s = r[i];
+            for p in ptr[i]..self.diag_pos[i] {
+                s -= val[p] * z[idx[p]];
+            }
+            z[i] = s;
+        }
+        // U z = y
+        for i in (0..n).rev() {
+            let mut s = z[i];
+            for p in self.diag_pos[i] + 1..ptr[i + 1] {
+                s -= val[p] * z[idx[p]];
+            }
+            z[i] = s / val[self.diag_pos[i]];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrixxd::MatrixXd;
+
+    // tridiagonal matrices have no fill-in, so the incomplete factorizations are exact
+    fn tridiagonal(n: usize, sym: bool) -> CsrMatrix {
+        let mut coo = CooMatrix::new(n, n);
+        for i in 0..n {
+            coo.push(i, i, 4.0 + i as f64 * 0.1);
+            if i + 1 < n {
+                coo.push(i, i + 1, -1.0);
+                coo.push(i + 1, i, if sym { -1.0 } else { -2.0 });
+            }
+        }
+        coo.to_csr()
+    }
+
+    fn check_inverse<P: Preconditioner>(p: &P, a: &CsrMatrix) {
+        let x: Vec<f64> = (0..a.get_rows()).map(|i| i as f64 - 2.5).collect();
+        let b = a.mul_vec(&x);
+        let mut z = vec![0.0; x.len()];
+        p.apply(&b, &mut z);
+        for (u, v) in z.iter().zip(&x) {
+            assert!((u - v).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn exact_on_tridiagonal() {
+        let a = tridiagonal(8, true);
+        check_inverse(&IncompleteCholesky::new(&a).unwrap(), &a);
+        let a = tridiagonal(8, false);
+        check_inverse(&Ilu0::new(&a).unwrap(), &a);
+    }
+
+    #[test]
+    fn ssor_on_diagonal_matrix() {
+        let a = CsrMatrix::from_dense(&MatrixXd::from_fn(4, 4, |i, j| if i == j { 2.0 } else { 0.0 }));
+        // for omega = 1 and a diagonal matrix SSOR is exactly D^-1
+        check_inverse(&Ssor::new(&a, 1.0).unwrap(), &a);
+        assert!(Ssor::new(&a, 2.0).is_none());
+    }
+
+    #[test]
+    fn jacobi_and_closures() {
+        let j = Jacobi::new(&[2.0, 4.0]).unwrap();
+        let mut z = [0.0; 2];
+        j.apply(&[1.0, 1.0], &mut z);
+        assert_eq!(z, [0.5, 0.25]);
+        assert!(Jacobi::new(&[1.0, 0.0]).is_none());
+
+        let double = |r: &[f64], z: &mut [f64]| z.iter_mut().zip(r).for_each(|(z, r)| *z = 2.0 * r);
+        double.apply(&[1.0, 3.0], &mut z);
+        assert_eq!(z, [2.0, 6.0]);
+    }
+
+    #[test]
+    fn incomplete_cholesky_rejects_indefinite() {
+        let a = CsrMatrix::from_dense(&MatrixXd::new(2, 2, vec![1.0, 2.0, 2.0, 1.0]));
+        assert!(IncompleteCholesky::new(&a).is_none());
+    }
+}
@@ -0,0 +1,386 @@
+// Robust geometric predicates on Vector2d and Vector3d (Shewchuk, "Adaptive Precision
+// Floating-Point Arithmetic and Fast Robust Geometric Predicates").
+//
+// The determinant is first evaluated in plain floating point and returned
+// when it is larger than a bound on its rounding error, which covers almost
+// every call. Otherwise it is recomputed exactly as an expansion: a sum of
+// non-overlapping f64s ordered by increasing magnitude, whose largest
+// component has the sign of the exact value. The result is an approximation
+// of the determinant whose sign is always right.
+
+use super::vector2d::*;
+use super::vector3d::*;
+use crate::math;
+
+const EPSILON: f64 = f64::EPSILON / 2.0;
+const CCW_ERR_BOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+const ICC_ERR_BOUND: f64 = (10.0 + 96.0 * EPSILON) * EPSILON;
+const O3D_ERR_BOUND: f64 = (7.0 + 56.0 * EPSILON) * EPSILON;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    CounterClockwise,
+    Clockwise,
+    Collinear,
+}
+
+impl Orientation {
+    pub fn from_sign(x: f64) -> Self {
+        if x > 0.0 {
+            Orientation::CounterClockwise
+        } else if x < 0.0 {
+            Orientation::Clockwise
+        } else {
+            Orientation::Collinear
+        }
+    }
+}
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let bv = x - a;
+    let av = x - bv;
+    (x, (a - av) + (b - bv))
+}
+
+fn fast_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    (x, b - (x - a))
+}
+
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    (x, math::fma(a, b, -x))
+}
+
+// an expansion of at most N components, smallest first
+#[derive(Clone, Copy)]
+struct Expansion<const N: usize> {
+    c: [f64; N],
+    n: usize,
+}
+
+impl<const N: usize> Expansion<N> {
+    fn empty() -> Self {
+        Self { c: [0.0; N], n: 0 }
+    }
+
+    fn terms(&self) -> &[f64] {
+        &self.c[..self.n]
+    }
+
+    fn push(&mut self, x: f64) {
+        self.c[self.n] = x;
+        self.n += 1;
+    }
+
+    // exact a - b
+    fn diff(a: f64, b: f64) -> Self {
+        let (x, y) = two_sum(a, -b);
+        let mut e = Self::empty();
+        e.push(y);
+        e.push(x);
+        e
+    }
+
+    fn neg(mut self) -> Self {
+        for x in &mut self.c[..self.n] {
+            *x = -*x;
+        }
+        self
+    }
+
+    // merges e and f by magnitude and renormalises, dropping zeros
+    fn sum(e: &[f64], f: &[f64]) -> Self {
+        let mut h = Self::empty();
+        let (mut i, mut j) = (0, 0);
+        let mut next = || {
+            let take_e = j == f.len() || (i < e.len() && e[i].abs() <= f[j].abs());
+            if take_e {
+                i += 1;
+                e[i - 1]
+            } else {
+                j += 1;
+                f[j - 1]
+            }
+        };
+        let mut q = next();
+        for _ in 1..e.len() + f.len() {
+            let (s, t) = two_sum(q, next());
+            if t != 0.0 {
+                h.push(t);
+            }
+            q = s;
+        }
+        if q != 0.0 || h.n == 0 {
+            h.push(q);
+        }
+        h
+    }
+
+    fn scale(e: &[f64], b: f64) -> Self {
+        let mut h = Self::empty();
+        let (mut q, t) = two_product(e[0], b);
+        if t != 0.0 {
+            h.push(t);
+        }
+        for &x in &e[1..] {
+            let (p1, p0) = two_product(x, b);
+            let (s, t) = two_sum(q, p0);
+            if t != 0.0 {
+                h.push(t);
+            }
+            let (s, t) = fast_two_sum(p1, s);
+            if t != 0.0 {
+                h.push(t);
+            }
+            q = s;
+        }
+        if q != 0.0 || h.n == 0 {
+            h.push(q);
+        }
+        h
+    }
+
+    fn product(e: &[f64], f: &[f64]) -> Self {
+        let mut acc = Self::scale(e, f[0]);
+        for &x in &f[1..] {
+            let t = Self::scale(e, x);
+            acc = Self::sum(acc.terms(), t.terms());
+        }
+        acc
+    }
+
+    // sign-exact approximation of the value
+    fn estimate(&self) -> f64 {
+        self.terms().iter().sum()
+    }
+}
+
+// positive when a, b, c turn counter-clockwise, negative when clockwise and
+// zero when collinear; twice the signed area of the triangle
+pub fn orient2d(a: Vector2d, b: Vector2d, c: Vector2d) -> f64 {
+    let left = (a.x - c.x) * (b.y - c.y);
+    let right = (a.y - c.y) * (b.x - c.x);
+    let det = left - right;
+    if det.abs() >= CCW_ERR_BOUND * (left.abs() + right.abs()) {
+        return det;
+    }
+    orient2d_exact(a, b, c)
+}
+
+fn orient2d_exact(a: Vector2d, b: Vector2d, c: Vector2d) -> f64 {
+    type E2 = Expansion<2>;
+    type E8 = Expansion<8>;
+    let (acx, bcy) = (E2::diff(a.x, c.x), E2::diff(b.y, c.y));
+    let (acy, bcx) = (E2::diff(a.y, c.y), E2::diff(b.x, c.x));
+    let left = E8::product(acx.terms(), bcy.terms());
+    let right = E8::product(acy.terms(), bcx.terms()).neg();
+    Expansion::<16>::sum(left.terms(), right.terms()).estimate()
+}
+
+pub fn orientation(a: Vector2d, b: Vector2d, c: Vector2d) -> Orientation {
+    Orientation::from_sign(orient2d(a, b, c))
+}
+
+// positive when d lies inside the circle through a, b, c (counter-clockwise),
+// negative outside and zero on it; the sign flips for clockwise a, b, c
+pub fn incircle(a: Vector2d, b: Vector2d, c: Vector2d, d: Vector2d) -> f64 {
+    let (adx, ady) = (a.x - d.x, a.y - d.y);
+    let (bdx, bdy) = (b.x - d.x, b.y - d.y);
+    let (cdx, cdy) = (c.x - d.x, c.y - d.y);
+    let (bdxcdy, cdxbdy) = (bdx * cdy, cdx * bdy);
+    let (cdxady, adxcdy) = (cdx * ady, adx * cdy);
+    let (adxbdy, bdxady) = (adx * bdy, bdx * ady);
+    let alift = adx * adx + ady * ady;
+    let blift = bdx * bdx + bdy * bdy;
+    let clift = cdx * cdx + cdy * cdy;
+    let det = alift * (bdxcdy - cdxbdy) + blift * (cdxady - adxcdy) + clift * (adxbdy - bdxady);
+    let permanent = (bdxcdy.abs() + cdxbdy.abs()) * alift
+        + (cdxady.abs() + adxcdy.abs()) * blift
+        + (adxbdy.abs() + bdxady.abs()) * clift;
+    if det.abs() >= ICC_ERR_BOUND * permanent {
+        return det;
+    }
+    incircle_exact(a, b, c, d)
+}
+
+fn incircle_exact(a: Vector2d, b: Vector2d, c: Vector2d, d: Vector2d) -> f64 {
+    type E2 = Expansion<2>;
+    type E8 = Expansion<8>;
+    type E16 = Expansion<16>;
+    type E512 = Expansion<512>;
+    let dx = [a, b, c].map(|p| E2::diff(p.x, d.x));
+    let dy = [a, b, c].map(|p| E2::diff(p.y, d.y));
+    // lift_i * (dx_j dy_k - dx_k dy_j) over the cyclic (i, j, k)
+    let term = |i: usize, j: usize, k: usize| {
+        let lift = E16::sum(E8::product(dx[i].terms(), dx[i].terms()).terms(), E8::product(dy[i].terms(), dy[i].terms()).terms());
+        let p = E8::product(dx[j].terms(), dy[k].terms());
+        let q = E8::product(dx[k].terms(), dy[j].terms()).neg();
+        E512::product(lift.terms(), E16::sum(p.terms(), q.terms()).terms())
+    };
+    let (ta, tb, tc) = (term(0, 1, 2), term(1, 2, 0), term(2, 0, 1));
+    let ab = Expansion::<1024>::sum(ta.terms(), tb.terms());
+    Expansion::<1536>::sum(ab.terms(), tc.terms()).estimate()
+}
+
+// positive when d lies above the plane through a, b, c, on the side that
+// (b - a) x (c - a) points to, negative below and zero on it; six times the
+// signed volume of the tetrahedron
+pub fn orient3d(a: Vector3d, b: Vector3d, c: Vector3d, d: Vector3d) -> f64 {
+    let (adx, ady, adz) = (a.x - d.x, a.y - d.y, a.z - d.z);
+    let (bdx, bdy, bdz) = (b.x - d.x, b.y - d.y, b.z - d.z);
+    let (cdx, cdy, cdz) = (c.x - d.x, c.y - d.y, c.z - d.z);
+    let (bdxcdy, cdxbdy) = (bdx * cdy, cdx * bdy);
+    let (cdxady, adxcdy) = (cdx * ady, adx * cdy);
+    let (adxbdy, bdxady) = (adx * bdy, bdx * ady);
+    let det = adz * (bdxcdy - cdxbdy) + bdz * (cdxady - adxcdy) + cdz * (adxbdy - bdxady);
+    let permanent = (bdxcdy.abs() + cdxbdy.abs()) * adz.abs()
+        + (cdxady.abs() + adxcdy.abs()) * bdz.abs()
+        + (adxbdy.abs() + bdxady.abs()) * cdz.abs();
+    if det.abs() >= O3D_ERR_BOUND * permanent {
+        return -det;
+    }
+    -orient3d_exact(a, b, c, d)
+}
+
+// det(a - d, b - d, c - d)
+fn orient3d_exact(a: Vector3d, b: Vector3d, c: Vector3d, d: Vector3d) -> f64 {
+    type E2 = Expansion<2>;
+    type E8 = Expansion<8>;
+    type E16 = Expansion<16>;
+    type E64 = Expansion<64>;
+    let dx = [a, b, c].map(|p| E2::diff(p.x, d.x));
+    let dy = [a, b, c].map(|p| E2::diff(p.y, d.y));
+    let dz = [a, b, c].map(|p| E2::diff(p.z, d.z));
+    // dz_i * (dx_j dy_k - dx_k dy_j) over the cyclic (i, j, k)
+    let term = |i: usize, j: usize, k: usize| {
+        let p = E8::product(dx[j].terms(), dy[k].terms());
+        let q = E8::product(dx[k].terms(), dy[j].terms()).neg();
+        E64::product(E16::sum(p.terms(), q.terms()).terms(), dz[i].terms())
+    };
+    let (ta, tb, tc) = (term(0, 1, 2), term(1, 2, 0), term(2, 0, 1));
+    let ab = Expansion::<128>::sum(ta.terms(), tb.terms());
+    Expansion::<192>::sum(ab.terms(), tc.terms()).estimate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2(x: f64, y: f64) -> Vector2d {
+        Vector2d::new_from([x, y])
+    }
+
+    // exact sign from integer coordinates in units of 2^-scale
+    fn int(x: f64, scale: i32) -> i128 {
+        let s = x * 2f64.powi(scale);
+        assert_eq!(s, s.trunc());
+        s as i128
+    }
+
+    #[test]
+    fn orient2d_near_degenerate() {
+        // points a few ulps off the line y = x (Kettner et al.)
+        let (q, r) = (v2(12.0, 12.0), v2(24.0, 24.0));
+        let ulp = 2f64.powi(-53);
+        let mut naive_wrong = 0;
+        for i in 0..64 {
+            for j in 0..64 {
+                let p = v2(0.5 + i as f64 * ulp, 0.5 + j as f64 * ulp);
+                let [px, py, qx, qy, rx, ry] = [p.x, p.y, q.x, q.y, r.x, r.y].map(|x| int(x, 53));
+                let exact = ((px - rx) * (qy - ry) - (py - ry) * (qx - rx)).signum();
+                let got = orient2d(p, q, r);
+                assert_eq!(Orientation::from_sign(got), Orientation::from_sign(exact as f64));
+                let naive = (p.x - r.x) * (q.y - r.y) - (p.y - r.y) * (q.x - r.x);
+                naive_wrong += (naive.signum() != got.signum() || (naive == 0.0) != (got == 0.0)) as usize;
+            }
+        }
+        assert!(naive_wrong > 0);
+        assert_eq!(orientation(v2(0.0, 0.0), v2(1.0, 0.0), v2(0.0, 1.0)), Orientation::CounterClockwise);
+        assert_eq!(orient2d(v2(0.0, 0.0), v2(1.0, 0.0), v2(0.0, 1.0)), 1.0);
+        assert_eq!(orientation(v2(0.1, 0.1), v2(0.3, 0.3), v2(0.7, 0.7)), Orientation::from_sign(orient2d_exact(v2(0.1, 0.1), v2(0.3, 0.3), v2(0.7, 0.7))));
+    }
+
+    #[test]
+    fn incircle_near_degenerate() {
+        let (a, b, c) = (v2(1.0, 0.0), v2(0.0, 1.0), v2(-1.0, 0.0));
+        assert_eq!(incircle(a, b, c, v2(0.0, -1.0)), 0.0);
+        assert!(incircle(a, b, c, v2(0.0, 0.0)) > 0.0);
+        assert!(incircle(a, b, c, v2(2.0, 0.0)) < 0.0);
+        assert!(incircle(c, b, a, v2(0.0, 0.0)) < 0.0);
+
+        // walk a point outwards across the circle through a, b, c one ulp at a
+        // time: the sign can only go from inside to outside, and must not
+        // depend on the order of a, b, c
+        let (a, b, c) = (v2(0.1, 0.2), v2(0.7, 0.3), v2(0.4, 0.9));
+        let d = 2.0 * (a.x * (b.y - c.y) + b.x * (c.y - a.y) + c.x * (a.y - b.y));
+        let sq = |p: Vector2d| p * p;
+        let ux = (sq(a) * (b.y - c.y) + sq(b) * (c.y - a.y) + sq(c) * (a.y - b.y)) / d;
+        let uy = (sq(a) * (c.x - b.x) + sq(b) * (a.x - c.x) + sq(c) * (b.x - a.x)) / d;
+        let x0 = ux + (a - v2(ux, uy)).length();
+        let naive = |p: Vector2d| {
+            let [(adx, ady), (bdx, bdy), (cdx, cdy)] = [a, b, c].map(|q| (q.x - p.x, q.y - p.y));
+            (adx * adx + ady * ady) * (bdx * cdy - cdx * bdy) + (bdx * bdx + bdy * bdy) * (cdx * ady - adx * cdy)
+                + (cdx * cdx + cdy * cdy) * (adx * bdy - bdx * ady)
+        };
+        let (mut prev, mut exact_path) = (1.0, 0);
+        let mut x = x0;
+        for _ in 0..300 {
+            x = x.next_down();
+        }
+        for _ in 0..600 {
+            let p = v2(x, uy);
+            let s = incircle(a, b, c, p).signum();
+            assert!(s <= prev, "sign went back inside at x = {}", x);
+            assert_eq!(incircle(b, c, a, p).signum(), s);
+            assert_eq!(incircle(b, a, c, p).signum(), -s);
+            // the filter gave up and the value came from the expansion
+            exact_path += (incircle(a, b, c, p) != naive(p)) as usize;
+            prev = s;
+            x = x.next_up();
+        }
+        assert_eq!(prev, -1.0);
+        assert!(exact_path > 0);
+    }
+
+    #[test]
+    fn orient3d_near_degenerate() {
+        let v3 = |x: f64, y: f64, z: f64| Vector3d::new_from([x, y, z]);
+        let (a, b, c) = (v3(0.0, 0.0, 0.0), v3(1.0, 0.0, 0.0), v3(0.0, 1.0, 0.0));
+        assert_eq!(orient3d(a, b, c, v3(0.3, 0.3, 1.0)), 1.0);
+        assert_eq!(orient3d(b, a, c, v3(0.3, 0.3, 1.0)), -1.0);
+        assert_eq!(orient3d(a, b, c, v3(5.0, -7.0, 0.0)), 0.0);
+
+        // points a few ulps either side of the plane x + y + z = 1.5 through
+        // three points on the axes; (b - a) x (c - a) points along (1, 1, 1)
+        let (a, b, c) = (v3(1.5, 0.0, 0.0), v3(0.0, 1.5, 0.0), v3(0.0, 0.0, 1.5));
+        let ulp = 2f64.powi(-53);
+        let (mut naive_wrong, mut seen) = (0, [false; 3]);
+        for i in 0..32 {
+            for j in 0..32 {
+                let d = v3(0.5 + i as f64 * ulp, 0.5 + j as f64 * ulp, 0.5 - 16.0 * ulp);
+                let exact = (int(d.x, 53) + int(d.y, 53) + int(d.z, 53) - int(1.5, 53)).signum();
+                let got = orient3d(a, b, c, d);
+                assert_eq!(Orientation::from_sign(got), Orientation::from_sign(exact as f64));
+                assert_eq!(orient3d(b, c, a, d).signum(), got.signum());
+                seen[Orientation::from_sign(got) as usize] = true;
+                let naive = (d - a) * (b - a).cross(c - a);
+                naive_wrong += (naive.signum() != got.signum() || (naive == 0.0) != (got == 0.0)) as usize;
+            }
+        }
+        assert_eq!(seen, [true; 3]);
+        assert!(naive_wrong > 0);
+    }
+
+    #[test]
+    fn expansions_are_exact() {
+        // 1 + 2^-60 - 1 is lost in floating point but not in an expansion
+        let tiny = 2f64.powi(-60);
+        let e = Expansion::<4>::sum(&[tiny, 1.0], &[-1.0]);
+        assert_eq!(e.terms(), [tiny]);
+        let p = Expansion::<8>::product(&[tiny, 1.0], &[tiny, 1.0]);
+        assert_eq!(p.terms(), [tiny * tiny, 2.0 * tiny, 1.0]);
+        assert_eq!(Expansion::<2>::diff(1.0, tiny).terms(), [-tiny, 1.0]);
+    }
+}
@@ -0,0 +1,418 @@
+// Convex hull of a 3d point cloud by quickhull.
+//
+// Starting from a tetrahedron of extreme points, every face keeps the points
+// above it. The furthest of them is added by removing all the faces it sees
+// and joining the horizon around them to it, until no point is left outside.
+// Above and below are decided with the exact orient3d, so the surface is
+// always closed and convex; flat regions are triangulated and may keep
+// coplanar points as vertices.
+
+use super::gjk::Support;
+use super::predicates::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use alloc::vec::Vec;
+
+struct Face {
+    v: [usize; 3],
+    // n[i] is the face across the edge opposite v[i]
+    n: [usize; 3],
+    // points above the face that no earlier face claimed
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Hull {
+    vertices: Vec<Vector3d>,
+    indices: Vec<usize>,
+    faces: Vec<[usize; 3]>,
+    normals: Vec<Vector3d>,
+    neighbors: Vec<[usize; 3]>,
+    flat: bool,
+}
+
+fn collinear(a: Vector3d, b: Vector3d, c: Vector3d) -> bool {
+    let [xy, yz, zx] = [(0, 1), (1, 2), (2, 0)].map(|(i, j)| {
+        let p = |v: Vector3d| Vector2d::new_from([v.to_list()[i], v.to_list()[j]]);
+        orient2d(p(a), p(b), p(c))
+    });
+    xy == 0.0 && yz == 0.0 && zx == 0.0
+}
+
+// three points spanning a large triangle, None when all are on a line
+fn initial_triangle(points: &[Vector3d]) -> Option<[usize; 3]> {
+    let i0 = (0..points.len()).min_by(|&a, &b| points[a].x.total_cmp(&points[b].x))?;
+    let a = points[i0];
+    let i1 = (0..points.len()).max_by(|&x, &y| (points[x] - a).length().total_cmp(&(points[y] - a).length()))?;
+    if points[i1] == a {
+        return None;
+    }
+    let b = points[i1];
+    let area = |i: usize| (points[i] - a).cross(b - a).length();
+    let i2 = (0..points.len()).max_by(|&x, &y| area(x).total_cmp(&area(y)))?;
+    if !collinear(a, b, points[i2]) {
+        return Some([i0, i1, i2]);
+    }
+    // rounding hid a sliver the exact test still sees
+    (0..points.len()).find(|&i| !collinear(a, b, points[i])).map(|i2| [i0, i1, i2])
+}
+
+// adjacency of a closed surface: the face holding each edge reversed
+fn link(faces: &[[usize; 3]]) -> Vec<[usize; 3]> {
+    let mut edges: Vec<(usize, usize, usize)> = Vec::with_capacity(faces.len() * 3);
+    for (f, v) in faces.iter().enumerate() {
+        for i in 0..3 {
+            edges.push((v[(i + 1) % 3], v[(i + 2) % 3], f));
+        }
+    }
+    edges.sort_unstable();
+    let across = |a: usize, b: usize| {
+        let at = edges.partition_point(|e| (e.0, e.1) < (b, a));
+        edges[at].2
+    };
+    faces.iter().map(|v| [0, 1, 2].map(|i| across(v[(i + 1) % 3], v[(i + 2) % 3]))).collect()
+}
+
+impl Hull {
+    // None when the points do not span a triangle; coplanar points give a
+    // flat hull of zero volume, with the polygon triangulated on both sides
+    pub fn new(points: &[Vector3d]) -> Option<Self> {
+        let [i0, i1, i2] = initial_triangle(points)?;
+        let (a, b, c) = (points[i0], points[i1], points[i2]);
+        let height = |i: usize| orient3d(a, b, c, points[i]).abs();
+        let i3 = (0..points.len()).max_by(|&x, &y| height(x).total_cmp(&height(y)))?;
+        if height(i3) == 0.0 {
+            return Some(Self::flat(points, [i0, i1, i2]));
+        }
+        let above = |v: [usize; 3], p: usize| orient3d(points[v[0]], points[v[1]], points[v[2]], points[p]);
+
+        // the tetrahedron, every face turned away from its fourth vertex
+        let simplex = [i0, i1, i2, i3];
+        let mut initial: Vec<[usize; 3]> = Vec::with_capacity(4);
+        for k in 0..4 {
+            let [p, q, r] = [1, 2, 3].map(|j| simplex[(k + j) % 4]);
+            initial.push(if above([p, q, r], simplex[k]) > 0.0 { [p, r, q] } else { [p, q, r] });
+        }
+        let mut faces: Vec<Face> = initial.iter().zip(link(&initial)).map(|(&v, n)| Face { v, n, outside: Vec::new(), alive: true }).collect();
+        for p in (0..points.len()).filter(|p| !simplex.contains(p)) {
+            if let Some(f) = faces.iter_mut().find(|f| above(f.v, p) > 0.0) {
+                f.outside.push(p);
+            }
+        }
+
+        let mut pending: Vec<usize> = (0..4).collect();
+        while let Some(f) = pending.pop() {
+            if !faces[f].alive || faces[f].outside.is_empty() {
+                continue;
+            }
+            let fv = faces[f].v;
+            let eye = *faces[f].outside.iter().max_by(|&&x, &&y| above(fv, x).total_cmp(&above(fv, y))).unwrap();
+            // the faces the eye sees and the horizon edges (a, b, face
+            // beyond, visible face) around them, a -> b as in the visible face
+            faces[f].alive = false;
+            let mut visible = alloc::vec![f];
+            let mut horizon: Vec<(usize, usize, usize, usize)> = Vec::new();
+            let mut k = 0;
+            while k < visible.len() {
+                let t = visible[k];
+                k += 1;
+                let (v, n) = (faces[t].v, faces[t].n);
+                for i in 0..3 {
+                    let u = n[i];
+                    if !faces[u].alive {
+                        continue;
+                    }
+                    if above(faces[u].v, eye) > 0.0 {
+                        faces[u].alive = false;
+                        visible.push(u);
+                    } else {
+                        horizon.push((v[(i + 1) % 3], v[(i + 2) % 3], u, t));
+                    }
+                }
+            }
+            let orphans: Vec<usize> =
+                visible.iter().flat_map(|&t| core::mem::take(&mut faces[t].outside)).filter(|&p| p != eye).collect();
+            // the cone from the eye over the horizon
+            let first = faces.len();
+            for &(a, b, u, old) in &horizon {
+                let id = faces.len();
+                faces.push(Face { v: [a, b, eye], n: [0, 0, u], outside: Vec::new(), alive: true });
+                let slot = faces[u].n.iter().position(|&x| x == old).unwrap();
+                faces[u].n[slot] = id;
+            }
+            // [a, b, eye] meets [b, c, eye] along b - eye
+            for id in first..faces.len() {
+                let b = faces[id].v[1];
+                let next = (first..faces.len()).find(|&g| faces[g].v[0] == b).unwrap();
+                faces[id].n[0] = next;
+                faces[next].n[1] = id;
+            }
+            // a point outside the new hull is above one of the new faces
+            for p in orphans {
+                if let Some(g) = (first..faces.len()).find(|&g| above(faces[g].v, p) > 0.0) {
+                    faces[g].outside.push(p);
+                }
+            }
+            pending.extend(first..faces.len());
+        }
+        let kept: Vec<[usize; 3]> = faces.iter().filter(|f| f.alive).map(|f| f.v).collect();
+        Some(Self::assemble(points, &kept, false))
+    }
+
+    // convex polygon of coplanar points, triangulated as a fan from its first
+    // vertex on top and from its second one underneath so no edge is shared
+    // by more than two faces
+    fn flat(points: &[Vector3d], [i0, i1, i2]: [usize; 3]) -> Self {
+        let normal = (points[i1] - points[i0]).cross(points[i2] - points[i0]).to_list();
+        let k = (0..3).max_by(|&x, &y| normal[x].abs().total_cmp(&normal[y].abs())).unwrap();
+        let project = |i: usize| {
+            let l = points[i].to_list();
+            Vector2d::new_from([l[(k + 1) % 3], l[(k + 2) % 3]])
+        };
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        order.sort_by(|&a, &b| project(a).x.total_cmp(&project(b).x).then(project(a).y.total_cmp(&project(b).y)));
+        order.dedup_by(|a, b| project(*a) == project(*b));
+        // monotone chain, lower then upper
+        let mut ring: Vec<usize> = Vec::new();
+        let turn = |ring: &[usize], i: usize| orient2d(project(ring[ring.len() - 2]), project(ring[ring.len() - 1]), project(i));
+        for &i in &order {
+            while ring.len() >= 2 && turn(&ring, i) <= 0.0 {
+                ring.pop();
+            }
+            ring.push(i);
+        }
+        let lower = ring.len() + 1;
+        for &i in order.iter().rev().skip(1) {
+            while ring.len() >= lower && turn(&ring, i) <= 0.0 {
+                ring.pop();
+            }
+            ring.push(i);
+        }
+        ring.pop();
+        let m = ring.len();
+        let mut faces: Vec<[usize; 3]> = (1..m - 1).map(|j| [ring[0], ring[j], ring[j + 1]]).collect();
+        faces.extend((2..m).map(|j| [ring[1], ring[(j + 1) % m], ring[j]]));
+        Self::assemble(points, &faces, true)
+    }
+
+    fn assemble(points: &[Vector3d], faces: &[[usize; 3]], flat: bool) -> Self {
+        let mut indices: Vec<usize> = faces.iter().flatten().copied().collect();
+        indices.sort_unstable();
+        indices.dedup();
+        let faces: Vec<[usize; 3]> = faces.iter().map(|f| f.map(|i| indices.binary_search(&i).unwrap())).collect();
+        let vertices: Vec<Vector3d> = indices.iter().map(|&i| points[i]).collect();
+        let normals = faces
+            .iter()
+            .map(|f| {
+                let n = (vertices[f[1]] - vertices[f[0]]).cross(vertices[f[2]] - vertices[f[0]]);
+                n / n.length()
+            })
+            .collect();
+        let neighbors = link(&faces);
+        Self { vertices, indices, faces, normals, neighbors, flat }
+    }
+
+    pub fn vertices(&self) -> &[Vector3d] {
+        &self.vertices
+    }
+
+    // position of each vertex in the input
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    // counter-clockwise seen from outside
+    pub fn faces(&self) -> &[[usize; 3]] {
+        &self.faces
+    }
+
+    // unit, pointing out
+    pub fn normals(&self) -> &[Vector3d] {
+        &self.normals
+    }
+
+    // neighbors()[f][i] is the face across the edge opposite faces()[f][i]
+    pub fn neighbors(&self) -> &[[usize; 3]] {
+        &self.neighbors
+    }
+
+    // all points are on one plane
+    pub fn is_flat(&self) -> bool {
+        self.flat
+    }
+
+    fn corners(&self, f: usize) -> [Vector3d; 3] {
+        self.faces[f].map(|i| self.vertices[i])
+    }
+
+    pub fn volume(&self) -> f64 {
+        if self.flat {
+            return 0.0;
+        }
+        let o = self.vertices[0];
+        (0..self.faces.len())
+            .map(|f| {
+                let [a, b, c] = self.corners(f);
+                (a - o) * (b - o).cross(c - o)
+            })
+            .sum::<f64>()
+            / 6.0
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        (0..self.faces.len())
+            .map(|f| {
+                let [a, b, c] = self.corners(f);
+                (b - a).cross(c - a).length()
+            })
+            .sum::<f64>()
+            * 0.5
+    }
+
+    // centre of mass of the solid, of the polygon when flat
+    pub fn centroid(&self) -> Vector3d {
+        let o = self.vertices[0];
+        let (mut sum, mut weight) = (Vector3d::new(), 0.0);
+        for f in 0..self.faces.len() {
+            let [a, b, c] = self.corners(f);
+            // tetrahedra against the first vertex, or the faces themselves
+            let (w, centre) = if self.flat {
+                ((b - a).cross(c - a).length(), (a + b + c) / 3.0)
+            } else {
+                ((a - o) * (b - o).cross(c - o), (o + a + b + c) / 4.0)
+            };
+            sum = sum + centre * w;
+            weight += w;
+        }
+        sum / weight
+    }
+}
+
+impl Support<Vector3d> for Hull {
+    fn support(&self, dir: Vector3d) -> Vector3d {
+        *self.vertices.iter().max_by(|&&a, &&b| (a * dir).total_cmp(&(b * dir))).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+    use crate::gjk::gjk_intersect;
+    use crate::math;
+
+    fn v3(x: f64, y: f64, z: f64) -> Vector3d {
+        Vector3d::new_from([x, y, z])
+    }
+
+    // closed, consistently linked and every input point on or below each face
+    fn check(hull: &Hull, points: &[Vector3d]) {
+        let (faces, v) = (hull.faces(), hull.vertices());
+        for (f, face) in faces.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (face[(i + 1) % 3], face[(i + 2) % 3]);
+                let g = hull.neighbors()[f][i];
+                let j = (0..3).find(|&j| (faces[g][(j + 1) % 3], faces[g][(j + 2) % 3]) == (b, a)).unwrap();
+                assert_eq!(hull.neighbors()[g][j], f);
+            }
+            let [a, b, c] = face.map(|i| v[i]);
+            assert!(points.iter().all(|&p| orient3d(a, b, c, p) <= 0.0));
+            assert!((hull.normals()[f].length() - 1.0).abs() < 1e-12);
+        }
+        for (k, &i) in hull.indices().iter().enumerate() {
+            assert_eq!(points[i], v[k]);
+        }
+        // Euler, with every edge shared by two faces
+        assert_eq!(v.len() + faces.len() - faces.len() * 3 / 2, 2);
+    }
+
+    #[test]
+    fn cube_with_inner_and_repeated_points() {
+        let mut points: Vec<Vector3d> = (0..8).map(|i| v3((i & 1) as f64, ((i >> 1) & 1) as f64, (i >> 2) as f64)).collect();
+        for i in 0..200 {
+            let t = i as f64;
+            points.push(v3(0.5 + 0.45 * math::sin(t * 1.3), 0.5 + 0.45 * math::cos(t * 2.1), 0.5 + 0.45 * math::sin(t * 0.7)));
+        }
+        points.extend([v3(1.0, 1.0, 1.0), v3(0.0, 0.0, 0.0)]);
+        let hull = Hull::new(&points).unwrap();
+        check(&hull, &points);
+        assert_eq!(hull.vertices().len(), 8);
+        assert_eq!(hull.faces().len(), 12);
+        assert!(!hull.is_flat());
+        assert!((hull.volume() - 1.0).abs() < 1e-12);
+        assert!((hull.surface_area() - 6.0).abs() < 1e-12);
+        assert!((hull.centroid() - v3(0.5, 0.5, 0.5)).length() < 1e-12);
+        // normals are the axes, pointing away from the centre
+        for (f, n) in hull.normals().iter().enumerate() {
+            let p = hull.vertices()[hull.faces()[f][0]];
+            assert!((*n - v3(0.5, 0.5, 0.5)) * *n > 0.0 && (p - v3(0.5, 0.5, 0.5)) * *n == 0.5);
+        }
+    }
+
+    #[test]
+    fn points_on_a_sphere_are_all_vertices() {
+        // Fibonacci sphere with a shell of interior points
+        let n = 400;
+        let golden = core::f64::consts::PI * (3.0 - math::sqrt(5.0));
+        let mut points: Vec<Vector3d> = (0..n)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+                let r = math::sqrt(1.0 - y * y);
+                let t = golden * i as f64;
+                v3(r * math::cos(t), y, r * math::sin(t))
+            })
+            .collect();
+        let inner: Vec<Vector3d> = points.iter().map(|&p| p * 0.9).collect();
+        points.extend(inner);
+        let hull = Hull::new(&points).unwrap();
+        check(&hull, &points);
+        assert_eq!(hull.vertices().len(), n);
+        assert_eq!(hull.indices(), (0..n).collect::<Vec<_>>());
+        let sphere_volume = 4.0 / 3.0 * core::f64::consts::PI;
+        assert!(hull.volume() < sphere_volume && hull.volume() > 0.98 * sphere_volume);
+        assert!(hull.surface_area() < 4.0 * core::f64::consts::PI && hull.surface_area() > 0.98 * 4.0 * core::f64::consts::PI);
+        assert!(hull.centroid().length() < 1e-3);
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        assert!(Hull::new(&[]).is_none());
+        assert!(Hull::new(&[v3(1.0, 2.0, 3.0); 5]).is_none());
+        let line: Vec<Vector3d> = (0..6).map(|i| v3(0.5 * i as f64, 0.25 * i as f64, 1.0 - 2.0 * i as f64)).collect();
+        assert!(Hull::new(&line).is_none());
+
+        // a tilted lattice: flat, with both sides
+        let (u, w) = (v3(1.0, 0.0, 1.0), v3(0.0, 2.0, 0.0));
+        let plane: Vec<Vector3d> = (0..25).map(|i| u * (i % 5) as f64 + w * (i / 5) as f64).collect();
+        let hull = Hull::new(&plane).unwrap();
+        check(&hull, &plane);
+        assert!(hull.is_flat());
+        assert_eq!(hull.vertices().len(), 4);
+        assert_eq!(hull.faces().len(), 4);
+        let area = u.cross(w).length() * 16.0;
+        assert!((hull.surface_area() - 2.0 * area).abs() < 1e-9);
+        assert_eq!(hull.volume(), 0.0);
+        assert!((hull.centroid() - (u + w) * 2.0).length() < 1e-12);
+        let up = u.cross(w) / u.cross(w).length();
+        assert_eq!(hull.normals().iter().filter(|&&n| (n - up).length() < 1e-12).count(), 2);
+        assert_eq!(hull.normals().iter().filter(|&&n| (n + up).length() < 1e-12).count(), 2);
+
+        // a slab a few ulps thick is still a closed solid
+        let mut slab = plane.clone();
+        slab.push(v3(2.0, 4.0, 2.0) + up * 1e-15);
+        let hull = Hull::new(&slab).unwrap();
+        check(&hull, &slab);
+        assert!(!hull.is_flat());
+        assert!(hull.volume() > 0.0 && hull.volume() < 1e-13);
+    }
+
+    #[test]
+    fn hull_as_a_collision_shape() {
+        let points: Vec<Vector3d> = (0..8).map(|i| v3((i & 1) as f64 * 2.0 - 1.0, ((i >> 1) & 1) as f64 * 2.0 - 1.0, (i >> 2) as f64 * 2.0 - 1.0)).collect();
+        let hull = Hull::new(&points).unwrap();
+        assert_eq!(hull.support(v3(1.0, 1.0, -1.0)), v3(1.0, 1.0, -1.0));
+        assert!(gjk_intersect(&hull, &Sphere::new(v3(1.5, 1.5, 0.0), 0.8)));
+        assert!(!gjk_intersect(&hull, &Sphere::new(v3(1.5, 1.5, 0.0), 0.6)));
+    }
+}
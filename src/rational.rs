@@ -0,0 +1,588 @@
+// Exact arithmetic: a Rational scalar and the integer matrix algorithms that
+// need it (fraction-free Bareiss elimination, Hermite and Smith normal forms).
+//
+// Rational is an i64 fraction, always reduced with a positive denominator, so
+// two equal values compare equal field by field. The arithmetic goes through
+// i128 and panics when the reduced result doesn't fit back in i64, a wrong
+// exact result would be worse than no result. The integer matrix algorithms
+// also run in i128, with checked arithmetic since their intermediates can
+// outgrow even that, and panic on results outside of i64.
+
+use super::matrix2d::*;
+use super::matrix3d::*;
+use super::matrix4d::*;
+use super::vector2d::*;
+use super::vector3d::*;
+use super::vector4d::*;
+use crate::scalar::{lu_solve, Scalar};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i64,
+    den: i64,
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    a = a.abs();
+    b = b.abs();
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// (g, x, y) with a x + b y = g = gcd(a, b) >= 0
+fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (1, 0);
+    let (mut y0, mut y1) = (0, 1);
+    while r1 != 0 {
+        let q = r0.div_euclid(r1);
+        (r0, r1) = (r1, r0 - q * r1);
+        (x0, x1) = (x1, x0 - q * x1);
+        (y0, y1) = (y1, y0 - q * y1);
+    }
+    if r0 < 0 { (-r0, -x0, -y0) } else { (r0, x0, y0) }
+}
+
+fn narrow(x: i128) -> i64 {
+    i64::try_from(x).expect("exact integer result does not fit in i64")
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { num: 0, den: 1 };
+    pub const ONE: Rational = Rational { num: 1, den: 1 };
+
+    pub fn new(num: i64, den: i64) -> Self {
+        Self::reduce(num as i128, den as i128)
+    }
+
+    pub fn integer(n: i64) -> Self {
+        Self { num: n, den: 1 }
+    }
+
+    fn reduce(num: i128, den: i128) -> Self {
+        assert!(den != 0, "rational with a zero denominator");
+        let g = gcd(num, den);
+        let s = if den < 0 { -1 } else { 1 };
+        Self { num: narrow(s * num / g), den: narrow(s * den / g) }
+    }
+
+    pub fn numer(&self) -> i64 {
+        self.num
+    }
+
+    pub fn denom(&self) -> i64 {
+        self.den
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == 1
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { num: narrow((self.num as i128).abs()), den: self.den }
+    }
+
+    pub fn recip(&self) -> Self {
+        Self::reduce(self.den as i128, self.num as i128)
+    }
+
+    pub fn floor(&self) -> i64 {
+        self.num.div_euclid(self.den)
+    }
+
+    pub fn ceil(&self) -> i64 {
+        -(-self.num).div_euclid(self.den)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(n: i64) -> Self {
+        Self::integer(n)
+    }
+}
+
+impl core::fmt::Display for Rational {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, o: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(o))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, o: &Self) -> core::cmp::Ordering {
+        (self.num as i128 * o.den as i128).cmp(&(o.num as i128 * self.den as i128))
+    }
+}
+
+impl core::ops::Add<Rational> for Rational {
+    type Output = Rational;
+    fn add(self, o: Rational) -> Self::Output {
+        let (a, b, c, d) = (self.num as i128, self.den as i128, o.num as i128, o.den as i128);
+        Self::reduce(a * d + c * b, b * d)
+    }
+}
+
+impl core::ops::Sub<Rational> for Rational {
+    type Output = Rational;
+    fn sub(self, o: Rational) -> Self::Output {
+        self + -o
+    }
+}
+
+impl core::ops::Mul<Rational> for Rational {
+    type Output = Rational;
+    fn mul(self, o: Rational) -> Self::Output {
+        Self::reduce(self.num as i128 * o.num as i128, self.den as i128 * o.den as i128)
+    }
+}
+
+impl core::ops::Div<Rational> for Rational {
+    type Output = Rational;
+    fn div(self, o: Rational) -> Self::Output {
+        Self::reduce(self.num as i128 * o.den as i128, self.den as i128 * o.num as i128)
+    }
+}
+
+impl core::ops::Neg for Rational {
+    type Output = Rational;
+    fn neg(self) -> Self::Output {
+        Self { num: narrow(-(self.num as i128)), den: self.den }
+    }
+}
+
+impl core::ops::Add<i64> for Rational {
+    type Output = Rational;
+    fn add(self, o: i64) -> Self::Output {
+        self + Rational::integer(o)
+    }
+}
+
+impl core::ops::Sub<i64> for Rational {
+    type Output = Rational;
+    fn sub(self, o: i64) -> Self::Output {
+        self - Rational::integer(o)
+    }
+}
+
+impl core::ops::Mul<i64> for Rational {
+    type Output = Rational;
+    fn mul(self, o: i64) -> Self::Output {
+        self * Rational::integer(o)
+    }
+}
+
+impl core::ops::Div<i64> for Rational {
+    type Output = Rational;
+    fn div(self, o: i64) -> Self::Output {
+        self / Rational::integer(o)
+    }
+}
+
+impl Scalar for Rational {
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn one() -> Self {
+        Self::ONE
+    }
+}
+
+type Square<const N: usize> = [[i128; N]; N];
+
+fn mul(a: i128, b: i128) -> i128 {
+    a.checked_mul(b).expect("exact integer intermediate overflows i128")
+}
+
+fn add(a: i128, b: i128) -> i128 {
+    a.checked_add(b).expect("exact integer intermediate overflows i128")
+}
+
+fn sub(a: i128, b: i128) -> i128 {
+    a.checked_sub(b).expect("exact integer intermediate overflows i128")
+}
+
+// Fraction-free (Bareiss) elimination to row echelon form. Every entry stays an
+// integer, the division by the previous pivot is exact. Returns the rank and
+// the sign of the row permutation; for a full rank square matrix the last
+// pivot is the determinant up to that sign.
+fn bareiss<const N: usize>(a: &mut Square<N>) -> (usize, i128) {
+    let (mut r, mut sign, mut prev) = (0, 1, 1);
+    for col in 0..N {
+        let Some(p) = (r..N).find(|&i| a[i][col] != 0) else { continue };
+        if p != r {
+            a.swap(p, r);
+            sign = -sign;
+        }
+        let (top, bottom) = a.split_at_mut(r + 1);
+        let pivot = &top[r];
+        for row in bottom.iter_mut() {
+            for j in col + 1..N {
+                row[j] = sub(mul(pivot[col], row[j]), mul(row[col], pivot[j])) / prev;
+            }
+            row[col] = 0;
+        }
+        prev = pivot[col];
+        r += 1;
+    }
+    (r, sign)
+}
+
+fn identity<const N: usize>() -> Square<N> {
+    core::array::from_fn(|i| core::array::from_fn(|j| (i == j) as i128))
+}
+
+// rows i and k replaced by (x ri + y rk, u ri + v rk), unimodular when x v - y u = +-1
+fn combine_rows<const N: usize>(a: &mut Square<N>, i: usize, k: usize, [x, y, u, v]: [i128; 4]) {
+    let (p, q) = (a[i], a[k]);
+    a[i] = core::array::from_fn(|j| add(mul(x, p[j]), mul(y, q[j])));
+    a[k] = core::array::from_fn(|j| add(mul(u, p[j]), mul(v, q[j])));
+}
+
+fn combine_cols<const N: usize>(a: &mut Square<N>, i: usize, k: usize, [x, y, u, v]: [i128; 4]) {
+    for row in a.iter_mut() {
+        let (p, q) = (row[i], row[k]);
+        row[i] = add(mul(x, p), mul(y, q));
+        row[k] = add(mul(u, p), mul(v, q));
+    }
+}
+
+// Row-style Hermite normal form H = U A with U unimodular: H is in echelon
+// form, the pivots are positive and the entries above a pivot are reduced into
+// [0, pivot).
+fn hermite<const N: usize>(mut a: Square<N>) -> (Square<N>, Square<N>) {
+    let mut u = identity::<N>();
+    let mut r = 0;
+    for col in 0..N {
+        for i in r + 1..N {
+            if a[i][col] != 0 {
+                // gcd of the two entries into row r, zero into row i
+                let (g, x, y) = ext_gcd(a[r][col], a[i][col]);
+                let m = [x, y, -a[i][col] / g, a[r][col] / g];
+                combine_rows(&mut a, r, i, m);
+                combine_rows(&mut u, r, i, m);
+            }
+        }
+        if a[r][col] == 0 {
+            continue;
+        }
+        if a[r][col] < 0 {
+            combine_rows(&mut a, r, r, [-1, 0, -1, 0]);
+            combine_rows(&mut u, r, r, [-1, 0, -1, 0]);
+        }
+        for i in 0..r {
+            let q = a[i][col].div_euclid(a[r][col]);
+            combine_rows(&mut a, i, r, [1, -q, 0, 1]);
+            combine_rows(&mut u, i, r, [1, -q, 0, 1]);
+        }
+        r += 1;
+        if r == N {
+            break;
+        }
+    }
+    (a, u)
+}
+
+// Smith normal form S = U A V with U, V unimodular: S is diagonal with
+// non-negative entries, each dividing the next.
+fn smith<const N: usize>(mut a: Square<N>) -> (Square<N>, Square<N>, Square<N>) {
+    let (mut u, mut v) = (identity::<N>(), identity::<N>());
+    let swap = [0, 1, 1, 0];
+    for t in 0..N {
+        loop {
+            // smallest nonzero entry of the trailing block as the pivot
+            let Some((pi, pj)) = (t..N)
+                .flat_map(|i| (t..N).map(move |j| (i, j)))
+                .filter(|&(i, j)| a[i][j] != 0)
+                .min_by_key(|&(i, j)| a[i][j].abs())
+            else {
+                return (a, u, v);
+            };
+            if pi != t {
+                combine_rows(&mut a, t, pi, swap);
+                combine_rows(&mut u, t, pi, swap);
+            }
+            if pj != t {
+                combine_cols(&mut a, t, pj, swap);
+                combine_cols(&mut v, t, pj, swap);
+            }
+
+            // reduce row and column t by the pivot, a remainder becomes the next pivot
+            let mut clean = true;
+            for i in t + 1..N {
+                let q = a[i][t].div_euclid(a[t][t]);
+                combine_rows(&mut a, i, t, [1, -q, 0, 1]);
+                combine_rows(&mut u, i, t, [1, -q, 0, 1]);
+                clean &= a[i][t] == 0;
+            }
+            for j in t + 1..N {
+                let q = a[t][j].div_euclid(a[t][t]);
+                combine_cols(&mut a, j, t, [1, -q, 0, 1]);
+                combine_cols(&mut v, j, t, [1, -q, 0, 1]);
+                clean &= a[t][j] == 0;
+            }
+            if !clean {
+                continue;
+            }
+
+            // the pivot has to divide the rest, otherwise add the offending row
+            if let Some(i) = (t + 1..N).find(|&i| (t + 1..N).any(|j| a[i][j] % a[t][t] != 0)) {
+                combine_rows(&mut a, t, i, [1, 1, 0, 1]);
+                combine_rows(&mut u, t, i, [1, 1, 0, 1]);
+                continue;
+            }
+            break;
+        }
+        if a[t][t] < 0 {
+            combine_rows(&mut a, t, t, [-1, 0, -1, 0]);
+            combine_rows(&mut u, t, t, [-1, 0, -1, 0]);
+        }
+    }
+    (a, u, v)
+}
+
+macro_rules! impl_exact_types {
+    ($m:ident, $v:ident, $n:expr) => {
+        impl $m<i64> {
+            fn as_wide(&self) -> Square<$n> {
+                let v = self.to_row_major_array();
+                core::array::from_fn(|i| core::array::from_fn(|j| v[i * $n + j] as i128))
+            }
+
+            fn from_wide(a: Square<$n>) -> Self {
+                $m::from_row_major(core::array::from_fn(|i| narrow(a[i / $n][i % $n])))
+            }
+
+            // exact determinant by Bareiss elimination, in i128 since it can
+            // overflow i64 even for small entries
+            pub fn determinant(&self) -> i128 {
+                let mut a = self.as_wide();
+                let (rank, sign) = bareiss(&mut a);
+                if rank < $n { 0 } else { mul(sign, a[$n - 1][$n - 1]) }
+            }
+
+            // fraction-free row echelon form and the rank
+            pub fn bareiss(&self) -> (Self, usize) {
+                let mut a = self.as_wide();
+                let (rank, _) = bareiss(&mut a);
+                (Self::from_wide(a), rank)
+            }
+
+            pub fn rank(&self) -> usize {
+                bareiss(&mut self.as_wide()).0
+            }
+
+            // (H, U) with H = U A, see hermite
+            pub fn hermite_normal_form(&self) -> (Self, Self) {
+                let (h, u) = hermite(self.as_wide());
+                (Self::from_wide(h), Self::from_wide(u))
+            }
+
+            // (S, U, V) with S = U A V, see smith
+            pub fn smith_normal_form(&self) -> (Self, Self, Self) {
+                let (s, u, v) = smith(self.as_wide());
+                (Self::from_wide(s), Self::from_wide(u), Self::from_wide(v))
+            }
+        }
+
+        impl $m<Rational> {
+            fn rows(&self) -> [[Rational; $n]; $n] {
+                let v = self.to_row_major_array();
+                core::array::from_fn(|i| core::array::from_fn(|j| v[i * $n + j]))
+            }
+
+            pub fn determinant(&self) -> Rational {
+                let mut a = self.rows();
+                let mut det = Rational::ONE;
+                for c in 0..$n {
+                    let Some(p) = (c..$n).find(|&i| !a[i][c].is_zero()) else { return Rational::ZERO };
+                    if p != c {
+                        a.swap(p, c);
+                        det = -det;
+                    }
+                    det = det * a[c][c];
+                    for r in c + 1..$n {
+                        let f = a[r][c] / a[c][c];
+                        for j in c..$n {
+                            a[r][j] = a[r][j] - f * a[c][j];
+                        }
+                    }
+                }
+                det
+            }
+
+            // exact solution of A x = b, None when A is singular
+            pub fn solve(&self, b: $v<Rational>) -> Option<$v<Rational>> {
+                // nonzero pivots with the smallest denominators keep the
+                // intermediate fractions small
+                let size = |x: Rational| if x.is_zero() { f64::NEG_INFINITY } else { -(x.den as f64) };
+                lu_solve(self.rows(), b.to_list(), size).map($v::new_from)
+            }
+
+            pub fn inverse(&self) -> Option<Self> {
+                let mut cols = [[Rational::ZERO; $n]; $n];
+                for (j, col) in cols.iter_mut().enumerate() {
+                    let e = core::array::from_fn(|i| if i == j { Rational::ONE } else { Rational::ZERO });
+                    *col = self.solve($v::new_from(e))?.to_list();
+                }
+                Some($m::from_col_major(core::array::from_fn(|i| cols[i / $n][i % $n])))
+            }
+        }
+
+        impl From<$m<i64>> for $m<Rational> {
+            fn from(m: $m<i64>) -> Self {
+                $m::from_row_major(m.to_row_major_array().map(Rational::integer))
+            }
+        }
+
+        impl From<$v<i64>> for $v<Rational> {
+            fn from(v: $v<i64>) -> Self {
+                $v::new_from(v.to_list().map(Rational::integer))
+            }
+        }
+    };
+}
+
+impl_exact_types!(Matrix2d, Vector2d, 2);
+impl_exact_types!(Matrix3d, Vector3d, 3);
+impl_exact_types!(Matrix4d, Vector4d, 4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q(n: i64, d: i64) -> Rational {
+        Rational::new(n, d)
+    }
+
+    #[test]
+    fn always_reduced() {
+        assert_eq!(q(1, 2) + q(1, 3), q(5, 6));
+        assert_eq!(q(2, -4), q(-1, 2));
+        assert_eq!((q(2, -4).numer(), q(2, -4).denom()), (-1, 2));
+        assert_eq!(q(3, 4) * q(8, 9), q(2, 3));
+        assert_eq!(q(3, 4) / q(3, 8), Rational::integer(2));
+        assert_eq!(q(1, 3) - q(1, 3), Rational::ZERO);
+        assert!(q(-1, 3) < q(-1, 4) && q(2, 3) > q(3, 5));
+        assert_eq!((q(-7, 2).floor(), q(-7, 2).ceil()), (-4, -3));
+        #[cfg(feature = "std")]
+        assert_eq!(format!("{} {}", q(6, 4), q(4, 2)), "3/2 2");
+
+        // i64::MAX / 2 * 2 / i64::MAX reduces back in range through i128
+        let big = q(i64::MAX, 2);
+        assert_eq!(big * q(2, i64::MAX), Rational::ONE);
+    }
+
+    #[test]
+    fn bareiss_determinant_and_rank() {
+        let a = Matrix4d::<i64>::new([3, 2, -1, 4, 2, 1, 5, 7, 0, 5, 2, -6, -1, 2, 1, 0]);
+        assert_eq!(a.determinant(), -418);
+        assert_eq!(Matrix4d::<Rational>::from(a).determinant(), Rational::integer(-418));
+
+        let s = Matrix3d::<i64>::new([1, 2, 3, 2, 4, 6, 1, 0, 1]);
+        assert_eq!(s.determinant(), 0);
+        assert_eq!(s.rank(), 2);
+        let (e, rank) = Matrix3d::<i64>::new([2, 1, 1, 4, -6, 0, -2, 7, 2]).bareiss();
+        assert_eq!(rank, 3);
+        // the last pivot of the fraction-free form is the determinant
+        assert_eq!(e.get(2, 2), -16);
+        assert_eq!(e.get(1, 0), 0);
+
+        // doesn't overflow when the f64 determinant would round
+        let m = 1 << 40;
+        let b = Matrix2d::<i64>::new([m + 1, m, m, m - 1]);
+        assert_eq!(b.determinant(), -1);
+        let n = i64::MAX;
+        assert_eq!(Matrix2d::<i64>::new([n, n - 1, n - 1, n - 2]).determinant(), -1);
+    }
+
+    // the products of the last Bareiss step reach 2^160 although the
+    // determinant itself is about 2^120
+    #[test]
+    #[should_panic(expected = "overflows i128")]
+    fn bareiss_overflow_panics() {
+        let m = 1 << 40;
+        Matrix3d::<i64>::new([m + 1, 1, 2, 3, m + 5, 7, 1, 2, m + 3]).determinant();
+    }
+
+    #[test]
+    #[should_panic(expected = "overflows i128")]
+    fn bareiss_overflow_near_i64_max_panics() {
+        let n = i64::MAX;
+        Matrix3d::<i64>::new([n, n - 1, 3, n - 2, n, 5, 7, n - 3, n]).determinant();
+    }
+
+    #[test]
+    fn exact_inverse_and_solve() {
+        // 4x4 Hilbert matrix, with a known integer inverse
+        let h = Matrix4d::new(core::array::from_fn(|k| q(1, (k / 4 + k % 4 + 1) as i64)));
+        let inv = h.inverse().unwrap();
+        assert_eq!(inv.get(0, 0), Rational::integer(16));
+        assert_eq!(inv.get(3, 3), Rational::integer(2800));
+        assert_eq!(inv.get(1, 2), Rational::integer(-2700));
+        assert_eq!(h * inv, Matrix4d::<Rational>::from(Matrix4d::<i64>::new(core::array::from_fn(|k| (k % 5 == 0) as i64))));
+        assert_eq!(h.determinant(), q(1, 6048000));
+
+        let a = Matrix3d::<Rational>::from(Matrix3d::<i64>::new([2, 1, 1, 4, -6, 0, -2, 7, 2]));
+        let x = Vector3d::new_from([q(1, 3), q(-2, 7), q(5, 1)]);
+        assert_eq!(a.solve(a * x), Some(x));
+        let b = Matrix2d::new([Rational::ZERO, q(1, 2), q(1, 3), Rational::ONE]);
+        assert_eq!(b.solve(Vector2d::new_from([q(1, 2), q(4, 3)])), Some(Vector2d::new_from([q(1, 1), q(1, 1)])));
+        assert!(Matrix2d::<Rational>::from(Matrix2d::<i64>::new([1, 2, 2, 4])).inverse().is_none());
+    }
+
+    #[test]
+    fn hermite_normal_form() {
+        let a = Matrix3d::<i64>::new([2, 3, 6, 4, 1, 5, 6, 8, 2]);
+        let (h, u) = a.hermite_normal_form();
+        assert_eq!(u * a, h);
+        assert_eq!(u.determinant().abs(), 1);
+        for i in 0..3 {
+            assert!(h.get(i, i) > 0);
+            for j in 0..i {
+                assert_eq!(h.get(i, j), 0);
+                assert!(h.get(j, i) >= 0 && h.get(j, i) < h.get(i, i));
+            }
+        }
+        assert_eq!(h.get(0, 0) * h.get(1, 1) * h.get(2, 2), a.determinant().abs() as i64);
+
+        // rank deficient input keeps a zero row
+        let (h, u) = Matrix3d::<i64>::new([1, 2, 3, 2, 4, 6, 1, 0, 1]).hermite_normal_form();
+        assert_eq!(h.to_row_major_array(), [1, 0, 1, 0, 2, 2, 0, 0, 0]);
+        assert_eq!(u.determinant().abs(), 1);
+    }
+
+    #[test]
+    fn smith_normal_form() {
+        let a = Matrix3d::<i64>::new([2, 4, 4, -6, 6, 12, 10, -4, -16]);
+        let (s, u, v) = a.smith_normal_form();
+        assert_eq!(u * a * v, s);
+        assert_eq!(s.to_row_major_array(), [2, 0, 0, 0, 6, 0, 0, 0, 12]);
+        assert_eq!(u.determinant().abs(), 1);
+        assert_eq!(v.determinant().abs(), 1);
+
+        let b = Matrix4d::<i64>::new([6, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10]);
+        let (s, u, v) = b.smith_normal_form();
+        assert_eq!(u * b * v, s);
+        assert_eq!(s.to_row_major_array(), [2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 60, 0, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,163 @@
+// Element type of the fixed size vectors and matrices. f64 is the default and
+// the only one that uses the SIMD kernels, the other scalar types of the crate
+// (Complex, Rational, ...) go through the plain loops.
+
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+pub trait Scalar:
+    Copy
+    + PartialEq
+    + core::fmt::Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+
+    // row-major 4x4 products, overridden by f64 to use the SIMD kernels
+    fn mat4_mul(a: &[Self; 16], b: &[Self; 16]) -> [Self; 16] {
+        let mut c = [Self::zero(); 16];
+        for i in 0..4 {
+            for j in 0..4 {
+                let mut s = Self::zero();
+                for k in 0..4 {
+                    s = s + a[i * 4 + k] * b[k * 4 + j];
+                }
+                c[i * 4 + j] = s;
+            }
+        }
+        c
+    }
+
+    fn mat4_mul_vec(a: &[Self; 16], v: &[Self; 4]) -> [Self; 4] {
+        let mut r = [Self::zero(); 4];
+        for (i, r) in r.iter_mut().enumerate() {
+            *r = a[i * 4] * v[0] + a[i * 4 + 1] * v[1] + a[i * 4 + 2] * v[2] + a[i * 4 + 3] * v[3];
+        }
+        r
+    }
+}
+
+impl Scalar for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+
+    fn one() -> Self {
+        1.0
+    }
+
+    fn mat4_mul(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+        crate::simd::mat4_mul(a, b)
+    }
+
+    fn mat4_mul_vec(a: &[f64; 16], v: &[f64; 4]) -> [f64; 4] {
+        crate::simd::mat4_mul_vec(a, v)
+    }
+}
+
+// integer matrices, for the exact algorithms of rational.rs. Division
+// truncates like the i64 operator does.
+impl Scalar for i64 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+}
+
+// Scalars with a square root, which is all the vector lengths need
+pub trait Real: Scalar {
+    fn sqrt(self) -> Self;
+}
+
+impl Real for f64 {
+    fn sqrt(self) -> Self {
+        crate::math::sqrt(self)
+    }
+}
+
+// Solves A x = b by Gaussian elimination with partial pivoting, the pivot is
+// the entry with the largest magnitude. None when A is singular.
+pub(crate) fn lu_solve<T: Scalar, const N: usize>(
+    mut a: [[T; N]; N],
+    mut b: [T; N],
+    magnitude: impl Fn(T) -> f64,
+) -> Option<[T; N]> {
+    for c in 0..N {
+        let p = (c..N).max_by(|i, j| magnitude(a[*i][c]).total_cmp(&magnitude(a[*j][c])))?;
+        if a[p][c] == T::zero() {
+            return None;
+        }
+        a.swap(p, c);
+        b.swap(p, c);
+        for r in c + 1..N {
+            let f = a[r][c] / a[c][c];
+            if f != T::zero() {
+                let (top, bottom) = a.split_at_mut(r);
+                for (x, p) in bottom[0][c..].iter_mut().zip(&top[c][c..]) {
+                    *x = *x - f * *p;
+                }
+                b[r] = b[r] - f * b[c];
+            }
+        }
+    }
+    let mut x = [T::zero(); N];
+    for i in (0..N).rev() {
+        let mut s = b[i];
+        for j in i + 1..N {
+            s = s - a[i][j] * x[j];
+        }
+        x[i] = s / a[i][i];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generic_mat4_kernels_match_simd() {
+        let a: [f64; 16] = core::array::from_fn(|i| i as f64 * 0.7 - 3.0);
+        let b: [f64; 16] = core::array::from_fn(|i| 1.0 / (i as f64 + 1.5));
+        let v = [1.0, -2.0, 0.5, 3.0];
+
+        // call the trait defaults through a wrapper that doesn't override them
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct W(f64);
+        impl Add for W { type Output = W; fn add(self, o: W) -> W { W(self.0 + o.0) } }
+        impl Sub for W { type Output = W; fn sub(self, o: W) -> W { W(self.0 - o.0) } }
+        impl Mul for W { type Output = W; fn mul(self, o: W) -> W { W(self.0 * o.0) } }
+        impl Div for W { type Output = W; fn div(self, o: W) -> W { W(self.0 / o.0) } }
+        impl Neg for W { type Output = W; fn neg(self) -> W { W(-self.0) } }
+        impl Scalar for W {
+            fn zero() -> W { W(0.0) }
+            fn one() -> W { W(1.0) }
+        }
+
+        let c = W::mat4_mul(&a.map(W), &b.map(W));
+        for (x, y) in c.iter().zip(f64::mat4_mul(&a, &b)) {
+            assert!((x.0 - y).abs() < 1e-14);
+        }
+        let r = W::mat4_mul_vec(&a.map(W), &v.map(W));
+        for (x, y) in r.iter().zip(f64::mat4_mul_vec(&a, &v)) {
+            assert!((x.0 - y).abs() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn lu_solve_pivots() {
+        let a = [[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [3.0, 0.0, 1.0]];
+        let x = lu_solve(a, [7.0, 3.0, 6.0], f64::abs).unwrap();
+        for (u, v) in x.iter().zip([1.0, 2.0, 3.0]) {
+            assert!((u - v).abs() < 1e-14);
+        }
+        assert!(lu_solve([[1.0, 2.0], [2.0, 4.0]], [1.0, 1.0], f64::abs).is_none());
+    }
+}
@@ -0,0 +1,452 @@
+// Signed distance functions: negative inside, positive outside and never more
+// than the true distance to the surface, so a sphere of that radius around
+// any point is free of surface and a ray can safely advance that far.
+//
+// The primitives are exact. Unions, intersections, differences, smooth
+// blends and non-uniform scales keep the bound but not the exact distance.
+
+use super::geometry::*;
+use super::math;
+use super::matrix4d::*;
+use super::vector3d::*;
+
+const NORMAL_STEP: f64 = 1e-6;
+
+pub trait Sdf {
+    fn distance(&self, p: Vector3d) -> f64;
+
+    // unit gradient, by central differences unless the shape knows better
+    fn normal(&self, p: Vector3d) -> Vector3d {
+        let h = NORMAL_STEP * (1.0 + p.length());
+        let axis = |i: usize| {
+            let e = Vector3d::new_from(core::array::from_fn(|j| if i == j { h } else { 0.0 }));
+            self.distance(p + e) - self.distance(p - e)
+        };
+        Vector3d::new_from([axis(0), axis(1), axis(2)]).normalize()
+    }
+}
+
+fn map(v: Vector3d, f: impl Fn(f64) -> f64) -> Vector3d {
+    Vector3d::new_from(v.to_list().map(f))
+}
+
+// distance to a box centred at the origin
+fn box_distance(p: Vector3d, half: Vector3d) -> f64 {
+    let q = map(p, f64::abs) - half;
+    map(q, |x| x.max(0.0)).length() + q.x.max(q.y).max(q.z).min(0.0)
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, p: Vector3d) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn normal(&self, p: Vector3d) -> Vector3d {
+        (p - self.center).normalize()
+    }
+}
+
+impl Sdf for Aabb {
+    fn distance(&self, p: Vector3d) -> f64 {
+        box_distance(p - self.center(), self.half_extents())
+    }
+}
+
+impl Sdf for Plane {
+    fn distance(&self, p: Vector3d) -> f64 {
+        self.signed_distance(p)
+    }
+
+    fn normal(&self, _: Vector3d) -> Vector3d {
+        self.normal
+    }
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: Vector3d) -> f64 {
+        self.segment.distance(p) - self.radius
+    }
+
+    fn normal(&self, p: Vector3d) -> Vector3d {
+        (p - self.segment.closest_point(p).0).normalize()
+    }
+}
+
+// A box with its edges and corners rounded off by radius, within the same
+// half extents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundedBox {
+    pub center: Vector3d,
+    pub half: Vector3d,
+    pub radius: f64,
+}
+
+impl RoundedBox {
+    pub fn new(center: Vector3d, half: Vector3d, radius: f64) -> Self {
+        assert!(radius >= 0.0 && half.to_list().iter().all(|&h| h >= radius), "the rounding must fit in the box");
+        Self { center, half, radius }
+    }
+}
+
+impl Sdf for RoundedBox {
+    fn distance(&self, p: Vector3d) -> f64 {
+        box_distance(p - self.center, self.half - Vector3d::new_from_const(self.radius)) - self.radius
+    }
+}
+
+// Ring around the y axis: the circle of radius major in the xz plane swept by
+// a disc of radius minor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Torus {
+    pub center: Vector3d,
+    pub major: f64,
+    pub minor: f64,
+}
+
+impl Torus {
+    pub fn new(center: Vector3d, major: f64, minor: f64) -> Self {
+        Self { center, major, minor }
+    }
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: Vector3d) -> f64 {
+        let q = p - self.center;
+        math::hypot(math::hypot(q.x, q.z) - self.major, q.y) - self.minor
+    }
+}
+
+// Capped cylinder along the y axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cylinder {
+    pub center: Vector3d,
+    pub radius: f64,
+    pub half_height: f64,
+}
+
+impl Cylinder {
+    pub fn new(center: Vector3d, radius: f64, half_height: f64) -> Self {
+        Self { center, radius, half_height }
+    }
+}
+
+impl Sdf for Cylinder {
+    fn distance(&self, p: Vector3d) -> f64 {
+        let q = p - self.center;
+        let (dr, dy) = (math::hypot(q.x, q.z) - self.radius, q.y.abs() - self.half_height);
+        math::hypot(dr.max(0.0), dy.max(0.0)) + dr.max(dy).min(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Union<A, B>(pub A, pub B);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Intersection<A, B>(pub A, pub B);
+
+// A with B carved out
+#[derive(Debug, Clone, Copy)]
+pub struct Difference<A, B>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Union<A, B> {
+    fn distance(&self, p: Vector3d) -> f64 {
+        self.0.distance(p).min(self.1.distance(p))
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Intersection<A, B> {
+    fn distance(&self, p: Vector3d) -> f64 {
+        self.0.distance(p).max(self.1.distance(p))
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Difference<A, B> {
+    fn distance(&self, p: Vector3d) -> f64 {
+        self.0.distance(p).max(-self.1.distance(p))
+    }
+}
+
+// polynomial smooth minimum: min(a, b) once they are k apart, up to k / 4
+// below it where they meet
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+// The blends round off the seam over a width of about k
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothUnion<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothIntersection<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothDifference<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+impl<A, B> SmoothUnion<A, B> {
+    pub fn new(a: A, b: B, k: f64) -> Self {
+        assert!(k > 0.0, "the blend width must be positive");
+        Self { a, b, k }
+    }
+}
+
+impl<A, B> SmoothIntersection<A, B> {
+    pub fn new(a: A, b: B, k: f64) -> Self {
+        assert!(k > 0.0, "the blend width must be positive");
+        Self { a, b, k }
+    }
+}
+
+impl<A, B> SmoothDifference<A, B> {
+    pub fn new(a: A, b: B, k: f64) -> Self {
+        assert!(k > 0.0, "the blend width must be positive");
+        Self { a, b, k }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: Vector3d) -> f64 {
+        smooth_min(self.a.distance(p), self.b.distance(p), self.k)
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothIntersection<A, B> {
+    fn distance(&self, p: Vector3d) -> f64 {
+        -smooth_min(-self.a.distance(p), -self.b.distance(p), self.k)
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothDifference<A, B> {
+    fn distance(&self, p: Vector3d) -> f64 {
+        -smooth_min(-self.a.distance(p), self.b.distance(p), self.k)
+    }
+}
+
+fn domain_inverse<S>(t: &Transformed<S>) -> &Matrix4d {
+    t.inverse().expect("domain transforms must be invertible")
+}
+
+// the distance is exact for rigid motions and uniform scales and a lower
+// bound otherwise
+impl<S: Sdf> Sdf for Transformed<S> {
+    fn distance(&self, p: Vector3d) -> f64 {
+        self.shape.distance(transform_point(domain_inverse(self), p)) * self.min_scale()
+    }
+
+    fn normal(&self, p: Vector3d) -> Vector3d {
+        // normals go through the inverse transpose
+        let m = domain_inverse(self);
+        let n = self.shape.normal(transform_point(m, p));
+        Vector3d::new_from(core::array::from_fn(|j| m.get(0, j) * n.x + m.get(1, j) * n.y + m.get(2, j) * n.z)).normalize()
+    }
+}
+
+// Sphere tracing: step along the ray by the distance bound until it drops
+// under tolerance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marcher {
+    pub t_max: f64,
+    pub tolerance: f64,
+    pub max_steps: usize,
+}
+
+impl Default for Marcher {
+    fn default() -> Self {
+        Self { t_max: 1e3, tolerance: 1e-6, max_steps: 256 }
+    }
+}
+
+impl Marcher {
+    // the first surface point along the ray, t = 0 when the origin is
+    // already inside; None when the ray escapes or runs out of steps
+    pub fn march<S: Sdf>(&self, sdf: &S, ray: &Ray) -> Option<RayHit> {
+        let speed = ray.dir.length();
+        let mut t = 0.0;
+        for _ in 0..self.max_steps {
+            let point = ray.at(t);
+            let d = sdf.distance(point);
+            if d < self.tolerance {
+                return Some(RayHit { t, point, normal: sdf.normal(point), barycentric: None });
+            }
+            t += d / speed;
+            if t > self.t_max {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v3(x: f64, y: f64, z: f64) -> Vector3d {
+        Vector3d::new_from([x, y, z])
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    // samples around the origin
+    fn probes() -> impl Iterator<Item = Vector3d> {
+        (0..200).map(|i| {
+            let t = i as f64;
+            v3(math::sin(t * 1.7) * 3.0, math::cos(t * 2.3) * 3.0, math::sin(t * 0.9 + 1.0) * 3.0)
+        })
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let sphere = Sphere::new(v3(1.0, 0.0, 0.0), 2.0);
+        assert!(close(sphere.distance(v3(1.0, 0.0, 5.0)), 3.0));
+        assert!(close(sphere.distance(v3(1.0, 0.0, 0.0)), -2.0));
+        let cube = Aabb::new(v3(-1.0, -1.0, -1.0), v3(1.0, 1.0, 1.0));
+        assert!(close(cube.distance(v3(2.0, 2.0, 1.0)), math::sqrt(2.0)));
+        assert!(close(cube.distance(v3(0.5, 0.0, 0.0)), -0.5));
+        let rounded = RoundedBox::new(Vector3d::new(), v3(1.0, 1.0, 1.0), 0.25);
+        assert!(close(rounded.distance(v3(2.0, 0.0, 0.0)), 1.0));
+        assert!(close(rounded.distance(v3(2.0, 2.0, 2.0)), math::sqrt(3.0) * 1.25 - 0.25));
+        let torus = Torus::new(v3(0.0, 1.0, 0.0), 2.0, 0.5);
+        assert!(close(torus.distance(v3(0.0, 1.0, 0.0)), 1.5));
+        assert!(close(torus.distance(v3(0.0, 1.0, 2.0)), -0.5));
+        assert!(close(torus.distance(v3(2.0, 3.0, 0.0)), 1.5));
+        let capsule = Capsule::new(v3(0.0, 0.0, 0.0), v3(0.0, 2.0, 0.0), 0.5);
+        assert!(close(Sdf::distance(&capsule, v3(0.0, 4.0, 0.0)), 1.5));
+        assert!(close(Sdf::distance(&capsule, v3(0.25, 1.0, 0.0)), -0.25));
+        let plane = Plane::new(v3(0.0, 0.0, 2.0), -2.0);
+        assert!(close(plane.distance(v3(5.0, 5.0, 3.0)), 2.0));
+        let cylinder = Cylinder::new(Vector3d::new(), 1.0, 2.0);
+        assert!(close(cylinder.distance(v3(3.0, 0.0, 0.0)), 2.0));
+        assert!(close(cylinder.distance(v3(0.0, 0.0, 0.5)), -0.5));
+        assert!(close(cylinder.distance(v3(0.0, 4.0, 2.0)), math::sqrt(5.0)));
+
+        // exact distances have unit gradients, and the analytic normals
+        // match the differences
+        for p in probes() {
+            for n in [sphere.normal(p), cube.normal(p), torus.normal(p), cylinder.normal(p), rounded.normal(p)] {
+                assert!((n.length() - 1.0).abs() < 1e-9);
+            }
+            let numeric = |s: &dyn Fn(Vector3d) -> f64| {
+                let h = 1e-6;
+                v3(s(p + v3(h, 0.0, 0.0)) - s(p - v3(h, 0.0, 0.0)), s(p + v3(0.0, h, 0.0)) - s(p - v3(0.0, h, 0.0)), s(p + v3(0.0, 0.0, h)) - s(p - v3(0.0, 0.0, h))) / (2.0 * h)
+            };
+            assert!((numeric(&|q| sphere.distance(q)) - sphere.normal(p)).length() < 1e-6);
+            assert!((numeric(&|q| Sdf::distance(&capsule, q)) - Sdf::normal(&capsule, p)).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn booleans_and_blends() {
+        let a = Sphere::new(v3(-0.75, 0.0, 0.0), 1.0);
+        let b = Sphere::new(v3(0.75, 0.0, 0.0), 1.0);
+        let p = v3(0.0, 0.0, 0.0);
+        assert!(close(Union(a, b).distance(v3(3.0, 0.0, 0.0)), 1.25));
+        assert!(close(Intersection(a, b).distance(p), -0.25));
+        assert!(close(Intersection(a, b).distance(v3(1.5, 0.0, 0.0)), 1.25));
+        assert!(close(Difference(a, b).distance(v3(-1.0, 0.0, 0.0)), -0.75));
+        assert!(close(Difference(a, b).distance(p), 0.25));
+
+        let (k, u, i, d) = (0.5, SmoothUnion::new(a, b, 0.5), SmoothIntersection::new(a, b, 0.5), SmoothDifference::new(a, b, 0.5));
+        for q in probes() {
+            let (da, db) = (a.distance(q), b.distance(q));
+            // within k / 4 of the sharp result and the same once apart
+            let within = |x: f64, sharp: f64, side: f64| (x - sharp) * side >= -1e-12 && (x - sharp) * side <= k / 4.0 + 1e-12;
+            assert!(within(u.distance(q), da.min(db), -1.0));
+            assert!(within(i.distance(q), da.max(db), 1.0));
+            assert!(within(d.distance(q), da.max(-db), 1.0));
+            if (da - db).abs() >= k {
+                assert!(close(u.distance(q), da.min(db)));
+            }
+        }
+        // the seam is filled in
+        assert!(close(u.distance(v3(0.0, 0.0, 0.0)), -0.25 - 0.125));
+    }
+
+    #[test]
+    fn domain_transforms() {
+        // a box turned a quarter around z and moved to (5, 0, 0)
+        let turn = Matrix4d::new([0.0, -1.0, 0.0, 5.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let moved = Transformed::new(Aabb::new(v3(-2.0, -1.0, -1.0), v3(2.0, 1.0, 1.0)), turn);
+        assert!(close(moved.distance(v3(5.0, 3.0, 0.0)), 1.0));
+        assert!(close(moved.distance(v3(7.0, 0.0, 0.0)), 1.0));
+        assert!(close(moved.distance(v3(5.0, 0.0, 0.0)), -1.0));
+
+        // a uniform scale scales the distances
+        let grown = Transformed::new(Sphere::new(Vector3d::new(), 1.0), Matrix4d::new([2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 1.0]));
+        assert!(close(grown.distance(v3(0.0, 5.0, 0.0)), 3.0));
+        // a stretch gives a lower bound, the ellipsoid 2 x 1 x 1 here
+        let stretched = Transformed::new(Sphere::new(Vector3d::new(), 1.0), Matrix4d::new([2.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]));
+        assert!(close(stretched.distance(v3(3.0, 0.0, 0.0)), 0.5));
+        assert!(close(stretched.distance(v3(0.0, 3.0, 0.0)), 2.0));
+        assert!((stretched.min_scale() - 1.0).abs() < 1e-12);
+        // the same value is a support mapping for the collision queries
+        use crate::gjk::Support;
+        assert!((stretched.support(v3(1.0, 0.0, 0.0)) - v3(2.0, 0.0, 0.0)).length() < 1e-12);
+        assert_eq!(moved.support(v3(0.0, 1.0, 0.0)).y, 2.0);
+        // and the surface stays where it belongs, with the right normal
+        assert!(close(stretched.distance(v3(2.0, 0.0, 0.0)), 0.0));
+        let p = v3(math::sqrt(2.0), math::sqrt(0.5), 0.0);
+        assert!(stretched.distance(p).abs() < 1e-12);
+        let n = stretched.normal(p);
+        assert!((n - v3(1.0, 2.0, 0.0) / math::sqrt(5.0)).length() < 1e-9);
+        // the analytic normal agrees with differences through the transform
+        let ring = Transformed::new(Torus::new(Vector3d::new(), 2.0, 0.5), turn);
+        struct Numeric<'a>(&'a dyn Fn(Vector3d) -> f64);
+        impl Sdf for Numeric<'_> {
+            fn distance(&self, p: Vector3d) -> f64 {
+                (self.0)(p)
+            }
+        }
+        let sphere = Transformed::new(Sphere::new(v3(0.0, 1.0, 0.0), 1.5), turn);
+        for q in probes() {
+            let exact = sphere.normal(q);
+            assert!((Numeric(&|x| sphere.distance(x)).normal(q) - exact).length() < 1e-6);
+            assert!((ring.normal(q).length() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn sphere_tracing() {
+        let marcher = Marcher::default();
+        let scene = SmoothUnion::new(Sphere::new(v3(0.0, 0.0, -5.0), 1.0), Plane::new(v3(0.0, 1.0, 0.0), 1.0), 0.2);
+        // straight at the sphere, with an unnormalised direction
+        let hit = marcher.march(&scene, &Ray::new(Vector3d::new(), v3(0.0, 0.0, -2.0))).unwrap();
+        assert!((hit.point - v3(0.0, 0.0, -4.0)).length() < 1e-5);
+        assert!((hit.t - 2.0).abs() < 1e-5);
+        assert!((hit.normal - v3(0.0, 0.0, 1.0)).length() < 1e-4);
+        assert_eq!(hit.barycentric, None);
+        // down onto the plane y = -1 far from the sphere
+        let hit = marcher.march(&scene, &Ray::new(v3(20.0, 3.0, 0.0), v3(0.0, -1.0, 0.0))).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-5);
+        assert!((hit.normal - v3(0.0, 1.0, 0.0)).length() < 1e-6);
+        // away from everything
+        assert!(marcher.march(&scene, &Ray::new(Vector3d::new(), v3(0.0, 1.0, 0.0))).is_none());
+        // from inside
+        let hit = marcher.march(&scene, &Ray::new(v3(0.0, 0.0, -5.0), v3(1.0, 0.0, 0.0))).unwrap();
+        assert_eq!(hit.t, 0.0);
+        // a grazing ray runs out of steps rather than looping forever
+        let tight = Marcher { max_steps: 8, ..marcher };
+        assert!(tight.march(&Sphere::new(Vector3d::new(), 1.0), &Ray::new(v3(-10.0, 1.0 + 1e-9, 0.0), v3(1.0, 0.0, 0.0))).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "invertible")]
+    fn singular_domain_transform_panics() {
+        let flatten = Matrix4d::new([1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        Transformed::new(Sphere::new(Vector3d::new(), 1.0), flatten).distance(v3(0.0, 0.0, 2.0));
+    }
+}
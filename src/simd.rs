@@ -0,0 +1,377 @@
+// SIMD kernels for the fixed-size 4x4 operations.
+//
+// On x86_64 SSE2 is always available, so it's the baseline. AVX is used when
+// the crate is compiled with it enabled, or when it's detected at runtime (std
+// only). Other architectures use the scalar path. All matrices are row-major
+// arrays, same as the storage of Matrix4d.
+
+pub fn mat4_mul(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx() {
+            return unsafe { avx::mat4_mul(a, b) };
+        }
+        unsafe { sse2::mat4_mul(a, b) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        scalar::mat4_mul(a, b)
+    }
+}
+
+pub fn mat4_mul_vec(a: &[f64; 16], v: &[f64; 4]) -> [f64; 4] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        unsafe { sse2::mat4_mul_vec(a, v) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        scalar::mat4_mul_vec(a, v)
+    }
+}
+
+pub fn dot4(a: &[f64; 4], b: &[f64; 4]) -> f64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        unsafe { sse2::dot4(a, b) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        scalar::dot4(a, b)
+    }
+}
+
+// returns None when the matrix is singular
+pub fn mat4_inverse(a: &[f64; 16]) -> Option<[f64; 16]> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        unsafe { sse2::mat4_inverse(a) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        scalar::mat4_inverse(a)
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "avx"))]
+fn has_avx() -> bool {
+    true
+}
+
+#[cfg(all(target_arch = "x86_64", not(target_feature = "avx"), feature = "std"))]
+fn has_avx() -> bool {
+    std::is_x86_feature_detected!("avx")
+}
+
+#[cfg(all(target_arch = "x86_64", not(target_feature = "avx"), not(feature = "std")))]
+fn has_avx() -> bool {
+    false
+}
+
+// the reference implementation, also used on targets without SIMD support
+pub mod scalar {
+    pub fn mat4_mul(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+        let mut v = [0.0; 16];
+        for r in 0..4 {
+            for c in 0..4 {
+                v[r * 4 + c] = a[r * 4] * b[c]
+                    + a[r * 4 + 1] * b[4 + c]
+                    + a[r * 4 + 2] * b[8 + c]
+                    + a[r * 4 + 3] * b[12 + c];
+            }
+        }
+        v
+    }
+
+    pub fn mat4_mul_vec(a: &[f64; 16], v: &[f64; 4]) -> [f64; 4] {
+        [
+            a[0] * v[0] + a[1] * v[1] + a[2] * v[2] + a[3] * v[3],
+            a[4] * v[0] + a[5] * v[1] + a[6] * v[2] + a[7] * v[3],
+            a[8] * v[0] + a[9] * v[1] + a[10] * v[2] + a[11] * v[3],
+            a[12] * v[0] + a[13] * v[1] + a[14] * v[2] + a[15] * v[3],
+        ]
+    }
+
+    pub fn dot4(a: &[f64; 4], b: &[f64; 4]) -> f64 {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+    }
+
+    // cofactor expansion using the 2x2 sub-determinants of the top (s) and
+    // bottom (c) row pairs
+    pub fn mat4_inverse(a: &[f64; 16]) -> Option<[f64; 16]> {
+        let s0 = a[0] * a[5] - a[4] * a[1];
+        let s1 = a[0] * a[6] - a[4] * a[2];
+        let s2 = a[0] * a[7] - a[4] * a[3];
+        let s3 = a[1] * a[6] - a[5] * a[2];
+        let s4 = a[1] * a[7] - a[5] * a[3];
+        let s5 = a[2] * a[7] - a[6] * a[3];
+
+        let c5 = a[10] * a[15] - a[14] * a[11];
+        let c4 = a[9] * a[15] - a[13] * a[11];
+        let c3 = a[9] * a[14] - a[13] * a[10];
+        let c2 = a[8] * a[15] - a[12] * a[11];
+        let c1 = a[8] * a[14] - a[12] * a[10];
+        let c0 = a[8] * a[13] - a[12] * a[9];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let d = 1.0 / det;
+
+        Some([
+            (a[5] * c5 - a[6] * c4 + a[7] * c3) * d,
+            (-a[1] * c5 + a[2] * c4 - a[3] * c3) * d,
+            (a[13] * s5 - a[14] * s4 + a[15] * s3) * d,
+            (-a[9] * s5 + a[10] * s4 - a[11] * s3) * d,
+
+            (-a[4] * c5 + a[6] * c2 - a[7] * c1) * d,
+            (a[0] * c5 - a[2] * c2 + a[3] * c1) * d,
+            (-a[12] * s5 + a[14] * s2 - a[15] * s1) * d,
+            (a[8] * s5 - a[10] * s2 + a[11] * s1) * d,
+
+            (a[4] * c4 - a[5] * c2 + a[7] * c0) * d,
+            (-a[0] * c4 + a[1] * c2 - a[3] * c0) * d,
+            (a[12] * s4 - a[13] * s2 + a[15] * s0) * d,
+            (-a[8] * s4 + a[9] * s2 - a[11] * s0) * d,
+
+            (-a[4] * c3 + a[5] * c1 - a[6] * c0) * d,
+            (a[0] * c3 - a[1] * c1 + a[2] * c0) * d,
+            (-a[12] * s3 + a[13] * s1 - a[14] * s0) * d,
+            (a[8] * s3 - a[9] * s1 + a[10] * s0) * d,
+        ])
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use core::arch::x86_64::*;
+
+    // each row is kept as two halves of two lanes
+    pub unsafe fn mat4_mul(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+        let mut out = [0.0; 16];
+        let b = b.as_ptr();
+        let lo = [
+            _mm_loadu_pd(b),
+            _mm_loadu_pd(b.add(4)),
+            _mm_loadu_pd(b.add(8)),
+            _mm_loadu_pd(b.add(12)),
+        ];
+        let hi = [
+            _mm_loadu_pd(b.add(2)),
+            _mm_loadu_pd(b.add(6)),
+            _mm_loadu_pd(b.add(10)),
+            _mm_loadu_pd(b.add(14)),
+        ];
+        for r in 0..4 {
+            let mut acc_lo = _mm_setzero_pd();
+            let mut acc_hi = _mm_setzero_pd();
+            for k in 0..4 {
+                let e = _mm_set1_pd(a[r * 4 + k]);
+                acc_lo = _mm_add_pd(acc_lo, _mm_mul_pd(e, lo[k]));
+                acc_hi = _mm_add_pd(acc_hi, _mm_mul_pd(e, hi[k]));
+            }
+            _mm_storeu_pd(out.as_mut_ptr().add(r * 4), acc_lo);
+            _mm_storeu_pd(out.as_mut_ptr().add(r * 4 + 2), acc_hi);
+        }
+        out
+    }
+
+    // two rows at a time, the final unpack sums the lanes of both rows at once
+    pub unsafe fn mat4_mul_vec(a: &[f64; 16], v: &[f64; 4]) -> [f64; 4] {
+        let mut out = [0.0; 4];
+        let a = a.as_ptr();
+        let v_lo = _mm_loadu_pd(v.as_ptr());
+        let v_hi = _mm_loadu_pd(v.as_ptr().add(2));
+        for r in (0..4).step_by(2) {
+            let t0 = _mm_add_pd(
+                _mm_mul_pd(_mm_loadu_pd(a.add(r * 4)), v_lo),
+                _mm_mul_pd(_mm_loadu_pd(a.add(r * 4 + 2)), v_hi),
+            );
+            let t1 = _mm_add_pd(
+                _mm_mul_pd(_mm_loadu_pd(a.add(r * 4 + 4)), v_lo),
+                _mm_mul_pd(_mm_loadu_pd(a.add(r * 4 + 6)), v_hi),
+            );
+            let sum = _mm_add_pd(_mm_unpacklo_pd(t0, t1), _mm_unpackhi_pd(t0, t1));
+            _mm_storeu_pd(out.as_mut_ptr().add(r), sum);
+        }
+        out
+    }
+
+    pub unsafe fn dot4(a: &[f64; 4], b: &[f64; 4]) -> f64 {
+        let t = _mm_add_pd(
+            _mm_mul_pd(_mm_loadu_pd(a.as_ptr()), _mm_loadu_pd(b.as_ptr())),
+            _mm_mul_pd(_mm_loadu_pd(a.as_ptr().add(2)), _mm_loadu_pd(b.as_ptr().add(2))),
+        );
+        _mm_cvtsd_f64(_mm_add_sd(t, _mm_unpackhi_pd(t, t)))
+    }
+
+    // same cofactor formula as the scalar path, but every pair of adjacent
+    // entries in a row of the inverse is computed in one register
+    pub unsafe fn mat4_inverse(a: &[f64; 16]) -> Option<[f64; 16]> {
+        let s0 = a[0] * a[5] - a[4] * a[1];
+        let s1 = a[0] * a[6] - a[4] * a[2];
+        let s2 = a[0] * a[7] - a[4] * a[3];
+        let s3 = a[1] * a[6] - a[5] * a[2];
+        let s4 = a[1] * a[7] - a[5] * a[3];
+        let s5 = a[2] * a[7] - a[6] * a[3];
+
+        let c5 = a[10] * a[15] - a[14] * a[11];
+        let c4 = a[9] * a[15] - a[13] * a[11];
+        let c3 = a[9] * a[14] - a[13] * a[10];
+        let c2 = a[8] * a[15] - a[12] * a[11];
+        let c1 = a[8] * a[14] - a[12] * a[10];
+        let c0 = a[8] * a[13] - a[12] * a[9];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let d = 1.0 / det;
+
+        // p[j] = [a1j, a0j] and q[j] = [a3j, a2j]
+        let p = [
+            _mm_set_pd(a[0], a[4]),
+            _mm_set_pd(a[1], a[5]),
+            _mm_set_pd(a[2], a[6]),
+            _mm_set_pd(a[3], a[7]),
+        ];
+        let q = [
+            _mm_set_pd(a[8], a[12]),
+            _mm_set_pd(a[9], a[13]),
+            _mm_set_pd(a[10], a[14]),
+            _mm_set_pd(a[11], a[15]),
+        ];
+        let pos_neg = _mm_set_pd(-d, d);
+        let neg_pos = _mm_set_pd(d, -d);
+
+        let comb = |x: __m128d, kx: f64, y: __m128d, ky: f64, z: __m128d, kz: f64, sign: __m128d| {
+            let t = _mm_sub_pd(_mm_mul_pd(x, _mm_set1_pd(kx)), _mm_mul_pd(y, _mm_set1_pd(ky)));
+            _mm_mul_pd(_mm_add_pd(t, _mm_mul_pd(z, _mm_set1_pd(kz))), sign)
+        };
+
+        let rows = [
+            comb(p[1], c5, p[2], c4, p[3], c3, pos_neg),
+            comb(q[1], s5, q[2], s4, q[3], s3, pos_neg),
+            comb(p[0], c5, p[2], c2, p[3], c1, neg_pos),
+            comb(q[0], s5, q[2], s2, q[3], s1, neg_pos),
+            comb(p[0], c4, p[1], c2, p[3], c0, pos_neg),
+            comb(q[0], s4, q[1], s2, q[3], s0, pos_neg),
+            comb(p[0], c3, p[1], c1, p[2], c0, neg_pos),
+            comb(q[0], s3, q[1], s1, q[2], s0, neg_pos),
+        ];
+
+        let mut out = [0.0; 16];
+        for (i, r) in rows.iter().enumerate() {
+            _mm_storeu_pd(out.as_mut_ptr().add(i * 2), *r);
+        }
+        Some(out)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx {
+    use core::arch::x86_64::*;
+
+    // a whole row fits in one register
+    #[target_feature(enable = "avx")]
+    pub unsafe fn mat4_mul(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+        let mut out = [0.0; 16];
+        let b = b.as_ptr();
+        let rows = [
+            _mm256_loadu_pd(b),
+            _mm256_loadu_pd(b.add(4)),
+            _mm256_loadu_pd(b.add(8)),
+            _mm256_loadu_pd(b.add(12)),
+        ];
+        for r in 0..4 {
+            let mut acc = _mm256_mul_pd(_mm256_set1_pd(a[r * 4]), rows[0]);
+            acc = _mm256_add_pd(acc, _mm256_mul_pd(_mm256_set1_pd(a[r * 4 + 1]), rows[1]));
+            acc = _mm256_add_pd(acc, _mm256_mul_pd(_mm256_set1_pd(a[r * 4 + 2]), rows[2]));
+            acc = _mm256_add_pd(acc, _mm256_mul_pd(_mm256_set1_pd(a[r * 4 + 3]), rows[3]));
+            _mm256_storeu_pd(out.as_mut_ptr().add(r * 4), acc);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(seed: f64) -> [f64; 16] {
+        let mut m = [0.0; 16];
+        for (i, e) in m.iter_mut().enumerate() {
+            let x = i as f64 + seed;
+            *e = (x * 0.37).fract() * 4.0 - 2.0 + if i % 5 == 0 { 3.0 } else { 0.0 };
+        }
+        m
+    }
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() <= 1e-12 * (1.0 + y.abs()), "{} != {}", x, y);
+        }
+    }
+
+    #[test]
+    fn mul_matches_scalar() {
+        for s in 0..10 {
+            let a = sample(s as f64);
+            let b = sample(s as f64 * 1.7 + 0.3);
+            assert_close(&mat4_mul(&a, &b), &scalar::mat4_mul(&a, &b));
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn both_x86_paths_match_scalar() {
+        let a = sample(1.0);
+        let b = sample(2.5);
+        let expected = scalar::mat4_mul(&a, &b);
+        assert_close(&unsafe { sse2::mat4_mul(&a, &b) }, &expected);
+        if has_avx() {
+            assert_close(&unsafe { avx::mat4_mul(&a, &b) }, &expected);
+        }
+    }
+
+    #[test]
+    fn mul_vec_and_dot_match_scalar() {
+        let a = sample(0.5);
+        let v = [1.5, -2.0, 0.25, 3.0];
+        assert_close(&mat4_mul_vec(&a, &v), &scalar::mat4_mul_vec(&a, &v));
+        assert_close(&[dot4(&v, &[2.0, 1.0, -4.0, 0.5])], &[scalar::dot4(&v, &[2.0, 1.0, -4.0, 0.5])]);
+    }
+
+    #[test]
+    fn inverse_matches_scalar() {
+        for s in 0..10 {
+            let a = sample(s as f64 * 0.9);
+            let inv = mat4_inverse(&a).unwrap();
+            assert_close(&inv, &scalar::mat4_inverse(&a).unwrap());
+
+            let mut id = [0.0; 16];
+            for i in 0..4 {
+                id[i * 5] = 1.0;
+            }
+            let prod = scalar::mat4_mul(&a, &inv);
+            for (x, y) in prod.iter().zip(id) {
+                assert!((x - y).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn singular_inverse() {
+        // the second row is twice the first one
+        let a = [
+            1.0, 2.0, 3.0, 4.0,
+            2.0, 4.0, 6.0, 8.0,
+            0.0, 1.0, 5.0, 2.0,
+            7.0, 1.0, 0.0, 3.0,
+        ];
+        assert!(mat4_inverse(&a).is_none());
+        assert!(scalar::mat4_inverse(&a).is_none());
+        assert!(mat4_inverse(&[0.0; 16]).is_none());
+    }
+}
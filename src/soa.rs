@@ -0,0 +1,217 @@
+// Structure-of-arrays storage for large batches of 3d vectors.
+//
+// Keeping every component in its own contiguous array lets the bulk loops
+// below run over plain f64 slices, which the compiler can vectorize.
+
+use super::matrix4d::*;
+use super::vector3d::*;
+use alloc::vec::Vec;
+
+// the three arrays always have the same length
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vector3dSoa {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    z: Vec<f64>,
+}
+
+impl Vector3dSoa {
+    pub fn new() -> Self {
+        Self {
+            x: Vec::new(),
+            y: Vec::new(),
+            z: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            x: Vec::with_capacity(n),
+            y: Vec::with_capacity(n),
+            z: Vec::with_capacity(n),
+        }
+    }
+
+    pub fn from_slice(points: &[Vector3d]) -> Self {
+        let mut soa = Self::with_capacity(points.len());
+        for p in points {
+            soa.push(*p);
+        }
+        soa
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    pub fn x(&self) -> &[f64] {
+        &self.x
+    }
+
+    pub fn y(&self) -> &[f64] {
+        &self.y
+    }
+
+    pub fn z(&self) -> &[f64] {
+        &self.z
+    }
+
+    // the component arrays for in-place bulk edits, which can't resize them
+    pub fn components_mut(&mut self) -> (&mut [f64], &mut [f64], &mut [f64]) {
+        (&mut self.x, &mut self.y, &mut self.z)
+    }
+
+    pub fn push(&mut self, p: Vector3d) {
+        self.x.push(p.x);
+        self.y.push(p.y);
+        self.z.push(p.z);
+    }
+
+    pub fn get(&self, i: usize) -> Vector3d {
+        Vector3d::new_from([self.x[i], self.y[i], self.z[i]])
+    }
+
+    pub fn set(&mut self, i: usize, p: Vector3d) {
+        self.x[i] = p.x;
+        self.y[i] = p.y;
+        self.z[i] = p.z;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Vector3d> + '_ {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+
+    // back to an array of structs
+    pub fn to_vectors(&self) -> Vec<Vector3d> {
+        self.iter().collect()
+    }
+
+    pub fn write_to(&self, dst: &mut [Vector3d]) {
+        assert_eq!(self.len(), dst.len(), "source and destination must have the same length");
+        for (i, out) in dst.iter_mut().enumerate() {
+            *out = self.get(i);
+        }
+    }
+
+    // applies m to every point (w = 1), with the projective divide when needed
+    pub fn transform_points(&mut self, m: &Matrix4d) {
+        let n = self.len();
+        let (xs, ys, zs) = (&mut self.x[..n], &mut self.y[..n], &mut self.z[..n]);
+        let affine = m.is_affine();
+        for i in 0..n {
+            let (x, y, z) = (xs[i], ys[i], zs[i]);
+            let tx = m[0] * x + m[1] * y + m[2] * z + m[3];
+            let ty = m[4] * x + m[5] * y + m[6] * z + m[7];
+            let tz = m[8] * x + m[9] * y + m[10] * z + m[11];
+            if affine {
+                xs[i] = tx;
+                ys[i] = ty;
+                zs[i] = tz;
+            } else {
+                let w = m[12] * x + m[13] * y + m[14] * z + m[15];
+                xs[i] = tx / w;
+                ys[i] = ty / w;
+                zs[i] = tz / w;
+            }
+        }
+    }
+
+    // applies m to every direction (w = 0)
+    pub fn transform_vectors(&mut self, m: &Matrix4d) {
+        let n = self.len();
+        let (xs, ys, zs) = (&mut self.x[..n], &mut self.y[..n], &mut self.z[..n]);
+        for i in 0..n {
+            let (x, y, z) = (xs[i], ys[i], zs[i]);
+            xs[i] = m[0] * x + m[1] * y + m[2] * z;
+            ys[i] = m[4] * x + m[5] * y + m[6] * z;
+            zs[i] = m[8] * x + m[9] * y + m[10] * z;
+        }
+    }
+
+    pub fn translate(&mut self, t: Vector3d) {
+        self.x.iter_mut().for_each(|e| *e += t.x);
+        self.y.iter_mut().for_each(|e| *e += t.y);
+        self.z.iter_mut().for_each(|e| *e += t.z);
+    }
+
+    pub fn scale(&mut self, l: f64) {
+        self.x.iter_mut().for_each(|e| *e *= l);
+        self.y.iter_mut().for_each(|e| *e *= l);
+        self.z.iter_mut().for_each(|e| *e *= l);
+    }
+
+    // dot product of every element with v
+    pub fn dot(&self, v: Vector3d, out: &mut [f64]) {
+        assert_eq!(self.len(), out.len(), "source and destination must have the same length");
+        let n = self.len();
+        let (xs, ys, zs) = (&self.x[..n], &self.y[..n], &self.z[..n]);
+        for i in 0..n {
+            out[i] = xs[i] * v.x + ys[i] * v.y + zs[i] * v.z;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> Vec<Vector3d> {
+        (0..10)
+            .map(|i| Vector3d::new_from([i as f64, 2.0 * i as f64 - 3.0, 0.5 * i as f64]))
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let pts = points();
+        let soa = Vector3dSoa::from_slice(&pts);
+        assert_eq!(soa.len(), 10);
+        assert_eq!(soa.get(3), pts[3]);
+        assert_eq!(soa.to_vectors(), pts);
+    }
+
+    #[test]
+    fn transforms_match_matrix4d() {
+        let m = Matrix4d::from_row_major([
+            0.0, -1.0, 0.0, 5.0,
+            1.0, 0.0, 0.0, -2.0,
+            0.0, 0.0, 2.0, 1.0,
+            0.0, 0.0, 0.0, 1.0,
+        ]);
+        let pts = points();
+        let mut expected = pts.clone();
+
+        let mut soa = Vector3dSoa::from_slice(&pts);
+        soa.transform_points(&m);
+        m.transform_points(&pts, &mut expected);
+        assert_eq!(soa.to_vectors(), expected);
+
+        let mut soa = Vector3dSoa::from_slice(&pts);
+        soa.transform_vectors(&m);
+        m.transform_vectors(&pts, &mut expected);
+        assert_eq!(soa.to_vectors(), expected);
+    }
+
+    #[test]
+    fn bulk_ops() {
+        let pts = points();
+        let mut soa = Vector3dSoa::from_slice(&pts);
+        soa.scale(2.0);
+        soa.translate(Vector3d::new_from([1.0, 0.0, -1.0]));
+        assert_eq!(soa.get(2), pts[2] * 2.0 + Vector3d::new_from([1.0, 0.0, -1.0]));
+
+        let mut d = [0.0; 10];
+        soa.dot(Vector3d::new_from([1.0, 1.0, 1.0]), &mut d);
+        assert_eq!(d[2], soa.get(2) * Vector3d::new_from_const(1.0));
+
+        // component access goes through fixed-length slices
+        let (xs, _, zs) = soa.components_mut();
+        xs[0] = 7.0;
+        zs[9] = -1.0;
+        assert_eq!((soa.x()[0], soa.z()[9], soa.y().len()), (7.0, -1.0, 10));
+    }
+}
@@ -0,0 +1,700 @@
+// Sparse matrices: a triplet (COO) builder, and compressed row (CSR) and
+// compressed column (CSC) storage.
+//
+// The compressed formats always keep the indices inside each row (or column)
+// sorted and without duplicates, the builder sums duplicate entries when it
+// gets compressed. Like MatrixXd, dimension mismatches panic.
+
+use super::matrixxd::*;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CooMatrix {
+    rows: usize,
+    cols: usize,
+    row_idx: Vec<usize>,
+    col_idx: Vec<usize>,
+    values: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsrMatrix {
+    rows: usize,
+    cols: usize,
+    row_ptr: Vec<usize>,
+    col_idx: Vec<usize>,
+    values: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CscMatrix {
+    rows: usize,
+    cols: usize,
+    col_ptr: Vec<usize>,
+    row_idx: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl CooMatrix {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            row_idx: Vec::new(),
+            col_idx: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(rows: usize, cols: usize, nnz: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            row_idx: Vec::with_capacity(nnz),
+            col_idx: Vec::with_capacity(nnz),
+            values: Vec::with_capacity(nnz),
+        }
+    }
+
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get_cols(&self) -> usize {
+        self.cols
+    }
+
+    // number of stored triplets, duplicates included
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    // adds value at (row, col), duplicates get summed on compression
+    pub fn push(&mut self, row: usize, col: usize, value: f64) {
+        assert!(row < self.rows && col < self.cols, "entry ({}, {}) out of bounds", row, col);
+        self.row_idx.push(row);
+        self.col_idx.push(col);
+        self.values.push(value);
+    }
+
+    pub fn triplets(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.row_idx
+            .iter()
+            .zip(&self.col_idx)
+            .zip(&self.values)
+            .map(|((r, c), v)| (*r, *c, *v))
+    }
+
+    pub fn to_csr(&self) -> CsrMatrix {
+        let (row_ptr, col_idx, values) = compress(self.rows, &self.row_idx, &self.col_idx, &self.values);
+        CsrMatrix {
+            rows: self.rows,
+            cols: self.cols,
+            row_ptr,
+            col_idx,
+            values,
+        }
+    }
+
+    pub fn to_csc(&self) -> CscMatrix {
+        let (col_ptr, row_idx, values) = compress(self.cols, &self.col_idx, &self.row_idx, &self.values);
+        CscMatrix {
+            rows: self.rows,
+            cols: self.cols,
+            col_ptr,
+            row_idx,
+            values,
+        }
+    }
+
+    // keeps only the non-zero entries
+    pub fn from_dense(m: &MatrixXd) -> Self {
+        let mut coo = Self::new(m.get_rows(), m.get_cols());
+        for r in 0..m.get_rows() {
+            for (c, v) in m.row(r).iter().enumerate() {
+                if *v != 0.0 {
+                    coo.push(r, c, *v);
+                }
+            }
+        }
+        coo
+    }
+
+    pub fn to_dense(&self) -> MatrixXd {
+        let mut m = MatrixXd::zeros(self.rows, self.cols);
+        for (r, c, v) in self.triplets() {
+            m[(r, c)] += v;
+        }
+        m
+    }
+}
+
+// groups the triplets by major index with a counting sort, then sorts each
+// group by minor index and sums the duplicates
+fn compress(n_major: usize, major: &[usize], minor: &[usize], values: &[f64]) -> (Vec<usize>, Vec<usize>, Vec<f64>) {
+    let mut ptr = vec![0; n_major + 1];
+    for m in major {
+        ptr[m + 1] += 1;
+    }
+    for i in 0..n_major {
+        ptr[i + 1] += ptr[i];
+    }
+
+    let mut next = ptr.clone();
+    let mut entries = vec![(0, 0.0); values.len()];
+    for ((m, i), v) in major.iter().zip(minor).zip(values) {
+        entries[next[*m]] = (*i, *v);
+        next[*m] += 1;
+    }
+
+    let mut out_ptr = Vec::with_capacity(n_major + 1);
+    let mut out_idx = Vec::with_capacity(values.len());
+    let mut out_val = Vec::with_capacity(values.len());
+    out_ptr.push(0);
+    for m in 0..n_major {
+        let group = &mut entries[ptr[m]..ptr[m + 1]];
+        group.sort_by_key(|e| e.0);
+        let start = out_idx.len();
+        for (i, v) in group.iter() {
+            if out_idx.len() > start && out_idx[out_idx.len() - 1] == *i {
+                *out_val.last_mut().unwrap() += *v;
+            } else {
+                out_idx.push(*i);
+                out_val.push(*v);
+            }
+        }
+        out_ptr.push(out_idx.len());
+    }
+    (out_ptr, out_idx, out_val)
+}
+
+// checks the invariants of a compressed structure
+fn validate(n_major: usize, n_minor: usize, ptr: &[usize], idx: &[usize], values: &[f64]) {
+    assert_eq!(ptr.len(), n_major + 1, "pointer array must have {} entries", n_major + 1);
+    assert_eq!(ptr[0], 0, "pointer array must start at 0");
+    assert_eq!(ptr[n_major], idx.len(), "pointer array must end at the number of entries");
+    assert_eq!(idx.len(), values.len(), "index and value arrays must have the same length");
+    for m in 0..n_major {
+        assert!(ptr[m] <= ptr[m + 1], "pointer array must be non-decreasing");
+        let group = &idx[ptr[m]..ptr[m + 1]];
+        for (k, i) in group.iter().enumerate() {
+            assert!(*i < n_minor, "index {} out of bounds", i);
+            assert!(k == 0 || group[k - 1] < *i, "indices must be sorted and unique");
+        }
+    }
+}
+
+// sparse * sparse with Gustavson's algorithm, both operands and the result
+// are row-compressed
+#[allow(clippy::type_complexity)]
+fn spgemm(
+    a: (usize, &[usize], &[usize], &[f64]),
+    b: (usize, &[usize], &[usize], &[f64]),
+) -> (Vec<usize>, Vec<usize>, Vec<f64>) {
+    let (a_rows, a_ptr, a_idx, a_val) = a;
+    let (b_cols, b_ptr, b_idx, b_val) = b;
+
+    let mut ptr = Vec::with_capacity(a_rows + 1);
+    let mut idx = Vec::new();
+    let mut val = Vec::new();
+    ptr.push(0);
+
+    // dense accumulator for the current row, marker tells which columns are in use
+    let mut acc = vec![0.0; b_cols];
+    let mut marker = vec![usize::MAX; b_cols];
+    let mut pattern = Vec::new();
+    for r in 0..a_rows {
+        pattern.clear();
+        for k in a_ptr[r]..a_ptr[r + 1] {
+            let (j, aij) = (a_idx[k], a_val[k]);
+            for l in b_ptr[j]..b_ptr[j + 1] {
+                let c = b_idx[l];
+                if marker[c] != r {
+                    marker[c] = r;
+                    acc[c] = 0.0;
+                    pattern.push(c);
+                }
+                acc[c] += aij * b_val[l];
+            }
+        }
+        pattern.sort_unstable();
+        for c in &pattern {
+            idx.push(*c);
+            val.push(acc[*c]);
+        }
+        ptr.push(idx.len());
+    }
+    (ptr, idx, val)
+}
+
+impl CsrMatrix {
+    pub fn from_raw_parts(rows: usize, cols: usize, row_ptr: Vec<usize>, col_idx: Vec<usize>, values: Vec<f64>) -> Self {
+        validate(rows, cols, &row_ptr, &col_idx, &values);
+        Self {
+            rows,
+            cols,
+            row_ptr,
+            col_idx,
+            values,
+        }
+    }
+
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            row_ptr: vec![0; rows + 1],
+            col_idx: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn identity(n: usize) -> Self {
+        Self {
+            rows: n,
+            cols: n,
+            row_ptr: (0..=n).collect(),
+            col_idx: (0..n).collect(),
+            values: vec![1.0; n],
+        }
+    }
+
+    pub fn from_dense(m: &MatrixXd) -> Self {
+        CooMatrix::from_dense(m).to_csr()
+    }
+
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get_cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn row_ptr(&self) -> &[usize] {
+        &self.row_ptr
+    }
+
+    pub fn col_idx(&self) -> &[usize] {
+        &self.col_idx
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [f64] {
+        &mut self.values
+    }
+
+    // column indices and values of row r
+    pub fn row(&self, r: usize) -> (&[usize], &[f64]) {
+        let (s, e) = (self.row_ptr[r], self.row_ptr[r + 1]);
+        (&self.col_idx[s..e], &self.values[s..e])
+    }
+
+    // 0 for entries that aren't stored
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        assert!(row < self.rows && col < self.cols, "index ({}, {}) out of bounds", row, col);
+        let (idx, val) = self.row(row);
+        match idx.binary_search(&col) {
+            Ok(k) => val[k],
+            Err(_) => 0.0,
+        }
+    }
+
+    pub fn triplets(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        (0..self.rows).flat_map(move |r| {
+            let (idx, val) = self.row(r);
+            idx.iter().zip(val).map(move |(c, v)| (r, *c, *v))
+        })
+    }
+
+    pub fn diagonal(&self) -> Vec<f64> {
+        (0..self.rows.min(self.cols)).map(|i| self.get(i, i)).collect()
+    }
+
+    pub fn mul_vec(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.rows];
+        self.mul_vec_into(x, &mut y);
+        y
+    }
+
+    pub fn mul_vec_into(&self, x: &[f64], y: &mut [f64]) {
+        assert_eq!(x.len(), self.cols, "vector length doesn't match the number of columns");
+        assert_eq!(y.len(), self.rows, "output length doesn't match the number of rows");
+        for (r, out) in y.iter_mut().enumerate() {
+            let (idx, val) = self.row(r);
+            let mut s = 0.0;
+            for (c, v) in idx.iter().zip(val) {
+                s += v * x[*c];
+            }
+            *out = s;
+        }
+    }
+
+    pub fn mul(&self, o: &CsrMatrix) -> CsrMatrix {
+        assert_eq!(self.cols, o.rows, "can't multiply a {}x{} matrix by a {}x{} one", self.rows, self.cols, o.rows, o.cols);
+        let (row_ptr, col_idx, values) = spgemm(
+            (self.rows, &self.row_ptr, &self.col_idx, &self.values),
+            (o.cols, &o.row_ptr, &o.col_idx, &o.values),
+        );
+        CsrMatrix {
+            rows: self.rows,
+            cols: o.cols,
+            row_ptr,
+            col_idx,
+            values,
+        }
+    }
+
+    pub fn transpose(&self) -> CsrMatrix {
+        // the CSC arrays of A are the CSR arrays of A^T
+        let csc = self.to_csc();
+        CsrMatrix {
+            rows: self.cols,
+            cols: self.rows,
+            row_ptr: csc.col_ptr,
+            col_idx: csc.row_idx,
+            values: csc.values,
+        }
+    }
+
+    pub fn to_csc(&self) -> CscMatrix {
+        let mut col_ptr = vec![0; self.cols + 1];
+        for c in &self.col_idx {
+            col_ptr[c + 1] += 1;
+        }
+        for i in 0..self.cols {
+            col_ptr[i + 1] += col_ptr[i];
+        }
+        let mut next = col_ptr.clone();
+        let mut row_idx = vec![0; self.nnz()];
+        let mut values = vec![0.0; self.nnz()];
+        // going through the rows in order keeps the row indices sorted
+        for r in 0..self.rows {
+            for k in self.row_ptr[r]..self.row_ptr[r + 1] {
+                let c = self.col_idx[k];
+                row_idx[next[c]] = r;
+                values[next[c]] = self.values[k];
+                next[c] += 1;
+            }
+        }
+        CscMatrix {
+            rows: self.rows,
+            cols: self.cols,
+            col_ptr,
+            row_idx,
+            values,
+        }
+    }
+
+    pub fn to_coo(&self) -> CooMatrix {
+        let mut coo = CooMatrix::with_capacity(self.rows, self.cols, self.nnz());
+        for (r, c, v) in self.triplets() {
+            coo.push(r, c, v);
+        }
+        coo
+    }
+
+    pub fn to_dense(&self) -> MatrixXd {
+        let mut m = MatrixXd::zeros(self.rows, self.cols);
+        for (r, c, v) in self.triplets() {
+            m[(r, c)] = v;
+        }
+        m
+    }
+}
+
+impl CscMatrix {
+    pub fn from_raw_parts(rows: usize, cols: usize, col_ptr: Vec<usize>, row_idx: Vec<usize>, values: Vec<f64>) -> Self {
+        validate(cols, rows, &col_ptr, &row_idx, &values);
+        Self {
+            rows,
+            cols,
+            col_ptr,
+            row_idx,
+            values,
+        }
+    }
+
+    pub fn identity(n: usize) -> Self {
+        CsrMatrix::identity(n).to_csc()
+    }
+
+    pub fn from_dense(m: &MatrixXd) -> Self {
+        CooMatrix::from_dense(m).to_csc()
+    }
+
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get_cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn col_ptr(&self) -> &[usize] {
+        &self.col_ptr
+    }
+
+    pub fn row_idx(&self) -> &[usize] {
+        &self.row_idx
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [f64] {
+        &mut self.values
+    }
+
+    // row indices and values of column c
+    pub fn col(&self, c: usize) -> (&[usize], &[f64]) {
+        let (s, e) = (self.col_ptr[c], self.col_ptr[c + 1]);
+        (&self.row_idx[s..e], &self.values[s..e])
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        assert!(row < self.rows && col < self.cols, "index ({}, {}) out of bounds", row, col);
+        let (idx, val) = self.col(col);
+        match idx.binary_search(&row) {
+            Ok(k) => val[k],
+            Err(_) => 0.0,
+        }
+    }
+
+    pub fn triplets(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        (0..self.cols).flat_map(move |c| {
+            let (idx, val) = self.col(c);
+            idx.iter().zip(val).map(move |(r, v)| (*r, c, *v))
+        })
+    }
+
+    pub fn mul_vec(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.rows];
+        self.mul_vec_into(x, &mut y);
+        y
+    }
+
+    // y = A x as a linear combination of the columns
+    pub fn mul_vec_into(&self, x: &[f64], y: &mut [f64]) {
+        assert_eq!(x.len(), self.cols, "vector length doesn't match the number of columns");
+        assert_eq!(y.len(), self.rows, "output length doesn't match the number of rows");
+        y.iter_mut().for_each(|e| *e = 0.0);
+        for (c, xc) in x.iter().enumerate() {
+            let (idx, val) = self.col(c);
+            for (r, v) in idx.iter().zip(val) {
+                y[*r] += v * xc;
+            }
+        }
+    }
+
+    pub fn mul(&self, o: &CscMatrix) -> CscMatrix {
+        assert_eq!(self.cols, o.rows, "can't multiply a {}x{} matrix by a {}x{} one", self.rows, self.cols, o.rows, o.cols);
+        // (A B)^T = B^T A^T, and the CSC arrays of a matrix are the CSR arrays of its transpose
+        let (col_ptr, row_idx, values) = spgemm(
+            (o.cols, &o.col_ptr, &o.row_idx, &o.values),
+            (self.rows, &self.col_ptr, &self.row_idx, &self.values),
+        );
+        CscMatrix {
+            rows: self.rows,
+            cols: o.cols,
+            col_ptr,
+            row_idx,
+            values,
+        }
+    }
+
+    pub fn transpose(&self) -> CscMatrix {
+        let csr = self.to_csr();
+        CscMatrix {
+            rows: self.cols,
+            cols: self.rows,
+            col_ptr: csr.row_ptr,
+            row_idx: csr.col_idx,
+            values: csr.values,
+        }
+    }
+
+    pub fn to_csr(&self) -> CsrMatrix {
+        // transposing the CSR view of A^T gives A back in CSR form
+        let t = CsrMatrix {
+            rows: self.cols,
+            cols: self.rows,
+            row_ptr: self.col_ptr.clone(),
+            col_idx: self.row_idx.clone(),
+            values: self.values.clone(),
+        }
+        .to_csc();
+        CsrMatrix {
+            rows: self.rows,
+            cols: self.cols,
+            row_ptr: t.col_ptr,
+            col_idx: t.row_idx,
+            values: t.values,
+        }
+    }
+
+    pub fn to_coo(&self) -> CooMatrix {
+        let mut coo = CooMatrix::with_capacity(self.rows, self.cols, self.nnz());
+        for (r, c, v) in self.triplets() {
+            coo.push(r, c, v);
+        }
+        coo
+    }
+
+    pub fn to_dense(&self) -> MatrixXd {
+        let mut m = MatrixXd::zeros(self.rows, self.cols);
+        for (r, c, v) in self.triplets() {
+            m[(r, c)] = v;
+        }
+        m
+    }
+}
+
+impl From<&CsrMatrix> for CscMatrix {
+    fn from(m: &CsrMatrix) -> Self {
+        m.to_csc()
+    }
+}
+
+impl From<&CscMatrix> for CsrMatrix {
+    fn from(m: &CscMatrix) -> Self {
+        m.to_csr()
+    }
+}
+
+impl core::ops::Mul<&[f64]> for &CsrMatrix {
+    type Output = Vec<f64>;
+
+    fn mul(self, x: &[f64]) -> Self::Output {
+        self.mul_vec(x)
+    }
+}
+
+impl core::ops::Mul<&CsrMatrix> for &CsrMatrix {
+    type Output = CsrMatrix;
+
+    fn mul(self, o: &CsrMatrix) -> Self::Output {
+        CsrMatrix::mul(self, o)
+    }
+}
+
+impl core::ops::Mul<&[f64]> for &CscMatrix {
+    type Output = Vec<f64>;
+
+    fn mul(self, x: &[f64]) -> Self::Output {
+        self.mul_vec(x)
+    }
+}
+
+impl core::ops::Mul<&CscMatrix> for &CscMatrix {
+    type Output = CscMatrix;
+
+    fn mul(self, o: &CscMatrix) -> Self::Output {
+        CscMatrix::mul(self, o)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a sparse-ish matrix with a few zero rows and columns
+    fn sample(rows: usize, cols: usize, seed: usize) -> MatrixXd {
+        MatrixXd::from_fn(rows, cols, |i, j| {
+            let h = (i * 7 + j * 13 + seed * 5) % 11;
+            if h < 3 && i != 2 && j != 3 {
+                h as f64 + 0.5 * (i as f64) - j as f64
+            } else {
+                0.0
+            }
+        })
+    }
+
+    #[test]
+    fn coo_sums_duplicates() {
+        let mut coo = CooMatrix::new(3, 3);
+        coo.push(0, 1, 1.0);
+        coo.push(2, 2, 4.0);
+        coo.push(0, 1, 2.5);
+        coo.push(1, 0, -1.0);
+        coo.push(0, 0, 3.0);
+        assert_eq!(coo.nnz(), 5);
+
+        let csr = coo.to_csr();
+        assert_eq!(csr.nnz(), 4);
+        assert_eq!(csr.row_ptr(), &[0, 2, 3, 4]);
+        assert_eq!(csr.col_idx(), &[0, 1, 0, 2]);
+        assert_eq!(csr.get(0, 1), 3.5);
+        assert_eq!(csr.get(1, 1), 0.0);
+
+        let csc = coo.to_csc();
+        assert_eq!(csc.col_ptr(), &[0, 2, 3, 4]);
+        assert_eq!(csc.row_idx(), &[0, 1, 0, 2]);
+        assert_eq!(csc.to_dense(), csr.to_dense());
+        assert_eq!(coo.to_dense(), csr.to_dense());
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let d = sample(7, 9, 1);
+        let csr = CsrMatrix::from_dense(&d);
+        let csc = CscMatrix::from_dense(&d);
+        assert_eq!(csr.to_dense(), d);
+        assert_eq!(csc.to_dense(), d);
+        assert_eq!(csr.to_csc(), csc);
+        assert_eq!(csc.to_csr(), csr);
+        assert_eq!(csr.to_coo().to_csr(), csr);
+        assert_eq!(CsrMatrix::from(&csc), csr);
+    }
+
+    #[test]
+    fn transpose() {
+        let d = sample(6, 4, 2);
+        assert_eq!(CsrMatrix::from_dense(&d).transpose().to_dense(), d.transpose());
+        assert_eq!(CscMatrix::from_dense(&d).transpose().to_dense(), d.transpose());
+    }
+
+    #[test]
+    fn mul_vec_matches_dense() {
+        let d = sample(8, 5, 3);
+        let x = [1.0, -2.0, 0.5, 3.0, 4.0];
+        let expected = d.mul_vec(&x);
+        assert_eq!(&CsrMatrix::from_dense(&d) * &x[..], expected);
+        assert_eq!(&CscMatrix::from_dense(&d) * &x[..], expected);
+    }
+
+    #[test]
+    fn sparse_products_match_dense() {
+        let a = sample(6, 8, 4);
+        let b = sample(8, 5, 5);
+        let expected = &a * &b;
+        let csr = &CsrMatrix::from_dense(&a) * &CsrMatrix::from_dense(&b);
+        let csc = &CscMatrix::from_dense(&a) * &CscMatrix::from_dense(&b);
+        for r in 0..6 {
+            for c in 0..5 {
+                assert!((csr.get(r, c) - expected[(r, c)]).abs() < 1e-12);
+                assert!((csc.get(r, c) - expected[(r, c)]).abs() < 1e-12);
+            }
+        }
+        assert_eq!(&CsrMatrix::identity(6) * &csr, csr);
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_raw_parts() {
+        CsrMatrix::from_raw_parts(2, 2, vec![0, 2, 2], vec![1, 0], vec![1.0, 2.0]);
+    }
+}
@@ -1,18 +1,20 @@
+use crate::scalar::{Real, Scalar};
+
 #[derive(Debug, Clone, Copy)]
-pub struct Vector2d {
-    pub x: f64,
-    pub y: f64,
+pub struct Vector2d<T = f64> {
+    pub x: T,
+    pub y: T,
 }
 
-impl Vector2d {
-    pub fn new_from(values: [f64; 2]) -> Self {
+impl<T: Scalar> Vector2d<T> {
+    pub fn new_from(values: [T; 2]) -> Self {
         Self {
             x: values[0],
             y: values[1],
         }
     }
 
-    pub fn new_from_const(c: f64) -> Self {
+    pub fn new_from_const(c: T) -> Self {
         Self {
             x: c,
             y: c,
@@ -20,21 +22,38 @@ impl Vector2d {
     }
 
     pub fn new() -> Self {
-        Self { x: 0.0, y: 0.0 }
+        Self { x: T::zero(), y: T::zero() }
     }
 
-    pub fn to_list(&self) -> [f64; 2] {
+    pub fn to_list(&self) -> [T; 2] {
         [self.x, self.y]
     }
 
-    pub fn to_vec(&self) -> Vec<f64> {
-        vec![self.x, self.y]
+    #[cfg(feature = "alloc")]
+    pub fn to_vec(&self) -> alloc::vec::Vec<T> {
+        alloc::vec![self.x, self.y]
     }
 }
 
+impl<T: Real> Vector2d<T> {
+    pub fn length(&self) -> T {
+        (*self * *self).sqrt()
+    }
+
+    // unit vector pointing in the same direction
+    pub fn normalize(&self) -> Self {
+        *self / self.length()
+    }
+}
 
-impl std::ops::Add<Vector2d> for Vector2d {
-    type Output = Vector2d;
+impl<T: Scalar> Default for Vector2d<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> core::ops::Add<Vector2d<T>> for Vector2d<T> {
+    type Output = Vector2d<T>;
     fn add(self, o: Self) -> Self::Output {
         Self {
             x: self.x + o.x,
@@ -43,9 +62,9 @@ impl std::ops::Add<Vector2d> for Vector2d {
     }
 }
 
-impl std::ops::Add<f64> for Vector2d {
-    type Output = Vector2d;
-    fn add(self, value: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Add<T> for Vector2d<T> {
+    type Output = Vector2d<T>;
+    fn add(self, value: T) -> Self::Output {
         Self {
             x: self.x + value,
             y: self.y + value,
@@ -53,8 +72,8 @@ impl std::ops::Add<f64> for Vector2d {
     }
 }
 
-impl std::ops::Sub<Vector2d> for Vector2d {
-    type Output = Vector2d;
+impl<T: Scalar> core::ops::Sub<Vector2d<T>> for Vector2d<T> {
+    type Output = Vector2d<T>;
     fn sub(self, o: Self) -> Self::Output {
         Self {
             x: self.x - o.x,
@@ -63,9 +82,9 @@ impl std::ops::Sub<Vector2d> for Vector2d {
     }
 }
 
-impl std::ops::Sub<f64> for Vector2d {
-    type Output = Vector2d;
-    fn sub(self, value: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Sub<T> for Vector2d<T> {
+    type Output = Vector2d<T>;
+    fn sub(self, value: T) -> Self::Output {
         Self {
             x: self.x - value,
             y: self.y - value,
@@ -73,16 +92,16 @@ impl std::ops::Sub<f64> for Vector2d {
     }
 }
 
-impl std::ops::Mul<Vector2d> for Vector2d {
-    type Output = f64;
+impl<T: Scalar> core::ops::Mul<Vector2d<T>> for Vector2d<T> {
+    type Output = T;
     fn mul(self, o: Self) -> Self::Output {
         self.x * o.x + self.y * o.y 
     }
 }
 
-impl std::ops::Mul<f64> for Vector2d {
-    type Output = Vector2d;
-    fn mul(self, o: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Mul<T> for Vector2d<T> {
+    type Output = Vector2d<T>;
+    fn mul(self, o: T) -> Self::Output {
         Self {
             x: o * self.x,
             y: o * self.y
@@ -90,9 +109,9 @@ impl std::ops::Mul<f64> for Vector2d {
     }
 }
 
-impl std::ops::Div<f64> for Vector2d {
-    type Output = Vector2d;
-    fn div(self, o: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Div<T> for Vector2d<T> {
+    type Output = Vector2d<T>;
+    fn div(self, o: T) -> Self::Output {
         Self {
             x: self.x / o,
             y: self.y / o 
@@ -100,13 +119,13 @@ impl std::ops::Div<f64> for Vector2d {
     }
 }
 
-impl PartialEq for Vector2d {
+impl<T: Scalar> PartialEq for Vector2d<T> {
     fn eq(&self, o: &Self) -> bool {
         self.x == o.x && self.y == o.y
     }
 }
 
-impl Eq for Vector2d { }
+impl<T: Scalar> Eq for Vector2d<T> {}
 
 #[macro_export]
 macro_rules! vec2d {
@@ -141,4 +160,11 @@ mod tests {
         let dotty = v1 * v2;
         assert_eq!(dotty, 1.0 + 4.0);
     }
+
+    #[test]
+    fn length_and_normalize() {
+        let v = Vector2d::new_from_const(2.0);
+        assert_eq!(v.length(), crate::math::sqrt(4.0 * 2.0));
+        assert!((v.normalize().length() - 1.0).abs() < 1e-12);
+    }
 }
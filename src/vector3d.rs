@@ -1,12 +1,14 @@
+use crate::scalar::{Real, Scalar};
+
 #[derive(Debug, Clone, Copy)]
-pub struct Vector3d {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
+pub struct Vector3d<T = f64> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
 }
 
-impl Vector3d {
-    pub fn new_from(values: [f64; 3]) -> Self {
+impl<T: Scalar> Vector3d<T> {
+    pub fn new_from(values: [T; 3]) -> Self {
         Self {
             x: values[0],
             y: values[1],
@@ -14,7 +16,7 @@ impl Vector3d {
         }
     }
 
-    pub fn new_from_const(c: f64) -> Self {
+    pub fn new_from_const(c: T) -> Self {
         Self {
             x: c,
             y: c,
@@ -24,23 +26,50 @@ impl Vector3d {
 
     pub fn new() -> Self {
         Self {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
+            x: T::zero(),
+            y: T::zero(),
+            z: T::zero(),
         }
     }
 
-    pub fn to_list(&self) -> [f64; 3] {
+    pub fn to_list(&self) -> [T; 3] {
         [self.x, self.y, self.z]
     }
 
-    pub fn to_vec(&self) -> Vec<f64> {
-        vec![self.x, self.y, self.z]
+    #[cfg(feature = "alloc")]
+    pub fn to_vec(&self) -> alloc::vec::Vec<T> {
+        alloc::vec![self.x, self.y, self.z]
+    }
+
+    // right-handed cross product
+    pub fn cross(&self, o: Self) -> Self {
+        Self {
+            x: self.y * o.z - self.z * o.y,
+            y: self.z * o.x - self.x * o.z,
+            z: self.x * o.y - self.y * o.x,
+        }
+    }
+}
+
+impl<T: Real> Vector3d<T> {
+    pub fn length(&self) -> T {
+        (*self * *self).sqrt()
+    }
+
+    // unit vector pointing in the same direction
+    pub fn normalize(&self) -> Self {
+        *self / self.length()
     }
 }
 
-impl std::ops::Add<Vector3d> for Vector3d {
-    type Output = Vector3d;
+impl<T: Scalar> Default for Vector3d<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> core::ops::Add<Vector3d<T>> for Vector3d<T> {
+    type Output = Vector3d<T>;
     fn add(self, o: Self) -> Self::Output {
         Self {
             x: self.x + o.x,
@@ -50,9 +79,9 @@ impl std::ops::Add<Vector3d> for Vector3d {
     }
 }
 
-impl std::ops::Add<f64> for Vector3d {
-    type Output = Vector3d;
-    fn add(self, value: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Add<T> for Vector3d<T> {
+    type Output = Vector3d<T>;
+    fn add(self, value: T) -> Self::Output {
         Self {
             x: self.x + value,
             y: self.y + value,
@@ -61,8 +90,8 @@ impl std::ops::Add<f64> for Vector3d {
     }
 }
 
-impl std::ops::Sub<Vector3d> for Vector3d {
-    type Output = Vector3d;
+impl<T: Scalar> core::ops::Sub<Vector3d<T>> for Vector3d<T> {
+    type Output = Vector3d<T>;
     fn sub(self, o: Self) -> Self::Output {
         Self {
             x: self.x - o.x,
@@ -72,9 +101,9 @@ impl std::ops::Sub<Vector3d> for Vector3d {
     }
 }
 
-impl std::ops::Sub<f64> for Vector3d {
-    type Output = Vector3d;
-    fn sub(self, value: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Sub<T> for Vector3d<T> {
+    type Output = Vector3d<T>;
+    fn sub(self, value: T) -> Self::Output {
         Self {
             x: self.x - value,
             y: self.y - value,
@@ -83,16 +112,16 @@ impl std::ops::Sub<f64> for Vector3d {
     }
 }
 
-impl std::ops::Mul<Vector3d> for Vector3d {
-    type Output = f64;
+impl<T: Scalar> core::ops::Mul<Vector3d<T>> for Vector3d<T> {
+    type Output = T;
     fn mul(self, o: Self) -> Self::Output {
         self.x * o.x + self.y * o.y + self.z * o.z
     }
 }
 
-impl std::ops::Mul<f64> for Vector3d {
-    type Output = Vector3d;
-    fn mul(self, o: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Mul<T> for Vector3d<T> {
+    type Output = Vector3d<T>;
+    fn mul(self, o: T) -> Self::Output {
         Self {
             x: o * self.x,
             y: o * self.y,
@@ -101,9 +130,9 @@ impl std::ops::Mul<f64> for Vector3d {
     }
 }
 
-impl std::ops::Div<f64> for Vector3d {
-    type Output = Vector3d;
-    fn div(self, o: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Div<T> for Vector3d<T> {
+    type Output = Vector3d<T>;
+    fn div(self, o: T) -> Self::Output {
         Self {
             x: self.x / o,
             y: self.y / o,
@@ -112,13 +141,13 @@ impl std::ops::Div<f64> for Vector3d {
     }
 }
 
-impl PartialEq for Vector3d {
+impl<T: Scalar> PartialEq for Vector3d<T> {
     fn eq(&self, o: &Self) -> bool {
         self.x == o.x && self.y == o.y && self.z == o.z
     }
 }
 
-impl Eq for Vector3d {}
+impl<T: Scalar> Eq for Vector3d<T> {}
 
 #[macro_export]
 macro_rules! vec3d {
@@ -154,6 +183,17 @@ mod tests {
         assert_eq!(dotty, 1.0 + 4.0 + 9.0);
     }
 
+    #[test]
+    fn cross_product_3d() {
+        let x = vec3d![1.0, 0.0, 0.0];
+        let y = vec3d![0.0, 1.0, 0.0];
+        assert_eq!(x.cross(y), vec3d![0.0, 0.0, 1.0]);
+        let v = vec3d![1.0, 2.0, 3.0];
+        let w = vec3d![-2.0, 0.5, 4.0];
+        assert_eq!(v.cross(w) * v, 0.0);
+        assert_eq!(v.cross(w), w.cross(v) * -1.0);
+    }
+
     #[test]
     fn vector3d_addition() {
         let v1 = vec3d![1.0, 2.0, 3.0];
@@ -209,4 +249,11 @@ mod tests {
         assert_eq!(quotient.y, 2.0);
         assert_eq!(quotient.z, 3.0);
     }
+
+    #[test]
+    fn length_and_normalize() {
+        let v = Vector3d::new_from_const(2.0);
+        assert_eq!(v.length(), crate::math::sqrt(4.0 * 3.0));
+        assert!((v.normalize().length() - 1.0).abs() < 1e-12);
+    }
 }
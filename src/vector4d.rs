@@ -1,13 +1,15 @@
+use crate::scalar::{Real, Scalar};
+
 #[derive(Debug, Clone, Copy)]
-pub struct Vector4d {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-    pub w: f64,
+pub struct Vector4d<T = f64> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+    pub w: T,
 }
 
-impl Vector4d {
-    pub fn new_from(values: [f64; 4]) -> Self {
+impl<T: Scalar> Vector4d<T> {
+    pub fn new_from(values: [T; 4]) -> Self {
         Self {
             x: values[0],
             y: values[1],
@@ -16,7 +18,7 @@ impl Vector4d {
         }
     }
 
-    pub fn new_from_const(c: f64) -> Self {
+    pub fn new_from_const(c: T) -> Self {
         Self {
             x: c,
             y: c,
@@ -27,23 +29,41 @@ impl Vector4d {
 
     pub fn new() -> Self {
         Self {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-            w: 0.0,
+            x: T::zero(),
+            y: T::zero(),
+            z: T::zero(),
+            w: T::zero(),
         }
     }
 
-    pub fn to_list(&self) -> [f64; 4] {
+    pub fn to_list(&self) -> [T; 4] {
         [self.x, self.y, self.z, self.w]
     }
-    pub fn to_vec(&self) -> Vec<f64> {
-        vec![self.x, self.y, self.z, self.w]
+    #[cfg(feature = "alloc")]
+    pub fn to_vec(&self) -> alloc::vec::Vec<T> {
+        alloc::vec![self.x, self.y, self.z, self.w]
     }
 }
 
-impl std::ops::Add<Vector4d> for Vector4d {
-    type Output = Vector4d;
+impl<T: Real> Vector4d<T> {
+    pub fn length(&self) -> T {
+        (*self * *self).sqrt()
+    }
+
+    // unit vector pointing in the same direction
+    pub fn normalize(&self) -> Self {
+        *self / self.length()
+    }
+}
+
+impl<T: Scalar> Default for Vector4d<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> core::ops::Add<Vector4d<T>> for Vector4d<T> {
+    type Output = Vector4d<T>;
     fn add(self, o: Self) -> Self::Output {
         Self {
             x: self.x + o.x,
@@ -54,9 +74,9 @@ impl std::ops::Add<Vector4d> for Vector4d {
     }
 }
 
-impl std::ops::Add<f64> for Vector4d {
-    type Output = Vector4d;
-    fn add(self, value: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Add<T> for Vector4d<T> {
+    type Output = Vector4d<T>;
+    fn add(self, value: T) -> Self::Output {
         Self {
             x: self.x + value,
             y: self.y + value,
@@ -66,8 +86,8 @@ impl std::ops::Add<f64> for Vector4d {
     }
 }
 
-impl std::ops::Sub<Vector4d> for Vector4d {
-    type Output = Vector4d;
+impl<T: Scalar> core::ops::Sub<Vector4d<T>> for Vector4d<T> {
+    type Output = Vector4d<T>;
     fn sub(self, o: Self) -> Self::Output {
         Self {
             x: self.x - o.x,
@@ -78,9 +98,9 @@ impl std::ops::Sub<Vector4d> for Vector4d {
     }
 }
 
-impl std::ops::Sub<f64> for Vector4d {
-    type Output = Vector4d;
-    fn sub(self, value: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Sub<T> for Vector4d<T> {
+    type Output = Vector4d<T>;
+    fn sub(self, value: T) -> Self::Output {
         Self {
             x: self.x - value,
             y: self.y - value,
@@ -90,16 +110,17 @@ impl std::ops::Sub<f64> for Vector4d {
     }
 }
 
-impl std::ops::Mul<Vector4d> for Vector4d {
-    type Output = f64;
+impl<T: Scalar> core::ops::Mul<Vector4d<T>> for Vector4d<T> {
+    type Output = T;
     fn mul(self, o: Self) -> Self::Output {
+        // plain scalar code, the SSE2 dot4 kernel isn't faster for a single product
         self.x * o.x + self.y * o.y + self.z * o.z + self.w * o.w
     }
 }
 
-impl std::ops::Mul<f64> for Vector4d {
-    type Output = Vector4d;
-    fn mul(self, o: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Mul<T> for Vector4d<T> {
+    type Output = Vector4d<T>;
+    fn mul(self, o: T) -> Self::Output {
         Self {
             x: o * self.x,
             y: o * self.y,
@@ -109,9 +130,9 @@ impl std::ops::Mul<f64> for Vector4d {
     }
 }
 
-impl std::ops::Div<f64> for Vector4d {
-    type Output = Vector4d;
-    fn div(self, o: f64) -> Self::Output {
+impl<T: Scalar> core::ops::Div<T> for Vector4d<T> {
+    type Output = Vector4d<T>;
+    fn div(self, o: T) -> Self::Output {
         Self {
             x: self.x / o,
             y: self.y / o,
@@ -121,13 +142,13 @@ impl std::ops::Div<f64> for Vector4d {
     }
 }
 
-impl PartialEq for Vector4d {
+impl<T: Scalar> PartialEq for Vector4d<T> {
     fn eq(&self, o: &Self) -> bool {
         self.x == o.x && self.y == o.y && self.z == o.z && self.w == o.w
     }
 }
 
-impl Eq for Vector4d {}
+impl<T: Scalar> Eq for Vector4d<T> {}
 
 #[macro_export]
 macro_rules! vec4d {
@@ -163,5 +184,11 @@ mod tests {
         let dotty = v1 * v2;
         assert_eq!(dotty, 1.0 + 4.0 + 9.0 + 16.0);
     }
-}
 
+    #[test]
+    fn length_and_normalize() {
+        let v = Vector4d::new_from_const(2.0);
+        assert_eq!(v.length(), crate::math::sqrt(4.0 * 4.0));
+        assert!((v.normalize().length() - 1.0).abs() < 1e-12);
+    }
+}